@@ -71,7 +71,7 @@ async fn compute_example() -> Result<()> {
         compute_pass.set_bind_group(0, &bind_group, &[]);
 
         // Dispatch workgroups (1024 elements / 64 threads per workgroup = 16 workgroups)
-        let workgroup_count = ((input_data.len() as u32) + 63) / 64;
+        let workgroup_count = (input_data.len() as u32).div_ceil(64);
         compute_pass.dispatch_workgroups(workgroup_count, 1, 1);
     }
 
@@ -0,0 +1,307 @@
+use crate::{
+    check_wgsl,
+    color_attachment,
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    GpuContext,
+    RenderCommands,
+    RenderPipeline,
+    Result,
+    TypedBuffer,
+};
+use crate::shadertoy::{ fullscreen_pipeline, FULLSCREEN_VERTEX };
+use std::sync::Arc;
+
+/// Fragments per pixel the resolve pass sorts; nodes past this are dropped
+pub const ABUFFER_MAX_FRAGMENTS: u32 = 16;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ABufferNode {
+    color: [u32; 2],
+    depth: f32,
+    next: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ABufferParams {
+    width: u32,
+    capacity: u32,
+    _padding: [u32; 2],
+}
+
+/// Per-pixel linked lists of transparent fragments, for order-independent
+/// transparency and decals.
+///
+/// Each frame, [`clear`](Self::clear) the lists, draw transparent geometry
+/// with pipelines that include [`wgsl`](Self::wgsl) and call
+/// `abuffer_insert` instead of writing a color, then [`resolve`](Self::resolve)
+/// the lists over the opaque image. Fragments are stored in a shared node
+/// pool of `capacity` entries; fragments past it are dropped.
+///
+/// The lists live in storage buffers written from fragment shaders, which
+/// downlevel (WebGL) adapters do not support.
+pub struct ABuffer {
+    heads: TypedBuffer<u32>,
+    nodes: TypedBuffer<ABufferNode>,
+    counter: TypedBuffer<u32>,
+    params: TypedBuffer<ABufferParams>,
+    layout: Arc<wgpu::BindGroupLayout>,
+    bind_group: wgpu::BindGroup,
+    pipeline: RenderPipeline,
+    size: (u32, u32),
+    capacity: u32,
+}
+
+impl ABuffer {
+    /// Lists for a `width` x `height` target with room for `capacity`
+    /// fragments in total, resolved into targets of `target_format`
+    pub fn new(
+        context: &GpuContext,
+        width: u32,
+        height: u32,
+        capacity: u32,
+        target_format: wgpu::TextureFormat
+    ) -> Result<Self> {
+        let visibility = wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE;
+        let layout = BindGroupLayoutBuilder::new()
+            .storage_buffer(0, visibility, false)
+            .storage_buffer(1, visibility, false)
+            .storage_buffer(2, visibility, false)
+            .uniform_buffer(3, visibility)
+            .build(context, Some("A-Buffer Layout"));
+        let source = Self::shader_source();
+        check_wgsl("A-Buffer Resolve", None, &source)?;
+        let pipeline = fullscreen_pipeline(
+            context,
+            &source,
+            vec![layout.clone()],
+            wgpu::ColorTargetState {
+                format: target_format,
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            },
+            "A-Buffer Resolve"
+        );
+
+        let (width, height) = (width.max(1), height.max(1));
+        // Node 0 is the end-of-list marker, so the pool has one extra entry
+        let capacity = capacity.max(1) + 1;
+        let heads = Self::create_heads(context, width, height)?;
+        let nodes = TypedBuffer::empty(
+            context,
+            capacity as usize,
+            wgpu::BufferUsages::STORAGE
+        )?;
+        let counter = TypedBuffer::empty(
+            context,
+            1,
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST
+        )?;
+        let params = TypedBuffer::uniform(
+            context,
+            &[ABufferParams { width, capacity, _padding: [0; 2] }]
+        )?;
+        let bind_group = Self::create_bind_group(
+            context,
+            &layout,
+            &heads,
+            &nodes,
+            &counter,
+            &params
+        );
+
+        Ok(Self {
+            heads,
+            nodes,
+            counter,
+            params,
+            layout,
+            bind_group,
+            pipeline,
+            size: (width, height),
+            capacity,
+        })
+    }
+
+    /// The resolve pass's WGSL
+    pub fn shader_source() -> String {
+        let max_fragments = ABUFFER_MAX_FRAGMENTS.to_string();
+        let resolve = RESOLVE_FRAGMENT.replace("{max_fragments}", &max_fragments);
+        format!("{}{}{}", FULLSCREEN_VERTEX, Self::wgsl(0), resolve)
+    }
+
+    /// Declarations and `abuffer_insert(position, color)` for shaders binding
+    /// [`bind_group`](Self::bind_group) at `group`. `position` is the
+    /// fragment's `@builtin(position)` and `color` is straight (not
+    /// premultiplied) alpha.
+    pub fn wgsl(group: u32) -> String {
+        ABUFFER_WGSL.replace("{group}", &group.to_string())
+    }
+
+    fn create_heads(context: &GpuContext, width: u32, height: u32) -> Result<TypedBuffer<u32>> {
+        TypedBuffer::empty(
+            context,
+            (width as usize) * (height as usize),
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST
+        )
+    }
+
+    fn create_bind_group(
+        context: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        heads: &TypedBuffer<u32>,
+        nodes: &TypedBuffer<ABufferNode>,
+        counter: &TypedBuffer<u32>,
+        params: &TypedBuffer<ABufferParams>
+    ) -> wgpu::BindGroup {
+        BindGroupBuilder::new(layout)
+            .buffer(0, heads.buffer())
+            .buffer(1, nodes.buffer())
+            .buffer(2, counter.buffer())
+            .buffer(3, params.buffer())
+            .build(context, Some("A-Buffer Bind Group"))
+    }
+
+    /// Follow a new target size; the lists are empty afterwards until the next
+    /// [`clear`](Self::clear)
+    pub fn resize(&mut self, context: &GpuContext, width: u32, height: u32) -> Result<()> {
+        let (width, height) = (width.max(1), height.max(1));
+        if self.size == (width, height) {
+            return Ok(());
+        }
+        self.heads = Self::create_heads(context, width, height)?;
+        self.params.write(
+            context,
+            &[ABufferParams { width, capacity: self.capacity, _padding: [0; 2] }]
+        )?;
+        self.bind_group = Self::create_bind_group(
+            context,
+            &self.layout,
+            &self.heads,
+            &self.nodes,
+            &self.counter,
+            &self.params
+        );
+        self.size = (width, height);
+        Ok(())
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Fragments the node pool holds
+    pub fn capacity(&self) -> u32 {
+        self.capacity - 1
+    }
+
+    /// Layout for pipelines that insert fragments
+    pub fn layout(&self) -> &Arc<wgpu::BindGroupLayout> {
+        &self.layout
+    }
+
+    /// Bind group matching [`wgsl`](Self::wgsl), usable from fragment and
+    /// compute shaders
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Empty every list. Takes an encoder so render and compute work can both
+    /// clear it, via [`RenderCommands::encoder`] or
+    /// [`ComputeCommands::encoder`](crate::ComputeCommands::encoder).
+    pub fn clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(self.heads.buffer(), 0, None);
+        encoder.clear_buffer(self.counter.buffer(), 0, None);
+    }
+
+    /// Sort each pixel's fragments by depth and blend them over `target`, a
+    /// view of the target format the lists' size
+    pub fn resolve(&self, commands: &mut RenderCommands, target: &wgpu::TextureView) {
+        let color = [Some(color_attachment(target, None))];
+        let mut pass = commands.begin_render_pass(&color, None, Some("A-Buffer Resolve"));
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+const ABUFFER_WGSL: &str =
+    r#"
+struct ABufferNode {
+    color: vec2<u32>,
+    depth: f32,
+    next: u32,
+}
+
+struct ABufferParams {
+    width: u32,
+    capacity: u32,
+}
+
+@group({group}) @binding(0) var<storage, read_write> abuffer_heads: array<atomic<u32>>;
+@group({group}) @binding(1) var<storage, read_write> abuffer_nodes: array<ABufferNode>;
+@group({group}) @binding(2) var<storage, read_write> abuffer_counter: atomic<u32>;
+@group({group}) @binding(3) var<uniform> abuffer_params: ABufferParams;
+
+fn abuffer_head(position: vec4<f32>) -> u32 {
+    let pixel = vec2<u32>(position.xy);
+    return pixel.y * abuffer_params.width + pixel.x;
+}
+
+fn abuffer_insert(position: vec4<f32>, color: vec4<f32>) {
+    let node = atomicAdd(&abuffer_counter, 1u) + 1u;
+    if (node >= abuffer_params.capacity) {
+        return;
+    }
+    let next = atomicExchange(&abuffer_heads[abuffer_head(position)], node);
+    let packed = vec2<u32>(pack2x16float(color.rg), pack2x16float(color.ba));
+    abuffer_nodes[node] = ABufferNode(packed, position.z, next);
+}
+"#;
+
+const RESOLVE_FRAGMENT: &str =
+    r#"
+const MAX_FRAGMENTS: u32 = {max_fragments}u;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    var colors: array<vec4<f32>, MAX_FRAGMENTS>;
+    var depths: array<f32, MAX_FRAGMENTS>;
+    var count = 0u;
+    var node = atomicLoad(&abuffer_heads[abuffer_head(in.clip_position)]);
+    loop {
+        if (node == 0u || count == MAX_FRAGMENTS) {
+            break;
+        }
+        let entry = abuffer_nodes[node];
+        let color = vec4<f32>(unpack2x16float(entry.color.x), unpack2x16float(entry.color.y));
+        // Insertion sort, nearest first
+        var i = count;
+        loop {
+            if (i == 0u || depths[i - 1u] <= entry.depth) {
+                break;
+            }
+            colors[i] = colors[i - 1u];
+            depths[i] = depths[i - 1u];
+            i = i - 1u;
+        }
+        colors[i] = color;
+        depths[i] = entry.depth;
+        count = count + 1u;
+        node = entry.next;
+    }
+    if (count == 0u) {
+        discard;
+    }
+
+    // Front to back, premultiplied
+    var result = vec4<f32>(0.0);
+    for (var i = 0u; i < count; i = i + 1u) {
+        let color = colors[i];
+        result = result + (1.0 - result.a) * vec4<f32>(color.rgb * color.a, color.a);
+    }
+    return result;
+}
+"#;
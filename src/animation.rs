@@ -0,0 +1,382 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    GpuContext,
+    MaterialShader,
+    Result,
+    TypedBuffer,
+};
+use crate::material::{ PBR_FLAT_AMBIENT, PBR_SHADER };
+use glam::{ Mat4, Quat, Vec3 };
+use std::sync::Arc;
+
+/// Per-vertex skinning data: four joint indices and their weights, read from
+/// vertex buffer slot 1 at shader locations 3 and 4
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkinWeights {
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
+}
+
+impl SkinWeights {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![3 => Uint32x4, 4 => Float32x4];
+
+    pub fn new(joints: [u32; 4], weights: [f32; 4]) -> Self {
+        Self { joints, weights }
+    }
+
+    /// Vertex layout for buffer slot 1
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Translation, rotation and scale of a joint relative to its parent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointTransform {
+    pub translation: [f32; 3],
+    /// Unit quaternion as `[x, y, z, w]`
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl JointTransform {
+    pub const IDENTITY: Self = Self {
+        translation: [0.0; 3],
+        rotation: [0.0, 0.0, 0.0, 1.0],
+        scale: [1.0; 3],
+    };
+
+    pub fn new(translation: [f32; 3], rotation: [f32; 4], scale: [f32; 3]) -> Self {
+        Self { translation, rotation, scale }
+    }
+
+    pub fn matrix(&self) -> [[f32; 4]; 4] {
+        Mat4::from_scale_rotation_translation(
+            self.scale.into(),
+            Quat::from_array(self.rotation),
+            self.translation.into()
+        ).to_cols_array_2d()
+    }
+
+    /// Interpolate towards `other` (spherically for the rotation)
+    pub fn blend(&self, other: &Self, weight: f32) -> Self {
+        let lerp = |a: [f32; 3], b: [f32; 3]| Vec3::from(a).lerp(b.into(), weight).to_array();
+        Self {
+            translation: lerp(self.translation, other.translation),
+            rotation: Quat::from_array(self.rotation)
+                .slerp(Quat::from_array(other.rotation), weight)
+                .to_array(),
+            scale: lerp(self.scale, other.scale),
+        }
+    }
+}
+
+impl Default for JointTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// A joint of a [`Skeleton`]
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub name: Option<String>,
+    /// Index of the parent joint
+    pub parent: Option<usize>,
+    /// Local transform when no animation is applied
+    pub rest: JointTransform,
+    /// Transform from mesh space into the joint's bind-pose space
+    pub inverse_bind: [[f32; 4]; 4],
+}
+
+/// A joint hierarchy that turns [`Pose`]s into skinning matrices
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    /// Transform applied above every root joint
+    pub root_transform: [[f32; 4]; 4],
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>) -> Self {
+        Self {
+            joints,
+            root_transform: Mat4::IDENTITY.to_cols_array_2d(),
+        }
+    }
+
+    /// The rest pose
+    pub fn rest_pose(&self) -> Pose {
+        Pose {
+            joints: self.joints
+                .iter()
+                .map(|joint| joint.rest)
+                .collect(),
+        }
+    }
+
+    /// Global (skeleton-space) transform of every joint in `pose`
+    pub fn global_transforms(&self, pose: &Pose) -> Vec<[[f32; 4]; 4]> {
+        let mut globals: Vec<Option<Mat4>> = vec![None; self.joints.len()];
+        for index in 0..self.joints.len() {
+            self.resolve_global(pose, index, &mut globals);
+        }
+        globals
+            .into_iter()
+            .map(|global| global.unwrap_or(Mat4::IDENTITY).to_cols_array_2d())
+            .collect()
+    }
+
+    /// Skinning matrices (global transform times inverse bind matrix) to upload
+    /// into a [`JointBuffer`]
+    pub fn joint_matrices(&self, pose: &Pose) -> Vec<[[f32; 4]; 4]> {
+        self.global_transforms(pose)
+            .iter()
+            .zip(&self.joints)
+            .map(|(global, joint)| {
+                let global = Mat4::from_cols_array_2d(global);
+                (global * Mat4::from_cols_array_2d(&joint.inverse_bind)).to_cols_array_2d()
+            })
+            .collect()
+    }
+
+    fn resolve_global(&self, pose: &Pose, index: usize, globals: &mut [Option<Mat4>]) -> Mat4 {
+        if let Some(global) = globals[index] {
+            return global;
+        }
+        // Seed the entry first so a malformed cycle terminates instead of recursing forever
+        globals[index] = Some(Mat4::IDENTITY);
+        let parent = match self.joints[index].parent {
+            Some(parent) => self.resolve_global(pose, parent, globals),
+            None => Mat4::from_cols_array_2d(&self.root_transform),
+        };
+        let local = pose.joints
+            .get(index)
+            .unwrap_or(&self.joints[index].rest)
+            .matrix();
+        let global = parent * Mat4::from_cols_array_2d(&local);
+        globals[index] = Some(global);
+        global
+    }
+}
+
+/// Local transforms for every joint of a skeleton
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub joints: Vec<JointTransform>,
+}
+
+impl Pose {
+    /// Interpolate every joint towards `other`; `weight` 0 keeps `self`, 1 gives `other`
+    pub fn blend(&self, other: &Pose, weight: f32) -> Pose {
+        Pose {
+            joints: self.joints
+                .iter()
+                .zip(&other.joints)
+                .map(|(a, b)| a.blend(b, weight))
+                .collect(),
+        }
+    }
+}
+
+/// How values between two keyframes are computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interpolation {
+    Step,
+    Linear,
+}
+
+/// Keyframe values of one animated property
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelValues {
+    Translation(Vec<[f32; 3]>),
+    /// Quaternions as `[x, y, z, w]`
+    Rotation(Vec<[f32; 4]>),
+    Scale(Vec<[f32; 3]>),
+}
+
+/// Keyframes animating one property of one joint
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationChannel {
+    /// Index of the animated joint
+    pub target: usize,
+    /// Keyframe times in seconds, ascending
+    pub times: Vec<f32>,
+    pub values: ChannelValues,
+    pub interpolation: Interpolation,
+}
+
+impl AnimationChannel {
+    /// Keyframe pair around `time` and the blend factor between them
+    fn keyframes(&self, time: f32) -> (usize, usize, f32) {
+        let last = self.times.len().saturating_sub(1);
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 {
+            return (0, 0, 0.0);
+        }
+        if next > last {
+            return (last, last, 0.0);
+        }
+        let previous = next - 1;
+        let span = self.times[next] - self.times[previous];
+        let factor = match self.interpolation {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear if span > 0.0 => (time - self.times[previous]) / span,
+            Interpolation::Linear => 0.0,
+        };
+        (previous, next, factor)
+    }
+
+    fn apply(&self, time: f32, transform: &mut JointTransform) {
+        if self.times.is_empty() {
+            return;
+        }
+        let (a, b, t) = self.keyframes(time);
+        let lerp = |values: &[[f32; 3]]| {
+            Vec3::from(values[a]).lerp(values[b].into(), t).to_array()
+        };
+        match &self.values {
+            ChannelValues::Translation(values) => {
+                transform.translation = lerp(values);
+            }
+            ChannelValues::Rotation(values) => {
+                transform.rotation = Quat::from_array(values[a])
+                    .slerp(Quat::from_array(values[b]), t)
+                    .normalize()
+                    .to_array();
+            }
+            ChannelValues::Scale(values) => {
+                transform.scale = lerp(values);
+            }
+        }
+    }
+}
+
+/// A keyframed animation of a skeleton's joints
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    pub name: Option<String>,
+    pub channels: Vec<AnimationChannel>,
+    /// Time of the last keyframe in seconds
+    pub duration: f32,
+}
+
+impl AnimationClip {
+    pub fn new(name: Option<String>, channels: Vec<AnimationChannel>) -> Self {
+        let duration = channels
+            .iter()
+            .filter_map(|channel| channel.times.last().copied())
+            .fold(0.0, f32::max);
+        Self { name, channels, duration }
+    }
+
+    /// Write the animated properties at `time` into `pose`, clamping to the clip's
+    /// range (wrap `time` with `% duration` to loop). Joints the clip does not
+    /// animate keep their values.
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        for channel in &self.channels {
+            if let Some(transform) = pose.joints.get_mut(channel.target) {
+                channel.apply(time, transform);
+            }
+        }
+    }
+
+    /// A copy with channel targets renumbered through `map`; channels whose
+    /// target maps to `None` are dropped
+    pub fn retarget(&self, map: impl Fn(usize) -> Option<usize>) -> Self {
+        let channels = self.channels
+            .iter()
+            .filter_map(|channel| {
+                map(channel.target).map(|target| AnimationChannel { target, ..channel.clone() })
+            })
+            .collect();
+        Self { name: self.name.clone(), channels, duration: self.duration }
+    }
+}
+
+/// Skinning matrices on the GPU, bound at group 3 of skinned material shaders.
+///
+/// The matrices live in a storage buffer read by the vertex stage, which
+/// WebGL2 does not support.
+pub struct JointBuffer {
+    matrices: TypedBuffer<[[f32; 4]; 4]>,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl JointBuffer {
+    /// Create a buffer for `joint_count` joints, initialized to identity
+    pub fn new(context: &GpuContext, joint_count: usize) -> Result<Self> {
+        let identity = vec![Mat4::IDENTITY.to_cols_array_2d(); joint_count.max(1)];
+        let matrices = TypedBuffer::storage(context, &identity)?;
+        let layout = Self::bind_group_layout(context);
+        let bind_group = BindGroupBuilder::new(&layout)
+            .buffer(0, matrices.buffer())
+            .build(context, Some("Joint Bind Group"));
+        Ok(Self { matrices, bind_group })
+    }
+
+    /// Upload skinning matrices, e.g. from [`Skeleton::joint_matrices`]
+    pub fn update(&self, context: &GpuContext, matrices: &[[[f32; 4]; 4]]) -> Result<()> {
+        self.matrices.write(context, matrices)
+    }
+
+    /// Number of joints the buffer holds
+    pub fn len(&self) -> usize {
+        self.matrices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matrices.is_empty()
+    }
+
+    pub(crate) fn bind_group_layout(context: &GpuContext) -> Arc<wgpu::BindGroupLayout> {
+        BindGroupLayoutBuilder::new()
+            .storage_buffer(0, wgpu::ShaderStages::VERTEX, true)
+            .build(context, Some("Joint Layout"))
+    }
+}
+
+impl MaterialShader {
+    /// [`pbr`](Self::pbr) for skinned meshes: vertices are blended by the
+    /// [`JointBuffer`] at group 3 using [`SkinWeights`] in vertex slot 1
+    pub fn pbr_skinned() -> Self {
+        let source = format!("{}{}{}", PBR_SHADER, SKINNED_VERTEX, PBR_FLAT_AMBIENT);
+        Self::new("Skinned PBR Material", &source, 4).skinned(true)
+    }
+}
+
+const SKINNED_VERTEX: &str =
+    r#"
+@group(3) @binding(0) var<storage, read> joints: array<mat4x4<f32>>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) joint_indices: vec4<u32>,
+    @location(4) joint_weights: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    let skin = joints[in.joint_indices.x] * in.joint_weights.x +
+        joints[in.joint_indices.y] * in.joint_weights.y +
+        joints[in.joint_indices.z] * in.joint_weights.z +
+        joints[in.joint_indices.w] * in.joint_weights.w;
+
+    var out: VertexOutput;
+    let world = object.model * skin * vec4<f32>(in.position, 1.0);
+    out.clip_position = camera.view_projection * world;
+    out.world_position = world.xyz;
+    out.normal = (object.normal * skin * vec4<f32>(in.normal, 0.0)).xyz;
+    out.uv = in.uv;
+    return out;
+}
+"#;
@@ -0,0 +1,250 @@
+use crate::{ GeepuError, GpuConfig, GpuContext, RenderCommands, Result, WindowConfig };
+#[cfg(feature = "gamepad")]
+use crate::Gamepads;
+use std::sync::Arc;
+use std::time::Instant;
+use winit::application::ApplicationHandler;
+use winit::event::WindowEvent;
+use winit::event_loop::{ ActiveEventLoop, EventLoop };
+use winit::window::{ Window, WindowId };
+
+/// One frame of [`WindowConfig::run`].
+///
+/// `view` is the acquired surface texture; `commands` is submitted and the
+/// texture presented after [`App::render`] returns.
+pub struct Frame<'a> {
+    pub context: &'a GpuContext,
+    pub view: wgpu::TextureView,
+    pub commands: RenderCommands,
+    /// Seconds since the previous frame
+    pub delta: f32,
+    /// Number of frames rendered before this one
+    pub index: u64,
+    /// Gamepad state, updated right before this frame
+    #[cfg(feature = "gamepad")]
+    pub gamepads: &'a Gamepads,
+}
+
+impl Frame<'_> {
+    /// Surface size in physical pixels
+    pub fn size(&self) -> (u32, u32) {
+        self.context.size()
+    }
+
+    /// Format of [`view`](Self::view), for creating pipelines that draw into it
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.context.surface_format().unwrap_or(wgpu::TextureFormat::Bgra8UnormSrgb)
+    }
+
+    /// Attachment for a pass into [`view`](Self::view), through the context's
+    /// MSAA target when [`GpuConfig::samples`] is above 1
+    pub fn color_attachment(
+        &self,
+        clear_color: Option<wgpu::Color>
+    ) -> wgpu::RenderPassColorAttachment<'_> {
+        self.context.surface_attachment(&self.view, clear_color)
+    }
+}
+
+/// An application driven by [`WindowConfig::run`].
+///
+/// Only [`render`](Self::render) is required; any
+/// `FnMut(&mut Frame) -> Result<()>` closure is an `App` too. An error from
+/// `init` or `render` stops the loop and is returned by `run`.
+pub trait App {
+    /// Create GPU resources once the window and context exist
+    fn init(&mut self, _context: &GpuContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Record the frame into `frame.commands`
+    fn render(&mut self, frame: &mut Frame) -> Result<()>;
+
+    /// Called after the surface was resized to `width` x `height` pixels
+    fn resize(&mut self, _context: &GpuContext, _width: u32, _height: u32) {}
+
+    /// Every window event, before the loop handles it (input, focus, ...)
+    fn window_event(&mut self, _context: &GpuContext, _event: &WindowEvent) {}
+}
+
+impl<F> App for F where F: FnMut(&mut Frame) -> Result<()> {
+    fn render(&mut self, frame: &mut Frame) -> Result<()> {
+        self(frame)
+    }
+}
+
+impl WindowConfig {
+    /// Open the window and run `app` until it is closed.
+    ///
+    /// Drives the winit event loop: creates the context on the first resume,
+    /// recreates the surface after a suspend, reconfigures it on resize and
+    /// scale factor changes, skips frames while minimized, and renders
+    /// continuously otherwise. With the `gamepad` feature it also polls
+    /// [`Frame::gamepads`] before every frame.
+    pub fn run(self, app: impl App) -> Result<()> {
+        self.run_with_config(GpuConfig::default(), app)
+    }
+
+    /// [`run`](Self::run) with a context created from `config`
+    pub fn run_with_config(self, config: GpuConfig, app: impl App) -> Result<()> {
+        let event_loop = EventLoop::new().map_err(|e|
+            GeepuError::Other(format!("Failed to create event loop: {}", e))
+        )?;
+        let mut runner = Runner {
+            window_config: self,
+            config,
+            app,
+            window: None,
+            context: None,
+            last_frame: Instant::now(),
+            frame_index: 0,
+            minimized: false,
+            error: None,
+            #[cfg(feature = "gamepad")]
+            gamepads: Gamepads::new()?,
+        };
+        event_loop
+            .run_app(&mut runner)
+            .map_err(|e| GeepuError::Other(format!("Event loop failed: {}", e)))?;
+        runner.error.map_or(Ok(()), Err)
+    }
+}
+
+struct Runner<A> {
+    window_config: WindowConfig,
+    config: GpuConfig,
+    app: A,
+    window: Option<Arc<Window>>,
+    context: Option<GpuContext>,
+    last_frame: Instant,
+    frame_index: u64,
+    minimized: bool,
+    error: Option<GeepuError>,
+    #[cfg(feature = "gamepad")]
+    gamepads: Gamepads,
+}
+
+impl<A> Runner<A> where A: App {
+    /// Stop the loop, keeping the first error for `run` to return
+    fn fail(&mut self, event_loop: &ActiveEventLoop, error: GeepuError) {
+        log::error!("{}", error);
+        self.error.get_or_insert(error);
+        event_loop.exit();
+    }
+
+    fn start(&mut self, event_loop: &ActiveEventLoop) -> Result<()> {
+        let window = self.window_config.create_window(event_loop)?;
+        let context = pollster::block_on(
+            GpuContext::new_with_window_and_config(window.clone(), self.config.clone())
+        )?;
+        self.app.init(&context)?;
+        window.request_redraw();
+        self.window = Some(window);
+        self.context = Some(context);
+        self.last_frame = Instant::now();
+        Ok(())
+    }
+
+    fn resized(&mut self) -> Result<()> {
+        let (Some(window), Some(context)) = (&self.window, &mut self.context) else {
+            return Ok(());
+        };
+        let size = window.inner_size();
+        self.minimized = size.width == 0 || size.height == 0;
+        if !self.minimized {
+            context.resize(size)?;
+            self.app.resize(context, size.width, size.height);
+            window.request_redraw();
+        }
+        Ok(())
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let (Some(window), Some(context)) = (&self.window, &mut self.context) else {
+            return Ok(());
+        };
+        if self.minimized || context.is_suspended() {
+            return Ok(());
+        }
+        let output = match context.get_current_texture() {
+            Ok(output) => output,
+            Err(e) => {
+                // Lost or outdated surfaces come back after reconfiguring; try next frame
+                log::warn!("{}", e);
+                context.resize(window.inner_size())?;
+                window.request_redraw();
+                return Ok(());
+            }
+        };
+
+        #[cfg(feature = "gamepad")]
+        self.gamepads.update();
+        let now = Instant::now();
+        let delta = now.duration_since(self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        let mut frame = Frame {
+            context,
+            view: output.texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            commands: RenderCommands::new(context, Some("Frame Commands")),
+            delta,
+            index: self.frame_index,
+            #[cfg(feature = "gamepad")]
+            gamepads: &self.gamepads,
+        };
+        self.app.render(&mut frame)?;
+        frame.commands.submit(context);
+        window.pre_present_notify();
+        output.present();
+
+        self.frame_index += 1;
+        window.request_redraw();
+        Ok(())
+    }
+}
+
+impl<A> ApplicationHandler for Runner<A> where A: App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        let result = match (&self.window, &mut self.context) {
+            // Coming back from a suspend: only the surface has to be recreated
+            (Some(window), Some(context)) if context.is_suspended() => {
+                let result = context.resume(window.clone());
+                window.request_redraw();
+                result
+            }
+            (Some(_), Some(_)) => Ok(()),
+            _ => self.start(event_loop),
+        };
+        if let Err(e) = result {
+            self.fail(event_loop, e);
+        }
+    }
+
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(context) = &mut self.context {
+            context.suspend();
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _window_id: WindowId,
+        event: WindowEvent
+    ) {
+        if let Some(context) = &self.context {
+            self.app.window_event(context, &event);
+        }
+        let result = match event {
+            WindowEvent::CloseRequested => {
+                event_loop.exit();
+                Ok(())
+            }
+            WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => self.resized(),
+            WindowEvent::RedrawRequested => self.render(),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            self.fail(event_loop, e);
+        }
+    }
+}
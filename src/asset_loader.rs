@@ -0,0 +1,270 @@
+use crate::{ GeepuError, GpuContext, Mesh, MeshData, Result };
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{ mpsc, Arc, Mutex };
+use std::task::{ Poll, Waker };
+
+/// Decoding work run off the main thread
+type DecodeJob = Box<dyn FnOnce() -> UploadJob + Send>;
+/// GPU upload finishing a decoded asset, run from [`AssetLoader::update`]
+type UploadJob = Box<dyn FnOnce(&GpuContext) + Send>;
+
+enum LoadState<T> {
+    Loading,
+    Ready(Arc<T>),
+    Failed(String),
+}
+
+struct HandleState<T> {
+    state: LoadState<T>,
+    wakers: Vec<Waker>,
+}
+
+/// An asset being loaded by an [`AssetLoader`].
+///
+/// Poll it with [`get`](Self::get) from a frame loop, or `.await` a clone of
+/// it to get the asset once it has been uploaded.
+pub struct AssetHandle<T> {
+    shared: Arc<Mutex<HandleState<T>>>,
+}
+
+impl<T> Clone for AssetHandle<T> {
+    fn clone(&self) -> Self {
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl<T> AssetHandle<T> {
+    fn new() -> Self {
+        Self {
+            shared: Arc::new(
+                Mutex::new(HandleState { state: LoadState::Loading, wakers: Vec::new() })
+            ),
+        }
+    }
+
+    fn resolve(&self, result: Result<T>) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.state = match result {
+            Ok(asset) => LoadState::Ready(Arc::new(asset)),
+            Err(e) => LoadState::Failed(e.to_string()),
+        };
+        for waker in shared.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// The asset, once it has been decoded and uploaded
+    pub fn get(&self) -> Option<Arc<T>> {
+        match &self.shared.lock().unwrap().state {
+            LoadState::Ready(asset) => Some(asset.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        matches!(self.shared.lock().unwrap().state, LoadState::Loading)
+    }
+
+    /// Why loading failed, if it did
+    pub fn error(&self) -> Option<String> {
+        match &self.shared.lock().unwrap().state {
+            LoadState::Failed(message) => Some(message.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<T> Future for AssetHandle<T> {
+    type Output = Result<Arc<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap();
+        match &shared.state {
+            LoadState::Ready(asset) => Poll::Ready(Ok(asset.clone())),
+            LoadState::Failed(message) => Poll::Ready(Err(GeepuError::Other(message.clone()))),
+            LoadState::Loading => {
+                shared.wakers.push(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Loads textures, meshes and scenes without stalling the frame loop.
+///
+/// Files are read and decoded on a pool of worker threads; the GPU uploads
+/// happen on the thread calling [`update`](Self::update), at most
+/// [`uploads_per_frame`](Self::uploads_per_frame) per call, so a burst of
+/// finished loads is spread over several frames. On the web, where there are
+/// no threads, decoding also happens in `update`.
+pub struct AssetLoader {
+    #[cfg(not(target_arch = "wasm32"))]
+    jobs: mpsc::Sender<DecodeJob>,
+    #[cfg(target_arch = "wasm32")]
+    jobs: VecDeque<DecodeJob>,
+    decoded: mpsc::Receiver<UploadJob>,
+    ready: VecDeque<UploadJob>,
+    uploads_per_frame: usize,
+    pending: usize,
+}
+
+impl AssetLoader {
+    /// Create a loader decoding on `threads` worker threads
+    pub fn new(threads: usize) -> Self {
+        let (decoded_sender, decoded) = mpsc::channel();
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            jobs: Self::spawn_workers(threads.max(1), decoded_sender),
+            #[cfg(target_arch = "wasm32")]
+            jobs: {
+                let _ = (threads, decoded_sender);
+                VecDeque::new()
+            },
+            decoded,
+            ready: VecDeque::new(),
+            uploads_per_frame: 4,
+            pending: 0,
+        }
+    }
+
+    /// Upload at most `count` finished assets per [`update`](Self::update) (default 4)
+    pub fn uploads_per_frame(mut self, count: usize) -> Self {
+        self.uploads_per_frame = count.max(1);
+        self
+    }
+
+    /// Decode with `decode` on a worker thread, then create the GPU asset
+    /// from the result with `upload` during a later [`update`](Self::update)
+    pub fn load<D, T>(
+        &mut self,
+        decode: impl FnOnce() -> Result<D> + Send + 'static,
+        upload: impl FnOnce(&GpuContext, D) -> Result<T> + Send + 'static
+    ) -> AssetHandle<T>
+        where D: Send + 'static, T: Send + Sync + 'static
+    {
+        let handle = AssetHandle::new();
+        let resolved = handle.clone();
+        let job: DecodeJob = Box::new(move || {
+            let decoded = decode();
+            Box::new(move |context: &GpuContext| {
+                resolved.resolve(decoded.and_then(|data| upload(context, data)));
+            })
+        });
+        self.pending += 1;
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = self.jobs.send(job);
+        #[cfg(target_arch = "wasm32")]
+        self.jobs.push_back(job);
+        handle
+    }
+
+    /// Load a PNG, JPEG or Radiance HDR image as a texture
+    #[cfg(feature = "image")]
+    pub fn load_texture(
+        &mut self,
+        path: impl Into<std::path::PathBuf>
+    ) -> AssetHandle<crate::Texture> {
+        use crate::ResultExt;
+
+        let path = path.into();
+        let label = path.display().to_string();
+        self.load(
+            move || image::open(&path).with_context(|| format!("loading {}", path.display())),
+            move |context, image| crate::Texture::from_image(context, &image, Some(&label))
+        )
+    }
+
+    /// Load a `.gltf` or `.glb` scene
+    #[cfg(feature = "gltf")]
+    pub fn load_gltf(
+        &mut self,
+        path: impl Into<std::path::PathBuf>
+    ) -> AssetHandle<crate::GltfScene> {
+        use crate::ResultExt;
+
+        let path = path.into();
+        self.load(
+            move || gltf::import(&path).with_context(|| format!("loading {}", path.display())),
+            |context, (document, buffers, images)| {
+                crate::GltfScene::from_document(context, &document, &buffers, &images)
+            }
+        )
+    }
+
+    /// Build mesh geometry with `decode` (e.g. parse a model file) and upload it
+    pub fn load_mesh<V>(
+        &mut self,
+        decode: impl FnOnce() -> Result<MeshData<V>> + Send + 'static,
+        layout: wgpu::VertexBufferLayout<'static>
+    ) -> AssetHandle<Mesh<V>>
+        where V: bytemuck::Pod + Send + Sync
+    {
+        self.load(decode, move |context, data| data.upload(context, layout))
+    }
+
+    /// Upload assets that finished decoding, up to the per-frame budget.
+    /// Call once per frame.
+    pub fn update(&mut self, context: &GpuContext) {
+        #[cfg(target_arch = "wasm32")]
+        for _ in 0..self.uploads_per_frame {
+            match self.jobs.pop_front() {
+                Some(job) => self.ready.push_back(job()),
+                None => {
+                    break;
+                }
+            }
+        }
+
+        self.ready.extend(self.decoded.try_iter());
+        for _ in 0..self.uploads_per_frame {
+            let Some(upload) = self.ready.pop_front() else {
+                break;
+            };
+            upload(context);
+            self.pending -= 1;
+        }
+    }
+
+    /// Number of assets still being decoded or waiting for upload
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn_workers(threads: usize, decoded: mpsc::Sender<UploadJob>) -> mpsc::Sender<DecodeJob> {
+        let (sender, receiver) = mpsc::channel::<DecodeJob>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for index in 0..threads {
+            let receiver = receiver.clone();
+            let decoded = decoded.clone();
+            let spawned = std::thread::Builder
+                ::new()
+                .name(format!("geepu-asset-{}", index))
+                .spawn(move || {
+                    loop {
+                        let job = receiver.lock().unwrap().recv();
+                        let Ok(job) = job else {
+                            break;
+                        };
+                        if decoded.send(job()).is_err() {
+                            break;
+                        }
+                    }
+                });
+            if let Err(e) = spawned {
+                log::warn!("Failed to spawn asset loader thread: {}", e);
+            }
+        }
+        sender
+    }
+}
+
+impl Default for AssetLoader {
+    /// A loader with one worker per available core, leaving one for the main thread
+    fn default() -> Self {
+        let cores = std::thread::available_parallelism().map_or(2, |cores| cores.get());
+        Self::new(cores.saturating_sub(1))
+    }
+}
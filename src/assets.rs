@@ -0,0 +1,543 @@
+use crate::{ check_wgsl, GeepuError, GpuContext, Result };
+use std::any::{ Any, TypeId };
+use std::collections::{ BTreeSet, HashMap };
+use std::marker::PhantomData;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Weak };
+use std::time::SystemTime;
+
+/// Stable identifier of an asset in an [`Assets`] registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssetId(u64);
+
+/// Reference-counted handle to an asset of type `T`.
+///
+/// The asset stays in the registry while any clone of the handle is alive;
+/// reloading replaces the value behind the handle without changing it.
+pub struct Handle<T> {
+    id: AssetId,
+    alive: Arc<()>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self { id: self.id, alive: self.alive.clone(), _marker: PhantomData }
+    }
+}
+
+impl<T> Handle<T> {
+    pub fn id(&self) -> AssetId {
+        self.id
+    }
+
+    /// The handle without its type, for listing dependencies
+    pub fn untyped(&self) -> UntypedHandle {
+        UntypedHandle { id: self.id, _alive: self.alive.clone() }
+    }
+}
+
+/// A [`Handle`] of any asset type, keeping the asset alive just the same
+#[derive(Clone)]
+pub struct UntypedHandle {
+    id: AssetId,
+    _alive: Arc<()>,
+}
+
+impl UntypedHandle {
+    pub fn id(&self) -> AssetId {
+        self.id
+    }
+}
+
+/// WGSL source loaded by [`Assets::load_wgsl`], for pipeline constructors
+/// that take source text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WgslSource {
+    /// The file's path, used as the shader's name in diagnostics
+    pub name: String,
+    pub source: String,
+}
+
+/// An `Arc<T>` of the asset's type
+type Value = Box<dyn Any>;
+type Loader = Box<dyn Fn(&GpuContext, &Path) -> Result<Value>>;
+type Builder = Box<dyn Fn(&Assets, &GpuContext) -> Result<Value>>;
+
+enum Source {
+    Value,
+    File {
+        path: PathBuf,
+        /// `path` with an absolute directory, as file system events name it
+        #[cfg(feature = "hot-reload")]
+        watched: PathBuf,
+        modified: Option<SystemTime>,
+        load: Loader,
+    },
+    Derived {
+        dependencies: Vec<UntypedHandle>,
+        build: Builder,
+    },
+}
+
+struct Entry {
+    value: Value,
+    alive: Weak<()>,
+    generation: u64,
+    source: Source,
+}
+
+/// Registry of textures, shaders, meshes and anything built from them.
+///
+/// Assets are loaded from files with a loader function, inserted directly, or
+/// [`derive`](Self::derive)d from other assets: a material and its bind group
+/// built from texture handles, or a pipeline built from a shader handle.
+/// Reloading an asset rebuilds everything derived from it, in dependency
+/// order. With [`watch`](Self::watch) on, [`update`](Self::update) reloads
+/// files that changed: reported by a file system watcher with the
+/// `hot-reload` feature, by polling modification times otherwise.
+#[derive(Default)]
+pub struct Assets {
+    entries: HashMap<AssetId, Entry>,
+    paths: HashMap<(PathBuf, TypeId), AssetId>,
+    next_id: u64,
+    watch: bool,
+    #[cfg(feature = "hot-reload")]
+    watcher: Option<FileWatcher>,
+}
+
+/// File system watcher on the directories of loaded files
+#[cfg(feature = "hot-reload")]
+struct FileWatcher {
+    watcher: notify::RecommendedWatcher,
+    events: std::sync::mpsc::Receiver<notify::Result<notify::Event>>,
+    directories: std::collections::HashSet<PathBuf>,
+}
+
+#[cfg(feature = "hot-reload")]
+impl FileWatcher {
+    fn new() -> notify::Result<Self> {
+        let (sender, events) = std::sync::mpsc::channel();
+        Ok(Self {
+            watcher: notify::recommended_watcher(sender)?,
+            events,
+            directories: Default::default(),
+        })
+    }
+
+    /// Watch the directory of `path`, so editors that save by replacing the
+    /// file are still noticed
+    fn watch(&mut self, path: &Path) {
+        use notify::Watcher;
+
+        let Some(directory) = path.parent() else {
+            return;
+        };
+        if self.directories.contains(directory) {
+            return;
+        }
+        match self.watcher.watch(directory, notify::RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                self.directories.insert(directory.to_path_buf());
+            }
+            Err(e) => log::warn!("Failed to watch {}: {}", directory.display(), e),
+        }
+    }
+
+    /// Paths created or modified since the last call
+    fn changed_paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) if event.kind.is_modify() || event.kind.is_create() => {
+                    paths.extend(event.paths);
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("File watcher error: {}", e),
+            }
+        }
+        paths
+    }
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reload changed files from [`update`](Self::update)
+    pub fn watch(mut self, enabled: bool) -> Self {
+        self.watch = enabled;
+        #[cfg(feature = "hot-reload")]
+        {
+            self.watcher = None;
+            if enabled {
+                match FileWatcher::new() {
+                    Ok(mut watcher) => {
+                        for entry in self.entries.values() {
+                            if let Source::File { watched, .. } = &entry.source {
+                                watcher.watch(watched);
+                            }
+                        }
+                        self.watcher = Some(watcher);
+                    }
+                    Err(e) => log::warn!("Falling back to polling for asset changes: {}", e),
+                }
+            }
+        }
+        self
+    }
+
+    /// Add an asset that is not loaded from a file
+    pub fn insert<T>(&mut self, value: T) -> Handle<T> where T: 'static {
+        self.add(Box::new(Arc::new(value)), Source::Value)
+    }
+
+    /// Load the asset at `path` with `loader`, or return the handle of the
+    /// `T` already loaded from that path
+    pub fn load<T>(
+        &mut self,
+        context: &GpuContext,
+        path: impl AsRef<Path>,
+        loader: impl Fn(&GpuContext, &Path) -> Result<T> + 'static
+    ) -> Result<Handle<T>>
+        where T: 'static
+    {
+        let path = path.as_ref().to_path_buf();
+        let key = (path.clone(), TypeId::of::<T>());
+        if let Some(handle) = self.paths.get(&key).and_then(|&id| self.handle(id)) {
+            return Ok(handle);
+        }
+
+        let load: Loader = Box::new(move |context, path| {
+            loader(context, path).map(|value| Box::new(Arc::new(value)) as Value)
+        });
+        let value = load(context, &path)?;
+        let modified = modified_time(&path);
+        #[cfg(feature = "hot-reload")]
+        let watched = absolute_path(&path);
+        #[cfg(feature = "hot-reload")]
+        if let Some(watcher) = &mut self.watcher {
+            watcher.watch(&watched);
+        }
+        let handle = self.add(value, Source::File {
+            path,
+            #[cfg(feature = "hot-reload")]
+            watched,
+            modified,
+            load,
+        });
+        self.paths.insert(key, handle.id);
+        Ok(handle)
+    }
+
+    /// Load and compile a WGSL shader module
+    pub fn load_shader(
+        &mut self,
+        context: &GpuContext,
+        path: impl AsRef<Path>
+    ) -> Result<Handle<wgpu::ShaderModule>> {
+        self.load(context, path, |context, path| {
+            let source = read_to_string(path)?;
+            let name = path.display().to_string();
+            check_wgsl(&name, None, &source)?;
+            Ok(
+                context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(&context.label(&name)),
+                    source: wgpu::ShaderSource::Wgsl(source.into()),
+                })
+            )
+        })
+    }
+
+    /// Load and validate WGSL source. Unlike [`load_shader`](Self::load_shader)
+    /// this keeps the text, so pipelines built with
+    /// [`derive_from_shader`](Self::derive_from_shader) are rebuilt when the
+    /// file changes. An edit that fails validation keeps the previous source.
+    pub fn load_wgsl(
+        &mut self,
+        context: &GpuContext,
+        path: impl AsRef<Path>
+    ) -> Result<Handle<WgslSource>> {
+        self.load(context, path, |_, path| {
+            let source = read_to_string(path)?;
+            let name = path.display().to_string();
+            check_wgsl(&name, None, &source)?;
+            Ok(WgslSource { name, source })
+        })
+    }
+
+    /// Load a PNG, JPEG or Radiance HDR texture
+    #[cfg(feature = "image")]
+    pub fn load_texture(
+        &mut self,
+        context: &GpuContext,
+        path: impl AsRef<Path>
+    ) -> Result<Handle<crate::Texture>> {
+        self.load(context, path, |context, path| {
+            let bytes = std::fs::read(path).map_err(|e| {
+                GeepuError::Other(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            crate::Texture::from_image_bytes(context, &bytes, Some(&path.display().to_string()))
+        })
+    }
+
+    /// Load a pre-compressed KTX2 or DDS texture with its mip chain
+    pub fn load_compressed_texture(
+        &mut self,
+        context: &GpuContext,
+        path: impl AsRef<Path>
+    ) -> Result<Handle<crate::Texture>> {
+        self.load(context, path, |context, path| {
+            let bytes = std::fs::read(path).map_err(|e| {
+                GeepuError::Other(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            let label = path.display().to_string();
+            crate::Texture::from_compressed_bytes(context, &bytes, Some(&label))
+        })
+    }
+
+    /// Build an asset from others with `build`, and build it again whenever
+    /// one of `dependencies` is reloaded
+    pub fn derive<T>(
+        &mut self,
+        context: &GpuContext,
+        dependencies: &[UntypedHandle],
+        build: impl Fn(&Assets, &GpuContext) -> Result<T> + 'static
+    ) -> Result<Handle<T>>
+        where T: 'static
+    {
+        let build: Builder = Box::new(move |assets, context| {
+            build(assets, context).map(|value| Box::new(Arc::new(value)) as Value)
+        });
+        let value = build(self, context)?;
+        let dependencies = dependencies.to_vec();
+        Ok(self.add(value, Source::Derived { dependencies, build }))
+    }
+
+    /// [`derive`](Self::derive) an asset, usually a pipeline, from one shader,
+    /// e.g. `|context, shader| ComputePipeline::new(context, &shader.source, vec![], None)`
+    pub fn derive_from_shader<T>(
+        &mut self,
+        context: &GpuContext,
+        shader: &Handle<WgslSource>,
+        build: impl Fn(&GpuContext, &WgslSource) -> Result<T> + 'static
+    ) -> Result<Handle<T>>
+        where T: 'static
+    {
+        let source = shader.clone();
+        self.derive(context, &[shader.untyped()], move |assets, context| {
+            let shader = assets
+                .get(&source)
+                .ok_or_else(|| GeepuError::Other("Shader asset was removed".to_string()))?;
+            build(context, &shader)
+        })
+    }
+
+    /// The current value behind `handle`
+    pub fn get<T>(&self, handle: &Handle<T>) -> Option<Arc<T>> where T: 'static {
+        self.entries.get(&handle.id)?.value.downcast_ref::<Arc<T>>().cloned()
+    }
+
+    /// The value behind `handle` if it was reloaded or rebuilt since the
+    /// generation in `seen`, which is then updated. Poll it each frame to swap
+    /// in a rebuilt pipeline.
+    pub fn get_if_changed<T>(&self, handle: &Handle<T>, seen: &mut u64) -> Option<Arc<T>>
+        where T: 'static
+    {
+        let generation = self.generation(handle.id)?;
+        if generation == *seen {
+            return None;
+        }
+        *seen = generation;
+        self.get(handle)
+    }
+
+    /// How many times the asset has been reloaded or rebuilt
+    pub fn generation(&self, id: AssetId) -> Option<u64> {
+        self.entries.get(&id).map(|entry| entry.generation)
+    }
+
+    /// Assets derived directly from `id`
+    pub fn dependents(&self, id: AssetId) -> Vec<AssetId> {
+        let mut dependents: Vec<AssetId> = self.entries
+            .iter()
+            .filter(|(_, entry)| {
+                matches!(
+                    &entry.source,
+                    Source::Derived { dependencies, .. }
+                        if dependencies.iter().any(|dependency| dependency.id == id)
+                )
+            })
+            .map(|(&dependent, _)| dependent)
+            .collect();
+        dependents.sort();
+        dependents
+    }
+
+    /// Reload `id` from its file and rebuild everything derived from it.
+    ///
+    /// Returns the assets that changed. When a rebuild fails the previous
+    /// value is kept and the error logged; only a failure of `id` itself is
+    /// returned.
+    pub fn reload(&mut self, context: &GpuContext, id: AssetId) -> Result<Vec<AssetId>> {
+        if let Some(value) = self.rebuild(context, id)? {
+            self.replace(id, value);
+        }
+
+        // Derived assets always have higher ids than what they were built
+        // from, so ascending order rebuilds dependencies first
+        let mut affected = BTreeSet::new();
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            for dependent in self.dependents(id) {
+                if affected.insert(dependent) {
+                    stack.push(dependent);
+                }
+            }
+        }
+
+        let mut changed = vec![id];
+        for dependent in affected {
+            match self.rebuild(context, dependent) {
+                Ok(Some(value)) => {
+                    self.replace(dependent, value);
+                    changed.push(dependent);
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to rebuild {:?}: {}", dependent, e),
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Drop unreferenced assets and, when watching, reload changed files.
+    /// Returns the assets that changed; call once per frame.
+    pub fn update(&mut self, context: &GpuContext) -> Vec<AssetId> {
+        self.collect_garbage();
+        if !self.watch {
+            return Vec::new();
+        }
+
+        let mut modified = self.modified_files();
+        modified.sort();
+        modified.dedup();
+
+        let mut changed = Vec::new();
+        for id in modified {
+            match self.reload(context, id) {
+                Ok(ids) => changed.extend(ids),
+                Err(e) => log::warn!("Failed to reload {:?}: {}", id, e),
+            }
+        }
+        changed
+    }
+
+    /// Remove assets no handle refers to any more
+    pub fn collect_garbage(&mut self) {
+        // Dropping a derived asset releases its dependencies, so repeat
+        loop {
+            let before = self.entries.len();
+            self.entries.retain(|_, entry| entry.alive.strong_count() > 0);
+            if self.entries.len() == before {
+                break;
+            }
+        }
+        let entries = &self.entries;
+        self.paths.retain(|_, id| entries.contains_key(id));
+    }
+
+    /// Number of assets in the registry
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Loaded files that changed since the last [`update`](Self::update)
+    fn modified_files(&mut self) -> Vec<AssetId> {
+        #[cfg(feature = "hot-reload")]
+        if let Some(watcher) = &self.watcher {
+            let paths = watcher.changed_paths();
+            return self.entries
+                .iter()
+                .filter(|(_, entry)| {
+                    matches!(&entry.source, Source::File { watched, .. } if paths.contains(watched))
+                })
+                .map(|(&id, _)| id)
+                .collect();
+        }
+
+        let mut modified = Vec::new();
+        for (&id, entry) in &mut self.entries {
+            if let Source::File { path, modified: last, .. } = &mut entry.source {
+                let current = modified_time(path);
+                if current != *last {
+                    *last = current;
+                    modified.push(id);
+                }
+            }
+        }
+        modified
+    }
+
+    fn add<T>(&mut self, value: Value, source: Source) -> Handle<T> {
+        let id = AssetId(self.next_id);
+        self.next_id += 1;
+        let alive = Arc::new(());
+        let entry = Entry { value, alive: Arc::downgrade(&alive), generation: 0, source };
+        self.entries.insert(id, entry);
+        Handle { id, alive, _marker: PhantomData }
+    }
+
+    fn handle<T>(&self, id: AssetId) -> Option<Handle<T>> {
+        let alive = self.entries.get(&id)?.alive.upgrade()?;
+        Some(Handle { id, alive, _marker: PhantomData })
+    }
+
+    /// A new value for `id`, or `None` for assets not loaded or derived
+    fn rebuild(&self, context: &GpuContext, id: AssetId) -> Result<Option<Value>> {
+        let entry = self.entries
+            .get(&id)
+            .ok_or_else(|| GeepuError::Other(format!("Unknown asset {:?}", id)))?;
+        match &entry.source {
+            Source::Value => Ok(None),
+            Source::File { path, load, .. } => load(context, path).map(Some),
+            Source::Derived { build, .. } => build(self, context).map(Some),
+        }
+    }
+
+    fn replace(&mut self, id: AssetId, value: Value) {
+        if let Some(entry) = self.entries.get_mut(&id) {
+            entry.value = value;
+            entry.generation += 1;
+        }
+    }
+}
+
+/// `path` with its directory made absolute and canonical; the file itself
+/// may be missing while an editor replaces it
+#[cfg(feature = "hot-reload")]
+fn absolute_path(path: &Path) -> PathBuf {
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+    let directory = directory.canonicalize().unwrap_or_else(|_| directory.to_path_buf());
+    match path.file_name() {
+        Some(name) => directory.join(name),
+        None => path.to_path_buf(),
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn read_to_string(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| {
+        GeepuError::Other(format!("Failed to read {}: {}", path.display(), e))
+    })
+}
@@ -0,0 +1,58 @@
+use crate::GpuContext;
+use crate::profiling::profile_scope;
+
+/// Finished [`RenderCommands`](crate::RenderCommands) and
+/// [`ComputeCommands`](crate::ComputeCommands) submitted together in a single
+/// `queue.submit`, in the order they were added
+#[derive(Default)]
+pub struct CommandBatch {
+    command_buffers: Vec<wgpu::CommandBuffer>,
+}
+
+impl CommandBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append commands to run after everything already in the batch.
+    ///
+    /// Accepts `RenderCommands`, `ComputeCommands` or any finished
+    /// `wgpu::CommandBuffer`.
+    pub fn push(&mut self, commands: impl Into<wgpu::CommandBuffer>) {
+        self.command_buffers.push(commands.into());
+    }
+
+    /// Builder form of [`push`](Self::push)
+    pub fn with(mut self, commands: impl Into<wgpu::CommandBuffer>) -> Self {
+        self.push(commands);
+        self
+    }
+
+    /// Number of command buffers in the batch
+    pub fn len(&self) -> usize {
+        self.command_buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.command_buffers.is_empty()
+    }
+
+    /// Submit every command buffer in order, preceded by any batched uploads
+    pub fn submit(self, context: &GpuContext) {
+        profile_scope!("geepu::submit");
+        if let Some(dump) = context.command_recorder() {
+            dump.record(format!("submit batch of {} command buffers", self.len()));
+        }
+        context.submit(self.command_buffers);
+    }
+
+    /// Submit the batch, calling `callback` once the GPU has finished all of it
+    pub fn submit_with_callback(
+        self,
+        context: &GpuContext,
+        callback: impl FnOnce() + Send + 'static
+    ) {
+        self.submit(context);
+        context.queue.on_submitted_work_done(callback);
+    }
+}
@@ -0,0 +1,287 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    Camera,
+    CameraUniform,
+    GpuContext,
+    InstanceBuffer,
+    RenderPass,
+    RenderPipeline,
+    Result,
+    Texture,
+    TypedBuffer,
+};
+
+/// How a billboard turns to face the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BillboardMode {
+    /// Always faces the camera (particles, labels)
+    Spherical,
+    /// Rotates only around the world Y axis (trees, impostors)
+    Cylindrical,
+}
+
+/// A camera-facing textured quad, drawn as one instance
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Billboard {
+    /// World-space center
+    pub position: [f32; 3],
+    mode: u32,
+    /// World-space width and height
+    pub size: [f32; 2],
+    /// Texture region as `[u_min, v_min, u_max, v_max]`
+    pub uv_rect: [f32; 4],
+    pub color: [f32; 4],
+}
+
+impl Billboard {
+    /// A spherical billboard showing the whole texture in white
+    pub fn new(position: [f32; 3], size: [f32; 2]) -> Self {
+        Self {
+            position,
+            mode: 0,
+            size,
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            color: [1.0; 4],
+        }
+    }
+
+    pub fn mode(mut self, mode: BillboardMode) -> Self {
+        self.mode = match mode {
+            BillboardMode::Spherical => 0,
+            BillboardMode::Cylindrical => 1,
+        };
+        self
+    }
+
+    /// Show a region of the texture, e.g. one cell of an atlas
+    pub fn uv_rect(mut self, uv_rect: [f32; 4]) -> Self {
+        self.uv_rect = uv_rect;
+        self
+    }
+
+    /// Tint multiplied with the texture
+    pub fn color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+/// Renders [`Billboard`]s as instanced quads expanded in the vertex shader.
+///
+/// [`prepare`](Self::prepare) sorts the billboards back-to-front so alpha
+/// blending composes correctly; they are depth-tested against the scene but do
+/// not write depth, so draw them after opaque geometry.
+pub struct BillboardRenderer {
+    instances: InstanceBuffer<Billboard>,
+    sorted: Vec<Billboard>,
+    camera: TypedBuffer<CameraUniform>,
+    bind_group: wgpu::BindGroup,
+    pipeline: RenderPipeline,
+    _texture: Texture,
+}
+
+impl BillboardRenderer {
+    /// Create a renderer drawing from `texture` (plain white quads when `None`)
+    pub fn new(
+        context: &GpuContext,
+        texture: Option<Texture>,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>
+    ) -> Result<Self> {
+        Self::new_multisampled(context, texture, color_format, depth_format, 1)
+    }
+
+    /// [`new`](Self::new) for targets with `samples` samples per pixel, e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    pub fn new_multisampled(
+        context: &GpuContext,
+        texture: Option<Texture>,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        samples: u32
+    ) -> Result<Self> {
+        let texture = match texture {
+            Some(texture) => texture,
+            None => Texture::from_rgba8(context, &[255; 4], 1, 1, Some("Billboard White"))?,
+        };
+        let camera = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::VERTEX)
+            .texture(
+                1,
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .sampler(2, wgpu::ShaderStages::FRAGMENT, wgpu::SamplerBindingType::Filtering)
+            .build(context, Some("Billboard Bind Group Layout"));
+        let bind_group = BindGroupBuilder::new(&layout)
+            .buffer(0, camera.buffer())
+            .texture_view(1, &texture.view)
+            .sampler(2, &texture.sampler)
+            .build(context, Some("Billboard Bind Group"));
+
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&context.label("Billboard Shader")),
+            source: wgpu::ShaderSource::Wgsl(format!("{}{}", Camera::WGSL, SHADER).into()),
+        });
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&context.label("Billboard Pipeline Layout")),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            })
+        );
+        let pipeline = context.device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some(&context.label("Billboard Pipeline")),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<Billboard>() as u64,
+                            step_mode: wgpu::VertexStepMode::Instance,
+                            attributes: &wgpu::vertex_attr_array![
+                                0 => Float32x3,
+                                1 => Uint32,
+                                2 => Float32x2,
+                                3 => Float32x4,
+                                4 => Float32x4
+                            ],
+                        },
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: color_format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: samples,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            })
+        );
+
+        Ok(Self {
+            instances: InstanceBuffer::with_capacity(context, 256)?,
+            sorted: Vec::new(),
+            camera,
+            bind_group,
+            pipeline: RenderPipeline {
+                pipeline,
+                bind_group_layouts: vec![layout],
+                vertex_buffer_count: 1,
+                bindings: Vec::new(),
+            },
+            _texture: texture,
+        })
+    }
+
+    /// Upload the camera and this frame's billboards, sorted back-to-front
+    pub fn prepare(
+        &mut self,
+        context: &GpuContext,
+        camera: &Camera,
+        billboards: &[Billboard]
+    ) -> Result<()> {
+        camera.update_buffer(context, &self.camera)?;
+
+        self.sorted.clear();
+        self.sorted.extend_from_slice(billboards);
+        self.sorted.sort_by(|a, b| {
+            camera.view_depth(b.position).total_cmp(&camera.view_depth(a.position))
+        });
+        self.instances.update(context, &self.sorted)
+    }
+
+    /// Draw the billboards uploaded by the last [`prepare`](Self::prepare)
+    pub fn render<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if self.instances.is_empty() {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instances.buffer());
+        pass.draw(0..4, 0..self.instances.len() as u32);
+    }
+}
+
+const SHADER: &str =
+    r#"
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var billboard_texture: texture_2d<f32>;
+@group(0) @binding(2) var billboard_sampler: sampler;
+
+struct BillboardInput {
+    @location(0) position: vec3<f32>,
+    @location(1) mode: u32,
+    @location(2) size: vec2<f32>,
+    @location(3) uv_rect: vec4<f32>,
+    @location(4) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, billboard: BillboardInput) -> VertexOutput {
+    // Triangle strip corners: (0, 0), (1, 0), (0, 1), (1, 1)
+    let corner = vec2<f32>(f32(vertex_index & 1u), f32(vertex_index >> 1u));
+
+    // Camera right and up are the first two rows of the view rotation
+    var right = vec3<f32>(camera.view[0].x, camera.view[1].x, camera.view[2].x);
+    var up = vec3<f32>(camera.view[0].y, camera.view[1].y, camera.view[2].y);
+    if (billboard.mode == 1u) {
+        up = vec3<f32>(0.0, 1.0, 0.0);
+        let to_camera = camera.position.xyz - billboard.position;
+        let side = cross(up, to_camera);
+        if (dot(side, side) > 1e-8) {
+            right = normalize(side);
+        }
+    }
+
+    let offset = (corner - 0.5) * billboard.size;
+    let world = billboard.position + right * offset.x + up * offset.y;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_projection * vec4<f32>(world, 1.0);
+    out.uv = mix(billboard.uv_rect.xw, billboard.uv_rect.zy, corner);
+    out.color = billboard.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(billboard_texture, billboard_sampler, in.uv) * in.color;
+}
+"#;
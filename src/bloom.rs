@@ -0,0 +1,408 @@
+use crate::{
+    check_wgsl,
+    color_attachment,
+    max_mip_levels,
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    GeepuError,
+    GpuContext,
+    RenderCommands,
+    RenderPipeline,
+    Result,
+    Texture,
+    TextureBuilder,
+    TypedBuffer,
+};
+use crate::shadertoy::{ fullscreen_pipeline, FULLSCREEN_VERTEX };
+use std::sync::Arc;
+
+/// Format of the [`Bloom`] mip chain
+pub const BLOOM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Parameters of a [`Bloom`] effect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BloomSettings {
+    /// Brightness (max of R, G, B) above which pixels start to glow
+    pub threshold: f32,
+    /// Width of the soft transition below `threshold`; 0 for a hard cutoff
+    pub knee: f32,
+    /// Strength of the glow added back onto the target
+    pub intensity: f32,
+    /// Spread of the upsampling filter, in texels of each level
+    pub radius: f32,
+    /// Levels in the chain, starting at half the target's size; more levels
+    /// give a wider glow
+    pub mip_levels: u32,
+}
+
+impl BloomSettings {
+    pub fn new() -> Self {
+        Self {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.5,
+            radius: 1.0,
+            mip_levels: 6,
+        }
+    }
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomParams {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    radius: f32,
+}
+
+/// Bloom as a post effect on an HDR image.
+///
+/// [`render`](Self::render) thresholds the source into the first level of a
+/// half-resolution mip chain, downsamples it level by level with a 13-tap
+/// filter, upsamples back up with a tent filter adding each level onto the
+/// one above, and adds the result onto the target. The target may be the
+/// source texture itself, as it is only read by the first pass.
+pub struct Bloom {
+    settings: BloomSettings,
+    params: TypedBuffer<BloomParams>,
+    layout: Arc<wgpu::BindGroupLayout>,
+    sampler: wgpu::Sampler,
+    prefilter: RenderPipeline,
+    downsample: RenderPipeline,
+    upsample: RenderPipeline,
+    composite: RenderPipeline,
+    chain: Texture,
+    /// Single-level views of the chain, rendered into
+    level_views: Vec<wgpu::TextureView>,
+    /// Bind groups sampling each level of the chain
+    level_groups: Vec<wgpu::BindGroup>,
+    size: (u32, u32),
+}
+
+impl Bloom {
+    /// Bloom for `width` x `height` images, composited onto targets of `target_format`
+    pub fn new(
+        context: &GpuContext,
+        width: u32,
+        height: u32,
+        target_format: wgpu::TextureFormat,
+        settings: BloomSettings
+    ) -> Result<Self> {
+        let layout = BindGroupLayoutBuilder::new()
+            .texture(
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .sampler(1, wgpu::ShaderStages::FRAGMENT, wgpu::SamplerBindingType::Filtering)
+            .uniform_buffer(2, wgpu::ShaderStages::FRAGMENT)
+            .build(context, Some("Bloom Layout"));
+        let sampler = context.device.create_sampler(
+            &(wgpu::SamplerDescriptor {
+                label: Some(&context.label("Bloom Sampler")),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            })
+        );
+
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+        let pipeline = |fragment: &str, format: wgpu::TextureFormat, blend, label: &str| {
+            let source = format!("{}{}{}", FULLSCREEN_VERTEX, BLOOM_COMMON, fragment);
+            check_wgsl(label, None, &source)?;
+            Ok::<_, GeepuError>(
+                fullscreen_pipeline(
+                    context,
+                    &source,
+                    vec![layout.clone()],
+                    wgpu::ColorTargetState { format, blend, write_mask: wgpu::ColorWrites::ALL },
+                    label
+                )
+            )
+        };
+        let prefilter = pipeline(PREFILTER_FRAGMENT, BLOOM_FORMAT, None, "Bloom Prefilter")?;
+        let downsample = pipeline(DOWNSAMPLE_FRAGMENT, BLOOM_FORMAT, None, "Bloom Downsample")?;
+        let upsample = pipeline(UPSAMPLE_FRAGMENT, BLOOM_FORMAT, Some(additive), "Bloom Upsample")?;
+        let composite = pipeline(
+            COMPOSITE_FRAGMENT,
+            target_format,
+            Some(additive),
+            "Bloom Composite"
+        )?;
+
+        let params = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let chain = Self::create_chain(context, width, height, settings.mip_levels)?;
+        let mut bloom = Self {
+            settings,
+            params,
+            layout,
+            sampler,
+            prefilter,
+            downsample,
+            upsample,
+            composite,
+            chain,
+            level_views: Vec::new(),
+            level_groups: Vec::new(),
+            size: (width, height),
+        };
+        bloom.create_levels(context);
+        bloom.write_params(context)?;
+        Ok(bloom)
+    }
+
+    fn create_chain(
+        context: &GpuContext,
+        width: u32,
+        height: u32,
+        levels: u32
+    ) -> Result<Texture> {
+        let (width, height) = ((width / 2).max(1), (height / 2).max(1));
+        TextureBuilder::new(width, height)
+            .format(BLOOM_FORMAT)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+            .mip_levels(levels.clamp(1, max_mip_levels(width, height)))
+            .label("Bloom Chain")
+            .build(context)
+    }
+
+    fn create_levels(&mut self, context: &GpuContext) {
+        self.level_views = (0..self.chain.mip_level_count())
+            .map(|level| self.chain.mip_view(level))
+            .collect();
+        self.level_groups = self.level_views
+            .iter()
+            .map(|view| self.bind_group(context, view))
+            .collect();
+    }
+
+    fn bind_group(&self, context: &GpuContext, view: &wgpu::TextureView) -> wgpu::BindGroup {
+        BindGroupBuilder::new(&self.layout)
+            .texture_view(0, view)
+            .sampler(1, &self.sampler)
+            .buffer(2, self.params.buffer())
+            .build(context, Some("Bloom Bind Group"))
+    }
+
+    fn write_params(&self, context: &GpuContext) -> Result<()> {
+        // Upsampling sums every level, so normalize by their count
+        let levels = self.chain.mip_level_count() as f32;
+        self.params.write(
+            context,
+            &[
+                BloomParams {
+                    threshold: self.settings.threshold,
+                    knee: self.settings.knee,
+                    intensity: self.settings.intensity / levels,
+                    radius: self.settings.radius,
+                },
+            ]
+        )
+    }
+
+    /// Follow a new source size
+    pub fn resize(&mut self, context: &GpuContext, width: u32, height: u32) -> Result<()> {
+        if self.size == (width, height) {
+            return Ok(());
+        }
+        self.size = (width, height);
+        self.chain = Self::create_chain(context, width, height, self.settings.mip_levels)?;
+        self.create_levels(context);
+        self.write_params(context)
+    }
+
+    pub fn settings(&self) -> &BloomSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, context: &GpuContext, settings: BloomSettings) -> Result<()> {
+        let levels_changed = settings.mip_levels != self.settings.mip_levels;
+        self.settings = settings;
+        if levels_changed {
+            let (width, height) = self.size;
+            self.chain = Self::create_chain(context, width, height, settings.mip_levels)?;
+            self.create_levels(context);
+        }
+        self.write_params(context)
+    }
+
+    /// The mip chain; after [`render`](Self::render) level 0 holds the glow
+    /// added onto the target, before `intensity`
+    pub fn chain(&self) -> &Texture {
+        &self.chain
+    }
+
+    /// Add the bloom of `source` onto `target`, both the size given at
+    /// creation or to [`resize`](Self::resize)
+    pub fn render(
+        &self,
+        context: &GpuContext,
+        commands: &mut RenderCommands,
+        source: &wgpu::TextureView,
+        target: &wgpu::TextureView
+    ) {
+        let source_group = self.bind_group(context, source);
+        let levels = self.level_views.len();
+
+        self.pass(
+            commands,
+            &self.level_views[0],
+            true,
+            &self.prefilter,
+            &source_group,
+            "Bloom Prefilter"
+        );
+        for level in 1..levels {
+            self.pass(
+                commands,
+                &self.level_views[level],
+                true,
+                &self.downsample,
+                &self.level_groups[level - 1],
+                "Bloom Downsample"
+            );
+        }
+        for level in (0..levels - 1).rev() {
+            self.pass(
+                commands,
+                &self.level_views[level],
+                false,
+                &self.upsample,
+                &self.level_groups[level + 1],
+                "Bloom Upsample"
+            );
+        }
+        self.pass(
+            commands,
+            target,
+            false,
+            &self.composite,
+            &self.level_groups[0],
+            "Bloom Composite"
+        );
+    }
+
+    fn pass(
+        &self,
+        commands: &mut RenderCommands,
+        view: &wgpu::TextureView,
+        clear: bool,
+        pipeline: &RenderPipeline,
+        bind_group: &wgpu::BindGroup,
+        label: &str
+    ) {
+        let color = [Some(color_attachment(view, clear.then_some(wgpu::Color::BLACK)))];
+        let mut pass = commands.begin_render_pass(&color, None, Some(label));
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+const BLOOM_COMMON: &str =
+    r#"
+struct BloomParams {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    radius: f32,
+}
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+@group(0) @binding(2) var<uniform> params: BloomParams;
+
+fn sample_source(uv: vec2<f32>, offset: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    return textureSampleLevel(source, source_sampler, uv + offset * texel, 0.0).rgb;
+}
+
+// 13-tap downsample from Jimenez, "Next Generation Post Processing in Call of Duty"
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let a = sample_source(uv, vec2<f32>(-2.0, -2.0));
+    let b = sample_source(uv, vec2<f32>(0.0, -2.0));
+    let c = sample_source(uv, vec2<f32>(2.0, -2.0));
+    let d = sample_source(uv, vec2<f32>(-2.0, 0.0));
+    let e = sample_source(uv, vec2<f32>(0.0, 0.0));
+    let f = sample_source(uv, vec2<f32>(2.0, 0.0));
+    let g = sample_source(uv, vec2<f32>(-2.0, 2.0));
+    let h = sample_source(uv, vec2<f32>(0.0, 2.0));
+    let i = sample_source(uv, vec2<f32>(2.0, 2.0));
+    let j = sample_source(uv, vec2<f32>(-1.0, -1.0));
+    let k = sample_source(uv, vec2<f32>(1.0, -1.0));
+    let l = sample_source(uv, vec2<f32>(-1.0, 1.0));
+    let m = sample_source(uv, vec2<f32>(1.0, 1.0));
+    return e * 0.125 + (a + c + g + i) * 0.03125 + (b + d + f + h) * 0.0625 +
+        (j + k + l + m) * 0.125;
+}
+"#;
+
+const PREFILTER_FRAGMENT: &str =
+    r#"
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = downsample(in.uv);
+    // Soft threshold: a quadratic ramp over the knee below the threshold
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - params.threshold + params.knee, 0.0, 2.0 * params.knee);
+    soft = soft * soft / (4.0 * params.knee + 1e-4);
+    let contribution = max(soft, brightness - params.threshold) / max(brightness, 1e-4);
+    return vec4<f32>(color * contribution, 1.0);
+}
+"#;
+
+const DOWNSAMPLE_FRAGMENT: &str =
+    r#"
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+"#;
+
+const UPSAMPLE_FRAGMENT: &str =
+    r#"
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    // 3x3 tent filter
+    let r = params.radius;
+    var sum = sample_source(in.uv, vec2<f32>(0.0, 0.0)) * 4.0;
+    sum += sample_source(in.uv, vec2<f32>(0.0, -r)) * 2.0;
+    sum += sample_source(in.uv, vec2<f32>(-r, 0.0)) * 2.0;
+    sum += sample_source(in.uv, vec2<f32>(r, 0.0)) * 2.0;
+    sum += sample_source(in.uv, vec2<f32>(0.0, r)) * 2.0;
+    sum += sample_source(in.uv, vec2<f32>(-r, -r)) + sample_source(in.uv, vec2<f32>(r, -r)) +
+        sample_source(in.uv, vec2<f32>(-r, r)) + sample_source(in.uv, vec2<f32>(r, r));
+    return vec4<f32>(sum / 16.0, 1.0);
+}
+"#;
+
+const COMPOSITE_FRAGMENT: &str =
+    r#"
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(sample_source(in.uv, vec2<f32>(0.0, 0.0)) * params.intensity, 0.0);
+}
+"#;
+
@@ -1,60 +1,210 @@
-use crate::{ GpuContext, GeepuError, Result };
+use crate::{ GpuContext, GeepuError, ResourceKind, Result };
+use crate::resources::TrackedResource;
+use crate::profiling::profile_scope;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::{ Arc, Mutex };
+use std::task::{ Poll, Waker };
 use wgpu::util::DeviceExt;
 
 /// A typed buffer wrapper that provides zero-cost abstractions
 pub struct TypedBuffer<T> {
     buffer: wgpu::Buffer,
     len: usize,
+    _tracked: Option<TrackedResource>,
     _phantom: PhantomData<T>,
 }
 
 impl<T> TypedBuffer<T> where T: bytemuck::Pod {
-    /// Create a new buffer with data
+    /// Create a new buffer with data, staged in one piece.
+    /// For data in the hundreds of megabytes use [`new_chunked`](Self::new_chunked).
     pub fn new(context: &GpuContext, data: &[T], usage: wgpu::BufferUsages) -> Result<Self> {
-        let buffer = context.device.create_buffer_init(
-            &(wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("TypedBuffer<{}>", std::any::type_name::<T>())),
-                contents: bytemuck::cast_slice(data),
-                usage,
-            })
-        );
+        profile_scope!("geepu::buffer_upload", std::any::type_name::<T>());
+        check_buffer_size(context, std::mem::size_of_val(data) as u64)?;
+        let label = format!("TypedBuffer<{}>", std::any::type_name::<T>());
+        let buffer = context.capture_errors("create_buffer", Some(&label), || {
+            context.device.create_buffer_init(
+                &(wgpu::util::BufferInitDescriptor {
+                    label: Some(&context.label(&label)),
+                    contents: bytemuck::cast_slice(data),
+                    usage,
+                })
+            )
+        })?;
 
         Ok(Self {
             buffer,
             len: data.len(),
+            _tracked: Self::track(context, &label, data.len()),
             _phantom: PhantomData,
         })
     }
 
-    /// Create an empty buffer with a specific size
+    /// Create an empty buffer with a specific size. The allocation is padded
+    /// to a multiple of 4 bytes, like [`new`](Self::new)'s, so every element
+    /// can be copied back.
     pub fn empty(context: &GpuContext, len: usize, usage: wgpu::BufferUsages) -> Result<Self> {
-        let buffer = context.device.create_buffer(
-            &(wgpu::BufferDescriptor {
-                label: Some(&format!("TypedBuffer<{}>", std::any::type_name::<T>())),
-                size: (len * std::mem::size_of::<T>()) as u64,
-                usage,
-                mapped_at_creation: false,
-            })
+        let size = ((len * std::mem::size_of::<T>()) as u64).next_multiple_of(
+            wgpu::COPY_BUFFER_ALIGNMENT
         );
+        check_buffer_size(context, size)?;
+        let label = format!("TypedBuffer<{}>", std::any::type_name::<T>());
+        let buffer = context.capture_errors("create_buffer", Some(&label), || {
+            context.device.create_buffer(
+                &(wgpu::BufferDescriptor {
+                    label: Some(&context.label(&label)),
+                    size,
+                    usage,
+                    mapped_at_creation: false,
+                })
+            )
+        })?;
 
         Ok(Self {
             buffer,
             len,
+            _tracked: Self::track(context, &label, len),
             _phantom: PhantomData,
         })
     }
 
+    fn track(context: &GpuContext, label: &str, len: usize) -> Option<TrackedResource> {
+        context.track_resource(ResourceKind::Buffer, label, (len * std::mem::size_of::<T>()) as u64)
+    }
+
     /// Write data to the buffer
     pub fn write(&self, context: &GpuContext, data: &[T]) -> Result<()> {
         if data.len() > self.len {
             return Err(GeepuError::BufferError("Data size exceeds buffer capacity".to_string()));
         }
 
-        context.queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(data));
+        profile_scope!("geepu::buffer_write", std::any::type_name::<T>());
+        context.write_buffer(&self.buffer, 0, bytemuck::cast_slice(data));
         Ok(())
     }
 
+    /// Write `data` starting at element `index`, leaving the other elements as they are
+    pub fn write_at(&self, context: &GpuContext, index: usize, data: &[T]) -> Result<()> {
+        if index.checked_add(data.len()).is_none_or(|end| end > self.len) {
+            return Err(GeepuError::BufferError("Data size exceeds buffer capacity".to_string()));
+        }
+        let offset = (index * std::mem::size_of::<T>()) as u64;
+        self.write_bytes(context, offset, bytemuck::cast_slice(data))
+    }
+
+    /// Write one field of element `index`, selected by `field`, without
+    /// re-uploading the rest of the struct, e.g.
+    /// `camera_buffer.write_field(&context, 0, |camera| &camera.view, &view)`.
+    ///
+    /// The field's offset and size must be multiples of 4 bytes, as for any
+    /// buffer write; fields of `f32`/`u32` based uniform structs always are.
+    pub fn write_field<F>(
+        &self,
+        context: &GpuContext,
+        index: usize,
+        field: impl Fn(&T) -> &F,
+        value: &F
+    ) -> Result<()>
+        where F: bytemuck::Pod
+    {
+        if index >= self.len {
+            return Err(
+                GeepuError::BufferError(
+                    format!("Element {} is out of bounds for {} elements", index, self.len)
+                )
+            );
+        }
+        let field_offset = field_offset(field).ok_or_else(|| {
+            GeepuError::BufferError("Field accessor must return a field of the element".to_string())
+        })?;
+        let offset = (index * std::mem::size_of::<T>() + field_offset) as u64;
+        self.write_bytes(context, offset, bytemuck::bytes_of(value))
+    }
+
+    fn write_bytes(&self, context: &GpuContext, offset: u64, bytes: &[u8]) -> Result<()> {
+        let align = wgpu::COPY_BUFFER_ALIGNMENT;
+        if !offset.is_multiple_of(align) || !(bytes.len() as u64).is_multiple_of(align) {
+            return Err(
+                GeepuError::BufferError(
+                    format!(
+                        "Write of {} bytes at offset {} is not {}-byte aligned",
+                        bytes.len(),
+                        offset,
+                        align
+                    )
+                )
+            );
+        }
+        profile_scope!("geepu::buffer_write", std::any::type_name::<T>());
+        context.write_buffer(&self.buffer, offset, bytes);
+        Ok(())
+    }
+
+    /// Read the whole buffer back; see [`read_range`](Self::read_range)
+    pub async fn read(&self, context: &GpuContext) -> Result<Vec<T>> {
+        self.read_range(context, 0..self.len).await
+    }
+
+    /// Read elements `range` back from the GPU. Only those elements are copied
+    /// to staging memory, so reading a counter out of a huge buffer stays cheap.
+    /// The buffer needs `COPY_SRC` usage.
+    pub async fn read_range(&self, context: &GpuContext, range: Range<usize>) -> Result<Vec<T>> {
+        if range.start > range.end || range.end > self.len {
+            return Err(
+                GeepuError::BufferError(
+                    format!("Range {:?} is out of bounds for {} elements", range, self.len)
+                )
+            );
+        }
+        if !self.buffer.usage().contains(wgpu::BufferUsages::COPY_SRC) {
+            return Err(
+                GeepuError::BufferError("Reading a buffer back needs COPY_SRC usage".to_string())
+            );
+        }
+        if range.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Copies are 4-byte aligned; widen the copy and trim it after mapping
+        let element = std::mem::size_of::<T>() as u64;
+        let start = (range.start as u64) * element;
+        let end = (range.end as u64) * element;
+        let align = wgpu::COPY_BUFFER_ALIGNMENT;
+        let copy_start = start - (start % align);
+        // Buffers are padded to the alignment at creation, so this stays in bounds
+        let copy_end = end.next_multiple_of(align);
+        let staging = StagingBuffer::new(context, copy_end - copy_start)?;
+        let mut encoder = context.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some(&context.label("Buffer Readback")),
+            })
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.buffer,
+            copy_start,
+            staging.buffer(),
+            0,
+            copy_end - copy_start
+        );
+        context.submit(std::iter::once(encoder.finish()));
+
+        let bytes: Vec<u8> = staging.read_data(context).await?;
+        let skip = (start - copy_start) as usize;
+        Ok(
+            bytes[skip..skip + ((end - start) as usize)]
+                .chunks_exact(element as usize)
+                .map(bytemuck::pod_read_unaligned)
+                .collect()
+        )
+    }
+
+    /// Read a single element back, e.g. an atomic counter
+    pub async fn read_at(&self, context: &GpuContext, index: usize) -> Result<T> {
+        Ok(self.read_range(context, index..index + 1).await?[0])
+    }
+
     /// Get the underlying wgpu buffer
     pub fn buffer(&self) -> &wgpu::Buffer {
         &self.buffer
@@ -104,6 +254,37 @@ impl VertexBufferBuilder {
         self
     }
 
+    /// Add an RGBA color packed as four bytes
+    /// (`Unorm8x4`, see [`pack_unorm8x4`](crate::pack_unorm8x4))
+    pub fn unorm8x4(self, shader_location: u32) -> Self {
+        self.attribute(wgpu::VertexFormat::Unorm8x4, shader_location)
+    }
+
+    /// Add a normal or tangent packed as four signed bytes (`Snorm8x4`)
+    pub fn snorm8x4(self, shader_location: u32) -> Self {
+        self.attribute(wgpu::VertexFormat::Snorm8x4, shader_location)
+    }
+
+    /// Add texture coordinates packed as two 16-bit fractions (`Unorm16x2`)
+    pub fn unorm16x2(self, shader_location: u32) -> Self {
+        self.attribute(wgpu::VertexFormat::Unorm16x2, shader_location)
+    }
+
+    /// Add a -1..1 pair packed as two 16-bit signed fractions (`Snorm16x2`)
+    pub fn snorm16x2(self, shader_location: u32) -> Self {
+        self.attribute(wgpu::VertexFormat::Snorm16x2, shader_location)
+    }
+
+    /// Add two half floats (`Float16x2`, see [`pack_float16x2`](crate::pack_float16x2))
+    pub fn float16x2(self, shader_location: u32) -> Self {
+        self.attribute(wgpu::VertexFormat::Float16x2, shader_location)
+    }
+
+    /// Add four half floats (`Float16x4`)
+    pub fn float16x4(self, shader_location: u32) -> Self {
+        self.attribute(wgpu::VertexFormat::Float16x4, shader_location)
+    }
+
     /// Set step mode (vertex or instance)
     pub fn step_mode(mut self, step_mode: wgpu::VertexStepMode) -> Self {
         self.step_mode = step_mode;
@@ -147,27 +328,166 @@ impl<T> TypedBuffer<T> where T: bytemuck::Pod {
     pub fn storage(context: &GpuContext, data: &[T]) -> Result<Self> {
         Self::new(context, data, wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST)
     }
+
+    /// Create a buffer of indirect draw or dispatch arguments, with `INDIRECT`
+    /// usage plus `STORAGE | COPY_DST` so the GPU or CPU can rewrite them
+    pub fn indirect(context: &GpuContext, data: &[T]) -> Result<Self> {
+        Self::new(
+            context,
+            data,
+            wgpu::BufferUsages::INDIRECT |
+                wgpu::BufferUsages::STORAGE |
+                wgpu::BufferUsages::COPY_DST
+        )
+    }
+}
+
+/// Byte offset of the field `field` selects within `T`, like `offset_of!`
+/// but from an accessor closure. `None` if it returns something outside `T`.
+pub fn field_offset<T, F>(field: impl Fn(&T) -> &F) -> Option<usize> where T: bytemuck::Pod {
+    let element = T::zeroed();
+    let base = &element as *const T as usize;
+    let address = field(&element) as *const F as usize;
+    let end = address.checked_add(std::mem::size_of::<F>())?;
+    (address >= base && end <= base + std::mem::size_of::<T>()).then(|| address - base)
+}
+
+/// Default chunk size for [`TypedBuffer::new_chunked`]
+pub const DEFAULT_UPLOAD_CHUNK: u64 = 64 << 20;
+
+/// How far a chunked upload has got, passed to its progress callback after each chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadProgress {
+    pub uploaded: u64,
+    pub total: u64,
+    pub chunk: usize,
+    pub chunks: usize,
+}
+
+impl UploadProgress {
+    /// Completed fraction, 0.0 to 1.0
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        ((self.uploaded as f64) / (self.total as f64)) as f32
+    }
+}
+
+/// Byte ranges a `size`-byte upload is split into, each at most `chunk_size`
+/// bytes and starting on a copy-aligned offset
+pub fn upload_chunks(size: u64, chunk_size: u64) -> impl Iterator<Item = Range<u64>> {
+    let align = wgpu::COPY_BUFFER_ALIGNMENT;
+    let chunk_size = (chunk_size / align).max(1) * align;
+    (0..size)
+        .step_by(chunk_size as usize)
+        .map(move |start| start..(start + chunk_size).min(size))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> TypedBuffer<T> where T: bytemuck::Pod {
+    /// Create a buffer too large to upload in one go.
+    ///
+    /// The data is written `chunk_size` bytes at a time, one submission per
+    /// chunk, with at most two chunks in flight so staging memory stays
+    /// bounded. Blocks until the last chunk has reached the GPU, calling
+    /// `progress` as each one completes. `COPY_DST` is added to `usage`.
+    pub fn new_chunked(
+        context: &GpuContext,
+        data: &[T],
+        usage: wgpu::BufferUsages,
+        chunk_size: u64,
+        mut progress: impl FnMut(UploadProgress)
+    ) -> Result<Self> {
+        profile_scope!("geepu::buffer_upload_chunked", std::any::type_name::<T>());
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let total = bytes.len() as u64;
+        let size = total.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+        check_buffer_size(context, size)?;
+        let label = format!("TypedBuffer<{}>", std::any::type_name::<T>());
+        let buffer = context.capture_errors("create_buffer", Some(&label), || {
+            context.device.create_buffer(
+                &(wgpu::BufferDescriptor {
+                    label: Some(&context.label(&label)),
+                    size,
+                    usage: usage | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            )
+        })?;
+
+        let chunks: Vec<Range<u64>> = upload_chunks(total, chunk_size).collect();
+        let mut in_flight: Option<(wgpu::SubmissionIndex, u64)> = None;
+        let mut report = |chunk: usize, uploaded: u64| {
+            progress(UploadProgress { uploaded, total, chunk, chunks: chunks.len() });
+        };
+        for (chunk, range) in chunks.iter().enumerate() {
+            let data = &bytes[range.start as usize..range.end as usize];
+            // Writes must be a multiple of 4 bytes; only the last chunk can fall short
+            let aligned = (data.len() as u64).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+            if aligned == (data.len() as u64) {
+                context.queue.write_buffer(&buffer, range.start, data);
+            } else {
+                let mut padded = data.to_vec();
+                padded.resize(aligned as usize, 0);
+                context.queue.write_buffer(&buffer, range.start, &padded);
+            }
+            let index = context.submit(std::iter::empty());
+            if let Some((previous, uploaded)) = in_flight.replace((index, range.end)) {
+                context.device.poll(wgpu::Maintain::WaitForSubmissionIndex(previous));
+                report(chunk - 1, uploaded);
+            }
+        }
+        if let Some((last, uploaded)) = in_flight {
+            context.device.poll(wgpu::Maintain::WaitForSubmissionIndex(last));
+            report(chunks.len() - 1, uploaded);
+        }
+
+        Ok(Self {
+            buffer,
+            len: data.len(),
+            _tracked: Self::track(context, &label, data.len()),
+            _phantom: PhantomData,
+        })
+    }
+}
+
+/// Fail clearly instead of with a validation error for buffers over the device limit
+fn check_buffer_size(context: &GpuContext, size: u64) -> Result<()> {
+    let max = context.device.limits().max_buffer_size;
+    if size > max {
+        return Err(
+            GeepuError::BufferError(
+                format!("Buffer of {} bytes exceeds the device's max_buffer_size of {}", size, max)
+            )
+        );
+    }
+    Ok(())
 }
 
 /// A staging buffer for CPU-GPU data transfers
 pub struct StagingBuffer {
     buffer: wgpu::Buffer,
     size: u64,
+    _tracked: Option<TrackedResource>,
 }
 
 impl StagingBuffer {
     /// Create a new staging buffer
     pub fn new(context: &GpuContext, size: u64) -> Result<Self> {
-        let buffer = context.device.create_buffer(
-            &(wgpu::BufferDescriptor {
-                label: Some("Staging Buffer"),
-                size,
-                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            })
-        );
-
-        Ok(Self { buffer, size })
+        let buffer = context.capture_errors("create_buffer", Some("Staging Buffer"), || {
+            context.device.create_buffer(
+                &(wgpu::BufferDescriptor {
+                    label: Some(&context.label("Staging Buffer")),
+                    size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            )
+        })?;
+
+        let _tracked = context.track_resource(ResourceKind::StagingBuffer, "Staging Buffer", size);
+        Ok(Self { buffer, size, _tracked })
     }
 
     /// Copy data from a GPU buffer to this staging buffer
@@ -184,19 +504,18 @@ impl StagingBuffer {
     /// Map the buffer and read data
     pub async fn read_data<T>(&self, context: &GpuContext) -> Result<Vec<T>> where T: bytemuck::Pod {
         let buffer_slice = self.buffer.slice(..);
+        let mapped = map_async(buffer_slice, wgpu::MapMode::Read);
 
-        // Use a simple future with shared state
-        let (sender, receiver) = std::sync::mpsc::channel();
-        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
-            let _ = sender.send(result);
-        });
-
+        // Native backends only run map callbacks while the device is polled;
+        // on the web the browser resolves them from its own event loop.
+        #[cfg(not(target_arch = "wasm32"))]
         context.device.poll(wgpu::Maintain::Wait);
+        #[cfg(target_arch = "wasm32")]
+        let _ = context;
 
-        receiver
-            .recv()
-            .unwrap()
-            .map_err(|e| { GeepuError::BufferError(format!("Failed to map buffer: {:?}", e)) })?;
+        mapped.await.map_err(|e| {
+            GeepuError::BufferError(format!("Failed to map buffer: {:?}", e))
+        })?;
 
         let data = buffer_slice.get_mapped_range();
         let result = bytemuck::cast_slice(&data).to_vec();
@@ -206,17 +525,172 @@ impl StagingBuffer {
         Ok(result)
     }
 
+    /// Map and read the buffer once the submitted work copying into it is
+    /// done. Unlike [`read_data`](Self::read_data) this never blocks the
+    /// executor: natively the device is polled without waiting each time the
+    /// future is polled, and other tasks run in between.
+    pub async fn read_submitted(&self, context: &GpuContext) -> Result<Vec<u8>> {
+        let buffer_slice = self.buffer.slice(..);
+        #[cfg(not(target_arch = "wasm32"))]
+        let mapped = map_async(buffer_slice, wgpu::MapMode::Read).polling(&context.device);
+        #[cfg(target_arch = "wasm32")]
+        let mapped = {
+            let _ = context;
+            map_async(buffer_slice, wgpu::MapMode::Read)
+        };
+        mapped.await.map_err(|e| {
+            GeepuError::BufferError(format!("Failed to map buffer: {:?}", e))
+        })?;
+
+        let data = buffer_slice.get_mapped_range();
+        let result = data.to_vec();
+        drop(data);
+        self.buffer.unmap();
+
+        Ok(result)
+    }
+
     /// Get the underlying buffer
     pub fn buffer(&self) -> &wgpu::Buffer {
         &self.buffer
     }
 }
 
-/// Convenience macro for creating vertex buffer layouts
+impl GpuContext {
+    /// Read a buffer back, e.g. compute results in a storage buffer, without
+    /// blocking the executor.
+    ///
+    /// The copy is submitted and the staging buffer's map callback awaited;
+    /// natively each poll of the future polls the device once without waiting,
+    /// so other tasks keep running meanwhile. The buffer needs `COPY_SRC` usage.
+    pub async fn read_storage_buffer<T>(&self, buffer: &TypedBuffer<T>) -> Result<Vec<T>>
+        where T: bytemuck::Pod
+    {
+        let Some(staging) = self.stage_readback(buffer)? else {
+            return Ok(Vec::new());
+        };
+        let bytes = staging.read_submitted(self).await.map_err(GeepuError::into_compute)?;
+        Ok(readback_elements(&bytes, buffer))
+    }
+
+    /// [`read_storage_buffer`](Self::read_storage_buffer) for callers without
+    /// an executor, waiting on the device instead of polling it repeatedly
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_storage_buffer_blocking<T>(&self, buffer: &TypedBuffer<T>) -> Result<Vec<T>>
+        where T: bytemuck::Pod
+    {
+        let Some(staging) = self.stage_readback(buffer)? else {
+            return Ok(Vec::new());
+        };
+        let bytes: Vec<u8> = pollster::block_on(staging.read_data(self))
+            .map_err(GeepuError::into_compute)?;
+        Ok(readback_elements(&bytes, buffer))
+    }
+
+    /// Submit a copy of `buffer` into a new staging buffer, or `None` when it is empty
+    fn stage_readback<T>(&self, buffer: &TypedBuffer<T>) -> Result<Option<StagingBuffer>>
+        where T: bytemuck::Pod
+    {
+        if !buffer.buffer().usage().contains(wgpu::BufferUsages::COPY_SRC) {
+            return Err(
+                GeepuError::BufferError("Reading a buffer back needs COPY_SRC usage".to_string())
+            );
+        }
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+
+        let size = buffer.buffer().size();
+        let staging = StagingBuffer::new(self, size)?;
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some(&self.label("Storage Buffer Readback")),
+            })
+        );
+        staging.copy_from_buffer(&mut encoder, buffer.buffer(), Some(size));
+        self.submit(std::iter::once(encoder.finish()));
+        Ok(Some(staging))
+    }
+}
+
+/// The elements of `buffer` from a staging copy of it, without the padding
+fn readback_elements<T>(bytes: &[u8], buffer: &TypedBuffer<T>) -> Vec<T> where T: bytemuck::Pod {
+    bytes[..buffer.size_bytes() as usize]
+        .chunks_exact(std::mem::size_of::<T>())
+        .map(bytemuck::pod_read_unaligned)
+        .collect()
+}
+
+/// Future resolved by a `map_async` callback, usable without blocking the executor
+struct MapFuture {
+    state: Arc<Mutex<MapState>>,
+    /// Device to poll while pending, for native backends that only run map
+    /// callbacks inside `Device::poll`
+    device: Option<Arc<wgpu::Device>>,
+}
+
+impl MapFuture {
+    /// Poll `device` without waiting on every poll of the future, asking the
+    /// executor to come back until the callback has run
+    #[cfg(not(target_arch = "wasm32"))]
+    fn polling(mut self, device: &Arc<wgpu::Device>) -> Self {
+        self.device = Some(device.clone());
+        self
+    }
+}
+
+#[derive(Default)]
+struct MapState {
+    result: Option<std::result::Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+impl Future for MapFuture {
+    type Output = std::result::Result<(), wgpu::BufferAsyncError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        if let Some(device) = &self.device {
+            device.poll(wgpu::Maintain::Poll);
+        }
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None if self.device.is_some() => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Start mapping `slice` and return a future that completes with the map result
+fn map_async(slice: wgpu::BufferSlice<'_>, mode: wgpu::MapMode) -> MapFuture {
+    let state = Arc::new(Mutex::new(MapState::default()));
+    let callback_state = state.clone();
+    slice.map_async(mode, move |result| {
+        let mut state = callback_state.lock().unwrap();
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    });
+    MapFuture { state, device: None }
+}
+
+/// Convenience macro for creating vertex buffer layouts.
+///
+/// Formats can be written without the `VertexFormat::` prefix:
+/// `vertex_layout![0 => Float32x3, 1 => Unorm8x4]`.
 #[macro_export]
 macro_rules! vertex_layout {
     ($($location:expr => $format:expr),* $(,)?) => {
         {
+            #[allow(unused_imports)]
+            use $crate::VertexFormat::*;
             let mut builder = $crate::VertexBufferBuilder::new();
             $(
                 builder = builder.attribute($format, $location);
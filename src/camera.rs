@@ -0,0 +1,254 @@
+use crate::{ GpuContext, Ray, Result, TypedBuffer };
+use glam::{ Mat4, Vec3 };
+
+/// How a camera maps view space to clip space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective {
+        /// Vertical field of view in radians
+        fov_y: f32,
+        near: f32,
+        far: f32,
+    },
+    Orthographic {
+        /// Visible height in world units; the width follows from the aspect ratio
+        height: f32,
+        near: f32,
+        far: f32,
+    },
+    /// Window pixels: (0, 0) at the top-left, Y down, kept in sync with the
+    /// viewport by [`Camera::set_viewport`]
+    Pixels {
+        width: f32,
+        height: f32,
+    },
+}
+
+/// A right-handed camera producing view/projection matrices for wgpu's clip space
+/// (depth 0..1, Y up)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    /// Viewport width divided by height
+    pub aspect: f32,
+    pub projection: Projection,
+}
+
+/// Six clip planes (left, right, bottom, top, near, far) as `ax + by + cz + d >= 0`
+/// inside, with normalized normals pointing inward
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [[f32; 4]; 6],
+}
+
+impl Frustum {
+    /// Extract the planes of a world to clip transform (wgpu depth range 0..1)
+    pub fn from_view_projection(view_projection: [[f32; 4]; 4]) -> Self {
+        let m = Mat4::from_cols_array_2d(&view_projection);
+        let [r0, r1, r2, r3] = [0, 1, 2, 3].map(|i| m.row(i));
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2].map(|plane| {
+            (plane / plane.truncate().length()).to_array()
+        });
+        Self { planes }
+    }
+
+    /// Whether an axis-aligned box is at least partly inside the frustum
+    pub fn intersects_aabb(&self, min: [f32; 3], max: [f32; 3]) -> bool {
+        let center = (Vec3::from(min) + Vec3::from(max)) * 0.5;
+        let extents = (Vec3::from(max) - Vec3::from(min)) * 0.5;
+        self.planes.iter().all(|&[a, b, c, d]| {
+            let normal = Vec3::new(a, b, c);
+            normal.dot(center) + d >= -extents.dot(normal.abs())
+        })
+    }
+}
+
+/// Camera matrices as laid out in a WGSL uniform block
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view: [[f32; 4]; 4],
+    pub projection: [[f32; 4]; 4],
+    pub view_projection: [[f32; 4]; 4],
+    /// World-space camera position (w = 1)
+    pub position: [f32; 4],
+}
+
+impl Camera {
+    /// WGSL declaration matching [`CameraUniform`]
+    pub const WGSL: &'static str =
+        r#"
+struct Camera {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_projection: mat4x4<f32>,
+    position: vec4<f32>,
+}
+"#;
+
+    /// Perspective camera looking from `position` at `target` with a vertical field of view in radians
+    pub fn perspective(
+        position: [f32; 3],
+        target: [f32; 3],
+        fov_y: f32,
+        aspect: f32,
+        near: f32,
+        far: f32
+    ) -> Self {
+        Self {
+            position,
+            target,
+            up: [0.0, 1.0, 0.0],
+            aspect,
+            projection: Projection::Perspective { fov_y, near, far },
+        }
+    }
+
+    /// Orthographic camera showing `height` world units vertically
+    pub fn orthographic(
+        position: [f32; 3],
+        target: [f32; 3],
+        height: f32,
+        aspect: f32,
+        near: f32,
+        far: f32
+    ) -> Self {
+        Self {
+            position,
+            target,
+            up: [0.0, 1.0, 0.0],
+            aspect,
+            projection: Projection::Orthographic { height, near, far },
+        }
+    }
+
+    /// 2D camera in window pixel coordinates (Y down) for a `width` x `height`
+    /// viewport. It looks down -Z, so anything with z between -1 and 1 is visible.
+    /// Flipping Y mirrors triangle winding: counter-clockwise on screen is
+    /// clockwise in pixel coordinates.
+    pub fn pixels(width: u32, height: u32) -> Self {
+        let mut camera = Self {
+            position: [0.0, 0.0, 1.0],
+            target: [0.0; 3],
+            up: [0.0, 1.0, 0.0],
+            aspect: 1.0,
+            projection: Projection::Pixels { width: 1.0, height: 1.0 },
+        };
+        camera.set_viewport(width, height);
+        camera
+    }
+
+    /// Update the aspect ratio (and a [`Projection::Pixels`] size) from a viewport
+    /// size (call on resize)
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+        self.aspect = width / height;
+        if let Projection::Pixels { width: w, height: h } = &mut self.projection {
+            (*w, *h) = (width, height);
+        }
+    }
+
+    /// World to view space transform
+    pub fn view_matrix(&self) -> [[f32; 4]; 4] {
+        self.view().to_cols_array_2d()
+    }
+
+    /// View to clip space transform
+    pub fn projection_matrix(&self) -> [[f32; 4]; 4] {
+        self.proj().to_cols_array_2d()
+    }
+
+    /// World to clip space transform
+    pub fn view_projection_matrix(&self) -> [[f32; 4]; 4] {
+        (self.proj() * self.view()).to_cols_array_2d()
+    }
+
+    /// Distance of a world-space point in front of the camera, along the view direction
+    pub fn view_depth(&self, point: [f32; 3]) -> f32 {
+        let forward = (Vec3::from(self.target) - Vec3::from(self.position)).normalize_or_zero();
+        forward.dot(Vec3::from(point) - Vec3::from(self.position))
+    }
+
+    /// World-space ray through pixel (`x`, `y`) of a `width` x `height` viewport,
+    /// with the origin at the top-left
+    pub fn screen_ray(&self, x: f32, y: f32, width: u32, height: u32) -> Ray {
+        let ndc_x = (x / (width.max(1) as f32)) * 2.0 - 1.0;
+        let ndc_y = 1.0 - (y / (height.max(1) as f32)) * 2.0;
+        let inverse = (self.proj() * self.view()).inverse();
+        let near = inverse.project_point3(Vec3::new(ndc_x, ndc_y, 0.0));
+        let far = inverse.project_point3(Vec3::new(ndc_x, ndc_y, 1.0));
+        Ray::new(near.to_array(), (far - near).to_array())
+    }
+
+    /// View frustum planes in world space
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_projection(self.view_projection_matrix())
+    }
+
+    /// Matrices ready to upload as a uniform
+    pub fn uniform(&self) -> CameraUniform {
+        let view = self.view();
+        let projection = self.proj();
+        let [x, y, z] = self.position;
+        CameraUniform {
+            view: view.to_cols_array_2d(),
+            projection: projection.to_cols_array_2d(),
+            view_projection: (projection * view).to_cols_array_2d(),
+            position: [x, y, z, 1.0],
+        }
+    }
+
+    /// [`uniform`](Self::uniform) with the projection offset by `jitter` in
+    /// clip-space units, e.g. from [`taa_jitter`](crate::taa_jitter)
+    pub fn jittered_uniform(&self, jitter: [f32; 2]) -> CameraUniform {
+        let offset = Mat4::from_translation(Vec3::new(jitter[0], jitter[1], 0.0));
+        let projection = offset * self.proj();
+        let view = self.view();
+        CameraUniform {
+            projection: projection.to_cols_array_2d(),
+            view_projection: (projection * view).to_cols_array_2d(),
+            ..self.uniform()
+        }
+    }
+
+    /// Create a uniform buffer holding this camera's matrices
+    pub fn create_buffer(&self, context: &GpuContext) -> Result<TypedBuffer<CameraUniform>> {
+        TypedBuffer::uniform(context, &[self.uniform()])
+    }
+
+    /// Upload this camera's current matrices into a buffer from [`create_buffer`](Self::create_buffer)
+    pub fn update_buffer(
+        &self,
+        context: &GpuContext,
+        buffer: &TypedBuffer<CameraUniform>
+    ) -> Result<()> {
+        buffer.write(context, &[self.uniform()])
+    }
+
+    pub(crate) fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(
+            Vec3::from(self.position),
+            Vec3::from(self.target),
+            Vec3::from(self.up)
+        )
+    }
+
+    pub(crate) fn proj(&self) -> Mat4 {
+        match self.projection {
+            Projection::Perspective { fov_y, near, far } => {
+                Mat4::perspective_rh(fov_y, self.aspect, near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                let half_h = height * 0.5;
+                let half_w = half_h * self.aspect;
+                Mat4::orthographic_rh(-half_w, half_w, -half_h, half_h, near, far)
+            }
+            Projection::Pixels { width, height } => {
+                // Bottom edge at `height`, top at 0 flips Y; the view sits at z = 1
+                Mat4::orthographic_rh(0.0, width, height, 0.0, 0.0, 2.0)
+            }
+        }
+    }
+}
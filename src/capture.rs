@@ -0,0 +1,74 @@
+use crate::{ GeepuError, Result };
+use std::path::PathBuf;
+
+/// Programmatic frame captures through RenderDoc's in-application API.
+///
+/// Only works when the application was launched from (or injected by)
+/// RenderDoc; [`new`](Self::new) fails otherwise, so a missing capture tool can
+/// be ignored at startup. Captures are written to RenderDoc's capture path.
+pub struct FrameCapture {
+    renderdoc: renderdoc::RenderDoc<renderdoc::V141>,
+}
+
+impl FrameCapture {
+    /// Connect to the RenderDoc instance the process is running under
+    pub fn new() -> Result<Self> {
+        let renderdoc = renderdoc::RenderDoc::new().map_err(|e| {
+            GeepuError::Other(format!("RenderDoc is not available: {}", e))
+        })?;
+        Ok(Self { renderdoc })
+    }
+
+    /// Capture the next presented frame
+    pub fn trigger_capture(&mut self) {
+        self.renderdoc.trigger_capture();
+    }
+
+    /// Capture the next `frames` presented frames, one file each
+    pub fn trigger_multi_frame_capture(&mut self, frames: u32) {
+        self.renderdoc.trigger_multi_frame_capture(frames);
+    }
+
+    /// Start capturing all GPU work until [`end_capture`](Self::end_capture),
+    /// e.g. around offscreen or compute-only work that is never presented
+    pub fn start_capture(&mut self) {
+        self.renderdoc.start_frame_capture(std::ptr::null(), std::ptr::null());
+    }
+
+    /// Finish a capture begun with [`start_capture`](Self::start_capture) and save it
+    pub fn end_capture(&mut self) {
+        self.renderdoc.end_frame_capture(std::ptr::null(), std::ptr::null());
+    }
+
+    /// Whether a capture is in progress
+    pub fn is_capturing(&self) -> bool {
+        self.renderdoc.is_frame_capturing()
+    }
+
+    /// Attach a comment to the most recent capture (shown in the replay UI)
+    pub fn set_comment(&mut self, comment: &str) {
+        self.renderdoc.set_capture_file_comments(None::<&str>, comment);
+    }
+
+    /// Number of captures made so far
+    pub fn capture_count(&self) -> u32 {
+        self.renderdoc.get_num_captures()
+    }
+
+    /// File path of the most recent capture
+    pub fn latest_capture(&self) -> Option<PathBuf> {
+        let count = self.capture_count();
+        if count == 0 {
+            return None;
+        }
+        self.renderdoc.get_capture(count - 1).map(|(path, _)| path)
+    }
+
+    /// Open the RenderDoc replay UI connected to this process
+    pub fn launch_replay_ui(&self) -> Result<()> {
+        self.renderdoc
+            .launch_replay_ui(true, None)
+            .map(|_| ())
+            .map_err(|e| GeepuError::Other(format!("Failed to launch RenderDoc UI: {}", e)))
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    check_wgsl,
+    color_attachment,
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    GpuContext,
+    RenderCommands,
+    RenderPipeline,
+    Result,
+    Texture,
+    TextureBuilder,
+};
+use crate::shadertoy::{ fullscreen_pipeline, FULLSCREEN_VERTEX };
+use std::sync::Arc;
+
+/// Format of [`LinearTarget`]s: linear, with headroom above 1.0
+pub const LINEAR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Decode an sRGB-encoded channel (as in color pickers and image files) to linear
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Encode a linear channel as sRGB
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Color space conversions for [`wgpu::Color`].
+///
+/// wgpu treats clear colors and shader outputs as linear, and encodes them
+/// itself when the target is sRGB. Colors picked in an editor or written as
+/// hex codes are sRGB-encoded, so convert them with
+/// [`from_srgb`](Self::from_srgb) before clearing or uploading them.
+pub trait ColorExt {
+    /// Linear color from sRGB-encoded channels in 0..1; alpha is kept as is
+    fn from_srgb(r: f64, g: f64, b: f64, a: f64) -> Self;
+
+    /// Linear color from an `0xRRGGBB` sRGB hex code, opaque
+    fn from_srgb_hex(hex: u32) -> Self;
+
+    /// Decode sRGB-encoded channels to linear
+    fn to_linear(self) -> Self;
+
+    /// Encode linear channels as sRGB
+    fn to_srgb(self) -> Self;
+
+    /// Channels as an `[r, g, b, a]` array for uniforms and vertex colors
+    fn to_array(self) -> [f32; 4];
+}
+
+impl ColorExt for wgpu::Color {
+    fn from_srgb(r: f64, g: f64, b: f64, a: f64) -> Self {
+        (Self { r, g, b, a }).to_linear()
+    }
+
+    fn from_srgb_hex(hex: u32) -> Self {
+        let channel = |shift: u32| (((hex >> shift) & 0xff) as f64) / 255.0;
+        Self::from_srgb(channel(16), channel(8), channel(0), 1.0)
+    }
+
+    fn to_linear(self) -> Self {
+        let convert = |value: f64| srgb_to_linear(value as f32) as f64;
+        Self { r: convert(self.r), g: convert(self.g), b: convert(self.b), a: self.a }
+    }
+
+    fn to_srgb(self) -> Self {
+        let convert = |value: f64| linear_to_srgb(value as f32) as f64;
+        Self { r: convert(self.r), g: convert(self.g), b: convert(self.b), a: self.a }
+    }
+
+    fn to_array(self) -> [f32; 4] {
+        [self.r as f32, self.g as f32, self.b as f32, self.a as f32]
+    }
+}
+
+/// A linear intermediate target and the pass writing it to the output.
+///
+/// Render the scene into [`view`](Self::view), then [`encode`](Self::encode)
+/// it into the surface texture. For sRGB surfaces the hardware does the
+/// encoding; for the non-sRGB surfaces some platforms only offer, the pass
+/// encodes in the shader, so the same linear rendering looks the same
+/// everywhere. Values are clamped to 0..1 on output; tonemap HDR first.
+pub struct LinearTarget {
+    texture: Texture,
+    layout: Arc<wgpu::BindGroupLayout>,
+    bind_group: wgpu::BindGroup,
+    pipeline: RenderPipeline,
+    output_format: wgpu::TextureFormat,
+}
+
+impl LinearTarget {
+    /// A `width` x `height` [`LINEAR_FORMAT`] target written to outputs of `output_format`
+    pub fn new(
+        context: &GpuContext,
+        width: u32,
+        height: u32,
+        output_format: wgpu::TextureFormat
+    ) -> Result<Self> {
+        let layout = BindGroupLayoutBuilder::new()
+            .texture(
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .build(context, Some("Linear Target Layout"));
+        let source = Self::shader_source(output_format);
+        check_wgsl("Linear Output", None, &source)?;
+        let pipeline = fullscreen_pipeline(
+            context,
+            &source,
+            vec![layout.clone()],
+            output_format.into(),
+            "Linear Output"
+        );
+        let texture = Self::create_texture(context, width, height)?;
+        let bind_group = Self::create_bind_group(context, &layout, &texture);
+        Ok(Self { texture, layout, bind_group, pipeline, output_format })
+    }
+
+    /// The output pass's WGSL for outputs of `output_format`
+    pub fn shader_source(output_format: wgpu::TextureFormat) -> String {
+        let encode = if output_format.is_srgb() { "false" } else { "true" };
+        format!("{}{}", FULLSCREEN_VERTEX, OUTPUT_FRAGMENT.replace("{encode}", encode))
+    }
+
+    fn create_texture(context: &GpuContext, width: u32, height: u32) -> Result<Texture> {
+        TextureBuilder::new(width.max(1), height.max(1))
+            .format(LINEAR_FORMAT)
+            .usage(
+                wgpu::TextureUsages::RENDER_ATTACHMENT |
+                    wgpu::TextureUsages::TEXTURE_BINDING |
+                    wgpu::TextureUsages::COPY_SRC
+            )
+            .label("Linear Target")
+            .build(context)
+    }
+
+    fn create_bind_group(
+        context: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        texture: &Texture
+    ) -> wgpu::BindGroup {
+        BindGroupBuilder::new(layout)
+            .texture_view(0, &texture.view)
+            .build(context, Some("Linear Target Bind Group"))
+    }
+
+    /// Follow a new output size
+    pub fn resize(&mut self, context: &GpuContext, width: u32, height: u32) -> Result<()> {
+        if self.texture.size() != (width.max(1), height.max(1)) {
+            self.texture = Self::create_texture(context, width, height)?;
+            self.bind_group = Self::create_bind_group(context, &self.layout, &self.texture);
+        }
+        Ok(())
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// View to render the scene into
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.texture.view
+    }
+
+    /// Whether [`encode`](Self::encode) converts to sRGB itself, because the
+    /// output format does not
+    pub fn encodes_in_shader(&self) -> bool {
+        !self.output_format.is_srgb()
+    }
+
+    /// Write the target into `output`, a view of the output format the same size
+    pub fn encode(&self, commands: &mut RenderCommands, output: &wgpu::TextureView) {
+        let color = [Some(color_attachment(output, None))];
+        let mut pass = commands.begin_render_pass(&color, None, Some("Linear Output"));
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+const OUTPUT_FRAGMENT: &str =
+    r#"
+const ENCODE_SRGB: bool = {encode};
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+
+fn linear_to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(source, vec2<i32>(in.clip_position.xy), 0);
+    var rgb = clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    if (ENCODE_SRGB) {
+        rgb = linear_to_srgb(rgb);
+    }
+    return vec4<f32>(rgb, color.a);
+}
+"#;
@@ -0,0 +1,129 @@
+use crate::{ GeepuError, Result, TypedBuffer };
+use std::path::PathBuf;
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::{ Arc, Mutex };
+
+#[derive(Default)]
+enum DumpState {
+    #[default]
+    Idle,
+    /// Waiting for the next frame to start
+    Armed(PathBuf),
+    Recording {
+        path: PathBuf,
+        lines: Vec<String>,
+    },
+}
+
+#[derive(Default)]
+struct Shared {
+    /// Whether commands are being recorded, checked before taking the lock
+    recording: AtomicBool,
+    state: Mutex<DumpState>,
+}
+
+/// Text log of the commands issued during one frame, written by
+/// [`GpuContext::dump_next_frame`](crate::GpuContext::dump_next_frame)
+#[derive(Clone, Default)]
+pub(crate) struct CommandDump {
+    shared: Arc<Shared>,
+}
+
+impl CommandDump {
+    /// Record the frame after the next frame boundary into `path`
+    pub(crate) fn arm(&self, path: PathBuf) {
+        *self.shared.state.lock().unwrap() = DumpState::Armed(path);
+    }
+
+    /// Start recording if armed, or finish the recording and write it out.
+    /// Returns the path written, if any.
+    pub(crate) fn frame_boundary(&self) -> Result<Option<PathBuf>> {
+        let mut state = self.shared.state.lock().unwrap();
+        match std::mem::take(&mut *state) {
+            DumpState::Idle => Ok(None),
+            DumpState::Armed(path) => {
+                let lines = vec![format!("# geepu command dump: {}", path.display())];
+                *state = DumpState::Recording { path, lines };
+                self.shared.recording.store(true, Ordering::Release);
+                Ok(None)
+            }
+            DumpState::Recording { path, mut lines } => {
+                self.shared.recording.store(false, Ordering::Release);
+                lines.push(String::new());
+                std::fs::write(&path, lines.join("\n")).map_err(|e| {
+                    GeepuError::Other(format!("Failed to write {}: {}", path.display(), e))
+                })?;
+                Ok(Some(path))
+            }
+        }
+    }
+
+    /// A handle to record into while a frame is being dumped
+    pub(crate) fn recorder(&self) -> Option<CommandDump> {
+        self.shared.recording.load(Ordering::Acquire).then(|| self.clone())
+    }
+
+    /// Append one line to the current dump
+    pub(crate) fn record(&self, line: String) {
+        if let DumpState::Recording { lines, .. } = &mut *self.shared.state.lock().unwrap() {
+            lines.push(line);
+        }
+    }
+}
+
+/// `"label"`, or `<unlabeled>`
+pub(crate) fn quoted(label: Option<&str>) -> String {
+    label.map_or("<unlabeled>".to_string(), |label| format!("\"{}\"", label))
+}
+
+/// `Id(..) (<len> x <type>)`
+pub(crate) fn describe_buffer<T>(buffer: &TypedBuffer<T>) -> String where T: bytemuck::Pod {
+    format!("{:?} ({} x {})", buffer.buffer().global_id(), buffer.len(), std::any::type_name::<T>())
+}
+
+pub(crate) fn buffer_to_buffer(
+    source: &wgpu::Buffer,
+    source_offset: u64,
+    destination: &wgpu::Buffer,
+    destination_offset: u64,
+    copy_size: u64
+) -> String {
+    format!(
+        "copy_buffer_to_buffer {:?}+{} -> {:?}+{} ({} bytes)",
+        source.global_id(),
+        source_offset,
+        destination.global_id(),
+        destination_offset,
+        copy_size
+    )
+}
+
+pub(crate) fn buffer_to_texture(
+    source: &wgpu::ImageCopyBuffer,
+    destination: &wgpu::ImageCopyTexture,
+    copy_size: wgpu::Extent3d
+) -> String {
+    format!(
+        "copy_buffer_to_texture {:?}+{} -> {:?} mip {} {:?}",
+        source.buffer.global_id(),
+        source.layout.offset,
+        destination.texture.global_id(),
+        destination.mip_level,
+        copy_size
+    )
+}
+
+pub(crate) fn texture_to_buffer(
+    source: &wgpu::ImageCopyTexture,
+    destination: &wgpu::ImageCopyBuffer,
+    copy_size: wgpu::Extent3d
+) -> String {
+    format!(
+        "copy_texture_to_buffer {:?} mip {} -> {:?}+{} {:?}",
+        source.texture.global_id(),
+        source.mip_level,
+        destination.buffer.global_id(),
+        destination.layout.offset,
+        copy_size
+    )
+}
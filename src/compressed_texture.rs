@@ -0,0 +1,424 @@
+use crate::{ GeepuError, GpuContext, Result, Texture };
+use crate::profiling::profile_scope;
+use wgpu::util::DeviceExt;
+use wgpu::{ AstcBlock, AstcChannel, TextureFormat };
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+
+/// A pre-compressed (or raw) mip chain read from a KTX2 or DDS container,
+/// ready to upload without decoding.
+///
+/// Only the container is parsed: the texel data is uploaded as is, so the
+/// device must support the format's compression family
+/// ([`required_features`](Self::required_features)). Request those with
+/// [`GpuConfig::optional_features`](crate::GpuConfig::optional_features) and
+/// keep a fallback for devices without them.
+#[derive(Debug, Clone)]
+pub struct CompressedImage {
+    pub format: TextureFormat,
+    /// Size of mip 0; `depth_or_array_layers` counts cube faces as layers
+    pub size: wgpu::Extent3d,
+    pub dimension: wgpu::TextureViewDimension,
+    pub mip_level_count: u32,
+    /// Every mip of every layer, in `order`
+    pub data: Vec<u8>,
+    pub order: wgpu::util::TextureDataOrder,
+}
+
+impl CompressedImage {
+    /// Parse a KTX2 or DDS file, told apart by their magic bytes
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.starts_with(&KTX2_IDENTIFIER) {
+            Self::from_ktx2(bytes)
+        } else if bytes.starts_with(DDS_MAGIC) {
+            Self::from_dds(bytes)
+        } else {
+            Err(GeepuError::TextureError("Not a KTX2 or DDS file".to_string()))
+        }
+    }
+
+    /// Parse a KTX2 file. Supercompressed (Basis Universal, Zstandard) files
+    /// are not supported.
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self> {
+        let reader = Reader(bytes);
+        if !bytes.starts_with(&KTX2_IDENTIFIER) {
+            return Err(GeepuError::TextureError("Missing KTX2 identifier".to_string()));
+        }
+        let vk_format = reader.u32(12)?;
+        let width = reader.u32(20)?;
+        let height = reader.u32(24)?.max(1);
+        let depth = reader.u32(28)?;
+        let layers = reader.u32(32)?;
+        let faces = reader.u32(36)?;
+        let levels = reader.u32(40)?.max(1);
+        let supercompression = reader.u32(44)?;
+        if supercompression != 0 {
+            return Err(
+                GeepuError::TextureError(
+                    format!("Unsupported KTX2 supercompression scheme {}", supercompression)
+                )
+            );
+        }
+        let format = vk_format_to_wgpu(vk_format).ok_or_else(|| {
+            GeepuError::TextureError(format!("Unsupported KTX2 VkFormat {}", vk_format))
+        })?;
+
+        let dimension = match (depth > 0, faces, layers > 0) {
+            (true, _, _) => wgpu::TextureViewDimension::D3,
+            (false, 6, false) => wgpu::TextureViewDimension::Cube,
+            (false, 6, true) => wgpu::TextureViewDimension::CubeArray,
+            (false, _, true) => wgpu::TextureViewDimension::D2Array,
+            (false, _, false) => wgpu::TextureViewDimension::D2,
+        };
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: depth.max(1) * layers.max(1) * faces.max(1),
+        };
+
+        // The level index follows the 80 byte header, mip 0 first
+        let mut data = Vec::new();
+        for level in 0..levels as usize {
+            let entry = 80 + level * 24;
+            let offset = reader.u64(entry)? as usize;
+            let length = reader.u64(entry + 8)? as usize;
+            data.extend_from_slice(reader.bytes(offset, length)?);
+        }
+
+        Self::checked(Self {
+            format,
+            size,
+            dimension,
+            mip_level_count: levels,
+            data,
+            order: wgpu::util::TextureDataOrder::MipMajor,
+        })
+    }
+
+    /// Parse a DDS file, with or without the DX10 header extension
+    pub fn from_dds(bytes: &[u8]) -> Result<Self> {
+        let reader = Reader(bytes);
+        if !bytes.starts_with(DDS_MAGIC) {
+            return Err(GeepuError::TextureError("Missing DDS magic".to_string()));
+        }
+        let height = reader.u32(12)?;
+        let width = reader.u32(16)?;
+        let depth = reader.u32(24)?;
+        let levels = reader.u32(28)?.max(1);
+        let pixel_flags = reader.u32(80)?;
+        let four_cc = reader.bytes(84, 4)?;
+        let caps2 = reader.u32(112)?;
+
+        const DDPF_FOURCC: u32 = 0x4;
+        const DDPF_RGB: u32 = 0x40;
+        const DDSCAPS2_CUBEMAP: u32 = 0x200;
+        const DDSCAPS2_VOLUME: u32 = 0x200000;
+        const DX10_MISC_TEXTURECUBE: u32 = 0x4;
+
+        let (format, layers, cube, data_offset) = if
+            pixel_flags & DDPF_FOURCC != 0 &&
+            four_cc == b"DX10"
+        {
+            let dxgi_format = reader.u32(128)?;
+            let misc = reader.u32(136)?;
+            let array_size = reader.u32(140)?.max(1);
+            let format = dxgi_format_to_wgpu(dxgi_format).ok_or_else(|| {
+                GeepuError::TextureError(format!("Unsupported DXGI format {}", dxgi_format))
+            })?;
+            (format, array_size, misc & DX10_MISC_TEXTURECUBE != 0, 148)
+        } else {
+            let format = if pixel_flags & DDPF_FOURCC != 0 {
+                four_cc_to_wgpu(four_cc)
+            } else if pixel_flags & DDPF_RGB != 0 && reader.u32(88)? == 32 {
+                match (reader.u32(92)?, reader.u32(100)?) {
+                    (0xff, 0xff0000) => Some(TextureFormat::Rgba8Unorm),
+                    (0xff0000, 0xff) => Some(TextureFormat::Bgra8Unorm),
+                    _ => None,
+                }
+            } else {
+                None
+            };
+            let format = format.ok_or_else(|| {
+                GeepuError::TextureError("Unsupported DDS pixel format".to_string())
+            })?;
+            (format, 1, caps2 & DDSCAPS2_CUBEMAP != 0, 128)
+        };
+
+        let volume = caps2 & DDSCAPS2_VOLUME != 0 && depth > 1;
+        let dimension = match (volume, cube, layers > 1) {
+            (true, _, _) => wgpu::TextureViewDimension::D3,
+            (false, true, false) => wgpu::TextureViewDimension::Cube,
+            (false, true, true) => wgpu::TextureViewDimension::CubeArray,
+            (false, false, true) => wgpu::TextureViewDimension::D2Array,
+            (false, false, false) => wgpu::TextureViewDimension::D2,
+        };
+        let faces = if cube { 6 } else { 1 };
+        let depth_or_array_layers = if volume { depth } else { layers * faces };
+
+        Self::checked(Self {
+            format,
+            size: wgpu::Extent3d { width, height: height.max(1), depth_or_array_layers },
+            dimension,
+            mip_level_count: levels,
+            data: bytes.get(data_offset..).unwrap_or_default().to_vec(),
+            order: wgpu::util::TextureDataOrder::LayerMajor,
+        })
+    }
+
+    /// Device features needed to sample this image's format
+    pub fn required_features(&self) -> wgpu::Features {
+        self.format.required_features()
+    }
+
+    /// Bytes the mip chain takes up, for all layers
+    pub fn expected_size(&self) -> usize {
+        let (block_width, block_height) = self.format.block_dimensions();
+        let block_size = self.format.block_copy_size(None).unwrap_or(0) as usize;
+        let volume = self.dimension == wgpu::TextureViewDimension::D3;
+        (0..self.mip_level_count)
+            .map(|level| {
+                let width = (self.size.width >> level).max(1);
+                let height = (self.size.height >> level).max(1);
+                let depth = if volume {
+                    (self.size.depth_or_array_layers >> level).max(1)
+                } else {
+                    self.size.depth_or_array_layers
+                };
+                (width.div_ceil(block_width) as usize) *
+                    (height.div_ceil(block_height) as usize) *
+                    (depth as usize) *
+                    block_size
+            })
+            .sum()
+    }
+
+    /// Trim trailing bytes and fail on truncated data
+    fn checked(mut self) -> Result<Self> {
+        let expected = self.expected_size();
+        if self.data.len() < expected {
+            return Err(
+                GeepuError::TextureError(
+                    format!(
+                        "Expected {} bytes for a {}x{}x{} {:?} mip chain of {} levels, got {}",
+                        expected,
+                        self.size.width,
+                        self.size.height,
+                        self.size.depth_or_array_layers,
+                        self.format,
+                        self.mip_level_count,
+                        self.data.len()
+                    )
+                )
+            );
+        }
+        self.data.truncate(expected);
+        Ok(self)
+    }
+}
+
+impl Texture {
+    /// Upload a [`CompressedImage`] with its full mip chain, failing with
+    /// [`GeepuError::MissingFeatures`] if the device cannot sample its format
+    pub fn from_compressed(
+        context: &GpuContext,
+        image: &CompressedImage,
+        label: Option<&str>
+    ) -> Result<Self> {
+        let missing = image.required_features() - context.device.features();
+        if !missing.is_empty() {
+            return Err(GeepuError::MissingFeatures(missing));
+        }
+
+        profile_scope!("geepu::texture_upload", label.unwrap_or_default());
+        let dimension = match image.dimension {
+            wgpu::TextureViewDimension::D1 => wgpu::TextureDimension::D1,
+            wgpu::TextureViewDimension::D3 => wgpu::TextureDimension::D3,
+            _ => wgpu::TextureDimension::D2,
+        };
+        let texture = context.capture_errors("create_texture", label, || {
+            context.device.create_texture_with_data(
+                &context.queue,
+                &(wgpu::TextureDescriptor {
+                    label: context.prefixed_label(label).as_deref(),
+                    size: image.size,
+                    mip_level_count: image.mip_level_count,
+                    sample_count: 1,
+                    dimension,
+                    format: image.format,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                }),
+                image.order,
+                &image.data
+            )
+        })?;
+
+        let view = texture.create_view(
+            &(wgpu::TextureViewDescriptor {
+                dimension: Some(image.dimension),
+                ..Default::default()
+            })
+        );
+        let sampler = context.device.create_sampler(
+            &(wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::Repeat,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            })
+        );
+
+        Ok(Self::tracked(context, texture, view, sampler, label))
+    }
+
+    /// Parse a KTX2 or DDS file's bytes and upload it
+    pub fn from_compressed_bytes(
+        context: &GpuContext,
+        bytes: &[u8],
+        label: Option<&str>
+    ) -> Result<Self> {
+        Self::from_compressed(context, &CompressedImage::parse(bytes)?, label)
+    }
+}
+
+/// Bounds-checked little-endian reads
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.0.get(offset..end))
+            .ok_or_else(|| {
+                GeepuError::TextureError(
+                    format!("Texture file truncated reading {} bytes at {}", len, offset)
+                )
+            })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(offset, 4)?.try_into().unwrap()))
+    }
+
+    fn u64(&self, offset: usize) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(offset, 8)?.try_into().unwrap()))
+    }
+}
+
+fn astc(block: AstcBlock, srgb: bool) -> TextureFormat {
+    let channel = if srgb { AstcChannel::UnormSrgb } else { AstcChannel::Unorm };
+    TextureFormat::Astc { block, channel }
+}
+
+fn vk_format_to_wgpu(format: u32) -> Option<TextureFormat> {
+    const ASTC_BLOCKS: [AstcBlock; 14] = [
+        AstcBlock::B4x4,
+        AstcBlock::B5x4,
+        AstcBlock::B5x5,
+        AstcBlock::B6x5,
+        AstcBlock::B6x6,
+        AstcBlock::B8x5,
+        AstcBlock::B8x6,
+        AstcBlock::B8x8,
+        AstcBlock::B10x5,
+        AstcBlock::B10x6,
+        AstcBlock::B10x8,
+        AstcBlock::B10x10,
+        AstcBlock::B12x10,
+        AstcBlock::B12x12,
+    ];
+    Some(match format {
+        9 => TextureFormat::R8Unorm,
+        16 => TextureFormat::Rg8Unorm,
+        37 => TextureFormat::Rgba8Unorm,
+        43 => TextureFormat::Rgba8UnormSrgb,
+        44 => TextureFormat::Bgra8Unorm,
+        50 => TextureFormat::Bgra8UnormSrgb,
+        97 => TextureFormat::Rgba16Float,
+        109 => TextureFormat::Rgba32Float,
+        // BC1 with or without alpha
+        131 | 133 => TextureFormat::Bc1RgbaUnorm,
+        132 | 134 => TextureFormat::Bc1RgbaUnormSrgb,
+        135 => TextureFormat::Bc2RgbaUnorm,
+        136 => TextureFormat::Bc2RgbaUnormSrgb,
+        137 => TextureFormat::Bc3RgbaUnorm,
+        138 => TextureFormat::Bc3RgbaUnormSrgb,
+        139 => TextureFormat::Bc4RUnorm,
+        140 => TextureFormat::Bc4RSnorm,
+        141 => TextureFormat::Bc5RgUnorm,
+        142 => TextureFormat::Bc5RgSnorm,
+        143 => TextureFormat::Bc6hRgbUfloat,
+        144 => TextureFormat::Bc6hRgbFloat,
+        145 => TextureFormat::Bc7RgbaUnorm,
+        146 => TextureFormat::Bc7RgbaUnormSrgb,
+        147 => TextureFormat::Etc2Rgb8Unorm,
+        148 => TextureFormat::Etc2Rgb8UnormSrgb,
+        149 => TextureFormat::Etc2Rgb8A1Unorm,
+        150 => TextureFormat::Etc2Rgb8A1UnormSrgb,
+        151 => TextureFormat::Etc2Rgba8Unorm,
+        152 => TextureFormat::Etc2Rgba8UnormSrgb,
+        153 => TextureFormat::EacR11Unorm,
+        154 => TextureFormat::EacR11Snorm,
+        155 => TextureFormat::EacRg11Unorm,
+        156 => TextureFormat::EacRg11Snorm,
+        // ASTC LDR blocks alternate UNORM and SRGB
+        157..=184 => {
+            let index = (format - 157) as usize;
+            astc(ASTC_BLOCKS[index / 2], index % 2 == 1)
+        }
+        _ => {
+            return None;
+        }
+    })
+}
+
+fn dxgi_format_to_wgpu(format: u32) -> Option<TextureFormat> {
+    Some(match format {
+        2 => TextureFormat::Rgba32Float,
+        10 => TextureFormat::Rgba16Float,
+        28 => TextureFormat::Rgba8Unorm,
+        29 => TextureFormat::Rgba8UnormSrgb,
+        49 => TextureFormat::Rg8Unorm,
+        61 => TextureFormat::R8Unorm,
+        71 => TextureFormat::Bc1RgbaUnorm,
+        72 => TextureFormat::Bc1RgbaUnormSrgb,
+        74 => TextureFormat::Bc2RgbaUnorm,
+        75 => TextureFormat::Bc2RgbaUnormSrgb,
+        77 => TextureFormat::Bc3RgbaUnorm,
+        78 => TextureFormat::Bc3RgbaUnormSrgb,
+        80 => TextureFormat::Bc4RUnorm,
+        81 => TextureFormat::Bc4RSnorm,
+        83 => TextureFormat::Bc5RgUnorm,
+        84 => TextureFormat::Bc5RgSnorm,
+        87 => TextureFormat::Bgra8Unorm,
+        91 => TextureFormat::Bgra8UnormSrgb,
+        95 => TextureFormat::Bc6hRgbUfloat,
+        96 => TextureFormat::Bc6hRgbFloat,
+        98 => TextureFormat::Bc7RgbaUnorm,
+        99 => TextureFormat::Bc7RgbaUnormSrgb,
+        _ => {
+            return None;
+        }
+    })
+}
+
+fn four_cc_to_wgpu(four_cc: &[u8]) -> Option<TextureFormat> {
+    Some(match four_cc {
+        b"DXT1" => TextureFormat::Bc1RgbaUnorm,
+        b"DXT2" | b"DXT3" => TextureFormat::Bc2RgbaUnorm,
+        b"DXT4" | b"DXT5" => TextureFormat::Bc3RgbaUnorm,
+        b"ATI1" | b"BC4U" => TextureFormat::Bc4RUnorm,
+        b"BC4S" => TextureFormat::Bc4RSnorm,
+        b"ATI2" | b"BC5U" => TextureFormat::Bc5RgUnorm,
+        b"BC5S" => TextureFormat::Bc5RgSnorm,
+        _ => {
+            return None;
+        }
+    })
+}
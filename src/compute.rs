@@ -1,33 +1,126 @@
-use crate::{ GpuContext, ComputePipeline, TypedBuffer };
+use crate::{
+    check_indirect_offset,
+    BindingRegistry,
+    DispatchIndirectArgs,
+    GeepuError,
+    GpuContext,
+    GpuProfiler,
+    ComputePipeline,
+    PassState,
+    QuerySet,
+    Result,
+    StagingBuffer,
+    TypedBuffer,
+};
+use crate::command_dump::{ self, describe_buffer, quoted, CommandDump };
+use crate::profiling::profile_scope;
 
 /// A high-level compute pass wrapper
 pub struct ComputePass<'a> {
     pass: wgpu::ComputePass<'a>,
+    debug_group: bool,
+    validation: Option<PassState>,
+    dump: Option<CommandDump>,
 }
 
 impl<'a> ComputePass<'a> {
     /// Create a new compute pass
     pub fn new(encoder: &'a mut wgpu::CommandEncoder, label: Option<&str>) -> Self {
+        Self::begin(encoder, None, label)
+    }
+
+    fn begin(
+        encoder: &'a mut wgpu::CommandEncoder,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites<'a>>,
+        label: Option<&str>
+    ) -> Self {
         let pass = encoder.begin_compute_pass(
             &(wgpu::ComputePassDescriptor {
                 label,
-                timestamp_writes: None,
+                timestamp_writes,
             })
         );
 
-        Self { pass }
+        Self { pass, debug_group: false, validation: None, dump: None }
+    }
+
+    /// Wrap the rest of the pass in a debug group named `label`, popped on drop
+    fn with_debug_group(mut self, label: Option<&str>, enabled: bool) -> Self {
+        if let (Some(label), true) = (label, enabled) {
+            self.pass.push_debug_group(label);
+            self.debug_group = true;
+        }
+        self
+    }
+
+    /// Track bound state so [`try_dispatch_workgroups`](Self::try_dispatch_workgroups) can check it
+    fn with_validation(mut self, label: Option<&str>, enabled: bool) -> Self {
+        if enabled {
+            self.validation = Some(PassState::new(label));
+        }
+        self
+    }
+
+    /// Record the pass into a frame dump, if one is in progress
+    fn with_dump(mut self, dump: Option<CommandDump>) -> Self {
+        self.dump = dump;
+        self
+    }
+
+    fn record(&self, line: impl FnOnce() -> String) {
+        if let Some(dump) = &self.dump {
+            dump.record(format!("    {}", line()));
+        }
     }
 
     /// Set the compute pipeline
     pub fn set_pipeline(&mut self, pipeline: &'a ComputePipeline) {
+        if let Some(state) = &mut self.validation {
+            state.set_pipeline(pipeline.bind_group_layouts.len(), 0);
+        }
+        self.record(|| {
+            format!(
+                "set_pipeline {:?} ({} bind group layouts)",
+                pipeline.pipeline.global_id(),
+                pipeline.bind_group_layouts.len()
+            )
+        });
         self.pass.set_pipeline(&pipeline.pipeline);
     }
 
     /// Set a bind group
     pub fn set_bind_group(&mut self, index: u32, bind_group: &'a wgpu::BindGroup, offsets: &[u32]) {
+        if let Some(state) = &mut self.validation {
+            state.set_bind_group(index);
+        }
+        self.record(|| {
+            format!("set_bind_group {} {:?} offsets {:?}", index, bind_group.global_id(), offsets)
+        });
         self.pass.set_bind_group(index, bind_group, offsets);
     }
 
+    /// Write a GPU timestamp into slot `index` of a timestamp [`QuerySet`].
+    /// Needs `TIMESTAMP_QUERY_INSIDE_PASSES`.
+    pub fn write_timestamp(&mut self, queries: &QuerySet, index: u32) {
+        self.record(|| format!("write_timestamp {:?} {}", queries.raw().global_id(), index));
+        self.pass.write_timestamp(queries.raw(), index);
+    }
+
+    /// Start counting pipeline statistics into slot `index` of `queries`
+    pub fn begin_pipeline_statistics_query(&mut self, queries: &'a QuerySet, index: u32) {
+        self.record(|| {
+            format!("begin_pipeline_statistics_query {:?} {}", queries.raw().global_id(), index)
+        });
+        self.pass.begin_pipeline_statistics_query(queries.raw(), index);
+    }
+
+    /// End the query started by
+    /// [`begin_pipeline_statistics_query`](Self::begin_pipeline_statistics_query)
+    pub fn end_pipeline_statistics_query(&mut self) {
+        self.record(|| "end_pipeline_statistics_query".to_string());
+        self.pass.end_pipeline_statistics_query();
+    }
+
     /// Dispatch compute workgroups
     pub fn dispatch_workgroups(
         &mut self,
@@ -35,9 +128,33 @@ impl<'a> ComputePass<'a> {
         workgroup_count_y: u32,
         workgroup_count_z: u32
     ) {
+        self.record(|| {
+            format!(
+                "dispatch_workgroups {}x{}x{}",
+                workgroup_count_x,
+                workgroup_count_y,
+                workgroup_count_z
+            )
+        });
         self.pass.dispatch_workgroups(workgroup_count_x, workgroup_count_y, workgroup_count_z);
     }
 
+    /// Like [`dispatch_workgroups`](Self::dispatch_workgroups), but first checks
+    /// that a pipeline and its bind groups are set when
+    /// [`GpuConfig::validate_commands`](crate::GpuConfig::validate_commands) is on
+    pub fn try_dispatch_workgroups(
+        &mut self,
+        workgroup_count_x: u32,
+        workgroup_count_y: u32,
+        workgroup_count_z: u32
+    ) -> Result<()> {
+        if let Some(state) = &self.validation {
+            state.check_dispatch()?;
+        }
+        self.dispatch_workgroups(workgroup_count_x, workgroup_count_y, workgroup_count_z);
+        Ok(())
+    }
+
     /// Dispatch compute workgroups indirectly
     pub fn dispatch_workgroups_indirect<T>(
         &mut self,
@@ -46,13 +163,58 @@ impl<'a> ComputePass<'a> {
     )
         where T: bytemuck::Pod
     {
+        self.record(|| {
+            let buffer = describe_buffer(indirect_buffer);
+            format!("dispatch_workgroups_indirect {}+{}", buffer, indirect_offset)
+        });
         self.pass.dispatch_workgroups_indirect(indirect_buffer.buffer(), indirect_offset);
     }
+
+    /// Like [`dispatch_workgroups_indirect`](Self::dispatch_workgroups_indirect),
+    /// but first checks that the offset is 4-byte aligned and in bounds and the
+    /// buffer has `INDIRECT` usage, plus the bound state when validation is on
+    pub fn try_dispatch_workgroups_indirect<T>(
+        &mut self,
+        indirect_buffer: &'a TypedBuffer<T>,
+        indirect_offset: u64
+    ) -> Result<()>
+        where T: bytemuck::Pod
+    {
+        let size = std::mem::size_of::<DispatchIndirectArgs>() as u64;
+        check_indirect_offset(indirect_buffer.buffer(), indirect_offset, size)
+            .map_err(GeepuError::into_compute)?;
+        if let Some(state) = &self.validation {
+            state.check_dispatch()?;
+        }
+        self.dispatch_workgroups_indirect(indirect_buffer, indirect_offset);
+        Ok(())
+    }
+
+    /// Dispatch with the workgroup counts at `index` in an indirect buffer
+    pub fn dispatch_indirect(&mut self, args: &'a TypedBuffer<DispatchIndirectArgs>, index: usize) {
+        let offset = (index * std::mem::size_of::<DispatchIndirectArgs>()) as u64;
+        self.dispatch_workgroups_indirect(args, offset);
+    }
+}
+
+impl Drop for ComputePass<'_> {
+    fn drop(&mut self) {
+        if self.debug_group {
+            self.pass.pop_debug_group();
+        }
+        if let Some(dump) = &self.dump {
+            dump.record("  end compute pass".to_string());
+        }
+    }
 }
 
 /// A high-level compute command builder
 pub struct ComputeCommands {
     encoder: wgpu::CommandEncoder,
+    label_prefix: Option<String>,
+    debug_groups: bool,
+    validate_commands: bool,
+    dump: Option<CommandDump>,
 }
 
 impl ComputeCommands {
@@ -60,16 +222,87 @@ impl ComputeCommands {
     pub fn new(context: &GpuContext, label: Option<&str>) -> Self {
         let encoder = context.device.create_command_encoder(
             &(wgpu::CommandEncoderDescriptor {
-                label,
+                label: context.prefixed_label(label).as_deref(),
             })
         );
+        let dump = context.command_recorder();
+        if let Some(dump) = &dump {
+            dump.record(format!("compute commands {}", quoted(label)));
+        }
+
+        Self {
+            encoder,
+            label_prefix: context.config.label_prefix.clone(),
+            debug_groups: context.config.debug_groups,
+            validate_commands: context.config.validate_commands,
+            dump,
+        }
+    }
+
+    fn pass_label(&self, label: Option<&str>) -> Option<String> {
+        label.map(|label| format!("{}{}", self.label_prefix.as_deref().unwrap_or(""), label))
+    }
+
+    fn record(&self, line: impl FnOnce() -> String) {
+        if let Some(dump) = &self.dump {
+            dump.record(format!("  {}", line()));
+        }
+    }
 
-        Self { encoder }
+    /// Dump the start of a pass and hand the dump on to it
+    fn record_pass(&self, label: Option<&str>) -> Option<CommandDump> {
+        self.record(|| format!("begin compute pass {}", quoted(label)));
+        self.dump.clone()
     }
 
-    /// Begin a compute pass
+    /// Begin a compute pass, wrapped in a debug group when labeled
     pub fn begin_compute_pass<'a>(&'a mut self, label: Option<&str>) -> ComputePass<'a> {
-        ComputePass::new(&mut self.encoder, label)
+        let label = self.pass_label(label);
+        let dump = self.record_pass(label.as_deref());
+        ComputePass::new(&mut self.encoder, label.as_deref())
+            .with_debug_group(label.as_deref(), self.debug_groups)
+            .with_validation(label.as_deref(), self.validate_commands)
+            .with_dump(dump)
+    }
+
+    /// Begin a compute pass timed by `profiler` under `label`
+    pub fn begin_profiled_compute_pass<'a>(
+        &'a mut self,
+        profiler: &'a mut GpuProfiler,
+        label: &str
+    ) -> ComputePass<'a> {
+        let scope = profiler.begin_scope(label);
+        let profiler: &'a GpuProfiler = profiler;
+        let label = self.pass_label(Some(label));
+        let timestamp_writes = profiler.compute_timestamp_writes(scope);
+        let dump = self.record_pass(label.as_deref());
+        ComputePass::begin(&mut self.encoder, timestamp_writes, label.as_deref())
+            .with_debug_group(label.as_deref(), self.debug_groups)
+            .with_validation(label.as_deref(), self.validate_commands)
+            .with_dump(dump)
+    }
+
+    /// Dispatch `pipeline` in a pass of its own, with its bind groups built
+    /// from the resources `registry` holds under the names its shader declares
+    pub fn dispatch_compute(
+        &mut self,
+        context: &GpuContext,
+        pipeline: &ComputePipeline,
+        registry: &BindingRegistry,
+        workgroups: [u32; 3],
+        label: Option<&str>
+    ) -> Result<()> {
+        let bind_groups = registry
+            .create_bind_groups(context, pipeline, &[])
+            .map_err(GeepuError::into_compute)?;
+        let mut pass = self.begin_compute_pass(label);
+        pass.set_pipeline(pipeline);
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            pass.set_bind_group(index as u32, bind_group, &[]);
+        }
+        let [x, y, z] = workgroups;
+        pass.dispatch_workgroups(x, y, z);
+        Ok(())
     }
 
     /// Copy buffer to buffer
@@ -81,6 +314,15 @@ impl ComputeCommands {
         destination_offset: u64,
         copy_size: u64
     ) {
+        self.record(|| {
+            command_dump::buffer_to_buffer(
+                source,
+                source_offset,
+                destination,
+                destination_offset,
+                copy_size
+            )
+        });
         self.encoder.copy_buffer_to_buffer(
             source,
             source_offset,
@@ -97,6 +339,7 @@ impl ComputeCommands {
         destination: wgpu::ImageCopyTexture,
         copy_size: wgpu::Extent3d
     ) {
+        self.record(|| command_dump::buffer_to_texture(&source, &destination, copy_size));
         self.encoder.copy_buffer_to_texture(source, destination, copy_size);
     }
 
@@ -107,6 +350,7 @@ impl ComputeCommands {
         destination: wgpu::ImageCopyBuffer,
         copy_size: wgpu::Extent3d
     ) {
+        self.record(|| command_dump::texture_to_buffer(&source, &destination, copy_size));
         self.encoder.copy_texture_to_buffer(source, destination, copy_size);
     }
 
@@ -125,9 +369,41 @@ impl ComputeCommands {
         self.encoder.pop_debug_group();
     }
 
+    /// Record resolving the used slots of `queries` for [`QuerySet::read`]
+    pub fn resolve_queries(&mut self, queries: &QuerySet) {
+        self.record(|| {
+            format!("resolve_queries {:?} 0..{}", queries.raw().global_id(), queries.used())
+        });
+        queries.resolve(&mut self.encoder);
+    }
+
     /// Finish and submit commands
     pub fn submit(self, context: &GpuContext) {
-        context.queue.submit(std::iter::once(self.encoder.finish()));
+        profile_scope!("geepu::submit");
+        self.record(|| "submit".to_string());
+        context.submit(std::iter::once(self.encoder.finish()));
+    }
+
+    /// Finish recording without submitting, to submit together with other
+    /// commands through a [`CommandBatch`](crate::CommandBatch)
+    pub fn finish(self) -> wgpu::CommandBuffer {
+        self.record(|| "finish".to_string());
+        self.encoder.finish()
+    }
+
+    /// Finish and submit commands, calling `callback` once the GPU has
+    /// finished executing them.
+    ///
+    /// The callback runs from a later submit or device poll, natively, and
+    /// from the browser's event loop on the web; keep it short, e.g. to flag a
+    /// buffer as free for reuse or a readback as ready.
+    pub fn submit_with_callback(
+        self,
+        context: &GpuContext,
+        callback: impl FnOnce() + Send + 'static
+    ) {
+        self.submit(context);
+        context.queue.on_submitted_work_done(callback);
     }
 
     /// Get the underlying encoder (for advanced usage)
@@ -136,6 +412,204 @@ impl ComputeCommands {
     }
 }
 
+/// Dispatches run before timing starts, to warm caches and clocks up
+const BENCH_WARMUP: u32 = 3;
+
+/// Timings of the dispatches measured by [`bench`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    /// Milliseconds per dispatch, in the order they ran
+    pub samples_ms: Vec<f32>,
+    pub min_ms: f32,
+    pub median_ms: f32,
+    pub mean_ms: f32,
+    /// Standard deviation of the samples in milliseconds
+    pub stddev_ms: f32,
+    /// Whether the samples come from GPU timestamps rather than wall time
+    /// from submit to completion, which includes submission overhead
+    pub gpu_timestamps: bool,
+    /// Workgroups per dispatch
+    pub workgroups: u64,
+}
+
+impl BenchReport {
+    /// Summarize per-dispatch timings
+    pub fn from_samples(samples_ms: Vec<f32>, gpu_timestamps: bool, workgroups: u64) -> Self {
+        let mut sorted = samples_ms.clone();
+        sorted.sort_by(f32::total_cmp);
+        let count = sorted.len().max(1) as f32;
+        let median_ms = match sorted.len() {
+            0 => 0.0,
+            len if len % 2 == 0 => (sorted[len / 2 - 1] + sorted[len / 2]) * 0.5,
+            len => sorted[len / 2],
+        };
+        let mean_ms = sorted.iter().sum::<f32>() / count;
+        let variance = sorted.iter().map(|sample| (sample - mean_ms).powi(2)).sum::<f32>() / count;
+        Self {
+            min_ms: sorted.first().copied().unwrap_or(0.0),
+            median_ms,
+            mean_ms,
+            stddev_ms: variance.sqrt(),
+            samples_ms,
+            gpu_timestamps,
+            workgroups,
+        }
+    }
+
+    /// Workgroups completed per second at the median time
+    pub fn workgroups_per_second(&self) -> f64 {
+        self.per_second(self.workgroups as f64)
+    }
+
+    /// `items` processed per dispatch, as items per second at the median time
+    pub fn throughput(&self, items: u64) -> f64 {
+        self.per_second(items as f64)
+    }
+
+    /// Gigabytes per second at the median time when each dispatch reads and
+    /// writes `bytes` in total
+    pub fn bandwidth_gb_per_second(&self, bytes: u64) -> f64 {
+        self.per_second(bytes as f64) / 1e9
+    }
+
+    fn per_second(&self, amount: f64) -> f64 {
+        if self.median_ms <= 0.0 {
+            return 0.0;
+        }
+        amount / ((self.median_ms as f64) / 1000.0)
+    }
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} dispatches: min {:.3} ms, median {:.3} ms, mean {:.3} ms, stddev {:.3} ms ({})",
+            self.samples_ms.len(),
+            self.min_ms,
+            self.median_ms,
+            self.mean_ms,
+            self.stddev_ms,
+            if self.gpu_timestamps { "GPU timestamps" } else { "wall time" }
+        )
+    }
+}
+
+/// Time `iterations` dispatches of `pipeline` with `workgroups` workgroups,
+/// for tuning kernels.
+///
+/// A few untimed dispatches run first. Each dispatch is measured with GPU
+/// timestamps when the device has `wgpu::Features::TIMESTAMP_QUERY`, and
+/// otherwise by waiting for it to finish on the CPU. Blocks until done.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn bench<'a>(
+    context: &GpuContext,
+    pipeline: &'a ComputePipeline,
+    bind_groups: &[&'a wgpu::BindGroup],
+    workgroups: (u32, u32, u32),
+    iterations: u32
+) -> Result<BenchReport> {
+    let (x, y, z) = workgroups;
+
+    let mut warmup = ComputeCommands::new(context, Some("Bench Warmup"));
+    for _ in 0..BENCH_WARMUP {
+        let mut pass = warmup.begin_compute_pass(Some("Bench Warmup"));
+        bench_dispatch(&mut pass, pipeline, bind_groups, workgroups);
+    }
+    warmup.submit(context);
+    context.device.poll(wgpu::Maintain::Wait);
+
+    let timestamps = context.device.features().contains(wgpu::Features::TIMESTAMP_QUERY);
+    let mut samples = Vec::with_capacity(iterations as usize);
+    if timestamps {
+        let period = context.queue.get_timestamp_period() as f64;
+        let max_batch = iterations.clamp(1, wgpu::QUERY_SET_MAX_QUERIES / 2);
+        let query_set = context.device.create_query_set(
+            &(wgpu::QuerySetDescriptor {
+                label: Some(&context.label("Bench Timestamps")),
+                ty: wgpu::QueryType::Timestamp,
+                count: max_batch * 2,
+            })
+        );
+        let size = ((max_batch * 2) as u64) * (wgpu::QUERY_SIZE as u64);
+        let resolve = context.device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some(&context.label("Bench Resolve")),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            })
+        );
+        let staging = StagingBuffer::new(context, size)?;
+
+        let mut remaining = iterations;
+        while remaining > 0 {
+            let batch = remaining.min(max_batch);
+            remaining -= batch;
+            let mut commands = ComputeCommands::new(context, Some("Bench"));
+            for index in 0..batch {
+                let timestamp_writes = wgpu::ComputePassTimestampWrites {
+                    query_set: &query_set,
+                    beginning_of_pass_write_index: Some(index * 2),
+                    end_of_pass_write_index: Some(index * 2 + 1),
+                };
+                let dump = commands.record_pass(Some("Bench"));
+                let mut pass = ComputePass::begin(
+                    &mut commands.encoder,
+                    Some(timestamp_writes),
+                    Some("Bench")
+                ).with_dump(dump);
+                bench_dispatch(&mut pass, pipeline, bind_groups, workgroups);
+            }
+            let count = batch * 2;
+            commands.encoder.resolve_query_set(&query_set, 0..count, &resolve, 0);
+            staging.copy_from_buffer(
+                &mut commands.encoder,
+                &resolve,
+                Some((count as u64) * (wgpu::QUERY_SIZE as u64))
+            );
+            commands.submit(context);
+
+            let ticks: Vec<u64> = pollster::block_on(staging.read_data(context))
+                .map_err(GeepuError::into_compute)?;
+            samples.extend(
+                ticks[..count as usize]
+                    .chunks_exact(2)
+                    .map(|pair| ((pair[1].saturating_sub(pair[0]) as f64) * period / 1e6) as f32)
+            );
+        }
+    } else {
+        for _ in 0..iterations {
+            let mut commands = ComputeCommands::new(context, Some("Bench"));
+            let mut pass = commands.begin_compute_pass(Some("Bench"));
+            bench_dispatch(&mut pass, pipeline, bind_groups, workgroups);
+            drop(pass);
+            let command_buffer = commands.finish();
+            let start = std::time::Instant::now();
+            let index = context.submit(std::iter::once(command_buffer));
+            context.device.poll(wgpu::Maintain::WaitForSubmissionIndex(index));
+            samples.push(start.elapsed().as_secs_f32() * 1000.0);
+        }
+    }
+
+    let total = (x as u64) * (y as u64) * (z as u64);
+    Ok(BenchReport::from_samples(samples, timestamps, total))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn bench_dispatch<'a>(
+    pass: &mut ComputePass<'a>,
+    pipeline: &'a ComputePipeline,
+    bind_groups: &[&'a wgpu::BindGroup],
+    (x, y, z): (u32, u32, u32)
+) {
+    pass.set_pipeline(pipeline);
+    for (index, bind_group) in bind_groups.iter().enumerate() {
+        pass.set_bind_group(index as u32, bind_group, &[]);
+    }
+    pass.dispatch_workgroups(x, y, z);
+}
+
 /// Helper for compute workgroup size calculations
 pub struct WorkgroupSize {
     pub x: u32,
@@ -164,9 +638,9 @@ impl WorkgroupSize {
         data_size_z: u32
     ) -> (u32, u32, u32) {
         (
-            (data_size_x + self.x - 1) / self.x,
-            (data_size_y + self.y - 1) / self.y,
-            (data_size_z + self.z - 1) / self.z,
+            data_size_x.div_ceil(self.x),
+            data_size_y.div_ceil(self.y),
+            data_size_z.div_ceil(self.z),
         )
     }
 }
@@ -369,3 +843,9 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>,
         )
     }
 }
+
+impl From<ComputeCommands> for wgpu::CommandBuffer {
+    fn from(commands: ComputeCommands) -> Self {
+        commands.finish()
+    }
+}
@@ -0,0 +1,358 @@
+/// Configuration used when creating a [`GpuContext`](crate::GpuContext)
+#[derive(Debug, Clone)]
+pub struct GpuConfig {
+    pub backends: wgpu::Backends,
+    pub instance_flags: wgpu::InstanceFlags,
+    pub dx12_shader_compiler: wgpu::Dx12Compiler,
+    pub gles_minor_version: wgpu::Gles3MinorVersion,
+    pub features: wgpu::Features,
+    /// Features enabled only when the adapter supports them, e.g. texture compression
+    pub optional_features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    pub power_preference: wgpu::PowerPreference,
+    pub memory_hints: wgpu::MemoryHints,
+    /// Wait for vertical blank when presenting (default true)
+    pub vsync: bool,
+    /// Frames the presentation engine may queue ahead of the GPU (default 2)
+    pub desired_maximum_frame_latency: u32,
+    /// Try the fallback (software) adapter before any hardware adapter
+    pub prefer_fallback_adapter: bool,
+    /// Fall back to a headless context when the window surface cannot be created
+    pub allow_missing_surface: bool,
+    /// Prepended to the debug label of every resource and pass geepu creates
+    pub label_prefix: Option<String>,
+    /// Wrap labeled passes in debug groups of the same name (default true)
+    pub debug_groups: bool,
+    /// Make adapter choice and presentation reproducible across runs
+    pub deterministic: bool,
+    /// Record live buffers and textures for leak reports (default on in debug builds)
+    pub track_resources: bool,
+    /// Check bound state in the passes' `try_` draw and dispatch calls (default on in debug)
+    pub validate_commands: bool,
+    /// Return wgpu errors from resource creation as results (default on in debug)
+    pub capture_errors: bool,
+    /// Collect buffer and texture writes into one staging copy per submit (default true)
+    pub batch_uploads: bool,
+    /// MSAA sample count of the surface's color and depth targets (default 1)
+    pub samples: u32,
+    /// Open the device with the Vulkan external memory extensions, for sharing
+    /// textures with other APIs and processes (default false)
+    #[cfg(all(feature = "vulkan-interop", not(any(target_arch = "wasm32", target_vendor = "apple"))))]
+    pub external_memory: bool,
+}
+
+impl GpuConfig {
+    pub fn new() -> Self {
+        Self {
+            backends: wgpu::Backends::PRIMARY,
+            instance_flags: wgpu::InstanceFlags::default(),
+            dx12_shader_compiler: wgpu::Dx12Compiler::default(),
+            gles_minor_version: wgpu::Gles3MinorVersion::default(),
+            features: wgpu::Features::empty(),
+            optional_features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            memory_hints: wgpu::MemoryHints::Performance,
+            vsync: true,
+            desired_maximum_frame_latency: 2,
+            prefer_fallback_adapter: false,
+            allow_missing_surface: false,
+            label_prefix: None,
+            debug_groups: true,
+            deterministic: false,
+            track_resources: cfg!(debug_assertions),
+            validate_commands: cfg!(debug_assertions),
+            capture_errors: cfg!(debug_assertions),
+            batch_uploads: true,
+            samples: 1,
+            #[cfg(all(feature = "vulkan-interop", not(any(target_arch = "wasm32", target_vendor = "apple"))))]
+            external_memory: false,
+        }
+    }
+
+    /// Configuration for GPU tests in headless CI containers.
+    ///
+    /// Prefers the software adapter (lavapipe, WARP) on any backend, only
+    /// requires downlevel limits, creates a headless context instead of
+    /// failing when no window surface is available, and is
+    /// [`deterministic`](Self::deterministic).
+    pub fn testing() -> Self {
+        Self {
+            backends: wgpu::Backends::all(),
+            limits: wgpu::Limits::downlevel_defaults(),
+            power_preference: wgpu::PowerPreference::LowPower,
+            prefer_fallback_adapter: true,
+            allow_missing_surface: true,
+            deterministic: true,
+            ..Self::new()
+        }
+    }
+
+    /// Restrict which backends the instance may use
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    /// Select backends from a comma separated list of names, e.g. `"vulkan,dx12"`.
+    ///
+    /// Accepts the same names as `WGPU_BACKEND`: `vulkan`/`vk`, `dx12`/`d3d12`,
+    /// `metal`/`mtl`, `opengl`/`gles`/`gl` and `webgpu`. Unknown names are ignored.
+    pub fn backends_by_name(mut self, names: &str) -> Self {
+        self.backends = wgpu::util::parse_backends_from_comma_list(names);
+        self
+    }
+
+    /// Override the backends from the `WGPU_BACKEND` environment variable, if set.
+    ///
+    /// Lets users and CI force a backend without recompiling; the configured
+    /// backends are kept when the variable is absent.
+    pub fn backend_from_env(mut self) -> Self {
+        if let Some(backends) = wgpu::util::backend_bits_from_env() {
+            self.backends = backends;
+        }
+        self
+    }
+
+    /// Set the instance flags passed to wgpu
+    pub fn instance_flags(mut self, flags: wgpu::InstanceFlags) -> Self {
+        self.instance_flags = flags;
+        self
+    }
+
+    /// Enable or disable backend validation layers (`InstanceFlags::VALIDATION`).
+    ///
+    /// Validation is on by default in debug builds only; release builds can opt
+    /// in explicitly, debug builds can strip the overhead.
+    pub fn validation(mut self, enabled: bool) -> Self {
+        self.instance_flags.set(wgpu::InstanceFlags::VALIDATION, enabled);
+        self
+    }
+
+    /// Enable or disable debug labels and markers in the backend (`InstanceFlags::DEBUG`)
+    pub fn debug_labels(mut self, enabled: bool) -> Self {
+        self.instance_flags.set(wgpu::InstanceFlags::DEBUG, enabled);
+        self
+    }
+
+    /// Choose the DX12 shader compiler.
+    ///
+    /// `Fxc` (the default) needs no extra DLLs; `Dxc` is faster and maintained but
+    /// requires shipping `dxcompiler.dll` and `dxil.dll` with the application.
+    pub fn dx12_shader_compiler(mut self, compiler: wgpu::Dx12Compiler) -> Self {
+        self.dx12_shader_compiler = compiler;
+        self
+    }
+
+    /// Choose which OpenGL ES 3 minor version the GL backend requests
+    pub fn gles_minor_version(mut self, version: wgpu::Gles3MinorVersion) -> Self {
+        self.gles_minor_version = version;
+        self
+    }
+
+    /// Enable or disable vsync.
+    ///
+    /// With vsync off, `Mailbox` is used when the surface supports it (no tearing,
+    /// lowest latency) and `Fifo` otherwise; `Immediate` is never picked implicitly.
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    /// Pick the present mode for this configuration from the modes a surface supports
+    pub fn present_mode_for(&self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        let mailbox = !self.vsync && !self.deterministic;
+        if mailbox && supported.contains(&wgpu::PresentMode::Mailbox) {
+            wgpu::PresentMode::Mailbox
+        } else {
+            // Fifo is the only mode every surface is required to support
+            wgpu::PresentMode::Fifo
+        }
+    }
+
+    /// Set how many frames may be queued for presentation.
+    ///
+    /// Latency-sensitive apps should use 1; throughput-oriented apps can raise it
+    /// above the default of 2 to keep the GPU busier.
+    pub fn frame_latency(mut self, latency: u32) -> Self {
+        self.desired_maximum_frame_latency = latency;
+        self
+    }
+
+    /// Render to the surface with `count` samples per pixel.
+    ///
+    /// The context keeps a multisampled color target that
+    /// [`RenderPassGuard`](crate::RenderPassGuard) draws into and resolves to
+    /// the surface. Pipelines and built-in renderers default to 1 sample so they
+    /// can draw into offscreen targets; for passes into the surface pass
+    /// [`GpuContext::sample_count`](crate::GpuContext::sample_count) to their
+    /// `new_multisampled` constructors or to
+    /// [`PipelineBuilder::samples`](crate::PipelineBuilder::samples).
+    /// Usually 4, the only count besides 1 every adapter supports.
+    pub fn samples(mut self, count: u32) -> Self {
+        self.samples = count.max(1);
+        self
+    }
+
+    /// Try the fallback (software) adapter before any hardware adapter
+    pub fn prefer_fallback_adapter(mut self, prefer: bool) -> Self {
+        self.prefer_fallback_adapter = prefer;
+        self
+    }
+
+    /// Create a headless context instead of failing when the surface cannot be created
+    pub fn allow_missing_surface(mut self, allow: bool) -> Self {
+        self.allow_missing_surface = allow;
+        self
+    }
+
+    /// Require device features
+    pub fn features(mut self, features: wgpu::Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Enable device features the adapter supports, without failing on the
+    /// rest; check `context.device.features()` for what was enabled
+    pub fn optional_features(mut self, features: wgpu::Features) -> Self {
+        self.optional_features = features;
+        self
+    }
+
+    /// Require device limits
+    pub fn limits(mut self, limits: wgpu::Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the adapter power preference
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    /// Set allocator memory hints.
+    ///
+    /// `Performance` (the default) favours large allocation blocks, `MemoryUsage`
+    /// keeps them small for memory-constrained targets, and `Manual` sets the
+    /// suballocated device memory block size range explicitly.
+    pub fn memory_hints(mut self, memory_hints: wgpu::MemoryHints) -> Self {
+        self.memory_hints = memory_hints;
+        self
+    }
+
+    /// Prefix geepu's debug labels, e.g. `"Editor/"` to tell several contexts apart in captures
+    pub fn label_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.label_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Push a debug group around every labeled render and compute pass
+    pub fn debug_groups(mut self, enabled: bool) -> Self {
+        self.debug_groups = enabled;
+        self
+    }
+
+    /// Render reproducibly, for snapshot tests.
+    ///
+    /// Adapters are ranked by a fixed order (type for the power preference,
+    /// backend, name, vendor and device id) instead of whatever the driver lists
+    /// first, and presentation always uses `Fifo` so no frames are dropped
+    /// depending on timing. On the web the browser still picks the adapter.
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Track buffers and textures so [`GpuContext::leak_report`](crate::GpuContext::leak_report)
+    /// can list the ones still alive
+    pub fn track_resources(mut self, enabled: bool) -> Self {
+        self.track_resources = enabled;
+        self
+    }
+
+    /// Validate command recording before encoding.
+    ///
+    /// Passes from [`RenderCommands`](crate::RenderCommands) and
+    /// [`ComputeCommands`](crate::ComputeCommands) then track the pipeline,
+    /// bind groups, vertex and index buffers they have bound, and
+    /// `try_draw`, `try_draw_indexed` and `try_dispatch_workgroups` return a
+    /// [`GeepuError::Validation`](crate::GeepuError::Validation) naming what is
+    /// missing instead of encoding a call the backend would reject.
+    pub fn validate_commands(mut self, enabled: bool) -> Self {
+        self.validate_commands = enabled;
+        self
+    }
+
+    /// Capture wgpu errors around buffer, texture and pipeline creation.
+    ///
+    /// [`GpuContext::capture_errors`](crate::GpuContext::capture_errors) then
+    /// returns them as [`GeepuError::Validation`](crate::GeepuError::Validation)
+    /// or `OutOfMemory`, at the cost of two error scopes and a wait for them on
+    /// every creation. Without it errors go to the device's uncaptured-error
+    /// handler, which panics by default. Has no effect on the web, where the
+    /// scopes resolve too late and errors always reach that handler.
+    pub fn capture_errors(mut self, enabled: bool) -> Self {
+        self.capture_errors = enabled;
+        self
+    }
+
+    /// Batch buffer and texture writes.
+    ///
+    /// Writes through [`TypedBuffer::write`](crate::TypedBuffer::write),
+    /// [`Texture::write_data`](crate::Texture::write_data) and geepu's own
+    /// per-frame updates are then staged in shared upload memory and copied in
+    /// a single command buffer submitted just before the next
+    /// [`RenderCommands`](crate::RenderCommands) or
+    /// [`ComputeCommands`](crate::ComputeCommands), instead of each becoming a
+    /// separate queue write. Applications submitting their own command buffers
+    /// call [`GpuContext::flush_uploads`](crate::GpuContext::flush_uploads)
+    /// first.
+    pub fn batch_uploads(mut self, enabled: bool) -> Self {
+        self.batch_uploads = enabled;
+        self
+    }
+
+    /// Open the device with the Vulkan external memory extensions the adapter
+    /// supports (`VK_KHR_external_memory_fd`, DMA-BUF and DRM format modifiers
+    /// on Unix, `VK_KHR_external_memory_win32` on Windows), for
+    /// [`Texture::from_external_memory`](crate::Texture::from_external_memory)
+    /// and [`Texture::new_exportable`](crate::Texture::new_exportable). Needs
+    /// the Vulkan backend; device creation fails on the others.
+    #[cfg(all(feature = "vulkan-interop", not(any(target_arch = "wasm32", target_vendor = "apple"))))]
+    pub fn external_memory(mut self, enabled: bool) -> Self {
+        self.external_memory = enabled;
+        self
+    }
+
+    /// Sort key deterministic mode uses to pick an adapter; the smallest key wins
+    pub fn adapter_sort_key(&self, info: &wgpu::AdapterInfo) -> impl Ord {
+        use wgpu::DeviceType;
+
+        let type_rank = match (info.device_type, self.power_preference) {
+            (DeviceType::Cpu, _) if self.prefer_fallback_adapter => 0,
+            (DeviceType::DiscreteGpu, wgpu::PowerPreference::LowPower) => 2,
+            (DeviceType::IntegratedGpu, wgpu::PowerPreference::LowPower) => 1,
+            (DeviceType::DiscreteGpu, _) => 1,
+            (DeviceType::IntegratedGpu, _) => 2,
+            (DeviceType::VirtualGpu, _) => 3,
+            (DeviceType::Other, _) => 4,
+            (DeviceType::Cpu, _) => 5,
+        };
+        (type_rank, info.backend as u8, info.name.clone(), info.vendor, info.device)
+    }
+
+    /// `label` with the configured prefix applied
+    pub fn prefixed_label(&self, label: &str) -> String {
+        match &self.label_prefix {
+            Some(prefix) => format!("{}{}", prefix, label),
+            None => label.to_string(),
+        }
+    }
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
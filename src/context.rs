@@ -1,9 +1,48 @@
-use crate::{ GeepuError, Result };
-use std::sync::Arc;
+use crate::{ GeepuError, GpuConfig, GpuMemoryUsage, LeakReport, ResourceKind, Result };
+use crate::command_dump::CommandDump;
+use crate::resources::{ ResourceTracker, TrackedResource };
+use crate::upload::{ FinishedUploads, UploadBelt };
+use std::future::Future;
+use std::pin::Pin;
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex, Weak };
+use std::task::{ Poll, Waker };
+#[cfg(feature = "window")]
 use winit::window::Window;
 use crate::pipeline::{ PipelineBuilder, SimpleRenderPipeline };
 use crate::ComputePipeline;
 
+/// Drive an async entry point to completion on the current platform.
+///
+/// Native targets block on the future with pollster; on `wasm32` the future is
+/// spawned onto the browser event loop with `wasm-bindgen-futures`, since the
+/// main thread there must never block.
+pub fn spawn<F>(future: F) where F: std::future::Future<Output = ()> + 'static {
+    #[cfg(not(target_arch = "wasm32"))]
+    pollster::block_on(future);
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+/// Information about the adapter and surface a context ended up with
+#[derive(Debug, Clone)]
+pub struct ContextInfo {
+    pub adapter: wgpu::AdapterInfo,
+    /// Present mode actually selected for the surface
+    pub present_mode: Option<wgpu::PresentMode>,
+    pub surface_format: Option<wgpu::TextureFormat>,
+    pub software: bool,
+}
+
+/// Depth buffer kept at the surface's size by [`GpuContext::enable_depth`]
+struct SurfaceDepth {
+    texture: crate::Texture,
+    clear_value: f32,
+}
+
+/// Live bind group layouts by their entries sorted by binding
+type LayoutCache = HashMap<Vec<wgpu::BindGroupLayoutEntry>, Weak<wgpu::BindGroupLayout>>;
+
 /// Main GPU context that wraps wgpu instance, adapter, device, and queue
 pub struct GpuContext {
     pub instance: wgpu::Instance,
@@ -12,6 +51,18 @@ pub struct GpuContext {
     pub queue: Arc<wgpu::Queue>,
     pub surface: Option<wgpu::Surface<'static>>,
     pub surface_config: Option<wgpu::SurfaceConfiguration>,
+    /// Configuration the context was created with
+    pub config: GpuConfig,
+    /// Window the surface was created from, kept to recreate it on resume
+    #[cfg(feature = "window")]
+    pub window: Option<Arc<Window>>,
+    resources: ResourceTracker,
+    command_dump: CommandDump,
+    uploads: Option<UploadBelt>,
+    bind_group_layouts: Mutex<LayoutCache>,
+    depth: Option<SurfaceDepth>,
+    /// Multisampled color target resolved into the surface, with `samples` > 1
+    msaa: Option<crate::Texture>,
 }
 
 impl GpuContext {
@@ -22,32 +73,14 @@ impl GpuContext {
 
     /// Create a new GPU context with specific features
     pub async fn new_with_features(features: wgpu::Features) -> Result<Self> {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            ..Default::default()
-        });
-
-        let adapter = instance
-            .request_adapter(
-                &(wgpu::RequestAdapterOptions {
-                    power_preference: wgpu::PowerPreference::HighPerformance,
-                    compatible_surface: None,
-                    force_fallback_adapter: false,
-                })
-            ).await
-            .ok_or(GeepuError::AdapterNotFound)?;
+        Self::new_with_config(GpuConfig::default().features(features)).await
+    }
 
-        let (device, queue) = adapter
-            .request_device(
-                &(wgpu::DeviceDescriptor {
-                    label: Some("Geepu Device"),
-                    required_features: features,
-                    required_limits: wgpu::Limits::default(),
-                    memory_hints: wgpu::MemoryHints::Performance,
-                }),
-                None
-            ).await
-            .map_err(GeepuError::DeviceCreationFailed)?;
+    /// Create a new GPU context from a full configuration
+    pub async fn new_with_config(config: GpuConfig) -> Result<Self> {
+        let instance = Self::create_instance(&config);
+        let adapter = Self::request_adapter(&instance, &config, None).await?;
+        let (device, queue) = Self::request_device(&adapter, &config).await?;
 
         Ok(Self {
             instance,
@@ -56,111 +89,681 @@ impl GpuContext {
             queue: Arc::new(queue),
             surface: None,
             surface_config: None,
+            resources: ResourceTracker::new(config.track_resources),
+            command_dump: CommandDump::default(),
+            uploads: config.batch_uploads.then(UploadBelt::new),
+            bind_group_layouts: Mutex::default(),
+            depth: None,
+            msaa: None,
+            config,
+            #[cfg(feature = "window")]
+            window: None,
+        })
+    }
+
+    fn create_instance(config: &GpuConfig) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: config.backends,
+            flags: config.instance_flags,
+            dx12_shader_compiler: config.dx12_shader_compiler.clone(),
+            gles_minor_version: config.gles_minor_version,
         })
     }
 
+    /// Request an adapter, trying the fallback (software) adapter first when preferred
+    async fn request_adapter(
+        instance: &wgpu::Instance,
+        config: &GpuConfig,
+        compatible_surface: Option<&wgpu::Surface<'static>>
+    ) -> Result<wgpu::Adapter> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if config.deterministic {
+            return instance
+                .enumerate_adapters(config.backends)
+                .into_iter()
+                .filter(|adapter| {
+                    compatible_surface.is_none_or(|surface| adapter.is_surface_supported(surface))
+                })
+                .min_by_key(|adapter| config.adapter_sort_key(&adapter.get_info()))
+                .ok_or(GeepuError::AdapterNotFound);
+        }
+
+        let request = |force_fallback_adapter| {
+            instance.request_adapter(
+                &(wgpu::RequestAdapterOptions {
+                    power_preference: config.power_preference,
+                    compatible_surface,
+                    force_fallback_adapter,
+                })
+            )
+        };
+
+        if config.prefer_fallback_adapter {
+            if let Some(adapter) = request(true).await {
+                return Ok(adapter);
+            }
+        }
+        request(false).await.ok_or(GeepuError::AdapterNotFound)
+    }
+
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+        config: &GpuConfig
+    ) -> Result<(wgpu::Device, wgpu::Queue)> {
+        #[cfg(all(feature = "vulkan-interop", not(any(target_arch = "wasm32", target_vendor = "apple"))))]
+        if config.external_memory {
+            return crate::interop::request_external_memory_device(adapter, config);
+        }
+        adapter
+            .request_device(
+                &(wgpu::DeviceDescriptor {
+                    label: Some(&config.prefixed_label("Geepu Device")),
+                    required_features: config.features |
+                    (config.optional_features & adapter.features()),
+                    required_limits: config.limits.clone(),
+                    memory_hints: config.memory_hints.clone(),
+                }),
+                None
+            ).await
+            .map_err(GeepuError::DeviceCreationFailed)
+    }
+
     /// Create a new GPU context with a window for rendering
+    #[cfg(feature = "window")]
     pub async fn new_with_window(window: Arc<Window>) -> Result<Self> {
         Self::new_with_window_and_features(window, wgpu::Features::empty()).await
     }
 
     /// Create a new GPU context with a window and specific features
+    #[cfg(feature = "window")]
     pub async fn new_with_window_and_features(
         window: Arc<Window>,
         features: wgpu::Features
     ) -> Result<Self> {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            ..Default::default()
-        });
+        Self::new_with_window_and_config(window, GpuConfig::default().features(features)).await
+    }
+
+    /// Create a new GPU context with a window from a full configuration
+    #[cfg(feature = "window")]
+    pub async fn new_with_window_and_config(
+        window: Arc<Window>,
+        config: GpuConfig
+    ) -> Result<Self> {
+        let instance = Self::create_instance(&config);
+
+        let surface = match instance.create_surface(window.clone()) {
+            Ok(surface) => surface,
+            // Containers without a display server still get a usable headless context
+            Err(_) if config.allow_missing_surface => {
+                return Self::new_with_config(config).await;
+            }
+            Err(_) => {
+                return Err(GeepuError::SurfaceCreationFailed);
+            }
+        };
+
+        let size = window.inner_size();
+        let mut context = Self::new_with_surface(
+            instance,
+            surface,
+            size.width,
+            size.height,
+            &config
+        ).await?;
+        context.window = Some(window);
+        Ok(context)
+    }
+
+    /// Create a new GPU context rendering into an HTML canvas (WebGPU)
+    #[cfg(target_arch = "wasm32")]
+    pub async fn new_with_canvas(
+        canvas: web_sys::HtmlCanvasElement,
+        features: wgpu::Features
+    ) -> Result<Self> {
+        Self::new_with_canvas_and_config(canvas, GpuConfig::default().features(features)).await
+    }
 
+    /// Create a new GPU context rendering into an HTML canvas from a full configuration
+    #[cfg(target_arch = "wasm32")]
+    pub async fn new_with_canvas_and_config(
+        canvas: web_sys::HtmlCanvasElement,
+        config: GpuConfig
+    ) -> Result<Self> {
+        let instance = Self::create_instance(&config);
+
+        let (width, height) = (canvas.width(), canvas.height());
         let surface = instance
-            .create_surface(window.clone())
+            .create_surface(wgpu::SurfaceTarget::Canvas(canvas))
             .map_err(|_| GeepuError::SurfaceCreationFailed)?;
 
-        let adapter = instance
-            .request_adapter(
-                &(wgpu::RequestAdapterOptions {
-                    power_preference: wgpu::PowerPreference::HighPerformance,
-                    compatible_surface: Some(&surface),
-                    force_fallback_adapter: false,
-                })
-            ).await
-            .ok_or(GeepuError::AdapterNotFound)?;
+        Self::new_with_surface(instance, surface, width, height, &config).await
+    }
 
-        let (device, queue) = adapter
-            .request_device(
-                &(wgpu::DeviceDescriptor {
-                    label: Some("Geepu Device"),
-                    required_features: features,
-                    required_limits: wgpu::Limits::default(),
-                    memory_hints: wgpu::MemoryHints::Performance,
-                }),
-                None
-            ).await
-            .map_err(GeepuError::DeviceCreationFailed)?;
+    /// Pick an adapter compatible with `surface`, create the device and configure the surface
+    #[cfg(any(feature = "window", target_arch = "wasm32"))]
+    async fn new_with_surface(
+        instance: wgpu::Instance,
+        surface: wgpu::Surface<'static>,
+        width: u32,
+        height: u32,
+        config: &GpuConfig
+    ) -> Result<Self> {
+        let adapter = Self::request_adapter(&instance, config, Some(&surface)).await?;
+        let (device, queue) = Self::request_device(&adapter, config).await?;
+
+        let surface_config = Self::default_surface_config(&surface, &adapter, config, width, height);
+        surface.configure(&device, &surface_config);
+
+        let mut context = Self {
+            instance,
+            adapter,
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+            surface: Some(surface),
+            surface_config: Some(surface_config),
+            config: config.clone(),
+            resources: ResourceTracker::new(config.track_resources),
+            command_dump: CommandDump::default(),
+            uploads: config.batch_uploads.then(UploadBelt::new),
+            bind_group_layouts: Mutex::default(),
+            depth: None,
+            msaa: None,
+            #[cfg(feature = "window")]
+            window: None,
+        };
+        context.resize_targets()?;
+        Ok(context)
+    }
 
-        let surface_caps = surface.get_capabilities(&adapter);
+    /// Surface configuration preferring an sRGB format
+    #[cfg(any(feature = "window", target_arch = "wasm32"))]
+    fn default_surface_config(
+        surface: &wgpu::Surface<'static>,
+        adapter: &wgpu::Adapter,
+        config: &GpuConfig,
+        width: u32,
+        height: u32
+    ) -> wgpu::SurfaceConfiguration {
+        let surface_caps = surface.get_capabilities(adapter);
         let surface_format = surface_caps.formats
             .iter()
             .find(|f| f.is_srgb())
             .copied()
             .unwrap_or(surface_caps.formats[0]);
 
-        let size = window.inner_size();
-        let surface_config = wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: config.present_mode_for(&surface_caps.present_modes),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
-            desired_maximum_frame_latency: 2,
+            desired_maximum_frame_latency: config.desired_maximum_frame_latency,
+        }
+    }
+
+    /// Drop the surface while the app is suspended (call from `ApplicationHandler::suspended`).
+    ///
+    /// The device, queue and every resource created from them stay alive; only the
+    /// platform surface is released, which Android requires once its native window
+    /// is destroyed.
+    pub fn suspend(&mut self) {
+        self.surface = None;
+    }
+
+    /// Recreate the surface after [`suspend`](Self::suspend) (call from `ApplicationHandler::resumed`).
+    ///
+    /// The previous surface configuration is kept, resized to the window's current size.
+    #[cfg(feature = "window")]
+    pub fn resume(&mut self, window: Arc<Window>) -> Result<()> {
+        let surface = self.instance
+            .create_surface(window.clone())
+            .map_err(|_| GeepuError::SurfaceCreationFailed)?;
+
+        let size = window.inner_size();
+        let config = match self.surface_config.take() {
+            Some(mut config) => {
+                config.width = size.width.max(1);
+                config.height = size.height.max(1);
+                config
+            }
+            None =>
+                Self::default_surface_config(
+                    &surface,
+                    &self.adapter,
+                    &self.config,
+                    size.width,
+                    size.height
+                ),
         };
+        surface.configure(&self.device, &config);
 
-        surface.configure(&device, &surface_config);
+        self.surface = Some(surface);
+        self.surface_config = Some(config);
+        self.window = Some(window);
+        self.resize_targets()
+    }
 
-        Ok(Self {
-            instance,
-            adapter,
-            device: Arc::new(device),
-            queue: Arc::new(queue),
-            surface: Some(surface),
-            surface_config: Some(surface_config),
-        })
+    /// Whether the surface is currently released by [`suspend`](Self::suspend)
+    pub fn is_suspended(&self) -> bool {
+        self.surface.is_none() && self.surface_config.is_some()
     }
 
     /// Resize the surface (call when window is resized)
+    #[cfg(feature = "window")]
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) -> Result<()> {
         if let (Some(surface), Some(config)) = (&self.surface, &mut self.surface_config) {
             config.width = new_size.width.max(1);
             config.height = new_size.height.max(1);
             surface.configure(&self.device, config);
         }
+        self.resize_targets()
+    }
+
+    /// Keep a depth buffer of `format` at the surface's size, recreated when
+    /// the surface is resized. [`RenderPassGuard`](crate::RenderPassGuard)
+    /// attaches it, and [`depth_attachment`](Self::depth_attachment) attaches
+    /// it to other passes. Depth is cleared to 1.0 unless changed with
+    /// [`set_depth_clear_value`](Self::set_depth_clear_value).
+    pub fn enable_depth(&mut self, format: wgpu::TextureFormat) -> Result<()> {
+        let clear_value = self.depth.as_ref().map_or(1.0, |depth| depth.clear_value);
+        let (width, height) = self.size();
+        let texture = self.create_depth(format, width, height)?;
+        self.depth = Some(SurfaceDepth { texture, clear_value });
         Ok(())
     }
 
-    /// Get the current surface texture for rendering
+    /// Stop keeping a surface depth buffer
+    pub fn disable_depth(&mut self) {
+        self.depth = None;
+    }
+
+    /// Value the surface depth buffer is cleared to, e.g. 0.0 for reversed Z
+    pub fn set_depth_clear_value(&mut self, value: f32) {
+        if let Some(depth) = &mut self.depth {
+            depth.clear_value = value;
+        }
+    }
+
+    /// The surface depth buffer, when [`enable_depth`](Self::enable_depth)d
+    pub fn depth_texture(&self) -> Option<&crate::Texture> {
+        self.depth.as_ref().map(|depth| &depth.texture)
+    }
+
+    /// Attachment clearing the surface depth buffer (and its stencil, if any)
+    pub fn depth_attachment(&self) -> Option<wgpu::RenderPassDepthStencilAttachment<'_>> {
+        let depth = self.depth.as_ref()?;
+        let stencil = depth.texture.format().has_stencil_aspect().then_some(0);
+        Some(crate::depth_stencil_attachment(&depth.texture.view, Some(depth.clear_value), stencil))
+    }
+
+    /// MSAA sample count of the surface targets, from [`GpuConfig::samples`]
+    pub fn sample_count(&self) -> u32 {
+        self.config.samples.max(1)
+    }
+
+    /// The multisampled color target passes into the surface draw to, when
+    /// [`sample_count`](Self::sample_count) is above 1
+    pub fn msaa_texture(&self) -> Option<&crate::Texture> {
+        self.msaa.as_ref()
+    }
+
+    /// Attachment for drawing into the surface texture `view`: the MSAA target
+    /// resolving into `view` when multisampling, `view` itself otherwise
+    pub fn surface_attachment<'a>(
+        &'a self,
+        view: &'a wgpu::TextureView,
+        clear_color: Option<wgpu::Color>
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        match &self.msaa {
+            Some(msaa) =>
+                wgpu::RenderPassColorAttachment {
+                    resolve_target: Some(view),
+                    ..crate::color_attachment(&msaa.view, clear_color)
+                },
+            None => crate::color_attachment(view, clear_color),
+        }
+    }
+
+    fn create_depth(
+        &self,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32
+    ) -> Result<crate::Texture> {
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if self.sample_count() == 1 {
+            usage |= wgpu::TextureUsages::TEXTURE_BINDING;
+        }
+        self.check_sample_count(format)?;
+        crate::TextureBuilder::new(width.max(1), height.max(1))
+            .format(format)
+            .usage(usage)
+            .sample_count(self.sample_count())
+            .label("Surface Depth")
+            .build(self)
+    }
+
+    fn check_sample_count(&self, format: wgpu::TextureFormat) -> Result<()> {
+        let samples = self.sample_count();
+        let flags = self.adapter.get_texture_format_features(format).flags;
+        if samples == 1 || flags.sample_count_supported(samples) {
+            Ok(())
+        } else {
+            Err(
+                GeepuError::TextureError(format!("{:?} does not support {}x MSAA", format, samples))
+            )
+        }
+    }
+
+    /// Recreate the depth buffer and MSAA target when the surface size changed
+    #[cfg(any(feature = "window", target_arch = "wasm32"))]
+    fn resize_targets(&mut self) -> Result<()> {
+        let (width, height) = self.size();
+        let size = (width.max(1), height.max(1));
+        if let Some(depth) = &self.depth {
+            if depth.texture.size() != size {
+                let texture = self.create_depth(depth.texture.format(), width, height)?;
+                if let Some(depth) = &mut self.depth {
+                    depth.texture = texture;
+                }
+            }
+        }
+
+        let Some(format) = self.surface_format() else {
+            return Ok(());
+        };
+        if self.sample_count() == 1 || self.msaa.as_ref().is_some_and(|msaa| msaa.size() == size) {
+            return Ok(());
+        }
+        self.check_sample_count(format)?;
+        self.msaa = Some(
+            crate::TextureBuilder::new(size.0, size.1)
+                .format(format)
+                .usage(wgpu::TextureUsages::RENDER_ATTACHMENT)
+                .sample_count(self.sample_count())
+                .label("Surface MSAA")
+                .build(self)?
+        );
+        Ok(())
+    }
+
+    /// Turn vsync on or off and reconfigure the surface with the resulting present mode
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.config.vsync = vsync;
+        if let (Some(surface), Some(config)) = (&self.surface, &mut self.surface_config) {
+            let supported = surface.get_capabilities(&self.adapter).present_modes;
+            config.present_mode = self.config.present_mode_for(&supported);
+            surface.configure(&self.device, config);
+        }
+    }
+
+    /// Report the adapter and the surface settings that were actually chosen
+    pub fn info(&self) -> ContextInfo {
+        ContextInfo {
+            adapter: self.adapter.get_info(),
+            present_mode: self.surface_config.as_ref().map(|c| c.present_mode),
+            surface_format: self.surface_format(),
+            software: self.is_software(),
+        }
+    }
+
+    /// Change how many frames the presentation engine may queue ahead and reconfigure the surface
+    pub fn set_frame_latency(&mut self, latency: u32) {
+        self.config.desired_maximum_frame_latency = latency;
+        if let (Some(surface), Some(config)) = (&self.surface, &mut self.surface_config) {
+            config.desired_maximum_frame_latency = latency;
+            surface.configure(&self.device, config);
+        }
+    }
+
+    /// Get the current surface texture for rendering.
+    ///
+    /// Acquiring the texture starts a new frame for
+    /// [`dump_next_frame`](Self::dump_next_frame).
     pub fn get_current_texture(&self) -> Result<wgpu::SurfaceTexture> {
+        if let Err(e) = self.end_frame() {
+            log::warn!("{}", e);
+        }
         if let Some(surface) = &self.surface {
             surface
                 .get_current_texture()
-                .map_err(|e| GeepuError::Other(format!("Failed to acquire surface texture: {}", e)))
+                .map_err(|e| GeepuError::SurfaceError(format!("Failed to acquire texture: {}", e)))
         } else {
             Err(
-                GeepuError::Other(
+                GeepuError::SurfaceError(
                     "No surface available - context was created without window".to_string()
                 )
             )
         }
     }
 
+    /// Whether the adapter is a software rasterizer (lavapipe, WARP, llvmpipe)
+    pub fn is_software(&self) -> bool {
+        self.adapter.get_info().device_type == wgpu::DeviceType::Cpu
+    }
+
     /// Get surface size
     pub fn size(&self) -> (u32, u32) {
         if let Some(config) = &self.surface_config { (config.width, config.height) } else { (0, 0) }
     }
 
+    /// Run `operation` inside wgpu error scopes, returning any validation or
+    /// out-of-memory error it raised as a [`GeepuError`] instead of passing it
+    /// to the device's uncaptured-error handler (which panics by default).
+    ///
+    /// Only with [`GpuConfig::capture_errors`], which is on in debug builds;
+    /// otherwise `operation` runs directly. On the web the scopes would resolve
+    /// asynchronously, after this returns, so `operation` always runs directly
+    /// there and errors reach the uncaptured-error handler.
+    pub fn capture_errors<T>(
+        &self,
+        source_op: &'static str,
+        label: Option<&str>,
+        operation: impl FnOnce() -> T
+    ) -> Result<T> {
+        if !self.config.capture_errors || cfg!(target_arch = "wasm32") {
+            return Ok(operation());
+        }
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let value = operation();
+        let validation = self.device.pop_error_scope();
+        let out_of_memory = self.device.pop_error_scope();
+
+        // Native scopes are resolved by the time they are popped
+        let error = match pollster::block_on(validation) {
+            Some(error) => Some(error),
+            None => pollster::block_on(out_of_memory),
+        };
+        match error {
+            Some(error) => Err(GeepuError::from_wgpu(error, label, source_op)),
+            None => Ok(value),
+        }
+    }
+
+    /// Buffers and textures created through this context that are still alive.
+    ///
+    /// Call at shutdown, after dropping everything that should have been
+    /// released, to find leaked resources. Empty unless
+    /// [`GpuConfig::track_resources`] is on.
+    pub fn leak_report(&self) -> LeakReport {
+        self.resources.report()
+    }
+
+    /// GPU memory in use, split into buffers and textures.
+    ///
+    /// The backend figures come from wgpu's internal allocation counters and
+    /// are only present with the `counters` feature; the
+    /// tracked estimates cover geepu's own buffers and textures and need
+    /// [`GpuConfig::track_resources`].
+    pub fn gpu_memory_usage(&self) -> GpuMemoryUsage {
+        let counters = self.device.get_internal_counters();
+        GpuMemoryUsage::new(&counters.hal, &self.resources)
+    }
+
+    /// Write every pass, pipeline, bind group, draw, dispatch and copy recorded
+    /// through [`RenderCommands`](crate::RenderCommands) and
+    /// [`ComputeCommands`](crate::ComputeCommands) during the next frame to a
+    /// text file at `path`.
+    ///
+    /// Frames are delimited by [`get_current_texture`](Self::get_current_texture);
+    /// applications without a surface call [`end_frame`](Self::end_frame)
+    /// instead. Objects are identified by their wgpu global ids.
+    pub fn dump_next_frame(&self, path: impl Into<std::path::PathBuf>) {
+        self.command_dump.arm(path.into());
+    }
+
+    /// Mark a frame boundary for [`dump_next_frame`](Self::dump_next_frame),
+    /// writing the dump if a frame was being recorded. Uploads still pending
+    /// from the frame are submitted.
+    pub fn end_frame(&self) -> Result<()> {
+        self.flush_uploads();
+        if let Some(path) = self.command_dump.frame_boundary()? {
+            log::info!("Wrote command dump {}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Submit the buffer and texture writes batched since the last submit.
+    ///
+    /// Geepu's own submits do this first; call it before submitting command
+    /// buffers straight to the queue when [`GpuConfig::batch_uploads`] is on.
+    pub fn flush_uploads(&self) -> Option<wgpu::SubmissionIndex> {
+        let uploads = self.uploads.as_ref()?.finish()?;
+        Some(self.submit_after(Some(uploads), std::iter::empty()))
+    }
+
+    /// Wait until the GPU has finished all work submitted so far.
+    ///
+    /// Natively this polls the device until the queue is idle; on the web it
+    /// resolves from the browser's event loop without blocking. To be told
+    /// about a single submission instead, use
+    /// [`RenderCommands::submit_with_callback`](crate::RenderCommands::submit_with_callback).
+    pub async fn queue_done(&self) {
+        let done = WorkDone::default();
+        let signal = done.clone();
+        self.queue.on_submitted_work_done(move || signal.complete());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.device.poll(wgpu::Maintain::Wait);
+
+        done.await
+    }
+
+    /// Submit `command_buffers` after any batched uploads
+    pub(crate) fn submit(
+        &self,
+        command_buffers: impl IntoIterator<Item = wgpu::CommandBuffer>
+    ) -> wgpu::SubmissionIndex {
+        let uploads = self.uploads.as_ref().and_then(UploadBelt::finish);
+        self.submit_after(uploads, command_buffers)
+    }
+
+    fn submit_after(
+        &self,
+        uploads: Option<FinishedUploads>,
+        command_buffers: impl IntoIterator<Item = wgpu::CommandBuffer>
+    ) -> wgpu::SubmissionIndex {
+        let upload_commands = uploads.map(|uploads| {
+            if let Some(dump) = self.command_recorder() {
+                dump.record(format!("uploads: {} copies, {} bytes", uploads.copies, uploads.bytes));
+            }
+            uploads.commands
+        });
+        let index = self.queue.submit(upload_commands.into_iter().chain(command_buffers));
+        if let Some(belt) = &self.uploads {
+            belt.recall();
+        }
+        index
+    }
+
+    /// Write `data` into `buffer` at `offset`, batched when
+    /// [`GpuConfig::batch_uploads`] is on
+    pub(crate) fn write_buffer(&self, buffer: &wgpu::Buffer, offset: u64, data: &[u8]) {
+        match &self.uploads {
+            Some(belt) => belt.write_buffer(&self.device, buffer, offset, data),
+            None => self.queue.write_buffer(buffer, offset, data),
+        }
+    }
+
+    /// Copy `size` bytes between the starts of two buffers, batched with the
+    /// uploads when [`GpuConfig::batch_uploads`] is on and submitted right away
+    /// otherwise
+    pub(crate) fn copy_buffer(&self, source: &wgpu::Buffer, destination: &wgpu::Buffer, size: u64) {
+        match &self.uploads {
+            Some(belt) => belt.copy_buffer(&self.device, source, destination, size),
+            None => {
+                let mut encoder = self.device.create_command_encoder(
+                    &(wgpu::CommandEncoderDescriptor {
+                        label: Some("Uploads"),
+                    })
+                );
+                encoder.copy_buffer_to_buffer(source, 0, destination, 0, size);
+                self.queue.submit(std::iter::once(encoder.finish()));
+            }
+        }
+    }
+
+    /// Write `data` into a texture, batched when [`GpuConfig::batch_uploads`] is on
+    pub(crate) fn write_texture(
+        &self,
+        texture: wgpu::ImageCopyTexture,
+        data: &[u8],
+        layout: wgpu::ImageDataLayout,
+        size: wgpu::Extent3d
+    ) {
+        let staged = self.uploads
+            .as_ref()
+            .is_some_and(|belt| belt.write_texture(&self.device, texture, data, layout, size));
+        if !staged {
+            self.queue.write_texture(texture, data, layout, size);
+        }
+    }
+
+    /// The layout with `entries`, shared with every other live layout created
+    /// from the same entries in any order, or a new one from `create`
+    pub(crate) fn shared_bind_group_layout(
+        &self,
+        entries: &[wgpu::BindGroupLayoutEntry],
+        create: impl FnOnce() -> wgpu::BindGroupLayout
+    ) -> Arc<wgpu::BindGroupLayout> {
+        let mut key = entries.to_vec();
+        key.sort_by_key(|entry| entry.binding);
+        let mut layouts = self.bind_group_layouts.lock().unwrap();
+        if let Some(layout) = layouts.get(&key).and_then(Weak::upgrade) {
+            return layout;
+        }
+        layouts.retain(|_, layout| layout.strong_count() > 0);
+        let layout = Arc::new(create());
+        layouts.insert(key, Arc::downgrade(&layout));
+        layout
+    }
+
+    /// Handle to record commands into while a frame dump is in progress
+    pub(crate) fn command_recorder(&self) -> Option<CommandDump> {
+        self.command_dump.recorder()
+    }
+
+    pub(crate) fn track_resource(
+        &self,
+        kind: ResourceKind,
+        label: &str,
+        size_bytes: u64
+    ) -> Option<TrackedResource> {
+        self.resources.track(kind, label, size_bytes)
+    }
+
+    /// `label` with the configured [`label_prefix`](GpuConfig::label_prefix) applied
+    pub fn label(&self, label: &str) -> String {
+        self.config.prefixed_label(label)
+    }
+
+    pub(crate) fn prefixed_label(&self, label: Option<&str>) -> Option<String> {
+        label.map(|label| self.label(label))
+    }
+
     /// Get surface format
     pub fn surface_format(&self) -> Option<wgpu::TextureFormat> {
         self.surface_config.as_ref().map(|c| c.format)
@@ -178,7 +781,9 @@ impl GpuContext {
     ) -> Result<SimpleRenderPipeline> {
         let format = self
             .surface_format()
-            .ok_or_else(|| crate::GeepuError::Other("No surface available for pipeline".into()))?;
+            .ok_or_else(|| {
+                crate::GeepuError::SurfaceError("No surface available for pipeline".into())
+            })?;
         // Create uniform buffer
         let data = std::slice::from_ref(uniform);
         let uni_buf = crate::TypedBuffer::uniform(self, data)?;
@@ -209,7 +814,7 @@ impl GpuContext {
         let mut layout = crate::pipeline::BindGroupLayoutBuilder
             ::new()
             .uniform_buffer(0, wgpu::ShaderStages::COMPUTE);
-        for (i, buf) in storage.iter().enumerate() {
+        for i in 0..storage.len() {
             layout = layout.storage_buffer((i + 1) as u32, wgpu::ShaderStages::COMPUTE, false);
         }
         let bind_layout = layout.build(self, label);
@@ -220,8 +825,44 @@ impl GpuContext {
         for (i, buf) in storage.iter().enumerate() {
             group = group.buffer((i + 1) as u32, buf);
         }
-        let bind_group = group.build(self, label);
+        let _bind_group = group.build(self, label);
         // Create compute pipeline
         ComputePipeline::new(self, cs_src, vec![bind_layout], label)
     }
 }
+
+/// Future completed by an `on_submitted_work_done` callback
+#[derive(Clone, Default)]
+struct WorkDone {
+    state: Arc<Mutex<WorkDoneState>>,
+}
+
+#[derive(Default)]
+struct WorkDoneState {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+impl WorkDone {
+    fn complete(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.done = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Future for WorkDone {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.done {
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    Camera,
+    ComputePass,
+    ComputePipeline,
+    DrawIndexedIndirectArgs,
+    GeepuError,
+    GpuContext,
+    HiZBuffer,
+    InstanceTransform,
+    Mesh,
+    RenderPass,
+    Result,
+    TypedBuffer,
+};
+use std::ops::Range;
+use std::sync::Arc;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// An instance to cull: its model matrix, local-space bounds and the submesh it draws
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CullInstance {
+    pub model: [[f32; 4]; 4],
+    pub aabb_min: [f32; 3],
+    pub submesh: u32,
+    pub aabb_max: [f32; 3],
+    _padding: u32,
+}
+
+impl CullInstance {
+    pub fn new(
+        model: [[f32; 4]; 4],
+        aabb_min: [f32; 3],
+        aabb_max: [f32; 3],
+        submesh: u32
+    ) -> Self {
+        Self { model, aabb_min, submesh, aabb_max, _padding: 0 }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullParams {
+    planes: [[f32; 4]; 6],
+    instance_count: u32,
+    _padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct OcclusionParams {
+    view_projection: [[f32; 4]; 4],
+    size: [f32; 2],
+    mip_count: u32,
+    _padding: u32,
+}
+
+/// GPU frustum culling feeding indirect draws.
+///
+/// Every instance draws one submesh of a shared mesh. [`cull`](Self::cull)
+/// tests each instance's transformed bounds against the camera frustum in a
+/// compute pass, compacts the survivors' [`InstanceTransform`]s into a vertex
+/// buffer grouped by submesh and counts them into one
+/// [`DrawIndexedIndirectArgs`] per submesh; [`draw`](Self::draw) then issues
+/// the draws without reading anything back. With `MULTI_DRAW_INDIRECT` and
+/// `INDIRECT_FIRST_INSTANCE` enabled all submeshes go out in a single
+/// `multi_draw_indexed_indirect` call, otherwise one indirect draw per submesh.
+///
+/// [`cull_occluded`](Self::cull_occluded) additionally drops instances hidden
+/// behind the depth in a [`HiZBuffer`].
+pub struct GpuCuller {
+    instances: TypedBuffer<CullInstance>,
+    instance_count: u32,
+    offsets: TypedBuffer<u32>,
+    batches: Vec<Range<usize>>,
+    initial_args: Vec<DrawIndexedIndirectArgs>,
+    args: TypedBuffer<DrawIndexedIndirectArgs>,
+    visible: TypedBuffer<InstanceTransform>,
+    params: TypedBuffer<CullParams>,
+    bind_group: wgpu::BindGroup,
+    pipeline: ComputePipeline,
+    occlusion_params: TypedBuffer<OcclusionParams>,
+    occlusion_layout: Arc<wgpu::BindGroupLayout>,
+    occlusion_pipeline: ComputePipeline,
+    multi_draw: bool,
+}
+
+impl GpuCuller {
+    /// Create a culler for instances of an indexed mesh
+    pub fn new<V>(
+        context: &GpuContext,
+        mesh: &Mesh<V>,
+        instances: &[CullInstance]
+    ) -> Result<Self>
+        where V: bytemuck::Pod
+    {
+        if !mesh.is_indexed() {
+            return Err(GeepuError::BufferError("GPU culling requires an indexed mesh".into()));
+        }
+        let features = context.device.features();
+        let multi_draw = features.contains(
+            wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE
+        );
+        let capacity = instances.len().max(1);
+        let batch_count = mesh.submeshes.len();
+
+        let storage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
+        let instance_buffer = TypedBuffer::empty(context, capacity, storage)?;
+        let offsets = TypedBuffer::empty(context, batch_count, storage)?;
+        let args = TypedBuffer::empty(
+            context,
+            batch_count,
+            storage | wgpu::BufferUsages::INDIRECT
+        )?;
+        let visible = TypedBuffer::empty(
+            context,
+            capacity,
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX
+        )?;
+        let params = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+
+        let layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::COMPUTE)
+            .storage_buffer(1, wgpu::ShaderStages::COMPUTE, true)
+            .storage_buffer(2, wgpu::ShaderStages::COMPUTE, true)
+            .storage_buffer(3, wgpu::ShaderStages::COMPUTE, false)
+            .storage_buffer(4, wgpu::ShaderStages::COMPUTE, false)
+            .build(context, Some("Culling Layout"));
+        let bind_group = BindGroupBuilder::new(&layout)
+            .buffer(0, params.buffer())
+            .buffer(1, instance_buffer.buffer())
+            .buffer(2, offsets.buffer())
+            .buffer(3, args.buffer())
+            .buffer(4, visible.buffer())
+            .build(context, Some("Culling Bind Group"));
+        let pipeline = ComputePipeline::new(
+            context,
+            &format!("{}{}", SHADER, NO_OCCLUSION),
+            vec![layout.clone()],
+            Some("Culling Pipeline")
+        )?;
+
+        let occlusion_params = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let occlusion_layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::COMPUTE)
+            .texture(
+                1,
+                wgpu::ShaderStages::COMPUTE,
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .build(context, Some("Occlusion Culling Layout"));
+        let occlusion_pipeline = ComputePipeline::new(
+            context,
+            &format!("{}{}", SHADER, HIZ_OCCLUSION),
+            vec![layout, occlusion_layout.clone()],
+            Some("Occlusion Culling Pipeline")
+        )?;
+
+        let mut culler = Self {
+            instances: instance_buffer,
+            instance_count: 0,
+            offsets,
+            batches: Vec::new(),
+            initial_args: Vec::new(),
+            args,
+            visible,
+            params,
+            bind_group,
+            pipeline,
+            occlusion_params,
+            occlusion_layout,
+            occlusion_pipeline,
+            multi_draw,
+        };
+        culler.update_instances(context, mesh, instances)?;
+        Ok(culler)
+    }
+
+    /// Replace the instances, which must fit in the count the culler was created with
+    pub fn update_instances<V>(
+        &mut self,
+        context: &GpuContext,
+        mesh: &Mesh<V>,
+        instances: &[CullInstance]
+    ) -> Result<()>
+        where V: bytemuck::Pod
+    {
+        let batch_count = self.offsets.len();
+        if mesh.submeshes.len() != batch_count {
+            return Err(
+                GeepuError::BufferError(
+                    format!(
+                        "Mesh has {} submeshes, culler was created for {}",
+                        mesh.submeshes.len(),
+                        batch_count
+                    )
+                )
+            );
+        }
+        let mut counts = vec![0usize; batch_count];
+        for instance in instances {
+            let submesh = instance.submesh as usize;
+            if submesh >= batch_count {
+                return Err(
+                    GeepuError::BufferError(
+                        format!(
+                            "Instance submesh {} out of range ({} submeshes)",
+                            submesh,
+                            batch_count
+                        )
+                    )
+                );
+            }
+            counts[submesh] += 1;
+        }
+        self.instances.write(context, instances)?;
+
+        // Each submesh's survivors are written to their own slice of the visible buffer
+        let mut start = 0;
+        self.batches = counts
+            .iter()
+            .map(|&count| {
+                let batch = start..start + count;
+                start += count;
+                batch
+            })
+            .collect();
+        let offsets: Vec<u32> = self.batches
+            .iter()
+            .map(|batch| batch.start as u32)
+            .collect();
+        self.initial_args = mesh.submeshes
+            .iter()
+            .zip(&self.batches)
+            .map(|(submesh, batch)| {
+                let first_instance = if self.multi_draw { batch.start as u32 } else { 0 };
+                DrawIndexedIndirectArgs::new(
+                    submesh.range.clone(),
+                    submesh.base_vertex,
+                    first_instance..first_instance
+                )
+            })
+            .collect();
+
+        self.offsets.write(context, &offsets)?;
+        self.instance_count = instances.len() as u32;
+        Ok(())
+    }
+
+    /// Record the culling pass for `camera` into `encoder`.
+    ///
+    /// The draw arguments are reset through the queue, so cull at most once per submission.
+    pub fn cull(
+        &self,
+        context: &GpuContext,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &Camera
+    ) -> Result<()> {
+        self.dispatch(context, encoder, camera, &self.pipeline, None)
+    }
+
+    /// [`cull`](Self::cull), also dropping instances whose bounds lie behind
+    /// the depth in `hiz`. The pyramid must already be built (usually from the
+    /// previous frame or a depth pre-pass); boxes crossing the near plane are kept.
+    pub fn cull_occluded(
+        &self,
+        context: &GpuContext,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &Camera,
+        hiz: &HiZBuffer
+    ) -> Result<()> {
+        let (width, height) = hiz.size();
+        self.occlusion_params.write(
+            context,
+            &[
+                OcclusionParams {
+                    view_projection: camera.view_projection_matrix(),
+                    size: [width as f32, height as f32],
+                    mip_count: hiz.mip_level_count(),
+                    _padding: 0,
+                },
+            ]
+        )?;
+        let bind_group = BindGroupBuilder::new(&self.occlusion_layout)
+            .buffer(0, self.occlusion_params.buffer())
+            .texture_view(1, &hiz.texture().view)
+            .build(context, Some("Occlusion Culling Bind Group"));
+        self.dispatch(context, encoder, camera, &self.occlusion_pipeline, Some(&bind_group))
+    }
+
+    fn dispatch(
+        &self,
+        context: &GpuContext,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &Camera,
+        pipeline: &ComputePipeline,
+        occlusion: Option<&wgpu::BindGroup>
+    ) -> Result<()> {
+        self.args.write(context, &self.initial_args)?;
+        self.params.write(
+            context,
+            &[
+                CullParams {
+                    planes: camera.frustum().planes,
+                    instance_count: self.instance_count,
+                    _padding: [0; 3],
+                },
+            ]
+        )?;
+        if self.instance_count == 0 {
+            return Ok(());
+        }
+
+        let mut pass = ComputePass::new(encoder, Some(&context.label("Culling Pass")));
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        if let Some(occlusion) = occlusion {
+            pass.set_bind_group(1, occlusion, &[]);
+        }
+        pass.dispatch_workgroups(self.instance_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        Ok(())
+    }
+
+    /// Draw the instances that survived the last [`cull`](Self::cull), with
+    /// their transforms in vertex buffer slot 1 (see [`InstanceTransform::layout`])
+    pub fn draw<'a, V>(&'a self, pass: &mut RenderPass<'a>, mesh: &'a Mesh<V>)
+        where V: bytemuck::Pod
+    {
+        if self.instance_count == 0 {
+            return;
+        }
+        pass.bind_mesh(mesh);
+        if self.multi_draw {
+            pass.set_vertex_buffer(1, &self.visible);
+            pass.multi_draw_indexed_indirect(&self.args, 0, self.batches.len() as u32);
+        } else {
+            for (index, batch) in self.batches.iter().enumerate() {
+                if batch.is_empty() {
+                    continue;
+                }
+                pass.set_vertex_buffer_range(1, &self.visible, batch.clone());
+                pass.draw_indexed_indirect(&self.args, index);
+            }
+        }
+    }
+
+    /// Whether draws go out as a single multi-draw call
+    pub fn uses_multi_draw(&self) -> bool {
+        self.multi_draw
+    }
+
+    /// Per-submesh draw arguments written by the culling pass
+    pub fn args(&self) -> &TypedBuffer<DrawIndexedIndirectArgs> {
+        &self.args
+    }
+
+    /// Compacted transforms of the visible instances, grouped by submesh
+    pub fn visible(&self) -> &TypedBuffer<InstanceTransform> {
+        &self.visible
+    }
+}
+
+const SHADER: &str =
+    r#"
+struct CullInstance {
+    model: mat4x4<f32>,
+    aabb_min: vec3<f32>,
+    submesh: u32,
+    aabb_max: vec3<f32>,
+}
+
+struct DrawArgs {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+struct CullParams {
+    planes: array<vec4<f32>, 6>,
+    instance_count: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: CullParams;
+@group(0) @binding(1) var<storage, read> instances: array<CullInstance>;
+@group(0) @binding(2) var<storage, read> offsets: array<u32>;
+@group(0) @binding(3) var<storage, read_write> args: array<DrawArgs>;
+@group(0) @binding(4) var<storage, read_write> visible: array<mat4x4<f32>>;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.instance_count) {
+        return;
+    }
+    let instance = instances[id.x];
+    let model = instance.model;
+
+    // World-space box enclosing the transformed local bounds
+    let local_center = (instance.aabb_min + instance.aabb_max) * 0.5;
+    let local_extents = (instance.aabb_max - instance.aabb_min) * 0.5;
+    let center = (model * vec4<f32>(local_center, 1.0)).xyz;
+    let abs_model = mat3x3<f32>(abs(model[0].xyz), abs(model[1].xyz), abs(model[2].xyz));
+    let extents = abs_model * local_extents;
+
+    for (var i = 0u; i < 6u; i++) {
+        let plane = params.planes[i];
+        if (dot(plane.xyz, center) + plane.w < -dot(extents, abs(plane.xyz))) {
+            return;
+        }
+    }
+    if (occluded(center, extents)) {
+        return;
+    }
+
+    let slot = atomicAdd(&args[instance.submesh].instance_count, 1u);
+    visible[offsets[instance.submesh] + slot] = model;
+}
+"#;
+
+const NO_OCCLUSION: &str =
+    r#"
+fn occluded(center: vec3<f32>, extents: vec3<f32>) -> bool {
+    return false;
+}
+"#;
+
+const HIZ_OCCLUSION: &str =
+    r#"
+struct OcclusionParams {
+    view_projection: mat4x4<f32>,
+    size: vec2<f32>,
+    mip_count: u32,
+}
+
+@group(1) @binding(0) var<uniform> occlusion: OcclusionParams;
+@group(1) @binding(1) var hiz: texture_2d<f32>;
+
+fn occluded(center: vec3<f32>, extents: vec3<f32>) -> bool {
+    var uv_min = vec2<f32>(1.0);
+    var uv_max = vec2<f32>(0.0);
+    var nearest = 1.0;
+    for (var i = 0u; i < 8u; i++) {
+        let corner = center + extents * vec3<f32>(
+            select(-1.0, 1.0, (i & 1u) != 0u),
+            select(-1.0, 1.0, (i & 2u) != 0u),
+            select(-1.0, 1.0, (i & 4u) != 0u)
+        );
+        let clip = occlusion.view_projection * vec4<f32>(corner, 1.0);
+        if (clip.w <= 0.0) {
+            return false;
+        }
+        let ndc = clip.xyz / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        uv_min = min(uv_min, uv);
+        uv_max = max(uv_max, uv);
+        nearest = min(nearest, ndc.z);
+    }
+    uv_min = clamp(uv_min, vec2<f32>(0.0), vec2<f32>(1.0));
+    uv_max = clamp(uv_max, vec2<f32>(0.0), vec2<f32>(1.0));
+
+    // The level where the box covers at most 2x2 texels
+    let extent = (uv_max - uv_min) * occlusion.size;
+    let fit = i32(ceil(log2(max(max(extent.x, extent.y), 1.0))));
+    let level = min(fit, i32(occlusion.mip_count) - 1);
+    let level_size = textureDimensions(hiz, level);
+    let lo = min(vec2<u32>(uv_min * vec2<f32>(level_size)), level_size - 1u);
+    let hi = min(vec2<u32>(uv_max * vec2<f32>(level_size)), level_size - 1u);
+    let farthest = max(
+        max(textureLoad(hiz, lo, level).r, textureLoad(hiz, vec2<u32>(hi.x, lo.y), level).r),
+        max(textureLoad(hiz, vec2<u32>(lo.x, hi.y), level).r, textureLoad(hiz, hi, level).r)
+    );
+    return nearest > farthest;
+}
+"#;
@@ -0,0 +1,262 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    Camera,
+    CameraUniform,
+    DynamicMesh,
+    GpuContext,
+    PolylineRenderer,
+    RenderPass,
+    RenderPipeline,
+    Result,
+    TypedBuffer,
+};
+use glam::{ Mat4, Vec3 };
+
+const SPHERE_SEGMENTS: usize = 32;
+
+/// Vertex of a debug line
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+/// Immediate-mode debug lines and gizmos.
+///
+/// Shapes accumulate on the CPU during the frame; [`prepare`](Self::prepare)
+/// uploads them into a [`DynamicMesh`] and starts a new frame, and
+/// [`render`](Self::render) draws them with a line-list pipeline. Record it in a
+/// pass after the main one (loading the existing color and depth) so lines
+/// are depth-tested against the scene.
+///
+/// Hardware lines are one pixel wide; [`debug_polyline`](Self::debug_polyline)
+/// draws anti-aliased lines of any width through a [`PolylineRenderer`], which
+/// needs the target size from [`set_viewport`](Self::set_viewport).
+pub struct DebugDraw {
+    lines: DynamicMesh<DebugVertex>,
+    polylines: PolylineRenderer,
+    camera: TypedBuffer<CameraUniform>,
+    camera_bind_group: wgpu::BindGroup,
+    pipeline: RenderPipeline,
+}
+
+impl DebugDraw {
+    /// Create a debug renderer for the given color (and optional depth) formats
+    pub fn new(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>
+    ) -> Result<Self> {
+        Self::new_multisampled(context, color_format, depth_format, 1)
+    }
+
+    /// [`new`](Self::new) for targets with `samples` samples per pixel, e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    pub fn new_multisampled(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        samples: u32
+    ) -> Result<Self> {
+        let camera = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let camera_layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::VERTEX)
+            .build(context, Some("Debug Draw Camera Layout"));
+        let camera_bind_group = BindGroupBuilder::new(&camera_layout)
+            .buffer(0, camera.buffer())
+            .build(context, Some("Debug Draw Camera Bind Group"));
+
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&context.label("Debug Draw Shader")),
+            source: wgpu::ShaderSource::Wgsl(format!("{}{}", Camera::WGSL, SHADER).into()),
+        });
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&context.label("Debug Draw Pipeline Layout")),
+                bind_group_layouts: &[&camera_layout],
+                push_constant_ranges: &[],
+            })
+        );
+        let pipeline = context.device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some(&context.label("Debug Draw Pipeline")),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<DebugVertex>() as u64,
+                            step_mode: wgpu::VertexStepMode::Vertex,
+                            attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+                        },
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: color_format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: samples,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            })
+        );
+
+        Ok(Self {
+            lines: DynamicMesh::new(context, 1024)?,
+            polylines: PolylineRenderer::new_multisampled(
+                context,
+                color_format,
+                depth_format,
+                samples
+            )?,
+            camera,
+            camera_bind_group,
+            pipeline: RenderPipeline {
+                pipeline,
+                bind_group_layouts: vec![camera_layout],
+                vertex_buffer_count: 1,
+                bindings: Vec::new(),
+            },
+        })
+    }
+
+    /// Draw a line segment from `a` to `b`
+    pub fn debug_line(&mut self, a: [f32; 3], b: [f32; 3], color: [f32; 4]) {
+        self.lines.push(DebugVertex { position: a, color });
+        self.lines.push(DebugVertex { position: b, color });
+    }
+
+    /// Draw the edges of an axis-aligned box
+    pub fn debug_aabb(&mut self, min: [f32; 3], max: [f32; 3], color: [f32; 4]) {
+        let corner = |i: usize| {
+            [
+                if (i & 1) == 0 { min[0] } else { max[0] },
+                if (i & 2) == 0 { min[1] } else { max[1] },
+                if (i & 4) == 0 { min[2] } else { max[2] },
+            ]
+        };
+        // Corners that differ in exactly one axis bit share an edge
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if (i & bit) == 0 {
+                    self.debug_line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    /// Draw a wire sphere as three axis-aligned circles
+    pub fn debug_sphere(&mut self, center: [f32; 3], radius: f32, color: [f32; 4]) {
+        let center = Vec3::from(center);
+        let axes = [
+            (Vec3::X, Vec3::Y),
+            (Vec3::Y, Vec3::Z),
+            (Vec3::Z, Vec3::X),
+        ];
+        for (u, v) in axes {
+            let point = |i: usize| {
+                let angle = ((i as f32) / (SPHERE_SEGMENTS as f32)) * std::f32::consts::TAU;
+                (center + (u * angle.cos() + v * angle.sin()) * radius).to_array()
+            };
+            for i in 0..SPHERE_SEGMENTS {
+                self.debug_line(point(i), point(i + 1), color);
+            }
+        }
+    }
+
+    /// Draw a transform's X (red), Y (green) and Z (blue) axes with length `size`
+    pub fn debug_axes(&mut self, transform: [[f32; 4]; 4], size: f32) {
+        let transform = Mat4::from_cols_array_2d(&transform);
+        let origin = transform.transform_point3(Vec3::ZERO);
+        let colors = [
+            (Vec3::X, [1.0, 0.0, 0.0, 1.0]),
+            (Vec3::Y, [0.0, 1.0, 0.0, 1.0]),
+            (Vec3::Z, [0.0, 0.0, 1.0, 1.0]),
+        ];
+        for (axis, color) in colors {
+            let end = transform.transform_point3(axis * size);
+            self.debug_line(origin.to_array(), end.to_array(), color);
+        }
+    }
+
+    /// Draw connected segments through `points`, `width` pixels wide
+    pub fn debug_polyline(&mut self, points: &[[f32; 3]], width: f32, color: [f32; 4]) {
+        self.polylines.polyline(points, width, color);
+    }
+
+    /// Set the size in pixels of the target, for wide lines (call on resize)
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.polylines.set_viewport(width, height);
+    }
+
+    /// Number of lines accumulated for the current frame
+    pub fn line_count(&self) -> usize {
+        self.lines.pending().len() / 2
+    }
+
+    /// Upload this frame's lines and camera, then clear the list for the next frame
+    pub fn prepare(&mut self, context: &GpuContext, camera: &Camera) -> Result<()> {
+        camera.update_buffer(context, &self.camera)?;
+        self.polylines.prepare(context, camera)?;
+        self.lines.upload(context)
+    }
+
+    /// Draw the lines uploaded by the last [`prepare`](Self::prepare)
+    pub fn render<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if self.lines.vertex_count() > 0 {
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            self.lines.draw(pass);
+        }
+        self.polylines.render(pass);
+    }
+}
+
+const SHADER: &str =
+    r#"
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_projection * vec4<f32>(position, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
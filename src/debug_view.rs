@@ -0,0 +1,225 @@
+use crate::Camera;
+
+/// How a [`MaterialRenderer`](crate::MaterialRenderer) shades its draws.
+///
+/// Every view except [`Lit`](Self::Lit) replaces the material shaders with a
+/// built-in one reading position (location 0, `Float32x3`) and, when present in
+/// the mesh's layout, normal (location 1, `Float32x3`) and uv (location 2,
+/// `Float32x2`). Skinned meshes are shown in their bind pose.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DebugView {
+    /// The materials' own shading
+    #[default]
+    Lit,
+    /// View distance on a logarithmic scale, white at the near plane and black at the far plane
+    Depth,
+    /// World-space normals mapped to RGB; faceted when the mesh has no normals
+    Normals,
+    /// Texture coordinates in red and green, wrapped to 0..1
+    Uvs,
+    /// Every fragment adds to the target without depth testing, so areas shaded
+    /// many times go from blue over cyan to white; clear to black first
+    Overdraw,
+    /// The lit scene with triangle edges drawn on top; requires
+    /// `Features::POLYGON_MODE_LINE`
+    Wireframe,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 6] = [
+        DebugView::Lit,
+        DebugView::Depth,
+        DebugView::Normals,
+        DebugView::Uvs,
+        DebugView::Overdraw,
+        DebugView::Wireframe,
+    ];
+
+    /// The view after this one in [`ALL`](Self::ALL), wrapping around; handy for a toggle key
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&view| view == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Device features the view needs
+    pub fn required_features(self) -> wgpu::Features {
+        match self {
+            DebugView::Wireframe => wgpu::Features::POLYGON_MODE_LINE,
+            _ => wgpu::Features::empty(),
+        }
+    }
+
+    /// WGSL the view draws meshes with `layout` in, or `None` for
+    /// [`Lit`](Self::Lit) and layouts without a `Float32x3` position at location 0.
+    ///
+    /// The camera is bound at group 0 and the object transform at group 1.
+    pub fn shader_source(self, layout: &wgpu::VertexBufferLayout) -> Option<String> {
+        let fragment = match self {
+            DebugView::Lit => {
+                return None;
+            }
+            DebugView::Depth => DEPTH_FRAGMENT,
+            DebugView::Normals => NORMALS_FRAGMENT,
+            DebugView::Uvs => UVS_FRAGMENT,
+            DebugView::Overdraw => OVERDRAW_FRAGMENT,
+            DebugView::Wireframe => WIREFRAME_FRAGMENT,
+        };
+        let has = |location: u32, format: wgpu::VertexFormat| {
+            layout.attributes
+                .iter()
+                .any(|attribute| {
+                    attribute.shader_location == location && attribute.format == format
+                })
+        };
+        if !has(0, wgpu::VertexFormat::Float32x3) {
+            return None;
+        }
+
+        let mut inputs = String::from("    @location(0) position: vec3<f32>,\n");
+        let normal = if has(1, wgpu::VertexFormat::Float32x3) {
+            inputs.push_str("    @location(1) normal: vec3<f32>,\n");
+            "(object.normal * vec4<f32>(in.normal, 0.0)).xyz"
+        } else {
+            "vec3<f32>(0.0)"
+        };
+        let uv = if has(2, wgpu::VertexFormat::Float32x2) {
+            inputs.push_str("    @location(2) uv: vec2<f32>,\n");
+            "in.uv"
+        } else {
+            "vec2<f32>(0.0)"
+        };
+        let vertex = VERTEX.replace("{inputs}", &inputs)
+            .replace("{normal}", normal)
+            .replace("{uv}", uv);
+        Some(format!("{}{}{}", Camera::WGSL, vertex, fragment))
+    }
+
+    /// Pipeline state the view draws with
+    pub(crate) fn primitive(self) -> wgpu::PrimitiveState {
+        wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: match self {
+                DebugView::Wireframe => wgpu::PolygonMode::Line,
+                _ => wgpu::PolygonMode::Fill,
+            },
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn blend(self) -> Option<wgpu::BlendState> {
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        match self {
+            DebugView::Overdraw => Some(wgpu::BlendState { color: additive, alpha: additive }),
+            _ => Some(wgpu::BlendState::REPLACE),
+        }
+    }
+
+    pub(crate) fn depth_stencil(self, format: wgpu::TextureFormat) -> wgpu::DepthStencilState {
+        let (depth_write_enabled, depth_compare, bias) = match self {
+            DebugView::Overdraw => (false, wgpu::CompareFunction::Always, Default::default()),
+            // Pull the edges towards the camera so they win against the lit faces
+            DebugView::Wireframe => {
+                let bias = wgpu::DepthBiasState { constant: -2, slope_scale: -1.0, clamp: 0.0 };
+                (false, wgpu::CompareFunction::LessEqual, bias)
+            }
+            _ => (true, wgpu::CompareFunction::Less, Default::default()),
+        };
+        wgpu::DepthStencilState {
+            format,
+            depth_write_enabled,
+            depth_compare,
+            stencil: wgpu::StencilState::default(),
+            bias,
+        }
+    }
+}
+
+const VERTEX: &str =
+    r#"
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> object: Object;
+
+struct VertexInput {
+{inputs}}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) view_depth: f32,
+    @location(2) normal: vec3<f32>,
+    @location(3) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let world = object.model * vec4<f32>(in.position, 1.0);
+    out.clip_position = camera.view_projection * world;
+    out.world_position = world.xyz;
+    out.view_depth = -(camera.view * world).z;
+    out.normal = {normal};
+    out.uv = {uv};
+    return out;
+}
+"#;
+
+const DEPTH_FRAGMENT: &str =
+    r#"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Near and far planes recovered from the right-handed, 0..1 depth projection
+    let p = camera.projection;
+    let near = max(p[3][2] / p[2][2], 1e-4);
+    var far = (p[3][2] - 1.0) / p[2][2];
+    if (p[2][3] != 0.0) {
+        far = p[3][2] / (p[2][2] + 1.0);
+    }
+    let t = log(max(in.view_depth, near) / near) / log(max(far, near * 1.001) / near);
+    return vec4<f32>(vec3<f32>(1.0 - clamp(t, 0.0, 1.0)), 1.0);
+}
+"#;
+
+const NORMALS_FRAGMENT: &str =
+    r#"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var n = normalize(cross(dpdx(in.world_position), dpdy(in.world_position)));
+    if (dot(in.normal, in.normal) > 0.0) {
+        n = normalize(in.normal);
+    }
+    return vec4<f32>(n * 0.5 + 0.5, 1.0);
+}
+"#;
+
+const UVS_FRAGMENT: &str =
+    r#"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(fract(in.uv), 0.0, 1.0);
+}
+"#;
+
+const OVERDRAW_FRAGMENT: &str =
+    r#"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.02, 0.06, 0.15, 1.0);
+}
+"#;
+
+const WIREFRAME_FRAGMENT: &str =
+    r#"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.1, 1.0, 0.3, 1.0);
+}
+"#;
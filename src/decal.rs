@@ -0,0 +1,438 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    Camera,
+    CameraUniform,
+    DrawQueue,
+    GpuContext,
+    Material,
+    MaterialRenderer,
+    MaterialShader,
+    Mesh,
+    MeshVertex,
+    ObjectUniform,
+    RenderPass,
+    RenderPipeline,
+    Result,
+    SortKey,
+    TypedBuffer,
+};
+use crate::describe;
+use glam::{ Mat4, Quat, Vec3 };
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// One decal: a material projected through a box at a model transform.
+///
+/// The box is the unit cube (-0.5..0.5) under `transform`. The material is
+/// projected along the box's local Y axis onto the scene surfaces inside it,
+/// with `u` along X and `v` along Z.
+pub struct Decal<'a> {
+    pub material: &'a Material,
+    pub transform: [[f32; 4]; 4],
+}
+
+impl<'a> Decal<'a> {
+    pub fn new(material: &'a Material, transform: [[f32; 4]; 4]) -> Self {
+        Self { material, transform }
+    }
+
+    /// A decal centred on `point` of a surface facing `normal`, e.g. where a
+    /// ray hit. `size` is the width (X), projection depth (Y) and height (Z).
+    pub fn on_surface(
+        material: &'a Material,
+        point: [f32; 3],
+        normal: [f32; 3],
+        size: [f32; 3]
+    ) -> Self {
+        let normal = Vec3::from(normal).normalize_or(Vec3::Y);
+        let transform = Mat4::from_scale_rotation_translation(
+            Vec3::from(size),
+            Quat::from_rotation_arc(Vec3::Y, normal),
+            Vec3::from(point)
+        );
+        Self::new(material, transform.to_cols_array_2d())
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DecalView {
+    inverse_view_projection: [[f32; 4]; 4],
+    target_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+impl MaterialShader {
+    /// Declarations and vertex stage shared by every decal shader
+    pub const DECAL_PRELUDE: &'static str =
+        r#"
+struct DecalView {
+    inverse_view_projection: mat4x4<f32>,
+    target_size: vec2<f32>,
+}
+
+@group(0) @binding(1) var decal_depth: texture_depth_2d;
+@group(0) @binding(2) var<uniform> decal_view: DecalView;
+
+struct DecalVertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> DecalVertexOutput {
+    var out: DecalVertexOutput;
+    out.clip_position = camera.view_projection * object.model * vec4<f32>(position, 1.0);
+    return out;
+}
+
+// World position of the scene surface behind a fragment, from the depth buffer
+fn decal_world_position(clip_position: vec4<f32>) -> vec3<f32> {
+    let depth = textureLoad(decal_depth, vec2<i32>(clip_position.xy), 0);
+    let uv = clip_position.xy / decal_view.target_size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = decal_view.inverse_view_projection * ndc;
+    return world.xyz / world.w;
+}
+
+// `world` in the decal box's space, where the box spans -0.5..0.5.
+// `object.normal` is the inverse transpose of the model matrix.
+fn decal_local_position(world: vec3<f32>) -> vec3<f32> {
+    return (transpose(object.normal) * vec4<f32>(world, 1.0)).xyz;
+}
+
+fn decal_inside(local: vec3<f32>) -> bool {
+    return all(abs(local) <= vec3<f32>(0.5));
+}
+
+fn decal_uv(local: vec3<f32>) -> vec2<f32> {
+    return local.xz + 0.5;
+}
+"#;
+
+    /// A decal shader variant from a WGSL fragment stage that samples
+    /// `texture_count` textures.
+    ///
+    /// The source is prefixed with [`DECAL_PRELUDE`](Self::DECAL_PRELUDE)
+    /// after the usual material prelude, so it only defines `fs_main`, taking
+    /// a `DecalVertexOutput`. Decals alpha blend, draw the box's back faces
+    /// so they still show with the camera inside it, and never write depth.
+    pub fn decal(label: impl Into<String>, source: &str, texture_count: u32) -> Self {
+        Self::new(label, &format!("{}{}", Self::DECAL_PRELUDE, source), texture_count)
+            .blend(Some(wgpu::BlendState::ALPHA_BLENDING))
+            .cull_mode(Some(wgpu::Face::Front))
+            .depth_write(false)
+            .depth_prepass(false)
+    }
+
+    /// Decal shader projecting one texture tinted by a base color, faded out
+    /// where surfaces turn away from the projection axis. Parameters are
+    /// [`BasicParams`](crate::BasicParams).
+    pub fn basic_decal() -> Self {
+        Self::decal("Basic Decal", BASIC_DECAL_SHADER, 1)
+    }
+}
+
+/// Draws [`Decal`]s over an already rendered scene, compiling one pipeline
+/// per decal shader on first use.
+///
+/// Decals read the scene's depth to find the surfaces inside their boxes, so
+/// they are recorded in a pass after the opaque geometry whose depth
+/// attachment, if any, is read-only (`depth_stencil_attachment(view, None,
+/// None)`); the same depth texture is bound through
+/// [`set_depth`](Self::set_depth). It must be single-sampled and have
+/// `TEXTURE_BINDING` usage; with a multisampled pass (see
+/// [`new_multisampled`](Self::new_multisampled)) bind a resolved copy of the
+/// multisampled depth attachment.
+///
+/// Like [`MaterialRenderer`], call [`prepare`](Self::prepare) before the pass
+/// and [`render`](Self::render) or [`queue`](Self::queue) inside it with the
+/// same decals.
+pub struct DecalRenderer {
+    color_format: wgpu::TextureFormat,
+    depth_format: Option<wgpu::TextureFormat>,
+    samples: u32,
+    cube: Mesh<MeshVertex>,
+    camera: TypedBuffer<CameraUniform>,
+    view: TypedBuffer<DecalView>,
+    view_layout: Arc<wgpu::BindGroupLayout>,
+    view_bind_group: Option<wgpu::BindGroup>,
+    object_layout: Arc<wgpu::BindGroupLayout>,
+    objects: wgpu::Buffer,
+    object_bind_group: wgpu::BindGroup,
+    object_stride: u64,
+    pipelines: HashMap<u64, RenderPipeline>,
+}
+
+impl DecalRenderer {
+    /// Create a renderer for passes with the given color (and optional
+    /// read-only depth) formats
+    pub fn new(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>
+    ) -> Result<Self> {
+        Self::new_multisampled(context, color_format, depth_format, 1)
+    }
+
+    /// [`new`](Self::new) for targets with `samples` samples per pixel, e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    pub fn new_multisampled(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        samples: u32
+    ) -> Result<Self> {
+        let view_layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::VERTEX_FRAGMENT)
+            .texture(
+                1,
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Depth,
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .uniform_buffer(2, wgpu::ShaderStages::FRAGMENT)
+            .build(context, Some("Decal View Layout"));
+
+        let object_size = std::mem::size_of::<ObjectUniform>() as u64;
+        let alignment = context.device.limits().min_uniform_buffer_offset_alignment as u64;
+        let object_stride = object_size.next_multiple_of(alignment);
+        let object_layout = BindGroupLayoutBuilder::new()
+            .dynamic_uniform_buffer(0, wgpu::ShaderStages::VERTEX_FRAGMENT, object_size)
+            .build(context, Some("Decal Object Layout"));
+        let (objects, object_bind_group) = MaterialRenderer::create_objects(
+            context,
+            &object_layout,
+            object_stride,
+            1
+        );
+
+        Ok(Self {
+            color_format,
+            depth_format,
+            samples,
+            cube: Mesh::cube(context)?,
+            camera: TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?,
+            view: TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?,
+            view_layout,
+            view_bind_group: None,
+            object_layout,
+            objects,
+            object_bind_group,
+            object_stride,
+            pipelines: HashMap::new(),
+        })
+    }
+
+    /// Upload the camera the scene was rendered with
+    pub fn set_camera(&self, context: &GpuContext, camera: &Camera) -> Result<()> {
+        self.set_camera_uniform(context, &camera.uniform())
+    }
+
+    /// Upload camera matrices directly, e.g. the jittered ones the scene was
+    /// rendered with under [`Taa`](crate::Taa)
+    pub fn set_camera_uniform(&self, context: &GpuContext, camera: &CameraUniform) -> Result<()> {
+        self.camera.write(context, std::slice::from_ref(camera))?;
+        let view_projection = Mat4::from_cols_array_2d(&camera.view_projection);
+        self.view.write_field(
+            context,
+            0,
+            |view| &view.inverse_view_projection,
+            &view_projection.inverse().to_cols_array_2d()
+        )
+    }
+
+    /// Bind the scene's depth, `width` x `height` like the color target
+    pub fn set_depth(
+        &mut self,
+        context: &GpuContext,
+        depth: &wgpu::TextureView,
+        width: u32,
+        height: u32
+    ) -> Result<()> {
+        let size = [width.max(1) as f32, height.max(1) as f32];
+        self.view.write_field(context, 0, |view| &view.target_size, &size)?;
+        self.view_bind_group = Some(
+            BindGroupBuilder::new(&self.view_layout)
+                .buffer(0, self.camera.buffer())
+                .texture_view(1, depth)
+                .buffer(2, self.view.buffer())
+                .build(context, Some("Decal View Bind Group"))
+        );
+        Ok(())
+    }
+
+    /// Number of pipelines compiled so far
+    pub fn pipeline_count(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    /// Compile pipelines the decals need and upload their transforms
+    pub fn prepare(&mut self, context: &GpuContext, decals: &[Decal]) -> Result<()> {
+        for decal in decals {
+            let shader = decal.material.shader.id();
+            if !self.pipelines.contains_key(&shader) {
+                let pipeline = self.create_pipeline(context, decal.material);
+                self.pipelines.insert(shader, pipeline);
+            }
+        }
+
+        let required = (decals.len().max(1) as u64) * self.object_stride;
+        if required > self.objects.size() {
+            let capacity = decals.len().next_power_of_two();
+            let (objects, bind_group) = MaterialRenderer::create_objects(
+                context,
+                &self.object_layout,
+                self.object_stride,
+                capacity
+            );
+            self.objects = objects;
+            self.object_bind_group = bind_group;
+        }
+
+        let mut data = vec![0u8; decals.len() * self.object_stride as usize];
+        for (i, decal) in decals.iter().enumerate() {
+            let offset = i * (self.object_stride as usize);
+            let object = ObjectUniform::new(decal.transform);
+            data[offset..offset + std::mem::size_of::<ObjectUniform>()].copy_from_slice(
+                bytemuck::bytes_of(&object)
+            );
+        }
+        context.write_buffer(&self.objects, 0, &data);
+        Ok(())
+    }
+
+    /// Record the decals into a render pass in order; `decals` must match the
+    /// last `prepare` call. Nothing is drawn before [`set_depth`](Self::set_depth).
+    pub fn render<'a>(&'a self, pass: &mut RenderPass<'a>, decals: &[Decal<'a>]) {
+        for (i, decal) in decals.iter().enumerate() {
+            self.record(pass, i, decal.material);
+        }
+    }
+
+    /// Queue the decals into a [`DrawQueue`] as transparent draws, sorted
+    /// back-to-front from `camera` so overlapping decals layer correctly;
+    /// `decals` must match the last `prepare` call
+    pub fn queue<'a>(&'a self, queue: &mut DrawQueue<'a>, camera: &Camera, decals: &[Decal<'a>]) {
+        for (i, decal) in decals.iter().enumerate() {
+            let [x, y, z, _] = decal.transform[3];
+            let material = decal.material;
+            queue.submit(SortKey::transparent(camera.view_depth([x, y, z])), move |pass| {
+                self.record(pass, i, material);
+            });
+        }
+    }
+
+    fn record<'a>(&'a self, pass: &mut RenderPass<'a>, index: usize, material: &'a Material) {
+        let Some(view_bind_group) = &self.view_bind_group else {
+            return;
+        };
+        let Some(pipeline) = self.pipelines.get(&material.shader.id()) else {
+            return;
+        };
+        let offset = ((index as u64) * self.object_stride) as u32;
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, view_bind_group, &[]);
+        pass.set_bind_group(1, &material.bind_group, &[]);
+        pass.set_bind_group(2, &self.object_bind_group, &[offset]);
+        pass.draw_mesh(&self.cube);
+    }
+
+    fn create_pipeline(&self, context: &GpuContext, material: &Material) -> RenderPipeline {
+        let shader = &material.shader;
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&shader.label),
+            source: wgpu::ShaderSource::Wgsl(shader.source.as_str().into()),
+        });
+        let bind_group_layouts: [&wgpu::BindGroupLayout; 3] = [
+            &self.view_layout,
+            &material.bind_group_layout,
+            &self.object_layout,
+        ];
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&shader.label),
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: &[],
+            })
+        );
+        let color_targets = [
+            Some(wgpu::ColorTargetState {
+                format: self.color_format,
+                blend: shader.blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+        ];
+
+        let descriptor = wgpu::RenderPipelineDescriptor {
+            label: Some(&shader.label),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: std::slice::from_ref(&self.cube.layout),
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &color_targets,
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: shader.cull_mode,
+                ..Default::default()
+            },
+            // The depth attachment is read-only; the shader does its own test
+            depth_stencil: self.depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: self.samples,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        };
+        log::debug!("{}", describe::render_pipeline(&descriptor, bind_group_layouts.len()));
+        let pipeline = context.device.create_render_pipeline(&descriptor);
+
+        RenderPipeline {
+            pipeline,
+            bind_group_layouts: Vec::new(),
+            vertex_buffer_count: 1,
+            bindings: Vec::new(),
+        }
+    }
+}
+
+const BASIC_DECAL_SHADER: &str =
+    r#"
+struct BasicParams {
+    base_color: vec4<f32>,
+}
+
+@group(1) @binding(0) var<uniform> params: BasicParams;
+@group(1) @binding(1) var base_texture: texture_2d<f32>;
+@group(1) @binding(2) var base_sampler: sampler;
+
+@fragment
+fn fs_main(in: DecalVertexOutput) -> @location(0) vec4<f32> {
+    let world = decal_world_position(in.clip_position);
+    let local = decal_local_position(world);
+    // Derivatives and implicit-lod sampling stay ahead of the discard
+    let normal = normalize(cross(dpdy(world), dpdx(world)));
+    let color = params.base_color * textureSample(base_texture, base_sampler, decal_uv(local));
+    if (!decal_inside(local)) {
+        discard;
+    }
+    let axis = normalize(object.model[1].xyz);
+    let fade = smoothstep(0.1, 0.4, dot(normal, axis));
+    return vec4<f32>(color.rgb, color.a * fade);
+}
+"#;
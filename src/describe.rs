@@ -0,0 +1,140 @@
+//! One-line-per-item descriptions of pipelines and bind groups, logged at
+//! `debug` level when geepu creates them so layout mismatches can be read off
+//! the log without a GPU debugger.
+
+use std::fmt::Write;
+
+fn name(label: Option<&str>) -> &str {
+    label.unwrap_or("<unlabeled>")
+}
+
+pub(crate) fn bind_group_layout(
+    label: Option<&str>,
+    entries: &[wgpu::BindGroupLayoutEntry]
+) -> String {
+    let mut out = format!("Created bind group layout \"{}\"", name(label));
+    for entry in entries {
+        let _ = write!(
+            out,
+            "\n  binding {}: {:?} visible to {:?}",
+            entry.binding,
+            entry.ty,
+            entry.visibility
+        );
+        if let Some(count) = entry.count {
+            let _ = write!(out, ", array of {}", count);
+        }
+    }
+    out
+}
+
+pub(crate) fn bind_group(label: Option<&str>, entries: &[wgpu::BindGroupEntry]) -> String {
+    let mut out = format!("Created bind group \"{}\"", name(label));
+    for entry in entries {
+        let resource = match &entry.resource {
+            wgpu::BindingResource::Buffer(binding) => {
+                let size = binding.size.map_or("rest".to_string(), |size| size.to_string());
+                format!("buffer at offset {} ({} bytes)", binding.offset, size)
+            }
+            wgpu::BindingResource::BufferArray(bindings) => {
+                format!("array of {} buffers", bindings.len())
+            }
+            wgpu::BindingResource::Sampler(_) => "sampler".to_string(),
+            wgpu::BindingResource::SamplerArray(samplers) => {
+                format!("array of {} samplers", samplers.len())
+            }
+            wgpu::BindingResource::TextureView(_) => "texture view".to_string(),
+            wgpu::BindingResource::TextureViewArray(views) => {
+                format!("array of {} texture views", views.len())
+            }
+            _ => "other resource".to_string(),
+        };
+        let _ = write!(out, "\n  binding {}: {}", entry.binding, resource);
+    }
+    out
+}
+
+pub(crate) fn render_pipeline(
+    descriptor: &wgpu::RenderPipelineDescriptor,
+    bind_group_layouts: usize
+) -> String {
+    let mut out = format!("Created render pipeline \"{}\"", name(descriptor.label));
+    let _ = write!(out, "\n  bind group layouts: {}", bind_group_layouts);
+    let _ = write!(out, "\n  vertex entry point: {}", descriptor.vertex.entry_point);
+    for (slot, layout) in descriptor.vertex.buffers.iter().enumerate() {
+        let _ = write!(
+            out,
+            "\n  vertex buffer {}: stride {}, {:?} step",
+            slot,
+            layout.array_stride,
+            layout.step_mode
+        );
+        for attribute in layout.attributes {
+            let _ = write!(
+                out,
+                "\n    @location({}) {:?} at offset {}",
+                attribute.shader_location,
+                attribute.format,
+                attribute.offset
+            );
+        }
+    }
+    match &descriptor.fragment {
+        Some(fragment) => {
+            let _ = write!(out, "\n  fragment entry point: {}", fragment.entry_point);
+            for (index, target) in fragment.targets.iter().enumerate() {
+                match target {
+                    Some(target) => {
+                        let _ = write!(
+                            out,
+                            "\n  color target {}: {:?}, blend {:?}, write {:?}",
+                            index,
+                            target.format,
+                            target.blend,
+                            target.write_mask
+                        );
+                    }
+                    None => {
+                        let _ = write!(out, "\n  color target {}: none", index);
+                    }
+                }
+            }
+        }
+        None => out.push_str("\n  no fragment stage"),
+    }
+    match &descriptor.depth_stencil {
+        Some(depth) => {
+            let _ = write!(
+                out,
+                "\n  depth: {:?}, compare {:?}, write {}",
+                depth.format,
+                depth.depth_compare,
+                depth.depth_write_enabled
+            );
+        }
+        None => out.push_str("\n  depth: none"),
+    }
+    let primitive = &descriptor.primitive;
+    let _ = write!(
+        out,
+        "\n  primitive: {:?}, {:?} front face, cull {:?}, {:?} fill",
+        primitive.topology,
+        primitive.front_face,
+        primitive.cull_mode,
+        primitive.polygon_mode
+    );
+    let _ = write!(out, "\n  samples: {}", descriptor.multisample.count);
+    out
+}
+
+pub(crate) fn compute_pipeline(
+    descriptor: &wgpu::ComputePipelineDescriptor,
+    bind_group_layouts: usize
+) -> String {
+    format!(
+        "Created compute pipeline \"{}\"\n  bind group layouts: {}\n  entry point: {}",
+        name(descriptor.label),
+        bind_group_layouts,
+        descriptor.entry_point
+    )
+}
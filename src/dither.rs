@@ -0,0 +1,235 @@
+use crate::{
+    check_wgsl,
+    color_attachment,
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    GpuContext,
+    RenderCommands,
+    RenderPipeline,
+    Result,
+    TypedBuffer,
+};
+use crate::shadertoy::{ fullscreen_pipeline, FULLSCREEN_VERTEX };
+use std::sync::Arc;
+
+/// Noise pattern a [`Dither`] pass adds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DitherMode {
+    /// Copy without dithering
+    Off,
+    /// 8x8 Bayer matrix: stable, with a faint regular pattern
+    Ordered,
+    /// Interleaved gradient noise, a cheap blue-noise substitute that changes
+    /// every frame so the eye (and TAA) averages it away
+    Noise,
+}
+
+impl DitherMode {
+    fn index(self) -> u32 {
+        match self {
+            Self::Off => 0,
+            Self::Ordered => 1,
+            Self::Noise => 2,
+        }
+    }
+}
+
+/// Parameters of a [`Dither`] pass
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DitherSettings {
+    pub mode: DitherMode,
+    /// Noise amplitude in quantization steps of the target; 1.0 hides banding
+    pub strength: f32,
+}
+
+impl DitherSettings {
+    pub fn new() -> Self {
+        Self { mode: DitherMode::Noise, strength: 1.0 }
+    }
+}
+
+impl Default for DitherSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DitherParams {
+    mode: u32,
+    frame: u32,
+    strength: f32,
+    levels: f32,
+}
+
+/// Quantization steps per channel of `format`: 1023 for 10-bit formats, 255 otherwise
+fn format_levels(format: wgpu::TextureFormat) -> f32 {
+    match format {
+        wgpu::TextureFormat::Rgb10a2Unorm => 1023.0,
+        _ => 255.0,
+    }
+}
+
+/// Final pass copying a high-precision image into a low-precision target
+/// (usually the 8-bit surface) with noise below one quantization step added,
+/// so smooth gradients dither instead of banding.
+///
+/// Noise is added in the target's encoding: for sRGB targets after the
+/// linear to sRGB conversion, where the 8-bit steps are. Source and target
+/// must be the same size.
+pub struct Dither {
+    settings: DitherSettings,
+    params: TypedBuffer<DitherParams>,
+    layout: Arc<wgpu::BindGroupLayout>,
+    pipeline: RenderPipeline,
+    levels: f32,
+    frame: u32,
+}
+
+impl Dither {
+    /// A pass writing into targets of `target_format`
+    pub fn new(
+        context: &GpuContext,
+        target_format: wgpu::TextureFormat,
+        settings: DitherSettings
+    ) -> Result<Self> {
+        let layout = BindGroupLayoutBuilder::new()
+            .texture(
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .uniform_buffer(1, wgpu::ShaderStages::FRAGMENT)
+            .build(context, Some("Dither Layout"));
+        let source = Self::shader_source(target_format);
+        check_wgsl("Dither", None, &source)?;
+        let pipeline = fullscreen_pipeline(
+            context,
+            &source,
+            vec![layout.clone()],
+            target_format.into(),
+            "Dither"
+        );
+
+        Ok(Self {
+            settings,
+            params: TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?,
+            layout,
+            pipeline,
+            levels: format_levels(target_format),
+            frame: 0,
+        })
+    }
+
+    /// The pass's WGSL for targets of `target_format`
+    pub fn shader_source(target_format: wgpu::TextureFormat) -> String {
+        let srgb = if target_format.is_srgb() { "true" } else { "false" };
+        format!("{}{}", FULLSCREEN_VERTEX, DITHER_FRAGMENT.replace("{srgb}", srgb))
+    }
+
+    pub fn settings(&self) -> &DitherSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: DitherSettings) {
+        self.settings = settings;
+    }
+
+    /// Copy `source` into `target`, dithered, and advance the noise
+    pub fn render(
+        &mut self,
+        context: &GpuContext,
+        commands: &mut RenderCommands,
+        source: &wgpu::TextureView,
+        target: &wgpu::TextureView
+    ) -> Result<()> {
+        self.params.write(
+            context,
+            &[
+                DitherParams {
+                    mode: self.settings.mode.index(),
+                    frame: self.frame,
+                    strength: self.settings.strength,
+                    levels: self.levels,
+                },
+            ]
+        )?;
+        self.frame = self.frame.wrapping_add(1);
+
+        let bind_group = BindGroupBuilder::new(&self.layout)
+            .texture_view(0, source)
+            .buffer(1, self.params.buffer())
+            .build(context, Some("Dither Bind Group"));
+        let color = [Some(color_attachment(target, None))];
+        let mut pass = commands.begin_render_pass(&color, None, Some("Dither"));
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
+
+const DITHER_FRAGMENT: &str =
+    r#"
+struct DitherParams {
+    mode: u32,
+    frame: u32,
+    strength: f32,
+    levels: f32,
+}
+
+const SRGB_TARGET: bool = {srgb};
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> params: DitherParams;
+
+// Bayer threshold: bit-reversed interleave of x ^ y and y
+fn bayer(pixel: vec2<u32>) -> f32 {
+    let v = (pixel.x ^ pixel.y) & 7u;
+    let y = pixel.y & 7u;
+    let m = ((v & 1u) << 5u) | ((y & 1u) << 4u) | ((v & 2u) << 2u) | ((y & 2u) << 1u) |
+        ((v & 4u) >> 1u) | ((y & 4u) >> 2u);
+    return (f32(m) + 0.5) / 64.0;
+}
+
+// Jimenez, "Next Generation Post Processing in Call of Duty: Advanced Warfare"
+fn interleaved_gradient_noise(pixel: vec2<f32>, frame: u32) -> f32 {
+    let p = pixel + 5.588238 * f32(frame % 64u);
+    return fract(52.9829189 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+fn to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+fn to_linear(srgb: vec3<f32>) -> vec3<f32> {
+    let low = srgb / 12.92;
+    let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(source, vec2<i32>(in.clip_position.xy), 0);
+    var threshold = 0.5;
+    if (params.mode == 1u) {
+        threshold = bayer(vec2<u32>(in.clip_position.xy));
+    } else if (params.mode == 2u) {
+        threshold = interleaved_gradient_noise(floor(in.clip_position.xy), params.frame);
+    }
+    let offset = (threshold - 0.5) * params.strength / params.levels;
+
+    var rgb = clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    if (SRGB_TARGET) {
+        rgb = to_linear(clamp(to_srgb(rgb) + offset, vec3<f32>(0.0), vec3<f32>(1.0)));
+    } else {
+        rgb = rgb + offset;
+    }
+    return vec4<f32>(rgb, color.a);
+}
+"#;
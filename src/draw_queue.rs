@@ -0,0 +1,191 @@
+use crate::{ Camera, Mesh, MeshLod, RenderPass };
+use std::cmp::Ordering;
+
+/// How a queued draw is ordered within its layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortMode {
+    /// Grouped by pipeline then material, front-to-back within a group
+    Opaque,
+    /// Back-to-front by depth, drawn after the layer's opaque draws
+    Transparent,
+}
+
+/// Sort key of a queued draw.
+///
+/// Layers are drawn in ascending order. `pipeline` and `material` are any ids
+/// that identify shared state, so draws sharing it end up adjacent; `depth` is
+/// the view-space distance (see [`Camera::view_depth`](crate::Camera::view_depth)).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub layer: u32,
+    pub mode: SortMode,
+    pub pipeline: u64,
+    pub material: u64,
+    pub depth: f32,
+}
+
+impl SortKey {
+    /// Key for an opaque draw in layer 0
+    pub fn opaque(pipeline: u64, material: u64, depth: f32) -> Self {
+        Self { layer: 0, mode: SortMode::Opaque, pipeline, material, depth }
+    }
+
+    /// Key for a transparent draw in layer 0
+    pub fn transparent(depth: f32) -> Self {
+        Self { layer: 0, mode: SortMode::Transparent, pipeline: 0, material: 0, depth }
+    }
+
+    /// Move the draw to another layer
+    pub fn layer(mut self, layer: u32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    fn compare(&self, other: &Self) -> Ordering {
+        let opaque = |key: &Self| key.mode == SortMode::Opaque;
+        self.layer
+            .cmp(&other.layer)
+            .then_with(|| opaque(other).cmp(&opaque(self)))
+            .then_with(|| {
+                match self.mode {
+                    SortMode::Opaque =>
+                        self.pipeline
+                            .cmp(&other.pipeline)
+                            .then_with(|| self.material.cmp(&other.material))
+                            .then_with(|| self.depth.total_cmp(&other.depth)),
+                    SortMode::Transparent => other.depth.total_cmp(&self.depth),
+                }
+            })
+    }
+}
+
+type DrawFn<'a> = Box<dyn FnOnce(&mut RenderPass<'a>) + 'a>;
+
+struct QueuedDraw<'a> {
+    key: SortKey,
+    draw: DrawFn<'a>,
+    /// Depth-only version recorded by [`DrawQueue::flush_prepass`]
+    depth_only: Option<DrawFn<'a>>,
+}
+
+/// A queue of draws that are sorted by [`SortKey`] before being recorded.
+///
+/// Each draw is a closure that sets its own pipeline and bind groups, so draws
+/// can be submitted in any order and [`flush`](Self::flush) records them in
+/// layer order: opaque draws grouped by state and front-to-back, then
+/// transparent draws back-to-front. Draws with equal keys keep submission order.
+///
+/// Opaque draws submitted with [`submit_with_prepass`](Self::submit_with_prepass)
+/// also carry a depth-only version. [`flush_prepass`](Self::flush_prepass)
+/// records those into an earlier pass that only fills the depth buffer, so the
+/// main pass (loading that depth with a `LessEqual` test) shades each pixel once.
+pub struct DrawQueue<'a> {
+    draws: Vec<QueuedDraw<'a>>,
+}
+
+impl<'a> DrawQueue<'a> {
+    pub fn new() -> Self {
+        Self { draws: Vec::new() }
+    }
+
+    /// Queue a draw
+    pub fn submit(&mut self, key: SortKey, draw: impl FnOnce(&mut RenderPass<'a>) + 'a) {
+        self.draws.push(QueuedDraw { key, draw: Box::new(draw), depth_only: None });
+    }
+
+    /// Queue a draw along with `depth_only`, which records the same geometry
+    /// with a fragment-less pipeline (see [`RenderPipeline::depth_only`]).
+    /// The depth-only version is dropped for transparent keys.
+    ///
+    /// [`RenderPipeline::depth_only`]: crate::RenderPipeline::depth_only
+    pub fn submit_with_prepass(
+        &mut self,
+        key: SortKey,
+        depth_only: impl FnOnce(&mut RenderPass<'a>) + 'a,
+        draw: impl FnOnce(&mut RenderPass<'a>) + 'a
+    ) {
+        let depth_only: Option<DrawFn<'a>> = match key.mode {
+            SortMode::Opaque => Some(Box::new(depth_only)),
+            SortMode::Transparent => None,
+        };
+        self.draws.push(QueuedDraw { key, draw: Box::new(draw), depth_only });
+    }
+
+    /// Queue the level of `mesh` that `lod` picks for an object at `center`,
+    /// after `setup` binds its pipeline and bind groups. Returns the submesh
+    /// drawn, or `None` when the object is past the last level and nothing is queued.
+    pub fn submit_lod<V>(
+        &mut self,
+        key: SortKey,
+        camera: &Camera,
+        center: [f32; 3],
+        mesh: &'a Mesh<V>,
+        lod: &MeshLod,
+        setup: impl FnOnce(&mut RenderPass<'a>) + 'a
+    ) -> Option<u32>
+        where V: bytemuck::Pod
+    {
+        let submesh = lod.select_for(camera, center)?;
+        self.submit(key, move |pass| {
+            setup(pass);
+            pass.draw_submesh(mesh, submesh as usize);
+        });
+        Some(submesh)
+    }
+
+    /// Number of queued draws
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// Number of queued draws with a depth-only version still to record
+    pub fn prepass_len(&self) -> usize {
+        self.draws
+            .iter()
+            .filter(|queued| queued.depth_only.is_some())
+            .count()
+    }
+
+    /// Queued keys in the order [`flush`](Self::flush) would record them
+    pub fn sorted_keys(&mut self) -> Vec<SortKey> {
+        self.sort();
+        self.draws
+            .iter()
+            .map(|queued| queued.key)
+            .collect()
+    }
+
+    /// Record the depth-only versions of the queued opaque draws, front-to-back
+    /// within each pipeline, into a pass that writes only depth. The draws stay
+    /// queued for [`flush`](Self::flush).
+    pub fn flush_prepass(&mut self, pass: &mut RenderPass<'a>) {
+        self.sort();
+        for queued in &mut self.draws {
+            if let Some(depth_only) = queued.depth_only.take() {
+                depth_only(pass);
+            }
+        }
+    }
+
+    /// Sort and record every queued draw into `pass`, leaving the queue empty
+    pub fn flush(&mut self, pass: &mut RenderPass<'a>) {
+        self.sort();
+        for queued in self.draws.drain(..) {
+            (queued.draw)(pass);
+        }
+    }
+
+    fn sort(&mut self) {
+        self.draws.sort_by(|a, b| a.key.compare(&b.key));
+    }
+}
+
+impl Default for DrawQueue<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{ GpuContext, RenderPass, Result, TypedBuffer };
+
+/// Vertex buffer for immediate-mode geometry rebuilt every frame.
+///
+/// Vertices are pushed on the CPU during the frame and
+/// [`upload`](Self::upload)ed once. The GPU side is a ring of buffers, one per
+/// frame that may still be in flight
+/// ([`GpuConfig::desired_maximum_frame_latency`](crate::GpuConfig) plus the
+/// one being recorded), so a frame never overwrites vertices an earlier frame
+/// is still drawing. A buffer that is too small is replaced by one at least
+/// twice its size.
+pub struct DynamicMesh<T> {
+    vertices: Vec<T>,
+    buffers: Vec<TypedBuffer<T>>,
+    current: usize,
+    vertex_count: u32,
+}
+
+impl<T> DynamicMesh<T> where T: bytemuck::Pod {
+    /// Create a mesh with room for `capacity` vertices before growing
+    pub fn new(context: &GpuContext, capacity: usize) -> Result<Self> {
+        let frames = (context.config.desired_maximum_frame_latency as usize).max(1) + 1;
+        let buffers = (0..frames)
+            .map(|_| Self::allocate(context, capacity.max(1)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            vertices: Vec::with_capacity(capacity),
+            buffers,
+            current: 0,
+            vertex_count: 0,
+        })
+    }
+
+    /// Append a vertex to this frame's geometry
+    pub fn push(&mut self, vertex: T) {
+        self.vertices.push(vertex);
+    }
+
+    /// Append several vertices to this frame's geometry
+    pub fn extend_from_slice(&mut self, vertices: &[T]) {
+        self.vertices.extend_from_slice(vertices);
+    }
+
+    /// Vertices pushed since the last upload
+    pub fn pending(&self) -> &[T] {
+        &self.vertices
+    }
+
+    /// Drop the vertices pushed since the last upload
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    /// Copy this frame's vertices into the next buffer of the ring, growing
+    /// it if needed, and start collecting the next frame
+    pub fn upload(&mut self, context: &GpuContext) -> Result<()> {
+        self.current = (self.current + 1) % self.buffers.len();
+        let buffer = &mut self.buffers[self.current];
+        if self.vertices.len() > buffer.len() {
+            let capacity = self.vertices.len().next_power_of_two().max(buffer.len() * 2);
+            *buffer = Self::allocate(context, capacity)?;
+        }
+        buffer.write(context, &self.vertices)?;
+        self.vertex_count = self.vertices.len() as u32;
+        self.vertices.clear();
+        Ok(())
+    }
+
+    /// Number of vertices written by the last upload
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    /// Vertices the buffer for the next upload holds without growing
+    pub fn capacity(&self) -> usize {
+        self.buffers[(self.current + 1) % self.buffers.len()].len()
+    }
+
+    /// The buffer holding the last upload
+    pub fn buffer(&self) -> &TypedBuffer<T> {
+        &self.buffers[self.current]
+    }
+
+    /// Bind the last upload to vertex slot 0 and draw it; the pipeline and
+    /// bind groups must already be set
+    pub fn draw<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        pass.set_vertex_buffer(0, self.buffer());
+        pass.draw(0..self.vertex_count, 0..1);
+    }
+
+    fn allocate(context: &GpuContext, capacity: usize) -> Result<TypedBuffer<T>> {
+        TypedBuffer::empty(
+            context,
+            capacity,
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST
+        )
+    }
+}
@@ -1,7 +1,9 @@
+use crate::ShaderDiagnostic;
 use std::fmt;
 
 /// Error types for Geepu operations
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum GeepuError {
     /// Failed to create wgpu adapter
     AdapterNotFound,
@@ -9,16 +11,46 @@ pub enum GeepuError {
     DeviceCreationFailed(wgpu::RequestDeviceError),
     /// Failed to create surface
     SurfaceCreationFailed,
-    /// Shader compilation error
-    ShaderError(String),
+    /// Shader compilation error with the offending source location
+    ShaderError(Box<ShaderDiagnostic>),
     /// Buffer creation error
     BufferError(String),
     /// Texture creation error
     TextureError(String),
     /// Pipeline creation error
     PipelineError(String),
+    /// Surface configuration or frame acquisition error
+    SurfaceError(String),
+    /// Compute dispatch or readback error
+    ComputeError(String),
+    /// An operation needs device features that were not enabled
+    MissingFeatures(wgpu::Features),
+    /// wgpu rejected an operation geepu performed
+    Validation {
+        message: String,
+        /// Label of the resource being created, if any
+        label: Option<String>,
+        /// The wgpu call that failed, e.g. `"create_texture"`
+        source_op: &'static str,
+    },
+    /// The device ran out of memory during an operation
+    OutOfMemory {
+        label: Option<String>,
+        source_op: &'static str,
+    },
+    /// Image decoding error
+    #[cfg(feature = "image")]
+    Image(image::ImageError),
+    /// glTF parsing or import error
+    #[cfg(feature = "gltf")]
+    Gltf(gltf::Error),
     /// Generic error with message
     Other(String),
+    /// An error annotated with what geepu was doing, see [`ResultExt::context`]
+    Context {
+        context: String,
+        source: Box<GeepuError>,
+    },
 }
 
 impl fmt::Display for GeepuError {
@@ -27,15 +59,128 @@ impl fmt::Display for GeepuError {
             GeepuError::AdapterNotFound => write!(f, "No suitable GPU adapter found"),
             GeepuError::DeviceCreationFailed(e) => write!(f, "Failed to create GPU device: {}", e),
             GeepuError::SurfaceCreationFailed => write!(f, "Failed to create rendering surface"),
-            GeepuError::ShaderError(msg) => write!(f, "Shader error: {}", msg),
+            GeepuError::ShaderError(diagnostic) => write!(f, "{}", diagnostic),
             GeepuError::BufferError(msg) => write!(f, "Buffer error: {}", msg),
             GeepuError::TextureError(msg) => write!(f, "Texture error: {}", msg),
             GeepuError::PipelineError(msg) => write!(f, "Pipeline error: {}", msg),
+            GeepuError::SurfaceError(msg) => write!(f, "Surface error: {}", msg),
+            GeepuError::ComputeError(msg) => write!(f, "Compute error: {}", msg),
+            GeepuError::MissingFeatures(features) =>
+                write!(f, "Missing device features: {:?}", features),
+            GeepuError::Validation { message, label, source_op } => {
+                write!(f, "Validation error in {}", source_op)?;
+                if let Some(label) = label {
+                    write!(f, " ({})", label)?;
+                }
+                write!(f, ": {}", message)
+            }
+            GeepuError::OutOfMemory { label, source_op } => {
+                write!(f, "Out of GPU memory in {}", source_op)?;
+                if let Some(label) = label {
+                    write!(f, " ({})", label)?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "image")]
+            GeepuError::Image(e) => write!(f, "Image error: {}", e),
+            #[cfg(feature = "gltf")]
+            GeepuError::Gltf(e) => write!(f, "glTF error: {}", e),
             GeepuError::Other(msg) => write!(f, "Error: {}", msg),
+            GeepuError::Context { context, source } => write!(f, "{}: {}", context, source),
         }
     }
 }
 
-impl std::error::Error for GeepuError {}
+impl std::error::Error for GeepuError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GeepuError::DeviceCreationFailed(e) => Some(e),
+            #[cfg(feature = "image")]
+            GeepuError::Image(e) => Some(e),
+            #[cfg(feature = "gltf")]
+            GeepuError::Gltf(e) => Some(e),
+            GeepuError::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl GeepuError {
+    /// The innermost error, past any [`Context`](GeepuError::Context) wrappers
+    pub fn root_cause(&self) -> &GeepuError {
+        match self {
+            GeepuError::Context { source, .. } => source.root_cause(),
+            _ => self,
+        }
+    }
+
+    /// Report a pipeline or buffer failure from compute work as
+    /// [`ComputeError`](GeepuError::ComputeError); validation, shader and memory errors pass
+    /// through with their fields intact
+    pub(crate) fn into_compute(self) -> Self {
+        match self {
+            GeepuError::PipelineError(message)
+            | GeepuError::BufferError(message) => GeepuError::ComputeError(message),
+            other => other,
+        }
+    }
+}
+
+/// Annotate errors with what was being done when they happened
+pub trait ResultExt<T> {
+    /// Wrap the error as `"<context>: <error>"`, e.g. `.context("creating shadow map")`
+    fn context(self, context: impl Into<String>) -> Result<T>;
+
+    /// Like [`context`](Self::context), building the message only on error
+    fn with_context<S>(self, context: impl FnOnce() -> S) -> Result<T> where S: Into<String>;
+}
+
+impl<T, E> ResultExt<T> for std::result::Result<T, E> where E: Into<GeepuError> {
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        self.map_err(|error| GeepuError::Context {
+            context: context.into(),
+            source: Box::new(error.into()),
+        })
+    }
+
+    fn with_context<S>(self, context: impl FnOnce() -> S) -> Result<T> where S: Into<String> {
+        self.map_err(|error| GeepuError::Context {
+            context: context().into(),
+            source: Box::new(error.into()),
+        })
+    }
+}
+
+impl GeepuError {
+    /// Wrap an error popped from a wgpu error scope
+    pub(crate) fn from_wgpu(
+        error: wgpu::Error,
+        label: Option<&str>,
+        source_op: &'static str
+    ) -> Self {
+        let label = label.map(str::to_string);
+        match error {
+            wgpu::Error::OutOfMemory { .. } => GeepuError::OutOfMemory { label, source_op },
+            | wgpu::Error::Validation { description, .. }
+            | wgpu::Error::Internal { description, .. } => {
+                GeepuError::Validation { message: description, label, source_op }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<image::ImageError> for GeepuError {
+    fn from(e: image::ImageError) -> Self {
+        GeepuError::Image(e)
+    }
+}
+
+#[cfg(feature = "gltf")]
+impl From<gltf::Error> for GeepuError {
+    fn from(e: gltf::Error) -> Self {
+        GeepuError::Gltf(e)
+    }
+}
 
 pub type Result<T> = std::result::Result<T, GeepuError>;
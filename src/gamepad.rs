@@ -0,0 +1,174 @@
+use crate::{ GeepuError, Result };
+use glam::Vec2;
+
+pub use gilrs::{ Axis as GamepadAxis, Button as GamepadButton, GamepadId };
+
+/// Connected gamepads through gilrs.
+///
+/// [`WindowConfig::run`](crate::WindowConfig::run) polls them once per frame
+/// and hands them to the app as [`Frame::gamepads`](crate::Frame::gamepads);
+/// outside the run loop call [`update`](Self::update) once per frame yourself.
+/// Stick and trigger values have the deadzones applied and are rescaled so
+/// they still cover the full 0..1 range past the deadzone.
+pub struct Gamepads {
+    gilrs: gilrs::Gilrs,
+    /// Stick deflection below which a stick reads as centered, applied to
+    /// the stick's length so diagonals are not clipped (0.15 by default)
+    pub stick_deadzone: f32,
+    /// Trigger travel below which a trigger reads as released (0.05 by default)
+    pub trigger_deadzone: f32,
+}
+
+impl Gamepads {
+    /// Start listening for gamepads. On platforms gilrs does not support this
+    /// succeeds without ever reporting a gamepad.
+    pub fn new() -> Result<Self> {
+        // The deadzones below replace gilrs' per-axis deadzone filter
+        let gilrs = match gilrs::GilrsBuilder::new().with_default_filters(false).build() {
+            Ok(gilrs) => gilrs,
+            Err(gilrs::Error::NotImplemented(dummy)) => {
+                log::warn!("Gamepads are not supported on this platform");
+                dummy
+            }
+            Err(e) => {
+                return Err(GeepuError::Other(format!("Failed to initialize gamepads: {}", e)));
+            }
+        };
+        Ok(Self { gilrs, stick_deadzone: 0.15, trigger_deadzone: 0.05 })
+    }
+
+    /// Set both deadzones, see [`stick_deadzone`](Self::stick_deadzone)
+    pub fn with_deadzones(mut self, stick: f32, trigger: f32) -> Self {
+        self.stick_deadzone = stick;
+        self.trigger_deadzone = trigger;
+        self
+    }
+
+    /// Apply the input received since the last call and start a new frame for
+    /// [`Gamepad::just_pressed`] and [`Gamepad::just_released`]
+    pub fn update(&mut self) {
+        self.gilrs.inc();
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                gilrs::EventType::Connected => {
+                    log::info!("Gamepad connected: {}", self.gilrs.gamepad(event.id).name());
+                }
+                gilrs::EventType::Disconnected => {
+                    log::info!("Gamepad {} disconnected", event.id);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// All connected gamepads
+    pub fn iter(&self) -> impl Iterator<Item = Gamepad<'_>> + '_ {
+        self.gilrs.gamepads().map(|(_, gamepad)| self.wrap(gamepad))
+    }
+
+    /// A connected gamepad by id
+    pub fn get(&self, id: GamepadId) -> Option<Gamepad<'_>> {
+        self.gilrs.connected_gamepad(id).map(|gamepad| self.wrap(gamepad))
+    }
+
+    /// The first connected gamepad, for single-player input
+    pub fn first(&self) -> Option<Gamepad<'_>> {
+        self.iter().next()
+    }
+
+    fn wrap<'a>(&'a self, gamepad: gilrs::Gamepad<'a>) -> Gamepad<'a> {
+        Gamepad {
+            gamepad,
+            frame: self.gilrs.counter(),
+            stick_deadzone: self.stick_deadzone,
+            trigger_deadzone: self.trigger_deadzone,
+        }
+    }
+}
+
+/// State of one connected gamepad, see [`Gamepads`]
+pub struct Gamepad<'a> {
+    gamepad: gilrs::Gamepad<'a>,
+    frame: u64,
+    stick_deadzone: f32,
+    trigger_deadzone: f32,
+}
+
+impl Gamepad<'_> {
+    pub fn id(&self) -> GamepadId {
+        self.gamepad.id()
+    }
+
+    /// Name reported by the device or its mapping
+    pub fn name(&self) -> &str {
+        self.gamepad.name()
+    }
+
+    /// Left stick in -1..1 on both axes, +y up
+    pub fn left_stick(&self) -> Vec2 {
+        self.stick(GamepadAxis::LeftStickX, GamepadAxis::LeftStickY)
+    }
+
+    /// Right stick in -1..1 on both axes, +y up
+    pub fn right_stick(&self) -> Vec2 {
+        self.stick(GamepadAxis::RightStickX, GamepadAxis::RightStickY)
+    }
+
+    /// Left analog trigger in 0..1
+    pub fn left_trigger(&self) -> f32 {
+        self.trigger(GamepadButton::LeftTrigger2)
+    }
+
+    /// Right analog trigger in 0..1
+    pub fn right_trigger(&self) -> f32 {
+        self.trigger(GamepadButton::RightTrigger2)
+    }
+
+    /// Whether `button` is held down
+    pub fn pressed(&self, button: GamepadButton) -> bool {
+        self.gamepad.is_pressed(button)
+    }
+
+    /// Whether `button` went down since the last [`Gamepads::update`]
+    pub fn just_pressed(&self, button: GamepadButton) -> bool {
+        self.changed(button) && self.pressed(button)
+    }
+
+    /// Whether `button` was let go since the last [`Gamepads::update`]
+    pub fn just_released(&self, button: GamepadButton) -> bool {
+        self.changed(button) && !self.pressed(button)
+    }
+
+    fn changed(&self, button: GamepadButton) -> bool {
+        self.gamepad.button_data(button).is_some_and(|data| data.counter() == self.frame)
+    }
+
+    fn stick(&self, x: GamepadAxis, y: GamepadAxis) -> Vec2 {
+        let raw = Vec2::new(self.gamepad.value(x), self.gamepad.value(y));
+        stick_deadzone(raw, self.stick_deadzone)
+    }
+
+    fn trigger(&self, button: GamepadButton) -> f32 {
+        let raw = self.gamepad.button_data(button).map_or(0.0, |data| data.value());
+        trigger_deadzone(raw, self.trigger_deadzone)
+    }
+}
+
+/// Zero `stick` inside a radial `deadzone` and rescale the rest so its length
+/// still reaches 1, keeping the direction
+pub fn stick_deadzone(stick: Vec2, deadzone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= deadzone {
+        return Vec2::ZERO;
+    }
+    let scaled = ((length - deadzone) / (1.0 - deadzone)).min(1.0);
+    stick * (scaled / length)
+}
+
+/// Zero `value` below `deadzone` and rescale the rest to 0..1
+pub fn trigger_deadzone(value: f32, deadzone: f32) -> f32 {
+    if value <= deadzone {
+        return 0.0;
+    }
+    ((value - deadzone) / (1.0 - deadzone)).min(1.0)
+}
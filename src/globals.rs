@@ -0,0 +1,123 @@
+use crate::{ BindGroupBuilder, BindGroupLayoutBuilder, GpuContext, Result, TypedBuffer };
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Per-frame values as laid out in a WGSL uniform block
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GlobalsUniform {
+    /// Seconds since the first update
+    pub time: f32,
+    /// Seconds since the previous update
+    pub delta: f32,
+    /// Number of updates before this one
+    pub frame: u32,
+    pub _padding: u32,
+    /// Render target size in pixels
+    pub resolution: [f32; 2],
+    /// Cursor position in pixels from the top-left corner
+    pub mouse: [f32; 2],
+}
+
+/// Time, resolution and mouse position shared by every procedural shader.
+///
+/// Call [`update`](Self::update) once per frame with the frame time, and bind
+/// [`bind_group`](Self::bind_group) (a single uniform at binding 0, visible to
+/// all stages) to any pipeline whose layout includes [`layout`](Self::layout).
+/// Shaders declare it with [`FrameGlobals::WGSL`]:
+///
+/// ```wgsl
+/// @group(0) @binding(0) var<uniform> globals: Globals;
+/// ```
+pub struct FrameGlobals {
+    uniform: GlobalsUniform,
+    buffer: TypedBuffer<GlobalsUniform>,
+    layout: Arc<wgpu::BindGroupLayout>,
+    bind_group: wgpu::BindGroup,
+    started: bool,
+}
+
+impl FrameGlobals {
+    /// WGSL declaration matching [`GlobalsUniform`]
+    pub const WGSL: &'static str =
+        r#"
+struct Globals {
+    time: f32,
+    delta: f32,
+    frame: u32,
+    _padding: u32,
+    resolution: vec2<f32>,
+    mouse: vec2<f32>,
+}
+"#;
+
+    pub fn new(context: &GpuContext) -> Result<Self> {
+        let (width, height) = context.size();
+        let uniform = GlobalsUniform {
+            resolution: [width as f32, height as f32],
+            ..Default::default()
+        };
+        let buffer = TypedBuffer::uniform(context, &[uniform])?;
+        let layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::all())
+            .build(context, Some("Frame Globals"));
+        let bind_group = BindGroupBuilder::new(&layout)
+            .buffer(0, buffer.buffer())
+            .build(context, Some("Frame Globals"));
+        Ok(Self { uniform, buffer, layout, bind_group, started: false })
+    }
+
+    /// Advance the clock by `delta`, pick up the context's current size and
+    /// upload the values for this frame
+    pub fn update(&mut self, context: &GpuContext, delta: Duration) -> Result<()> {
+        self.update_with_resolution(context, delta, context.size())
+    }
+
+    /// [`update`](Self::update) for a render target other than the surface
+    pub fn update_with_resolution(
+        &mut self,
+        context: &GpuContext,
+        delta: Duration,
+        resolution: (u32, u32)
+    ) -> Result<()> {
+        // The first frame starts the clock rather than advancing it
+        if self.started {
+            self.uniform.delta = delta.as_secs_f32();
+            self.uniform.time += self.uniform.delta;
+            self.uniform.frame += 1;
+        }
+        self.started = true;
+        self.uniform.resolution = [resolution.0 as f32, resolution.1 as f32];
+        self.buffer.write(context, &[self.uniform])
+    }
+
+    /// Set the cursor position in pixels, uploaded by the next update
+    pub fn set_mouse(&mut self, x: f32, y: f32) {
+        self.uniform.mouse = [x, y];
+    }
+
+    /// Track the cursor from `CursorMoved` events
+    #[cfg(feature = "window")]
+    pub fn handle_event(&mut self, event: &winit::event::WindowEvent) {
+        if let winit::event::WindowEvent::CursorMoved { position, .. } = event {
+            self.set_mouse(position.x as f32, position.y as f32);
+        }
+    }
+
+    /// The values uploaded by the last update
+    pub fn uniform(&self) -> &GlobalsUniform {
+        &self.uniform
+    }
+
+    pub fn buffer(&self) -> &TypedBuffer<GlobalsUniform> {
+        &self.buffer
+    }
+
+    pub fn layout(&self) -> &Arc<wgpu::BindGroupLayout> {
+        &self.layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
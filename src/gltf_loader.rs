@@ -0,0 +1,708 @@
+use crate::{
+    AnimationChannel,
+    AnimationClip,
+    ChannelValues,
+    GeepuError,
+    GpuContext,
+    Interpolation,
+    Joint,
+    JointBuffer,
+    JointTransform,
+    Material,
+    MaterialDraw,
+    MaterialShader,
+    Mesh,
+    MeshData,
+    MeshVertex,
+    PbrParams,
+    Result,
+    Skeleton,
+    SkinWeights,
+    Texture,
+    TypedBuffer,
+};
+use glam::Mat4;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A glTF 2.0 scene uploaded into geepu meshes, PBR materials and textures.
+///
+/// Only triangle-list primitives are imported; normal maps and additional UV
+/// sets are ignored. Primitives without normals get smooth normals generated
+/// from their triangles. Skins and joint animations are imported for GPU
+/// skinning; morph targets are not.
+pub struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    /// Materials by glTF index, followed by the default material and skinned
+    /// variants as primitives need them
+    pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
+    /// Every node in the document, indexed as in the file
+    pub nodes: Vec<GltfNode>,
+    /// Root nodes of the imported scene
+    pub roots: Vec<usize>,
+    pub skins: Vec<GltfSkin>,
+    /// Animations with channels targeting node indices (see [`GltfSkin::animation`])
+    pub animations: Vec<AnimationClip>,
+}
+
+/// A glTF mesh: one geepu mesh per primitive
+pub struct GltfMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+pub struct GltfPrimitive {
+    pub mesh: Mesh<MeshVertex>,
+    /// Index into [`GltfScene::materials`]
+    pub material: usize,
+    /// Joint weights for skinned primitives, drawn in vertex slot 1
+    pub skin_weights: Option<TypedBuffer<SkinWeights>>,
+}
+
+/// A glTF skin: a skeleton whose joints are scene nodes
+pub struct GltfSkin {
+    pub name: Option<String>,
+    pub skeleton: Skeleton,
+    /// Node index of each joint
+    pub joint_nodes: Vec<usize>,
+}
+
+impl GltfSkin {
+    /// `animation` with its node channels retargeted to this skin's joints
+    pub fn animation(&self, animation: &AnimationClip) -> AnimationClip {
+        animation.retarget(|node| self.joint_nodes.iter().position(|&joint| joint == node))
+    }
+}
+
+/// A node in the scene hierarchy
+#[derive(Debug, Clone)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Transform relative to the parent
+    pub transform: [[f32; 4]; 4],
+    /// Transform relative to the scene root
+    pub world_transform: [[f32; 4]; 4],
+    /// Index into [`GltfScene::meshes`]
+    pub mesh: Option<usize>,
+    /// Index into [`GltfScene::skins`]
+    pub skin: Option<usize>,
+}
+
+impl GltfScene {
+    /// Import a `.gltf` or `.glb` file, resolving external buffers and images relative to it
+    pub fn load(context: &GpuContext, path: impl AsRef<Path>) -> Result<Self> {
+        let (document, buffers, images) = gltf::import(path)?;
+        Self::from_document(context, &document, &buffers, &images)
+    }
+
+    /// Import a glTF document from memory (typically a `.glb` with embedded data)
+    pub fn from_slice(context: &GpuContext, bytes: &[u8]) -> Result<Self> {
+        let (document, buffers, images) = gltf::import_slice(bytes)?;
+        Self::from_document(context, &document, &buffers, &images)
+    }
+
+    /// Draws for every rigid mesh instance in the scene at its world transform;
+    /// skinned primitives are left to [`skinned_draws`](Self::skinned_draws)
+    pub fn draws(&self) -> Vec<MaterialDraw<'_, MeshVertex>> {
+        self.nodes
+            .iter()
+            .filter_map(|node| node.mesh.map(|mesh| (node, &self.meshes[mesh])))
+            .flat_map(|(node, mesh)| {
+                mesh.primitives
+                    .iter()
+                    .filter(|primitive| primitive.skin_weights.is_none())
+                    .map(|primitive| {
+                        MaterialDraw::new(
+                            &primitive.mesh,
+                            &self.materials[primitive.material],
+                            node.world_transform
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// Draws for every skinned mesh instance, with `joints[i]` holding the
+    /// matrices of [`skins[i]`](Self::skins)
+    pub fn skinned_draws<'a>(
+        &'a self,
+        joints: &'a [JointBuffer]
+    ) -> Vec<MaterialDraw<'a, MeshVertex>> {
+        let identity = Mat4::IDENTITY.to_cols_array_2d();
+        self.nodes
+            .iter()
+            .filter_map(|node| {
+                let joints = joints.get(node.skin?)?;
+                Some((&self.meshes[node.mesh?], joints))
+            })
+            .flat_map(|(mesh, joints)| {
+                mesh.primitives
+                    .iter()
+                    .filter_map(move |primitive| {
+                        let weights = primitive.skin_weights.as_ref()?;
+                        let material = &self.materials[primitive.material];
+                        let draw = MaterialDraw::new(&primitive.mesh, material, identity);
+                        Some(draw.skinned(weights, joints))
+                    })
+            })
+            .collect()
+    }
+
+    /// One identity-initialized [`JointBuffer`] per skin, for
+    /// [`skinned_draws`](Self::skinned_draws)
+    pub fn create_joint_buffers(&self, context: &GpuContext) -> Result<Vec<JointBuffer>> {
+        self.skins
+            .iter()
+            .map(|skin| JointBuffer::new(context, skin.skeleton.joints.len()))
+            .collect()
+    }
+
+    pub(crate) fn from_document(
+        context: &GpuContext,
+        document: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        images: &[gltf::image::Data]
+    ) -> Result<Self> {
+        let mut textures = TextureCache::new(context, images)?;
+        let mut shaders = ShaderVariants::default();
+
+        let mut materials = Vec::new();
+        let mut material_indices = HashMap::new();
+        for material in document.materials() {
+            materials.push(
+                create_material(context, &material, false, &mut shaders, &mut textures)?
+            );
+            material_indices.insert((material.index(), false), materials.len() - 1);
+        }
+
+        let mut meshes = Vec::new();
+        for mesh in document.meshes() {
+            let mut primitives = Vec::new();
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    continue;
+                }
+                let mut data = read_primitive(&primitive, buffers)?;
+                let mut skin_weights = read_skin_weights(&primitive, buffers);
+                optimize_primitive(&mut data, &mut skin_weights);
+                let skin_weights = skin_weights
+                    .map(|weights| {
+                        TypedBuffer::new(context, &weights, wgpu::BufferUsages::VERTEX)
+                    })
+                    .transpose()?;
+                // The default material and skinned variants are created on first use
+                let key = (primitive.material().index(), skin_weights.is_some());
+                let material = match material_indices.get(&key) {
+                    Some(&index) => index,
+                    None => {
+                        let material = primitive.material();
+                        materials.push(
+                            create_material(context, &material, key.1, &mut shaders, &mut textures)?
+                        );
+                        *material_indices.entry(key).or_insert(materials.len() - 1)
+                    }
+                };
+                primitives.push(GltfPrimitive {
+                    mesh: data.upload(context, MeshVertex::layout())?,
+                    material,
+                    skin_weights,
+                });
+            }
+            meshes.push(GltfMesh {
+                name: mesh.name().map(str::to_string),
+                primitives,
+            });
+        }
+
+        let mut nodes: Vec<GltfNode> = document
+            .nodes()
+            .map(|node| GltfNode {
+                name: node.name().map(str::to_string),
+                parent: None,
+                children: node
+                    .children()
+                    .map(|child| child.index())
+                    .collect(),
+                transform: node.transform().matrix(),
+                world_transform: node.transform().matrix(),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                skin: node.skin().map(|skin| skin.index()),
+            })
+            .collect();
+
+        let roots: Vec<usize> = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .map(|scene| {
+                scene
+                    .nodes()
+                    .map(|node| node.index())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for &root in &roots {
+            propagate_transforms(&mut nodes, root, None, Mat4::IDENTITY);
+        }
+
+        let skins = document
+            .skins()
+            .map(|skin| read_skin(&skin, buffers, &nodes))
+            .collect();
+        let animations = document
+            .animations()
+            .map(|animation| read_animation(&animation, buffers))
+            .collect();
+
+        Ok(Self {
+            meshes,
+            materials,
+            textures: textures.textures,
+            nodes,
+            roots,
+            skins,
+            animations,
+        })
+    }
+}
+
+/// Set parent links and world transforms below `index`
+fn propagate_transforms(
+    nodes: &mut [GltfNode],
+    index: usize,
+    parent: Option<usize>,
+    parent_world: Mat4
+) {
+    let world = parent_world * Mat4::from_cols_array_2d(&nodes[index].transform);
+    nodes[index].parent = parent;
+    nodes[index].world_transform = world.to_cols_array_2d();
+    for child in nodes[index].children.clone() {
+        propagate_transforms(nodes, child, Some(index), world);
+    }
+}
+
+fn read_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data]
+) -> Result<MeshData<MeshVertex>> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .ok_or_else(|| GeepuError::BufferError("glTF primitive has no positions".to_string()))?
+        .collect();
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|normals| normals.collect());
+    let uvs: Option<Vec<[f32; 2]>> = reader
+        .read_tex_coords(0)
+        .map(|uvs| uvs.into_f32().collect());
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    let mut data = MeshData {
+        vertices: positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| {
+                MeshVertex::new(
+                    position,
+                    normals.as_ref().map_or([0.0; 3], |normals| normals[i]),
+                    uvs.as_ref().map_or([0.0; 2], |uvs| uvs[i])
+                )
+            })
+            .collect(),
+        indices,
+    };
+    if normals.is_none() {
+        generate_normals(&mut data);
+    }
+    Ok(data)
+}
+
+/// Reorder a primitive for the vertex cache and fetch locality when the
+/// `optimize-meshes` feature is on, keeping its skin weights in step
+fn optimize_primitive(
+    data: &mut MeshData<MeshVertex>,
+    skin_weights: &mut Option<Vec<SkinWeights>>
+) {
+    #[cfg(feature = "optimize-meshes")]
+    {
+        let order = data.optimize();
+        if let Some(weights) = skin_weights {
+            *weights = order
+                .iter()
+                .map(|&index| weights[index as usize])
+                .collect();
+        }
+    }
+    #[cfg(not(feature = "optimize-meshes"))]
+    let _ = (data, skin_weights);
+}
+
+/// First joint/weight set of a skinned primitive
+fn read_skin_weights(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data]
+) -> Option<Vec<SkinWeights>> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    let joints = reader.read_joints(0)?.into_u16();
+    let weights = reader.read_weights(0)?.into_f32();
+    Some(
+        joints
+            .zip(weights)
+            .map(|(joints, weights)| SkinWeights::new(joints.map(u32::from), weights))
+            .collect()
+    )
+}
+
+fn read_skin(skin: &gltf::Skin, buffers: &[gltf::buffer::Data], nodes: &[GltfNode]) -> GltfSkin {
+    let reader = skin.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    let inverse_binds: Vec<[[f32; 4]; 4]> = reader
+        .read_inverse_bind_matrices()
+        .map(|matrices| matrices.collect())
+        .unwrap_or_default();
+    let joint_nodes: Vec<usize> = skin
+        .joints()
+        .map(|joint| joint.index())
+        .collect();
+
+    let joints = skin
+        .joints()
+        .enumerate()
+        .map(|(i, node)| {
+            let (translation, rotation, scale) = node.transform().decomposed();
+            let parent = nodes[node.index()].parent;
+            Joint {
+                name: node.name().map(str::to_string),
+                parent: parent.and_then(|parent| joint_nodes.iter().position(|&n| n == parent)),
+                rest: JointTransform::new(translation, rotation, scale),
+                inverse_bind: inverse_binds
+                    .get(i)
+                    .copied()
+                    .unwrap_or(Mat4::IDENTITY.to_cols_array_2d()),
+            }
+        })
+        .collect();
+
+    // Joint transforms are relative to the scene, so carry whatever sits above the root joint
+    let mut skeleton = Skeleton::new(joints);
+    let root = skeleton.joints.iter().position(|joint| joint.parent.is_none());
+    if let Some(parent) = root.and_then(|root| nodes[joint_nodes[root]].parent) {
+        skeleton.root_transform = nodes[parent].world_transform;
+    }
+
+    GltfSkin {
+        name: skin.name().map(str::to_string),
+        skeleton,
+        joint_nodes,
+    }
+}
+
+fn read_animation(animation: &gltf::Animation, buffers: &[gltf::buffer::Data]) -> AnimationClip {
+    use gltf::animation::{ util::ReadOutputs, Interpolation as GltfInterpolation };
+
+    let channels = animation
+        .channels()
+        .filter_map(|channel| {
+            let reader = channel.reader(|buffer| {
+                buffers.get(buffer.index()).map(|data| &data.0[..])
+            });
+            let times: Vec<f32> = reader.read_inputs()?.collect();
+            // Cubic spline keys store (in-tangent, value, out-tangent); keep the values
+            // and interpolate them linearly
+            let cubic = channel.sampler().interpolation() == GltfInterpolation::CubicSpline;
+            let interpolation = match channel.sampler().interpolation() {
+                GltfInterpolation::Step => Interpolation::Step,
+                _ => Interpolation::Linear,
+            };
+            let values = match reader.read_outputs()? {
+                ReadOutputs::Translations(values) =>
+                    ChannelValues::Translation(spline_values(values.collect(), cubic)),
+                ReadOutputs::Rotations(values) =>
+                    ChannelValues::Rotation(spline_values(values.into_f32().collect(), cubic)),
+                ReadOutputs::Scales(values) =>
+                    ChannelValues::Scale(spline_values(values.collect(), cubic)),
+                ReadOutputs::MorphTargetWeights(_) => {
+                    return None;
+                }
+            };
+            Some(AnimationChannel {
+                target: channel.target().node().index(),
+                times,
+                values,
+                interpolation,
+            })
+        })
+        .collect();
+    AnimationClip::new(animation.name().map(str::to_string), channels)
+}
+
+fn spline_values<T: Copy>(values: Vec<T>, cubic: bool) -> Vec<T> {
+    if cubic {
+        values
+            .chunks_exact(3)
+            .map(|key| key[1])
+            .collect()
+    } else {
+        values
+    }
+}
+
+/// Smooth vertex normals from area-weighted face normals
+fn generate_normals(data: &mut MeshData<MeshVertex>) {
+    let mut normals = vec![glam::Vec3::ZERO; data.vertices.len()];
+    for triangle in data.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let [pa, pb, pc] = [a, b, c].map(|i| glam::Vec3::from(data.vertices[i].position));
+        let face = (pb - pa).cross(pc - pa);
+        for i in [a, b, c] {
+            normals[i] += face;
+        }
+    }
+    for (vertex, normal) in data.vertices.iter_mut().zip(normals) {
+        vertex.normal = normal.normalize_or(glam::Vec3::Y).to_array();
+    }
+}
+
+/// The PBR shader variants a glTF file can need, created on first use
+#[derive(Default)]
+struct ShaderVariants {
+    variants: HashMap<(bool, bool, bool), Arc<MaterialShader>>,
+}
+
+impl ShaderVariants {
+    fn get(&mut self, blend: bool, double_sided: bool, skinned: bool) -> Arc<MaterialShader> {
+        self.variants
+            .entry((blend, double_sided, skinned))
+            .or_insert_with(|| {
+                let mut shader = if skinned {
+                    MaterialShader::pbr_skinned()
+                } else {
+                    MaterialShader::pbr()
+                };
+                if blend {
+                    shader = shader.blend(Some(wgpu::BlendState::ALPHA_BLENDING)).depth_write(false);
+                }
+                if double_sided {
+                    shader = shader.cull_mode(None);
+                }
+                Arc::new(shader)
+            })
+            .clone()
+    }
+}
+
+fn create_material(
+    context: &GpuContext,
+    material: &gltf::Material,
+    skinned: bool,
+    shaders: &mut ShaderVariants,
+    textures: &mut TextureCache
+) -> Result<Material> {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b] = material.emissive_factor();
+    let params = PbrParams {
+        base_color: pbr.base_color_factor(),
+        emissive: [r, g, b, 0.0],
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        occlusion_strength: material.occlusion_texture().map_or(1.0, |t| t.strength()),
+        alpha_cutoff: match material.alpha_mode() {
+            gltf::material::AlphaMode::Mask => material.alpha_cutoff().unwrap_or(0.5),
+            _ => 0.0,
+        },
+    };
+
+    let base_color = textures.get(
+        context,
+        pbr.base_color_texture().map(|info| info.texture()),
+        true
+    )?;
+    let metallic_roughness = textures.get(
+        context,
+        pbr.metallic_roughness_texture().map(|info| info.texture()),
+        false
+    )?;
+    let occlusion = textures.get(
+        context,
+        material.occlusion_texture().map(|info| info.texture()),
+        false
+    )?;
+    let emissive = textures.get(
+        context,
+        material.emissive_texture().map(|info| info.texture()),
+        true
+    )?;
+
+    let shader = shaders.get(
+        material.alpha_mode() == gltf::material::AlphaMode::Blend,
+        material.double_sided(),
+        skinned
+    );
+    let textures = &textures.textures;
+    Material::new(
+        context,
+        shader,
+        &params,
+        &[
+            &textures[base_color],
+            &textures[metallic_roughness],
+            &textures[occlusion],
+            &textures[emissive],
+        ]
+    )
+}
+
+/// Uploads each (image, sampler, color space) combination once
+struct TextureCache<'a> {
+    images: &'a [gltf::image::Data],
+    textures: Vec<Texture>,
+    cache: HashMap<(usize, Option<usize>, bool), usize>,
+    white_srgb: usize,
+    white_linear: usize,
+}
+
+impl<'a> TextureCache<'a> {
+    fn new(context: &GpuContext, images: &'a [gltf::image::Data]) -> Result<Self> {
+        let white = [255u8; 4];
+        let textures = vec![
+            Texture::from_bytes(
+                context,
+                &white,
+                1,
+                1,
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+                Some("glTF White sRGB")
+            )?,
+            Texture::from_bytes(
+                context,
+                &white,
+                1,
+                1,
+                wgpu::TextureFormat::Rgba8Unorm,
+                Some("glTF White Linear")
+            )?
+        ];
+        Ok(Self {
+            images,
+            textures,
+            cache: HashMap::new(),
+            white_srgb: 0,
+            white_linear: 1,
+        })
+    }
+
+    /// Index of the texture for `texture`, or a white placeholder when absent
+    fn get(
+        &mut self,
+        context: &GpuContext,
+        texture: Option<gltf::Texture>,
+        srgb: bool
+    ) -> Result<usize> {
+        let Some(texture) = texture else {
+            return Ok(if srgb { self.white_srgb } else { self.white_linear });
+        };
+
+        let image = texture.source().index();
+        let sampler = texture.sampler();
+        let key = (image, sampler.index(), srgb);
+        if let Some(&index) = self.cache.get(&key) {
+            return Ok(index);
+        }
+
+        let data = self.images
+            .get(image)
+            .ok_or_else(|| GeepuError::TextureError(format!("glTF image {} is missing", image)))?;
+        let pixels = to_rgba8(data)?;
+        let format = if srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        let mut uploaded = Texture::from_bytes(
+            context,
+            &pixels,
+            data.width,
+            data.height,
+            format,
+            texture.source().name()
+        )?;
+        uploaded.sampler = context.device.create_sampler(&sampler_descriptor(&sampler));
+
+        self.textures.push(uploaded);
+        let index = self.textures.len() - 1;
+        self.cache.insert(key, index);
+        Ok(index)
+    }
+}
+
+fn sampler_descriptor(sampler: &gltf::texture::Sampler) -> wgpu::SamplerDescriptor<'static> {
+    use gltf::texture::{ MagFilter, MinFilter, WrappingMode };
+
+    let address_mode = |mode: WrappingMode| {
+        match mode {
+            WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+            WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+        }
+    };
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+        _ => wgpu::FilterMode::Linear,
+    };
+    let min_filter = match sampler.min_filter() {
+        Some(MinFilter::Nearest | MinFilter::NearestMipmapNearest | MinFilter::NearestMipmapLinear) =>
+            wgpu::FilterMode::Nearest,
+        _ => wgpu::FilterMode::Linear,
+    };
+
+    wgpu::SamplerDescriptor {
+        label: Some("glTF Sampler"),
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        mag_filter,
+        min_filter,
+        ..Default::default()
+    }
+}
+
+/// Expand a decoded glTF image to tightly packed RGBA8
+fn to_rgba8(image: &gltf::image::Data) -> Result<Vec<u8>> {
+    use gltf::image::Format;
+
+    let (channels, bytes_per_channel) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        format => {
+            return Err(
+                GeepuError::TextureError(format!("Unsupported glTF image format {:?}", format))
+            );
+        }
+    };
+
+    let pixel_size = channels * bytes_per_channel;
+    let mut rgba = Vec::with_capacity((image.width * image.height * 4) as usize);
+    for pixel in image.pixels.chunks_exact(pixel_size) {
+        // 16-bit channels are little endian; keep the most significant byte
+        let channel = |i: usize| pixel[i * bytes_per_channel + bytes_per_channel - 1];
+        let rgba_pixel = match channels {
+            1 => [channel(0), channel(0), channel(0), 255],
+            2 => [channel(0), channel(0), channel(0), channel(1)],
+            3 => [channel(0), channel(1), channel(2), 255],
+            _ => [channel(0), channel(1), channel(2), channel(3)],
+        };
+        rgba.extend_from_slice(&rgba_pixel);
+    }
+    Ok(rgba)
+}
@@ -0,0 +1,154 @@
+use crate::{
+    max_mip_levels,
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    ComputePass,
+    ComputePipeline,
+    GeepuError,
+    GpuContext,
+    MipFilter,
+    MipGenerator,
+    Result,
+    Texture,
+    TextureBuilder,
+};
+use std::sync::Arc;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// A hierarchical-Z pyramid: an `R32Float` copy of a depth buffer whose mips
+/// hold the farthest depth of the texels they cover.
+///
+/// [`build`](Self::build) copies a `Depth32Float` depth buffer of the same
+/// size into level 0 and reduces the rest with a [`MipGenerator`]. Depth is
+/// expected in the usual 0 (near) to 1 (far) convention. Feed it to
+/// [`GpuCuller::cull_occluded`](crate::GpuCuller::cull_occluded), usually
+/// built from the previous frame's depth or a depth pre-pass.
+pub struct HiZBuffer {
+    texture: Texture,
+    layout: Arc<wgpu::BindGroupLayout>,
+    pipeline: ComputePipeline,
+    mips: MipGenerator,
+}
+
+impl HiZBuffer {
+    pub fn new(context: &GpuContext, width: u32, height: u32) -> Result<Self> {
+        let layout = BindGroupLayoutBuilder::new()
+            .texture(
+                0,
+                wgpu::ShaderStages::COMPUTE,
+                wgpu::TextureSampleType::Depth,
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .storage_texture(
+                1,
+                wgpu::ShaderStages::COMPUTE,
+                wgpu::StorageTextureAccess::WriteOnly,
+                wgpu::TextureFormat::R32Float,
+                wgpu::TextureViewDimension::D2
+            )
+            .build(context, Some("Hi-Z Seed Layout"));
+        let pipeline = ComputePipeline::new(
+            context,
+            SEED_SHADER,
+            vec![layout.clone()],
+            Some("Hi-Z Seed Pipeline")
+        )?;
+        Ok(Self {
+            texture: Self::create_texture(context, width, height)?,
+            layout,
+            pipeline,
+            mips: MipGenerator::new(context, wgpu::TextureFormat::R32Float, MipFilter::Max)?,
+        })
+    }
+
+    fn create_texture(context: &GpuContext, width: u32, height: u32) -> Result<Texture> {
+        TextureBuilder::new(width, height)
+            .format(wgpu::TextureFormat::R32Float)
+            .usage(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING)
+            .mip_levels(max_mip_levels(width, height))
+            .label("Hi-Z Buffer")
+            .build(context)
+    }
+
+    /// Recreate the pyramid for a depth buffer of a new size
+    pub fn resize(&mut self, context: &GpuContext, width: u32, height: u32) -> Result<()> {
+        if self.size() != (width, height) {
+            self.texture = Self::create_texture(context, width, height)?;
+        }
+        Ok(())
+    }
+
+    /// Record the passes rebuilding the pyramid from `depth`, a single-sampled
+    /// `Depth32Float` texture with `TEXTURE_BINDING` usage and the pyramid's size
+    pub fn build(
+        &self,
+        context: &GpuContext,
+        encoder: &mut wgpu::CommandEncoder,
+        depth: &Texture
+    ) -> Result<()> {
+        let format = depth.format();
+        if format != wgpu::TextureFormat::Depth32Float || depth.texture.sample_count() != 1 {
+            return Err(
+                GeepuError::TextureError(
+                    "Hi-Z needs a single-sampled Depth32Float depth buffer".to_string()
+                )
+            );
+        }
+        if depth.size() != self.size() {
+            return Err(
+                GeepuError::TextureError(
+                    format!("Depth buffer is {:?}, Hi-Z buffer is {:?}", depth.size(), self.size())
+                )
+            );
+        }
+
+        let level0 = self.texture.mip_view(0);
+        let bind_group = BindGroupBuilder::new(&self.layout)
+            .texture_view(0, &depth.view)
+            .texture_view(1, &level0)
+            .build(context, Some("Hi-Z Seed Bind Group"));
+        {
+            let mut pass = ComputePass::new(encoder, Some(&context.label("Hi-Z Seed")));
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            let (width, height) = self.size();
+            pass.dispatch_workgroups(
+                width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1
+            );
+        }
+        self.mips.generate(context, encoder, &self.texture)
+    }
+
+    /// The pyramid, with every mip level
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Size of level 0
+    pub fn size(&self) -> (u32, u32) {
+        self.texture.size()
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.texture.mip_level_count()
+    }
+}
+
+const SEED_SHADER: &str =
+    r#"
+@group(0) @binding(0) var depth: texture_depth_2d;
+@group(0) @binding(1) var destination: texture_storage_2d<r32float, write>;
+
+@compute @workgroup_size(8, 8)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(destination);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    textureStore(destination, id.xy, vec4<f32>(textureLoad(depth, id.xy, 0), 0.0, 0.0, 1.0));
+}
+"#;
@@ -0,0 +1,442 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    GpuContext,
+    MaterialShader,
+    Result,
+    Skybox,
+    Texture,
+    TextureBuilder,
+    TypedBuffer,
+    CUBEMAP_FORMAT,
+};
+use crate::material::{ PBR_SHADER, PBR_VERTEX };
+use crate::skybox::{ render_cube_faces, CUBE_FACE_WGSL };
+
+/// Sizes and sample counts for [`IblMaps`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IblOptions {
+    /// Face size of the diffuse irradiance cubemap
+    pub irradiance_size: u32,
+    /// Face size of the top mip of the prefiltered specular cubemap
+    pub prefiltered_size: u32,
+    /// Mip levels of the prefiltered cubemap, from roughness 0 to 1
+    pub prefiltered_mip_levels: u32,
+    /// Importance samples per texel of the prefiltered cubemap
+    pub sample_count: u32,
+    /// Width and height of the BRDF lookup table
+    pub brdf_lut_size: u32,
+}
+
+impl IblOptions {
+    pub fn new() -> Self {
+        Self {
+            irradiance_size: 32,
+            prefiltered_size: 128,
+            prefiltered_mip_levels: 5,
+            sample_count: 512,
+            brdf_lut_size: 256,
+        }
+    }
+}
+
+impl Default for IblOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FilterParams {
+    mip_count: u32,
+    sample_count: u32,
+    _padding: [u32; 2],
+}
+
+/// Image-based lighting maps precomputed from an environment cubemap: diffuse
+/// irradiance, a prefiltered specular mip chain and the split-sum BRDF table.
+///
+/// Bind them, in [`textures`](Self::textures) order, after the four PBR
+/// textures of a [`MaterialShader::pbr_ibl`] material.
+pub struct IblMaps {
+    pub irradiance: Texture,
+    pub prefiltered: Texture,
+    pub brdf_lut: Texture,
+}
+
+impl IblMaps {
+    /// Precompute the maps from a filterable environment cubemap
+    pub fn from_cubemap(
+        context: &GpuContext,
+        environment: &Texture,
+        options: &IblOptions
+    ) -> Result<Self> {
+        let max_mips = options.prefiltered_size.max(1).ilog2() + 1;
+        let mip_levels = options.prefiltered_mip_levels.clamp(1, max_mips);
+        let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT;
+        let irradiance = Texture::create_cubemap(
+            context,
+            options.irradiance_size,
+            CUBEMAP_FORMAT,
+            1,
+            usage,
+            Some("IBL Irradiance")
+        )?;
+        let prefiltered = Texture::create_cubemap(
+            context,
+            options.prefiltered_size,
+            CUBEMAP_FORMAT,
+            mip_levels,
+            usage,
+            Some("IBL Prefiltered")
+        )?;
+
+        let params = TypedBuffer::uniform(
+            context,
+            &[
+                FilterParams {
+                    mip_count: mip_levels,
+                    sample_count: options.sample_count.max(1),
+                    _padding: [0; 2],
+                },
+            ]
+        )?;
+        let layout = BindGroupLayoutBuilder::new()
+            .texture(
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::TextureViewDimension::Cube,
+                false
+            )
+            .sampler(1, wgpu::ShaderStages::FRAGMENT, wgpu::SamplerBindingType::Filtering)
+            .uniform_buffer(2, wgpu::ShaderStages::FRAGMENT)
+            .build(context, Some("IBL Filter Layout"));
+        let bind_group = BindGroupBuilder::new(&layout)
+            .texture_view(0, &environment.view)
+            .sampler(1, &environment.sampler)
+            .buffer(2, params.buffer())
+            .build(context, Some("IBL Filter Bind Group"));
+
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&context.label("IBL Filter Shader")),
+            source: wgpu::ShaderSource::Wgsl(
+                format!("{}{}{}", CUBE_FACE_WGSL, SAMPLING_WGSL, FILTER_SHADER).into()
+            ),
+        });
+        let irradiance_pipeline = create_pipeline(
+            context,
+            &module,
+            "vs_main",
+            "fs_irradiance",
+            &layout,
+            CUBEMAP_FORMAT
+        );
+        let prefilter_pipeline = create_pipeline(
+            context,
+            &module,
+            "vs_main",
+            "fs_prefilter",
+            &layout,
+            CUBEMAP_FORMAT
+        );
+
+        render_cube_faces(context, &irradiance, 1, &irradiance_pipeline, &bind_group, "IBL Irradiance");
+        render_cube_faces(
+            context,
+            &prefiltered,
+            mip_levels,
+            &prefilter_pipeline,
+            &bind_group,
+            "IBL Prefilter"
+        );
+
+        Ok(Self {
+            irradiance,
+            prefiltered,
+            brdf_lut: Self::create_brdf_lut(context, options.brdf_lut_size)?,
+        })
+    }
+
+    /// Precompute the maps from an equirectangular environment map
+    pub fn from_equirectangular(
+        context: &GpuContext,
+        equirect: &Texture,
+        options: &IblOptions
+    ) -> Result<Self> {
+        let environment = Skybox::equirect_to_cubemap(
+            context,
+            equirect,
+            options.prefiltered_size * 2
+        )?;
+        Self::from_cubemap(context, &environment, options)
+    }
+
+    /// Integrate the split-sum BRDF into an `Rg16Float` table indexed by
+    /// (N·V, roughness), holding the scale and bias applied to F0
+    pub fn create_brdf_lut(context: &GpuContext, size: u32) -> Result<Texture> {
+        let lut = TextureBuilder::new(size, size)
+            .format(wgpu::TextureFormat::Rg16Float)
+            .usage(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT)
+            .label("IBL BRDF LUT")
+            .sampler(wgpu::SamplerDescriptor {
+                label: Some("IBL BRDF LUT Sampler"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            })
+            .build(context)?;
+
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&context.label("IBL BRDF Shader")),
+            source: wgpu::ShaderSource::Wgsl(format!("{}{}", SAMPLING_WGSL, BRDF_SHADER).into()),
+        });
+        let layout = BindGroupLayoutBuilder::new().build(context, Some("IBL BRDF Layout"));
+        let pipeline = create_pipeline(
+            context,
+            &module,
+            "vs_main",
+            "fs_main",
+            &layout,
+            wgpu::TextureFormat::Rg16Float
+        );
+
+        let mut encoder = context.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some(&context.label("IBL BRDF LUT")),
+            })
+        );
+        {
+            let mut pass = encoder.begin_render_pass(
+                &(wgpu::RenderPassDescriptor {
+                    label: Some(&context.label("IBL BRDF LUT")),
+                    color_attachments: &[
+                        Some(wgpu::RenderPassColorAttachment {
+                            view: &lut.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                store: wgpu::StoreOp::Store,
+                            },
+                        }),
+                    ],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                })
+            );
+            pass.set_pipeline(&pipeline);
+            pass.draw(0..3, 0..1);
+        }
+        context.submit(std::iter::once(encoder.finish()));
+
+        Ok(lut)
+    }
+
+    /// Irradiance, prefiltered and BRDF textures in binding order
+    pub fn textures(&self) -> [&Texture; 3] {
+        [&self.irradiance, &self.prefiltered, &self.brdf_lut]
+    }
+}
+
+impl MaterialShader {
+    /// [`pbr`](Self::pbr) with ambient light from [`IblMaps`]. Takes the four PBR
+    /// textures followed by the irradiance cube, prefiltered cube and BRDF table.
+    pub fn pbr_ibl() -> Self {
+        use wgpu::TextureViewDimension::{ Cube, D2 };
+
+        let source = format!("{}{}{}", PBR_SHADER, PBR_VERTEX, PBR_IBL_AMBIENT);
+        Self::new("PBR IBL Material", &source, 7).textures(vec![D2, D2, D2, D2, Cube, Cube, D2])
+    }
+}
+
+fn create_pipeline(
+    context: &GpuContext,
+    module: &wgpu::ShaderModule,
+    vertex_entry: &str,
+    fragment_entry: &str,
+    layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat
+) -> wgpu::RenderPipeline {
+    let pipeline_layout = context.device.create_pipeline_layout(
+        &(wgpu::PipelineLayoutDescriptor {
+            label: Some(fragment_entry),
+            bind_group_layouts: &[layout],
+            push_constant_ranges: &[],
+        })
+    );
+    context.device.create_render_pipeline(
+        &(wgpu::RenderPipelineDescriptor {
+            label: Some(fragment_entry),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: vertex_entry,
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: fragment_entry,
+                targets: &[Some(format.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    )
+}
+
+const SAMPLING_WGSL: &str =
+    r#"
+const PI: f32 = 3.14159265;
+
+fn tangent_frame(n: vec3<f32>) -> mat3x3<f32> {
+    let up = select(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), abs(n.y) > 0.999);
+    let right = normalize(cross(up, n));
+    return mat3x3<f32>(right, cross(n, right), n);
+}
+
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+fn importance_sample_ggx(xi: vec2<f32>, n: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    let h = vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+    return normalize(tangent_frame(n) * h);
+}
+"#;
+
+const FILTER_SHADER: &str =
+    r#"
+struct FilterParams {
+    mip_count: u32,
+    sample_count: u32,
+}
+
+@group(0) @binding(0) var environment_texture: texture_cube<f32>;
+@group(0) @binding(1) var environment_sampler: sampler;
+@group(0) @binding(2) var<uniform> filter_params: FilterParams;
+
+@fragment
+fn fs_irradiance(in: FaceOutput) -> @location(0) vec4<f32> {
+    let n = cube_direction(in.face % 6u, in.uv);
+    let frame = tangent_frame(n);
+    var sum = vec3<f32>(0.0);
+    var count = 0.0;
+    for (var phi = 0.0; phi < 2.0 * PI; phi += 0.05) {
+        for (var theta = 0.0; theta < 0.5 * PI; theta += 0.05) {
+            let local = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let radiance = textureSampleLevel(environment_texture, environment_sampler, frame * local, 0.0).rgb;
+            sum += radiance * cos(theta) * sin(theta);
+            count += 1.0;
+        }
+    }
+    return vec4<f32>(PI * sum / count, 1.0);
+}
+
+@fragment
+fn fs_prefilter(in: FaceOutput) -> @location(0) vec4<f32> {
+    let n = cube_direction(in.face % 6u, in.uv);
+    let mip = in.face / 6u;
+    let roughness = f32(mip) / f32(max(filter_params.mip_count, 2u) - 1u);
+    var color = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < filter_params.sample_count; i++) {
+        let h = importance_sample_ggx(hammersley(i, filter_params.sample_count), n, roughness);
+        let l = normalize(2.0 * dot(n, h) * h - n);
+        let n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            color += textureSampleLevel(environment_texture, environment_sampler, l, 0.0).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+    return vec4<f32>(color / max(weight, 1e-4), 1.0);
+}
+"#;
+
+const BRDF_SHADER: &str =
+    r#"
+const BRDF_SAMPLES: u32 = 512u;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex << 1u) & 2u), f32(vertex & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn geometry_schlick(n_dot_x: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n_dot_v = max(in.uv.x, 1e-3);
+    let roughness = in.uv.y;
+    let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let n = vec3<f32>(0.0, 0.0, 1.0);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < BRDF_SAMPLES; i++) {
+        let h = importance_sample_ggx(hammersley(i, BRDF_SAMPLES), n, roughness);
+        let l = normalize(2.0 * dot(v, h) * h - v);
+        let n_dot_l = max(l.z, 0.0);
+        let n_dot_h = max(h.z, 0.0);
+        let v_dot_h = max(dot(v, h), 0.0);
+        if (n_dot_l > 0.0) {
+            let g = geometry_schlick(n_dot_v, roughness) * geometry_schlick(n_dot_l, roughness);
+            let g_vis = g * v_dot_h / max(n_dot_h * n_dot_v, 1e-4);
+            let fc = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+    return vec4<f32>(scale / f32(BRDF_SAMPLES), bias / f32(BRDF_SAMPLES), 0.0, 1.0);
+}
+"#;
+
+const PBR_IBL_AMBIENT: &str =
+    r#"
+@group(1) @binding(9) var irradiance_texture: texture_cube<f32>;
+@group(1) @binding(10) var irradiance_sampler: sampler;
+@group(1) @binding(11) var prefiltered_texture: texture_cube<f32>;
+@group(1) @binding(12) var prefiltered_sampler: sampler;
+@group(1) @binding(13) var brdf_lut_texture: texture_2d<f32>;
+@group(1) @binding(14) var brdf_lut_sampler: sampler;
+
+fn ambient(n: vec3<f32>, v: vec3<f32>, albedo: vec3<f32>, metallic: f32, roughness: f32) -> vec3<f32> {
+    let n_dot_v = max(dot(n, v), 1e-4);
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let f = f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(1.0 - n_dot_v, 5.0);
+    let kd = (1.0 - f) * (1.0 - metallic);
+
+    let irradiance = textureSampleLevel(irradiance_texture, irradiance_sampler, n, 0.0).rgb;
+    let max_lod = f32(textureNumLevels(prefiltered_texture) - 1u);
+    let prefiltered = textureSampleLevel(
+        prefiltered_texture,
+        prefiltered_sampler,
+        reflect(-v, n),
+        roughness * max_lod
+    ).rgb;
+    let brdf = textureSampleLevel(brdf_lut_texture, brdf_lut_sampler, vec2<f32>(n_dot_v, roughness), 0.0).rg;
+    return kd * irradiance * albedo + prefiltered * (f * brdf.x + brdf.y);
+}
+"#;
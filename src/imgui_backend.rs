@@ -0,0 +1,582 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    GpuContext,
+    RenderPass,
+    RenderPipeline,
+    Result,
+    Texture,
+    TypedBuffer,
+};
+use imgui::{ DrawCmd, DrawCmdParams, TextureId };
+use std::sync::Arc;
+
+/// Vertex layout matching `imgui::DrawVert`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ImguiVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [u8; 4],
+}
+
+/// One scissored, textured range of the frame's index buffer
+struct ImguiDrawCall {
+    texture: TextureId,
+    scissor: [u32; 4],
+    indices: std::ops::Range<u32>,
+    base_vertex: i32,
+}
+
+/// Renders Dear ImGui draw data (via imgui-rs) into a geepu render pass.
+///
+/// The font atlas is uploaded on creation; other textures are made available
+/// to `imgui::Image` through [`register_texture`](Self::register_texture).
+/// Call [`prepare`](Self::prepare) with the result of `imgui::Context::render`
+/// each frame, then [`render`](Self::render) in a pass that loads the target.
+pub struct ImguiRenderer {
+    textures: imgui::Textures<wgpu::BindGroup>,
+    texture_layout: Arc<wgpu::BindGroupLayout>,
+    projection: TypedBuffer<[[f32; 4]; 4]>,
+    projection_bind_group: wgpu::BindGroup,
+    pipeline: RenderPipeline,
+    vertices: TypedBuffer<ImguiVertex>,
+    indices: TypedBuffer<u16>,
+    draws: Vec<ImguiDrawCall>,
+    _font_texture: Texture,
+}
+
+impl ImguiRenderer {
+    /// Create a renderer for `color_format` and upload `imgui`'s font atlas
+    pub fn new(
+        context: &GpuContext,
+        imgui: &mut imgui::Context,
+        color_format: wgpu::TextureFormat
+    ) -> Result<Self> {
+        Self::new_multisampled(context, imgui, color_format, 1)
+    }
+
+    /// [`new`](Self::new) for targets with `samples` samples per pixel, e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    pub fn new_multisampled(
+        context: &GpuContext,
+        imgui: &mut imgui::Context,
+        color_format: wgpu::TextureFormat,
+        samples: u32
+    ) -> Result<Self> {
+        imgui.set_renderer_name(Some(format!("geepu {}", env!("CARGO_PKG_VERSION"))));
+        imgui.io_mut().backend_flags.insert(imgui::BackendFlags::RENDERER_HAS_VTX_OFFSET);
+
+        let projection = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let projection_layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::VERTEX)
+            .build(context, Some("ImGui Projection Layout"));
+        let projection_bind_group = BindGroupBuilder::new(&projection_layout)
+            .buffer(0, projection.buffer())
+            .build(context, Some("ImGui Projection Bind Group"));
+        let texture_layout = BindGroupLayoutBuilder::new()
+            .texture(
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .sampler(1, wgpu::ShaderStages::FRAGMENT, wgpu::SamplerBindingType::Filtering)
+            .build(context, Some("ImGui Texture Layout"));
+
+        // Vertex colors are sRGB; convert them when the target re-encodes on write
+        let shader = format!("const SRGB_TARGET: bool = {};\n{}", color_format.is_srgb(), SHADER);
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&context.label("ImGui Shader")),
+            source: wgpu::ShaderSource::Wgsl(shader.into()),
+        });
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&context.label("ImGui Pipeline Layout")),
+                bind_group_layouts: &[&projection_layout, &texture_layout],
+                push_constant_ranges: &[],
+            })
+        );
+        let pipeline = context.device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some(&context.label("ImGui Pipeline")),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<ImguiVertex>() as u64,
+                            step_mode: wgpu::VertexStepMode::Vertex,
+                            attributes: &wgpu::vertex_attr_array![
+                                0 => Float32x2,
+                                1 => Float32x2,
+                                2 => Unorm8x4
+                            ],
+                        },
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: color_format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: samples,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            })
+        );
+
+        let font_texture = {
+            let atlas = imgui.fonts();
+            let data = atlas.build_rgba32_texture();
+            Texture::from_bytes(
+                context,
+                data.data,
+                data.width,
+                data.height,
+                wgpu::TextureFormat::Rgba8Unorm,
+                Some("ImGui Font Atlas")
+            )?
+        };
+        let mut textures = imgui::Textures::new();
+        let font_bind_group = Self::texture_bind_group(context, &texture_layout, &font_texture);
+        imgui.fonts().tex_id = textures.insert(font_bind_group);
+
+        Ok(Self {
+            textures,
+            texture_layout,
+            projection,
+            projection_bind_group,
+            pipeline: RenderPipeline {
+                pipeline,
+                bind_group_layouts: vec![projection_layout],
+                vertex_buffer_count: 1,
+                bindings: Vec::new(),
+            },
+            vertices: Self::create_vertices(context, 4096)?,
+            indices: Self::create_indices(context, 8192)?,
+            draws: Vec::new(),
+            _font_texture: font_texture,
+        })
+    }
+
+    /// Make a texture available to imgui widgets under the returned id
+    pub fn register_texture(&mut self, context: &GpuContext, texture: &Texture) -> TextureId {
+        let bind_group = Self::texture_bind_group(context, &self.texture_layout, texture);
+        self.textures.insert(bind_group)
+    }
+
+    /// Forget a texture registered with [`register_texture`](Self::register_texture)
+    pub fn unregister_texture(&mut self, id: TextureId) {
+        self.textures.remove(id);
+    }
+
+    /// Upload this frame's vertices, indices and projection
+    pub fn prepare(&mut self, context: &GpuContext, draw_data: &imgui::DrawData) -> Result<()> {
+        self.draws.clear();
+
+        let [scale_x, scale_y] = draw_data.framebuffer_scale;
+        let target_width = draw_data.display_size[0] * scale_x;
+        let target_height = draw_data.display_size[1] * scale_y;
+        if target_width <= 0.0 || target_height <= 0.0 {
+            return Ok(());
+        }
+
+        let [left, top] = draw_data.display_pos;
+        let right = left + draw_data.display_size[0];
+        let bottom = top + draw_data.display_size[1];
+        let projection = glam::Mat4::orthographic_rh(left, right, bottom, top, -1.0, 1.0);
+        self.projection.write(context, &[projection.to_cols_array_2d()])?;
+
+        let mut vertices = Vec::with_capacity(draw_data.total_vtx_count as usize);
+        let mut indices = Vec::with_capacity(draw_data.total_idx_count as usize);
+        for draw_list in draw_data.draw_lists() {
+            let base_vertex = vertices.len();
+            let base_index = indices.len();
+            vertices.extend(
+                draw_list.vtx_buffer().iter().map(|vertex| ImguiVertex {
+                    position: vertex.pos,
+                    uv: vertex.uv,
+                    color: vertex.col,
+                })
+            );
+            indices.extend_from_slice(draw_list.idx_buffer());
+
+            for command in draw_list.commands() {
+                let DrawCmd::Elements { count, cmd_params } = command else {
+                    continue;
+                };
+                let DrawCmdParams { clip_rect, texture_id, vtx_offset, idx_offset } = cmd_params;
+                let min_x = ((clip_rect[0] - left) * scale_x).max(0.0);
+                let min_y = ((clip_rect[1] - top) * scale_y).max(0.0);
+                let max_x = ((clip_rect[2] - left) * scale_x).min(target_width);
+                let max_y = ((clip_rect[3] - top) * scale_y).min(target_height);
+                if max_x <= min_x || max_y <= min_y {
+                    continue;
+                }
+
+                let start = (base_index + idx_offset) as u32;
+                self.draws.push(ImguiDrawCall {
+                    texture: texture_id,
+                    scissor: [
+                        min_x as u32,
+                        min_y as u32,
+                        (max_x - min_x).ceil() as u32,
+                        (max_y - min_y).ceil() as u32,
+                    ],
+                    indices: start..start + (count as u32),
+                    base_vertex: (base_vertex + vtx_offset) as i32,
+                });
+            }
+        }
+        // Buffer writes must be a multiple of four bytes
+        if indices.len() % 2 == 1 {
+            indices.push(0);
+        }
+
+        if vertices.len() > self.vertices.len() {
+            self.vertices = Self::create_vertices(context, vertices.len().next_power_of_two())?;
+        }
+        if indices.len() > self.indices.len() {
+            self.indices = Self::create_indices(context, indices.len().next_power_of_two())?;
+        }
+        self.vertices.write(context, &vertices)?;
+        self.indices.write(context, &indices)
+    }
+
+    /// Draw the ImGui frame uploaded by the last [`prepare`](Self::prepare)
+    pub fn render<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if self.draws.is_empty() {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.projection_bind_group, &[]);
+        pass.set_vertex_buffer(0, &self.vertices);
+        pass.set_index_buffer(&self.indices);
+        for draw in &self.draws {
+            let Some(bind_group) = self.textures.get(draw.texture) else {
+                continue;
+            };
+            let [x, y, width, height] = draw.scissor;
+            pass.set_scissor_rect(x, y, width, height);
+            pass.set_bind_group(1, bind_group, &[]);
+            pass.draw_indexed(draw.indices.clone(), draw.base_vertex, 0..1);
+        }
+    }
+
+    fn texture_bind_group(
+        context: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        texture: &Texture
+    ) -> wgpu::BindGroup {
+        BindGroupBuilder::new(layout)
+            .texture_view(0, &texture.view)
+            .sampler(1, &texture.sampler)
+            .build(context, Some("ImGui Texture Bind Group"))
+    }
+
+    fn create_vertices(context: &GpuContext, len: usize) -> Result<TypedBuffer<ImguiVertex>> {
+        TypedBuffer::empty(context, len, wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST)
+    }
+
+    fn create_indices(context: &GpuContext, len: usize) -> Result<TypedBuffer<u16>> {
+        TypedBuffer::empty(context, len, wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST)
+    }
+}
+
+/// Feeds winit window events and frame timing into an imgui context
+#[cfg(feature = "window")]
+pub struct ImguiPlatform {
+    scale_factor: f64,
+}
+
+#[cfg(feature = "window")]
+impl ImguiPlatform {
+    /// Attach imgui to `window`, taking its size and DPI scale
+    pub fn new(imgui: &mut imgui::Context, window: &winit::window::Window) -> Self {
+        imgui.set_platform_name(Some(format!("geepu-winit {}", env!("CARGO_PKG_VERSION"))));
+        let platform = Self { scale_factor: window.scale_factor() };
+        platform.update_display(imgui.io_mut(), window);
+        platform
+    }
+
+    /// Forward a window event to imgui.
+    ///
+    /// Returns `true` when imgui wants to consume the input, in which case the
+    /// application should not also act on it.
+    pub fn handle_event(
+        &mut self,
+        imgui: &mut imgui::Context,
+        window: &winit::window::Window,
+        event: &winit::event::WindowEvent
+    ) -> bool {
+        use winit::event::{ ElementState, MouseScrollDelta, WindowEvent };
+        use winit::keyboard::PhysicalKey;
+
+        let io = imgui.io_mut();
+        match event {
+            WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                self.scale_factor = window.scale_factor();
+                self.update_display(io, window);
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = position.to_logical::<f32>(self.scale_factor);
+                io.add_mouse_pos_event([position.x, position.y]);
+                io.want_capture_mouse
+            }
+            WindowEvent::CursorLeft { .. } => {
+                io.add_mouse_pos_event([f32::MAX, f32::MAX]);
+                false
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                if let Some(button) = mouse_button(*button) {
+                    io.add_mouse_button_event(button, *state == ElementState::Pressed);
+                }
+                io.want_capture_mouse
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let wheel = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => [*x, *y],
+                    MouseScrollDelta::PixelDelta(position) => {
+                        let position = position.to_logical::<f32>(self.scale_factor);
+                        // Roughly one line per 16 logical pixels
+                        [position.x / 16.0, position.y / 16.0]
+                    }
+                };
+                io.add_mouse_wheel_event(wheel);
+                io.want_capture_mouse
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                let state = modifiers.state();
+                io.add_key_event(imgui::Key::ModShift, state.shift_key());
+                io.add_key_event(imgui::Key::ModCtrl, state.control_key());
+                io.add_key_event(imgui::Key::ModAlt, state.alt_key());
+                io.add_key_event(imgui::Key::ModSuper, state.super_key());
+                false
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                let pressed = event.state == ElementState::Pressed;
+                if let PhysicalKey::Code(code) = event.physical_key {
+                    if let Some(key) = key(code) {
+                        io.add_key_event(key, pressed);
+                    }
+                }
+                if pressed {
+                    for character in event.text.iter().flat_map(|text| text.chars()) {
+                        if !character.is_control() {
+                            io.add_input_character(character);
+                        }
+                    }
+                }
+                io.want_capture_keyboard
+            }
+            _ => false,
+        }
+    }
+
+    /// Advance imgui's clock by `delta`; call before `imgui::Context::new_frame`
+    pub fn prepare_frame(
+        &mut self,
+        imgui: &mut imgui::Context,
+        window: &winit::window::Window,
+        delta: std::time::Duration
+    ) {
+        let io = imgui.io_mut();
+        // imgui asserts on a zero delta
+        io.update_delta_time(delta.max(std::time::Duration::from_micros(1)));
+        self.update_display(io, window);
+    }
+
+    fn update_display(&self, io: &mut imgui::Io, window: &winit::window::Window) {
+        let size = window.inner_size().to_logical::<f32>(self.scale_factor);
+        io.display_size = [size.width, size.height];
+        io.display_framebuffer_scale = [self.scale_factor as f32, self.scale_factor as f32];
+    }
+}
+
+#[cfg(feature = "window")]
+fn mouse_button(button: winit::event::MouseButton) -> Option<imgui::MouseButton> {
+    use winit::event::MouseButton;
+
+    match button {
+        MouseButton::Left => Some(imgui::MouseButton::Left),
+        MouseButton::Right => Some(imgui::MouseButton::Right),
+        MouseButton::Middle => Some(imgui::MouseButton::Middle),
+        MouseButton::Back => Some(imgui::MouseButton::Extra1),
+        MouseButton::Forward => Some(imgui::MouseButton::Extra2),
+        MouseButton::Other(_) => None,
+    }
+}
+
+#[cfg(feature = "window")]
+fn key(code: winit::keyboard::KeyCode) -> Option<imgui::Key> {
+    use imgui::Key;
+    use winit::keyboard::KeyCode;
+
+    Some(match code {
+        KeyCode::Tab => Key::Tab,
+        KeyCode::ArrowLeft => Key::LeftArrow,
+        KeyCode::ArrowRight => Key::RightArrow,
+        KeyCode::ArrowUp => Key::UpArrow,
+        KeyCode::ArrowDown => Key::DownArrow,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::Insert => Key::Insert,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Space => Key::Space,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Escape => Key::Escape,
+        KeyCode::ControlLeft => Key::LeftCtrl,
+        KeyCode::ShiftLeft => Key::LeftShift,
+        KeyCode::AltLeft => Key::LeftAlt,
+        KeyCode::SuperLeft => Key::LeftSuper,
+        KeyCode::ControlRight => Key::RightCtrl,
+        KeyCode::ShiftRight => Key::RightShift,
+        KeyCode::AltRight => Key::RightAlt,
+        KeyCode::SuperRight => Key::RightSuper,
+        KeyCode::ContextMenu => Key::Menu,
+        KeyCode::Digit0 => Key::Alpha0,
+        KeyCode::Digit1 => Key::Alpha1,
+        KeyCode::Digit2 => Key::Alpha2,
+        KeyCode::Digit3 => Key::Alpha3,
+        KeyCode::Digit4 => Key::Alpha4,
+        KeyCode::Digit5 => Key::Alpha5,
+        KeyCode::Digit6 => Key::Alpha6,
+        KeyCode::Digit7 => Key::Alpha7,
+        KeyCode::Digit8 => Key::Alpha8,
+        KeyCode::Digit9 => Key::Alpha9,
+        KeyCode::KeyA => Key::A,
+        KeyCode::KeyB => Key::B,
+        KeyCode::KeyC => Key::C,
+        KeyCode::KeyD => Key::D,
+        KeyCode::KeyE => Key::E,
+        KeyCode::KeyF => Key::F,
+        KeyCode::KeyG => Key::G,
+        KeyCode::KeyH => Key::H,
+        KeyCode::KeyI => Key::I,
+        KeyCode::KeyJ => Key::J,
+        KeyCode::KeyK => Key::K,
+        KeyCode::KeyL => Key::L,
+        KeyCode::KeyM => Key::M,
+        KeyCode::KeyN => Key::N,
+        KeyCode::KeyO => Key::O,
+        KeyCode::KeyP => Key::P,
+        KeyCode::KeyQ => Key::Q,
+        KeyCode::KeyR => Key::R,
+        KeyCode::KeyS => Key::S,
+        KeyCode::KeyT => Key::T,
+        KeyCode::KeyU => Key::U,
+        KeyCode::KeyV => Key::V,
+        KeyCode::KeyW => Key::W,
+        KeyCode::KeyX => Key::X,
+        KeyCode::KeyY => Key::Y,
+        KeyCode::KeyZ => Key::Z,
+        KeyCode::F1 => Key::F1,
+        KeyCode::F2 => Key::F2,
+        KeyCode::F3 => Key::F3,
+        KeyCode::F4 => Key::F4,
+        KeyCode::F5 => Key::F5,
+        KeyCode::F6 => Key::F6,
+        KeyCode::F7 => Key::F7,
+        KeyCode::F8 => Key::F8,
+        KeyCode::F9 => Key::F9,
+        KeyCode::F10 => Key::F10,
+        KeyCode::F11 => Key::F11,
+        KeyCode::F12 => Key::F12,
+        KeyCode::Quote => Key::Apostrophe,
+        KeyCode::Comma => Key::Comma,
+        KeyCode::Minus => Key::Minus,
+        KeyCode::Period => Key::Period,
+        KeyCode::Slash => Key::Slash,
+        KeyCode::Semicolon => Key::Semicolon,
+        KeyCode::Equal => Key::Equal,
+        KeyCode::BracketLeft => Key::LeftBracket,
+        KeyCode::Backslash => Key::Backslash,
+        KeyCode::BracketRight => Key::RightBracket,
+        KeyCode::Backquote => Key::GraveAccent,
+        KeyCode::CapsLock => Key::CapsLock,
+        KeyCode::ScrollLock => Key::ScrollLock,
+        KeyCode::NumLock => Key::NumLock,
+        KeyCode::PrintScreen => Key::PrintScreen,
+        KeyCode::Pause => Key::Pause,
+        KeyCode::Numpad0 => Key::Keypad0,
+        KeyCode::Numpad1 => Key::Keypad1,
+        KeyCode::Numpad2 => Key::Keypad2,
+        KeyCode::Numpad3 => Key::Keypad3,
+        KeyCode::Numpad4 => Key::Keypad4,
+        KeyCode::Numpad5 => Key::Keypad5,
+        KeyCode::Numpad6 => Key::Keypad6,
+        KeyCode::Numpad7 => Key::Keypad7,
+        KeyCode::Numpad8 => Key::Keypad8,
+        KeyCode::Numpad9 => Key::Keypad9,
+        KeyCode::NumpadDecimal => Key::KeypadDecimal,
+        KeyCode::NumpadDivide => Key::KeypadDivide,
+        KeyCode::NumpadMultiply => Key::KeypadMultiply,
+        KeyCode::NumpadSubtract => Key::KeypadSubtract,
+        KeyCode::NumpadAdd => Key::KeypadAdd,
+        KeyCode::NumpadEnter => Key::KeypadEnter,
+        KeyCode::NumpadEqual => Key::KeypadEqual,
+        _ => return None,
+    })
+}
+
+const SHADER: &str =
+    r#"
+@group(0) @binding(0) var<uniform> projection: mat4x4<f32>;
+@group(1) @binding(0) var imgui_texture: texture_2d<f32>;
+@group(1) @binding(1) var imgui_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = projection * vec4<f32>(in.position, 0.0, 1.0);
+    out.uv = in.uv;
+    out.color = in.color;
+    if (SRGB_TARGET) {
+        out.color = vec4<f32>(srgb_to_linear(in.color.rgb), in.color.a);
+    }
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color * textureSample(imgui_texture, imgui_sampler, in.uv);
+}
+"#;
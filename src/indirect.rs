@@ -0,0 +1,177 @@
+use crate::{ GeepuError, GpuContext, Mesh, Result, TypedBuffer };
+use std::ops::Range;
+
+/// Arguments of one non-indexed indirect draw, laid out as wgpu expects them in an
+/// `INDIRECT` buffer
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawIndirectArgs {
+    pub vertex_count: u32,
+    pub instance_count: u32,
+    pub first_vertex: u32,
+    /// Must be 0 unless `Features::INDIRECT_FIRST_INSTANCE` is enabled
+    pub first_instance: u32,
+}
+
+impl DrawIndirectArgs {
+    pub fn new(vertices: Range<u32>, instances: Range<u32>) -> Self {
+        Self {
+            vertex_count: vertices.end - vertices.start,
+            instance_count: instances.end - instances.start,
+            first_vertex: vertices.start,
+            first_instance: instances.start,
+        }
+    }
+}
+
+/// Arguments of one indexed indirect draw, laid out as wgpu expects them in an
+/// `INDIRECT` buffer
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawIndexedIndirectArgs {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    /// Must be 0 unless `Features::INDIRECT_FIRST_INSTANCE` is enabled
+    pub first_instance: u32,
+}
+
+impl DrawIndexedIndirectArgs {
+    pub fn new(indices: Range<u32>, base_vertex: i32, instances: Range<u32>) -> Self {
+        Self {
+            index_count: indices.end - indices.start,
+            instance_count: instances.end - instances.start,
+            first_index: indices.start,
+            base_vertex,
+            first_instance: instances.start,
+        }
+    }
+}
+
+/// Workgroup counts of one indirect dispatch, laid out as wgpu expects them in
+/// an `INDIRECT` buffer
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DispatchIndirectArgs {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl DispatchIndirectArgs {
+    pub fn new(x: u32, y: u32, z: u32) -> Self {
+        Self { x, y, z }
+    }
+}
+
+/// Check that `size` bytes of indirect arguments can be read from `buffer` at
+/// `offset`: the offset is 4-byte aligned, in bounds, and the buffer has
+/// `INDIRECT` usage
+pub fn check_indirect_offset(buffer: &wgpu::Buffer, offset: u64, size: u64) -> Result<()> {
+    if !offset.is_multiple_of(4) {
+        return Err(
+            GeepuError::BufferError(format!("Indirect offset {} is not 4-byte aligned", offset))
+        );
+    }
+    if offset.checked_add(size).is_none_or(|end| end > buffer.size()) {
+        return Err(
+            GeepuError::BufferError(
+                format!(
+                    "Indirect arguments at {}..{} are out of bounds for a {}-byte buffer",
+                    offset,
+                    offset.saturating_add(size),
+                    buffer.size()
+                )
+            )
+        );
+    }
+    if !buffer.usage().contains(wgpu::BufferUsages::INDIRECT) {
+        return Err(GeepuError::BufferError("Indirect buffer lacks INDIRECT usage".to_string()));
+    }
+    Ok(())
+}
+
+/// Builder for a typed buffer of indirect draw or dispatch arguments.
+///
+/// The buffer is created with [`TypedBuffer::indirect`], so compute shaders
+/// can rewrite the arguments in place.
+pub struct IndirectBufferBuilder<T> {
+    args: Vec<T>,
+}
+
+impl<T> IndirectBufferBuilder<T> where T: bytemuck::Pod {
+    pub fn new() -> Self {
+        Self { args: Vec::new() }
+    }
+
+    /// Append one set of draw arguments
+    pub fn push(mut self, args: T) -> Self {
+        self.args.push(args);
+        self
+    }
+
+    /// Number of draws added so far
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    /// Create the buffer
+    pub fn build(self, context: &GpuContext) -> Result<TypedBuffer<T>> {
+        TypedBuffer::indirect(context, &self.args)
+    }
+}
+
+impl IndirectBufferBuilder<DrawIndirectArgs> {
+    /// Append a non-indexed draw
+    pub fn draw(self, vertices: Range<u32>, instances: Range<u32>) -> Self {
+        self.push(DrawIndirectArgs::new(vertices, instances))
+    }
+
+    /// Append one draw per submesh of a non-indexed mesh
+    pub fn mesh<V>(self, mesh: &Mesh<V>, instances: Range<u32>) -> Self
+        where V: bytemuck::Pod
+    {
+        mesh.submeshes
+            .iter()
+            .fold(self, |builder, submesh| builder.draw(submesh.range.clone(), instances.clone()))
+    }
+}
+
+impl IndirectBufferBuilder<DrawIndexedIndirectArgs> {
+    /// Append an indexed draw
+    pub fn draw_indexed(
+        self,
+        indices: Range<u32>,
+        base_vertex: i32,
+        instances: Range<u32>
+    ) -> Self {
+        self.push(DrawIndexedIndirectArgs::new(indices, base_vertex, instances))
+    }
+
+    /// Append one draw per submesh of an indexed mesh
+    pub fn mesh<V>(self, mesh: &Mesh<V>, instances: Range<u32>) -> Self
+        where V: bytemuck::Pod
+    {
+        mesh.submeshes.iter().fold(self, |builder, submesh| {
+            builder.draw_indexed(submesh.range.clone(), submesh.base_vertex, instances.clone())
+        })
+    }
+}
+
+impl IndirectBufferBuilder<DispatchIndirectArgs> {
+    /// Append a dispatch of `x` x `y` x `z` workgroups
+    pub fn dispatch(self, x: u32, y: u32, z: u32) -> Self {
+        self.push(DispatchIndirectArgs::new(x, y, z))
+    }
+}
+
+impl<T> Default for IndirectBufferBuilder<T> where T: bytemuck::Pod {
+    fn default() -> Self {
+        Self::new()
+    }
+}
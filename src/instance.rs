@@ -0,0 +1,97 @@
+use crate::{ GpuContext, Result, TypedBuffer };
+
+/// Per-instance model matrix, read as four `vec4<f32>` columns at shader
+/// locations 3 to 6 (after [`MeshVertex`](crate::MeshVertex)'s attributes)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceTransform {
+    pub model: [[f32; 4]; 4],
+}
+
+impl InstanceTransform {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] =
+        wgpu::vertex_attr_array![3 => Float32x4, 4 => Float32x4, 5 => Float32x4, 6 => Float32x4];
+
+    pub fn new(model: [[f32; 4]; 4]) -> Self {
+        Self { model }
+    }
+
+    /// Translation-only instance
+    pub fn from_translation(translation: [f32; 3]) -> Self {
+        Self::new(glam::Mat4::from_translation(translation.into()).to_cols_array_2d())
+    }
+
+    /// Instance-rate vertex layout for buffer slot 1
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as u64,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// A growable instance-rate vertex buffer.
+///
+/// [`update`](Self::update) rewrites the contents and reallocates (doubling)
+/// when the new data does not fit, so per-frame instance data can be pushed
+/// without tracking capacity.
+pub struct InstanceBuffer<T> {
+    buffer: TypedBuffer<T>,
+    len: usize,
+}
+
+impl<T> InstanceBuffer<T> where T: bytemuck::Pod {
+    /// Create a buffer holding `instances`
+    pub fn new(context: &GpuContext, instances: &[T]) -> Result<Self> {
+        let mut buffer = Self::with_capacity(context, instances.len())?;
+        buffer.update(context, instances)?;
+        Ok(buffer)
+    }
+
+    /// Create an empty buffer with room for `capacity` instances
+    pub fn with_capacity(context: &GpuContext, capacity: usize) -> Result<Self> {
+        Ok(Self {
+            buffer: Self::allocate(context, capacity.max(1))?,
+            len: 0,
+        })
+    }
+
+    /// Replace the instance data, growing the buffer if needed
+    pub fn update(&mut self, context: &GpuContext, instances: &[T]) -> Result<()> {
+        if instances.len() > self.buffer.len() {
+            let capacity = instances.len().max(self.buffer.len() * 2);
+            self.buffer = Self::allocate(context, capacity)?;
+        }
+        self.buffer.write(context, instances)?;
+        self.len = instances.len();
+        Ok(())
+    }
+
+    /// Number of instances written by the last update
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of instances that fit without reallocating
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The underlying vertex buffer
+    pub fn buffer(&self) -> &TypedBuffer<T> {
+        &self.buffer
+    }
+
+    fn allocate(context: &GpuContext, capacity: usize) -> Result<TypedBuffer<T>> {
+        TypedBuffer::empty(
+            context,
+            capacity,
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST
+        )
+    }
+}
@@ -0,0 +1,676 @@
+use crate::{ GeepuError, GpuConfig, GpuContext, Result, Texture };
+use ash::vk;
+use std::ffi::CStr;
+#[cfg(unix)]
+use std::os::fd::{ AsRawFd, FromRawFd, IntoRawFd, OwnedFd };
+use wgpu::hal::{ self, api::Vulkan };
+
+/// Device extensions [`GpuConfig::external_memory`] enables where the adapter has them
+#[cfg(unix)]
+const EXTERNAL_MEMORY_EXTENSIONS: &[&CStr] = &[
+    ash::khr::external_memory_fd::NAME,
+    ash::ext::external_memory_dma_buf::NAME,
+    ash::khr::image_format_list::NAME,
+    ash::ext::image_drm_format_modifier::NAME,
+];
+#[cfg(windows)]
+const EXTERNAL_MEMORY_EXTENSIONS: &[&CStr] = &[ash::khr::external_memory_win32::NAME];
+
+/// Raw Vulkan objects behind a [`GpuContext`], for handing its device to other
+/// Vulkan code (decoders, capture pipelines, external renderers).
+///
+/// The handles stay owned by wgpu: they are valid while the context is alive
+/// and must not be destroyed. Work submitted to `queue` from outside wgpu must
+/// be externally synchronized with the context's own submissions.
+#[derive(Debug, Clone, Copy)]
+pub struct VulkanHandles {
+    pub instance: vk::Instance,
+    pub physical_device: vk::PhysicalDevice,
+    pub device: vk::Device,
+    pub queue: vk::Queue,
+    pub queue_family_index: u32,
+}
+
+impl GpuContext {
+    /// The context's Vulkan handles, or `None` when it runs on another backend
+    pub fn vulkan_handles(&self) -> Option<VulkanHandles> {
+        // SAFETY: only handle values are copied out; nothing is destroyed
+        unsafe {
+            self.device.as_hal::<Vulkan, _, _>(|device| {
+                device.map(|device| VulkanHandles {
+                    instance: device.shared_instance().raw_instance().handle(),
+                    physical_device: device.raw_physical_device(),
+                    device: device.raw_device().handle(),
+                    queue: device.raw_queue(),
+                    queue_family_index: device.queue_family_index(),
+                })
+            })
+        }.flatten()
+    }
+}
+
+/// Open the adapter's device with the external memory extensions enabled, for
+/// [`GpuConfig::external_memory`]. wgpu's own `request_device` leaves them off.
+pub(crate) fn request_external_memory_device(
+    adapter: &wgpu::Adapter,
+    config: &GpuConfig
+) -> Result<(wgpu::Device, wgpu::Queue)> {
+    let features = config.features | (config.optional_features & adapter.features());
+    let open = unsafe {
+        adapter.as_hal::<Vulkan, _, _>(|adapter| {
+            adapter.map(|adapter| open_external_memory_device(adapter, features, config))
+        })
+    }.ok_or_else(|| GeepuError::Other("External memory needs the Vulkan backend".to_string()))??;
+
+    // SAFETY: `open` was created from this adapter with the features requested below
+    unsafe {
+        adapter.create_device_from_hal(
+            open,
+            &(wgpu::DeviceDescriptor {
+                label: Some(&config.prefixed_label("Geepu Device")),
+                required_features: features,
+                required_limits: config.limits.clone(),
+                memory_hints: config.memory_hints.clone(),
+            }),
+            None
+        )
+    }.map_err(GeepuError::DeviceCreationFailed)
+}
+
+/// What wgpu-hal's `Adapter::open` does, plus [`EXTERNAL_MEMORY_EXTENSIONS`]
+fn open_external_memory_device(
+    adapter: &hal::vulkan::Adapter,
+    features: wgpu::Features,
+    config: &GpuConfig
+) -> Result<hal::OpenDevice<Vulkan>> {
+    let capabilities = adapter.physical_device_capabilities();
+    if capabilities.properties().api_version < vk::API_VERSION_1_1 {
+        return Err(GeepuError::Other("External memory needs a Vulkan 1.1 device".to_string()));
+    }
+    let mut extensions = adapter.required_device_extensions(features);
+    for &extension in EXTERNAL_MEMORY_EXTENSIONS {
+        if capabilities.supports_extension(extension) && !extensions.contains(&extension) {
+            extensions.push(extension);
+        }
+    }
+    let mut device_features = adapter.physical_device_features(&extensions, features);
+
+    // Queue family 0, as wgpu-hal picks it
+    let priorities = [1.0];
+    let queue_infos = [
+        vk::DeviceQueueCreateInfo::default().queue_family_index(0).queue_priorities(&priorities),
+    ];
+    let names: Vec<_> = extensions
+        .iter()
+        .map(|extension| extension.as_ptr())
+        .collect();
+    let info = device_features.add_to_device_create(
+        vk::DeviceCreateInfo::default()
+            .queue_create_infos(&queue_infos)
+            .enabled_extension_names(&names)
+    );
+    let instance = adapter.shared_instance().raw_instance();
+    let device = unsafe {
+        instance.create_device(adapter.raw_physical_device(), &info, None)
+    }.map_err(|e| vulkan_error("vkCreateDevice", e))?;
+    unsafe {
+        adapter.device_from_raw(device, true, &extensions, features, &config.memory_hints, 0, 0)
+    }.map_err(|e| GeepuError::Other(format!("Failed to open Vulkan device: {}", e)))
+}
+
+/// Memory shared by another API, process or driver, see
+/// [`Texture::from_external_memory`]
+#[derive(Debug)]
+pub enum ExternalMemory {
+    /// Vulkan memory exported as a file descriptor (`VK_KHR_external_memory_fd`),
+    /// or an OpenGL memory object (`GL_EXT_memory_object_fd`)
+    #[cfg(unix)]
+    OpaqueFd(OwnedFd),
+    /// A Linux DMA-BUF, e.g. from VA-API, V4L2, PipeWire or a Wayland client,
+    /// with its DRM format modifier and the layout of its single plane
+    #[cfg(unix)]
+    DmaBuf {
+        fd: OwnedFd,
+        modifier: u64,
+        offset: u64,
+        row_pitch: u64,
+    },
+    /// Vulkan memory exported as an NT handle (`VK_KHR_external_memory_win32`)
+    #[cfg(windows)]
+    OpaqueWin32(vk::HANDLE),
+    /// Shared NT handle of a D3D11 texture, from `IDXGIResource1::CreateSharedHandle`
+    #[cfg(windows)]
+    D3D11Texture(vk::HANDLE),
+    /// Shared NT handle of a D3D12 committed resource, from
+    /// `ID3D12Device::CreateSharedHandle`
+    #[cfg(windows)]
+    D3D12Resource(vk::HANDLE),
+}
+
+impl ExternalMemory {
+    fn handle_type(&self) -> vk::ExternalMemoryHandleTypeFlags {
+        match self {
+            #[cfg(unix)]
+            ExternalMemory::OpaqueFd(_) => vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+            #[cfg(unix)]
+            ExternalMemory::DmaBuf { .. } => vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT,
+            #[cfg(windows)]
+            ExternalMemory::OpaqueWin32(_) => vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32,
+            #[cfg(windows)]
+            ExternalMemory::D3D11Texture(_) => vk::ExternalMemoryHandleTypeFlags::D3D11_TEXTURE,
+            #[cfg(windows)]
+            ExternalMemory::D3D12Resource(_) => vk::ExternalMemoryHandleTypeFlags::D3D12_RESOURCE,
+        }
+    }
+
+    /// Device extensions importing the handle needs
+    fn extensions(&self) -> &'static [&'static CStr] {
+        match self {
+            #[cfg(unix)]
+            ExternalMemory::OpaqueFd(_) => &[ash::khr::external_memory_fd::NAME],
+            #[cfg(unix)]
+            ExternalMemory::DmaBuf { .. } =>
+                &[
+                    ash::khr::external_memory_fd::NAME,
+                    ash::ext::external_memory_dma_buf::NAME,
+                    ash::ext::image_drm_format_modifier::NAME,
+                ],
+            #[cfg(windows)]
+            _ => &[ash::khr::external_memory_win32::NAME],
+        }
+    }
+
+    /// Memory types the handle can be imported into. Opaque handles come from
+    /// a matching Vulkan allocation, so Vulkan does not report them.
+    unsafe fn memory_type_bits(&self, device: &hal::vulkan::Device) -> Result<u32> {
+        let instance = device.shared_instance().raw_instance();
+        match self {
+            #[cfg(unix)]
+            ExternalMemory::DmaBuf { fd, .. } => {
+                let functions = ash::khr::external_memory_fd::Device::new(
+                    instance,
+                    device.raw_device()
+                );
+                let mut properties = vk::MemoryFdPropertiesKHR::default();
+                unsafe {
+                    functions.get_memory_fd_properties(
+                        self.handle_type(),
+                        fd.as_raw_fd(),
+                        &mut properties
+                    )
+                }.map_err(|e| vulkan_error("vkGetMemoryFdPropertiesKHR", e))?;
+                Ok(properties.memory_type_bits)
+            }
+            #[cfg(windows)]
+            ExternalMemory::D3D11Texture(handle) | ExternalMemory::D3D12Resource(handle) => {
+                let functions = ash::khr::external_memory_win32::Device::new(
+                    instance,
+                    device.raw_device()
+                );
+                let mut properties = vk::MemoryWin32HandlePropertiesKHR::default();
+                unsafe {
+                    functions.get_memory_win32_handle_properties(
+                        self.handle_type(),
+                        *handle,
+                        &mut properties
+                    )
+                }.map_err(|e| vulkan_error("vkGetMemoryWin32HandlePropertiesKHR", e))?;
+                Ok(properties.memory_type_bits)
+            }
+            _ => Ok(!0),
+        }
+    }
+
+    /// Allocate `size` bytes of `memory_type` backed by the handle, dedicated to `image`
+    unsafe fn import(
+        self,
+        device: &ash::Device,
+        image: vk::Image,
+        size: u64,
+        memory_type: u32
+    ) -> Result<vk::DeviceMemory> {
+        let handle_type = self.handle_type();
+        let mut dedicated = vk::MemoryDedicatedAllocateInfo::default().image(image);
+        let info = vk::MemoryAllocateInfo::default()
+            .allocation_size(size)
+            .memory_type_index(memory_type)
+            .push_next(&mut dedicated);
+        match self {
+            #[cfg(unix)]
+            ExternalMemory::OpaqueFd(fd) | ExternalMemory::DmaBuf { fd, .. } => {
+                let mut import = vk::ImportMemoryFdInfoKHR::default()
+                    .handle_type(handle_type)
+                    .fd(fd.as_raw_fd());
+                let memory = unsafe {
+                    device.allocate_memory(&info.push_next(&mut import), None)
+                }.map_err(|e| vulkan_error("vkAllocateMemory", e))?;
+                // A successful import owns the descriptor now
+                let _ = fd.into_raw_fd();
+                Ok(memory)
+            }
+            #[cfg(windows)]
+            ExternalMemory::OpaqueWin32(handle)
+            | ExternalMemory::D3D11Texture(handle)
+            | ExternalMemory::D3D12Resource(handle) => {
+                let mut import = vk::ImportMemoryWin32HandleInfoKHR::default()
+                    .handle_type(handle_type)
+                    .handle(handle);
+                unsafe {
+                    device.allocate_memory(&info.push_next(&mut import), None)
+                }.map_err(|e| vulkan_error("vkAllocateMemory", e))
+            }
+        }
+    }
+}
+
+/// A texture whose memory other APIs or processes can import, from
+/// [`Texture::new_exportable`]
+pub struct ExportedTexture {
+    pub texture: Texture,
+    /// Handle to the texture's memory, owned by the caller
+    pub memory: ExternalMemory,
+    /// Size of the allocation in bytes, which importers such as
+    /// `glImportMemoryFdEXT` need
+    pub size: u64,
+}
+
+impl Texture {
+    /// Wrap a `VkImage` created on this context's device (see
+    /// [`GpuContext::vulkan_handles`]) without copying it. Memory shared by
+    /// other APIs or processes goes through
+    /// [`from_external_memory`](Self::from_external_memory) instead.
+    ///
+    /// `drop_guard` is dropped when wgpu is done with the texture; use it to
+    /// destroy the image and free its memory. Without one the image is not
+    /// destroyed by wgpu.
+    ///
+    /// # Safety
+    ///
+    /// `image` must belong to this context's device, be created to match
+    /// `descriptor` (format, size, mip levels, samples and a usage covering
+    /// `descriptor.usage`), be bound to memory and be in
+    /// `VK_IMAGE_LAYOUT_UNDEFINED` or a layout wgpu can transition from.
+    /// Access from other queues or APIs must be synchronized externally.
+    pub unsafe fn from_vulkan_image(
+        context: &GpuContext,
+        image: vk::Image,
+        descriptor: &wgpu::TextureDescriptor,
+        drop_guard: Option<hal::DropGuard>
+    ) -> Result<Self> {
+        if context.vulkan_handles().is_none() {
+            return Err(
+                GeepuError::TextureError("Vulkan images need a Vulkan-backed context".to_string())
+            );
+        }
+        let hal_descriptor = hal::TextureDescriptor {
+            label: descriptor.label,
+            size: descriptor.size,
+            mip_level_count: descriptor.mip_level_count,
+            sample_count: descriptor.sample_count,
+            dimension: descriptor.dimension,
+            format: descriptor.format,
+            usage: texture_uses(descriptor.usage, descriptor.format),
+            memory_flags: hal::MemoryFlags::empty(),
+            view_formats: descriptor.view_formats.to_vec(),
+        };
+        let texture = unsafe {
+            let raw = hal::vulkan::Device::texture_from_raw(image, &hal_descriptor, drop_guard);
+            context.device.create_texture_from_hal::<Vulkan>(raw, descriptor)
+        };
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = context.device.create_sampler(
+            &(wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            })
+        );
+        Ok(Self::tracked(context, texture, view, sampler, descriptor.label))
+    }
+
+    /// Import memory shared by another API, process or driver as a texture,
+    /// without copying it. The context must be created with
+    /// [`GpuConfig::external_memory`], and `descriptor` must describe a single
+    /// 2D image without mips or multisampling.
+    ///
+    /// A file descriptor is consumed by a successful import; Windows handles
+    /// stay owned by the caller. The memory is released when the texture is
+    /// dropped.
+    ///
+    /// # Safety
+    ///
+    /// The memory must hold an image of `descriptor`'s size and format, laid
+    /// out the way Vulkan expects for the handle type (the exporter's optimal
+    /// tiling for opaque and D3D handles, `modifier` for DMA-BUFs). Access
+    /// from the exporter must be synchronized externally, e.g. by waiting for
+    /// its work before using the texture.
+    pub unsafe fn from_external_memory(
+        context: &GpuContext,
+        memory: ExternalMemory,
+        descriptor: &wgpu::TextureDescriptor
+    ) -> Result<Self> {
+        let image = unsafe {
+            context.device.as_hal::<Vulkan, _, _>(|device| {
+                device.map(|device| import_image(device, memory, descriptor))
+            })
+        }.flatten().ok_or_else(not_vulkan)??;
+        let raw = image.image;
+        unsafe { Self::from_vulkan_image(context, raw, descriptor, Some(Box::new(image))) }
+    }
+
+    /// Create a texture whose memory is exported for other APIs or processes:
+    /// an opaque file descriptor on Unix, an NT handle on Windows. The context
+    /// must be created with [`GpuConfig::external_memory`], and `descriptor`
+    /// must describe a single 2D image without mips or multisampling.
+    ///
+    /// Importers see the image in the layout wgpu last left it in and must
+    /// synchronize with wgpu's queue, e.g. through [`GpuContext::queue_done`].
+    pub fn new_exportable(
+        context: &GpuContext,
+        descriptor: &wgpu::TextureDescriptor
+    ) -> Result<ExportedTexture> {
+        let (image, memory, size) = unsafe {
+            context.device.as_hal::<Vulkan, _, _>(|device| {
+                device.map(|device| export_image(device, descriptor))
+            })
+        }.flatten().ok_or_else(not_vulkan)??;
+        let raw = image.image;
+        // SAFETY: the image was just created on this device to match `descriptor`
+        let texture = unsafe {
+            Self::from_vulkan_image(context, raw, descriptor, Some(Box::new(image)))?
+        };
+        Ok(ExportedTexture { texture, memory, size })
+    }
+
+    /// The `VkImage` behind this texture, to export it to other Vulkan code
+    /// on the same device, or `None` on another backend.
+    ///
+    /// # Safety
+    ///
+    /// The image must not be destroyed, and must not be used after the
+    /// texture is dropped. Other users must synchronize with wgpu's queue and
+    /// leave the image in the layout they found it in.
+    pub unsafe fn vulkan_image(&self) -> Option<vk::Image> {
+        unsafe {
+            self.texture.as_hal::<Vulkan, _, _>(|texture| {
+                texture.map(|texture| texture.raw_handle())
+            })
+        }
+    }
+}
+
+/// wgpu-hal usage states matching `usage`, as wgpu-core derives them
+fn texture_uses(usage: wgpu::TextureUsages, format: wgpu::TextureFormat) -> hal::TextureUses {
+    let mut uses = hal::TextureUses::empty();
+    uses.set(hal::TextureUses::COPY_SRC, usage.contains(wgpu::TextureUsages::COPY_SRC));
+    uses.set(hal::TextureUses::COPY_DST, usage.contains(wgpu::TextureUsages::COPY_DST));
+    uses.set(hal::TextureUses::RESOURCE, usage.contains(wgpu::TextureUsages::TEXTURE_BINDING));
+    uses.set(
+        hal::TextureUses::STORAGE_READ | hal::TextureUses::STORAGE_READ_WRITE,
+        usage.contains(wgpu::TextureUsages::STORAGE_BINDING)
+    );
+    let attachment = usage.contains(wgpu::TextureUsages::RENDER_ATTACHMENT);
+    if format.is_depth_stencil_format() {
+        uses.set(
+            hal::TextureUses::DEPTH_STENCIL_READ | hal::TextureUses::DEPTH_STENCIL_WRITE,
+            attachment
+        );
+    } else {
+        uses.set(hal::TextureUses::COLOR_TARGET, attachment);
+    }
+    uses
+}
+
+/// An image created for external memory; destroys it and frees the memory once
+/// wgpu is done with the texture
+struct ExternalImage {
+    device: ash::Device,
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+}
+
+impl Drop for ExternalImage {
+    fn drop(&mut self) {
+        // SAFETY: wgpu drops the guard after its last use of the image
+        unsafe {
+            self.device.destroy_image(self.image, None);
+            self.device.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// Create an unbound image for memory of `handle_type`, laid out by a DRM
+/// format modifier when `modifier` is given
+fn create_external_image(
+    device: &hal::vulkan::Device,
+    descriptor: &wgpu::TextureDescriptor,
+    handle_type: vk::ExternalMemoryHandleTypeFlags,
+    modifier: Option<(u64, vk::SubresourceLayout)>
+) -> Result<ExternalImage> {
+    let single_image =
+        descriptor.dimension == wgpu::TextureDimension::D2 &&
+        descriptor.size.depth_or_array_layers == 1 &&
+        descriptor.mip_level_count == 1 &&
+        descriptor.sample_count == 1;
+    if !single_image {
+        let message = "External memory textures must be single 2D images without mips or MSAA";
+        return Err(GeepuError::TextureError(message.to_string()));
+    }
+    let format = vulkan_format(descriptor.format)?;
+
+    let mut external = vk::ExternalMemoryImageCreateInfo::default().handle_types(handle_type);
+    let mut info = vk::ImageCreateInfo::default()
+        .image_type(vk::ImageType::TYPE_2D)
+        .format(format)
+        .extent(vk::Extent3D {
+            width: descriptor.size.width,
+            height: descriptor.size.height,
+            depth: 1,
+        })
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(image_usage(descriptor.usage, descriptor.format))
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .push_next(&mut external);
+    let plane_layouts;
+    let mut explicit_modifier;
+    if let Some((modifier, layout)) = modifier {
+        plane_layouts = [layout];
+        explicit_modifier = vk::ImageDrmFormatModifierExplicitCreateInfoEXT::default()
+            .drm_format_modifier(modifier)
+            .plane_layouts(&plane_layouts);
+        info = info
+            .tiling(vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT)
+            .push_next(&mut explicit_modifier);
+    }
+
+    let raw = device.raw_device();
+    let image = unsafe { raw.create_image(&info, None) }.map_err(|e|
+        vulkan_error("vkCreateImage", e)
+    )?;
+    Ok(ExternalImage { device: raw.clone(), image, memory: vk::DeviceMemory::null() })
+}
+
+/// Fail unless the context's device was opened with `extensions`
+fn require_extensions(device: &hal::vulkan::Device, extensions: &[&'static CStr]) -> Result<()> {
+    let enabled = device.enabled_device_extensions();
+    match extensions.iter().find(|extension| !enabled.contains(extension)) {
+        Some(missing) => {
+            let message = format!(
+                "{} is not enabled; create the context with GpuConfig::external_memory",
+                missing.to_string_lossy()
+            );
+            Err(GeepuError::TextureError(message))
+        }
+        None => Ok(()),
+    }
+}
+
+fn import_image(
+    device: &hal::vulkan::Device,
+    memory: ExternalMemory,
+    descriptor: &wgpu::TextureDescriptor
+) -> Result<ExternalImage> {
+    require_extensions(device, memory.extensions())?;
+    let modifier = match &memory {
+        #[cfg(unix)]
+        ExternalMemory::DmaBuf { modifier, offset, row_pitch, .. } => {
+            let layout = vk::SubresourceLayout::default().offset(*offset).row_pitch(*row_pitch);
+            Some((*modifier, layout))
+        }
+        _ => None,
+    };
+    let mut image = create_external_image(device, descriptor, memory.handle_type(), modifier)?;
+    let raw = device.raw_device();
+    let requirements = unsafe { raw.get_image_memory_requirements(image.image) };
+    let memory_types = requirements.memory_type_bits & unsafe { memory.memory_type_bits(device)? };
+    let memory_type = memory_type(device, memory_types)?;
+    image.memory = unsafe { memory.import(raw, image.image, requirements.size, memory_type)? };
+    unsafe { raw.bind_image_memory(image.image, image.memory, 0) }.map_err(|e|
+        vulkan_error("vkBindImageMemory", e)
+    )?;
+    Ok(image)
+}
+
+fn export_image(
+    device: &hal::vulkan::Device,
+    descriptor: &wgpu::TextureDescriptor
+) -> Result<(ExternalImage, ExternalMemory, u64)> {
+    #[cfg(unix)]
+    let handle_type = vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD;
+    #[cfg(windows)]
+    let handle_type = vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32;
+    #[cfg(unix)]
+    require_extensions(device, &[ash::khr::external_memory_fd::NAME])?;
+    #[cfg(windows)]
+    require_extensions(device, &[ash::khr::external_memory_win32::NAME])?;
+
+    let mut image = create_external_image(device, descriptor, handle_type, None)?;
+    let raw = device.raw_device();
+    let requirements = unsafe { raw.get_image_memory_requirements(image.image) };
+    let mut export = vk::ExportMemoryAllocateInfo::default().handle_types(handle_type);
+    let mut dedicated = vk::MemoryDedicatedAllocateInfo::default().image(image.image);
+    let info = vk::MemoryAllocateInfo::default()
+        .allocation_size(requirements.size)
+        .memory_type_index(memory_type(device, requirements.memory_type_bits)?)
+        .push_next(&mut export)
+        .push_next(&mut dedicated);
+    image.memory = unsafe { raw.allocate_memory(&info, None) }.map_err(|e|
+        vulkan_error("vkAllocateMemory", e)
+    )?;
+    unsafe { raw.bind_image_memory(image.image, image.memory, 0) }.map_err(|e|
+        vulkan_error("vkBindImageMemory", e)
+    )?;
+
+    let instance = device.shared_instance().raw_instance();
+    #[cfg(unix)]
+    let handle = {
+        let functions = ash::khr::external_memory_fd::Device::new(instance, raw);
+        let info = vk::MemoryGetFdInfoKHR::default()
+            .memory(image.memory)
+            .handle_type(handle_type);
+        let fd = unsafe { functions.get_memory_fd(&info) }.map_err(|e|
+            vulkan_error("vkGetMemoryFdKHR", e)
+        )?;
+        // SAFETY: vkGetMemoryFdKHR hands out a new descriptor owned by the caller
+        ExternalMemory::OpaqueFd(unsafe { OwnedFd::from_raw_fd(fd) })
+    };
+    #[cfg(windows)]
+    let handle = {
+        let functions = ash::khr::external_memory_win32::Device::new(instance, raw);
+        let info = vk::MemoryGetWin32HandleInfoKHR::default()
+            .memory(image.memory)
+            .handle_type(handle_type);
+        let handle = unsafe { functions.get_memory_win32_handle(&info) }.map_err(|e|
+            vulkan_error("vkGetMemoryWin32HandleKHR", e)
+        )?;
+        ExternalMemory::OpaqueWin32(handle)
+    };
+    Ok((image, handle, requirements.size))
+}
+
+/// Device-local memory type among `memory_types` if there is one, else the first
+fn memory_type(device: &hal::vulkan::Device, memory_types: u32) -> Result<u32> {
+    let properties = unsafe {
+        device
+            .shared_instance()
+            .raw_instance()
+            .get_physical_device_memory_properties(device.raw_physical_device())
+    };
+    (0..properties.memory_type_count)
+        .filter(|index| memory_types & (1 << index) != 0)
+        .min_by_key(|&index| {
+            let flags = properties.memory_types[index as usize].property_flags;
+            !flags.contains(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+        })
+        .ok_or_else(|| {
+            GeepuError::TextureError("No memory type can hold the external image".to_string())
+        })
+}
+
+/// Vulkan usage flags matching `usage`
+fn image_usage(usage: wgpu::TextureUsages, format: wgpu::TextureFormat) -> vk::ImageUsageFlags {
+    let mut flags = vk::ImageUsageFlags::empty();
+    if usage.contains(wgpu::TextureUsages::COPY_SRC) {
+        flags |= vk::ImageUsageFlags::TRANSFER_SRC;
+    }
+    if usage.contains(wgpu::TextureUsages::COPY_DST) {
+        flags |= vk::ImageUsageFlags::TRANSFER_DST;
+    }
+    if usage.contains(wgpu::TextureUsages::TEXTURE_BINDING) {
+        flags |= vk::ImageUsageFlags::SAMPLED;
+    }
+    if usage.contains(wgpu::TextureUsages::STORAGE_BINDING) {
+        flags |= vk::ImageUsageFlags::STORAGE;
+    }
+    if usage.contains(wgpu::TextureUsages::RENDER_ATTACHMENT) {
+        flags |= if format.is_depth_stencil_format() {
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+        } else {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+        };
+    }
+    flags
+}
+
+/// Vulkan format of the color formats images are usually shared in
+fn vulkan_format(format: wgpu::TextureFormat) -> Result<vk::Format> {
+    use wgpu::TextureFormat as F;
+    Ok(match format {
+        F::R8Unorm => vk::Format::R8_UNORM,
+        F::Rg8Unorm => vk::Format::R8G8_UNORM,
+        F::R16Unorm => vk::Format::R16_UNORM,
+        F::Rg16Unorm => vk::Format::R16G16_UNORM,
+        F::Rgba8Unorm => vk::Format::R8G8B8A8_UNORM,
+        F::Rgba8UnormSrgb => vk::Format::R8G8B8A8_SRGB,
+        F::Bgra8Unorm => vk::Format::B8G8R8A8_UNORM,
+        F::Bgra8UnormSrgb => vk::Format::B8G8R8A8_SRGB,
+        F::Rgb10a2Unorm => vk::Format::A2B10G10R10_UNORM_PACK32,
+        F::Rgba16Float => vk::Format::R16G16B16A16_SFLOAT,
+        F::Rgba32Float => vk::Format::R32G32B32A32_SFLOAT,
+        F::R32Float => vk::Format::R32_SFLOAT,
+        F::Depth32Float => vk::Format::D32_SFLOAT,
+        other => {
+            return Err(
+                GeepuError::TextureError(
+                    format!("{:?} textures cannot be shared through external memory", other)
+                )
+            );
+        }
+    })
+}
+
+fn not_vulkan() -> GeepuError {
+    GeepuError::TextureError("External memory needs a Vulkan-backed context".to_string())
+}
+
+fn vulkan_error(call: &str, result: vk::Result) -> GeepuError {
+    GeepuError::Other(format!("{} failed: {}", call, result))
+}
@@ -2,22 +2,180 @@
 //!
 //! This library provides a simplified interface to wgpu while maintaining
 //! performance and zero-cost abstractions.
+//!
+//! The same code runs natively and in the browser (`wasm32-unknown-unknown`
+//! with WebGPU). Use [`spawn`] as the entry point so the async setup blocks on
+//! native targets and is scheduled on the browser event loop on the web:
+//!
+//! ```no_run
+//! use geepu::*;
+//!
+//! async fn run() -> Result<()> {
+//!     let context = GpuContext::new().await?;
+//!     let input = TypedBuffer::storage(&context, &[1.0f32, 2.0, 3.0, 4.0])?;
+//!     let staging = StagingBuffer::new(&context, input.size_bytes())?;
+//!
+//!     let mut commands = ComputeCommands::new(&context, Some("Readback"));
+//!     staging.copy_from_buffer(commands.encoder(), input.buffer(), None);
+//!     commands.submit(&context);
+//!
+//!     // Never blocks the browser thread; resolves once the GPU copy is done.
+//!     let values: Vec<f32> = staging.read_data(&context).await?;
+//!     assert_eq!(values, [1.0, 2.0, 3.0, 4.0]);
+//!     Ok(())
+//! }
+//!
+//! geepu::spawn(async {
+//!     if let Err(e) = run().await {
+//!         eprintln!("{}", e);
+//!     }
+//! });
+//! ```
+//!
+//! In the browser, create the context from a canvas with
+//! `GpuContext::new_with_canvas`, or from a winit window attached to one.
+
+// wgpu handles are not Send/Sync on the web, but the shared Arc handles are
+// still what native multi-threaded users need.
+#![cfg_attr(target_arch = "wasm32", allow(clippy::arc_with_non_send_sync))]
 
 pub mod context;
+pub mod camera;
+pub mod globals;
+pub mod config;
 pub mod buffer;
+pub mod packing;
+pub mod streaming;
 pub mod texture;
+pub mod compressed_texture;
 pub mod pipeline;
+pub mod shader;
 pub mod render;
 pub mod compute;
+pub mod batch;
+pub mod mesh;
+pub mod lod;
+pub mod dynamic_mesh;
+pub mod instance;
+pub mod indirect;
+pub mod material;
+pub mod animation;
+pub mod draw_queue;
+pub mod asset_loader;
+pub mod assets;
+#[cfg(feature = "gltf")]
+pub mod gltf_loader;
+pub mod primitives;
+pub mod debug_draw;
+pub mod polyline;
+pub mod point_cloud;
+pub mod debug_view;
+pub mod billboard;
+pub mod picking;
+pub mod ui;
+#[cfg(feature = "imgui")]
+pub mod imgui_backend;
+pub mod skybox;
+pub mod shadertoy;
+pub mod ibl;
+pub mod culling;
+pub mod mipmap;
+pub mod hiz;
+pub mod bloom;
+pub mod taa;
+pub mod dither;
+pub mod color;
+pub mod abuffer;
+pub mod decal;
+pub mod profiler;
+pub mod overlay;
+pub mod query;
+pub mod resources;
+pub mod validation;
+#[cfg(feature = "renderdoc")]
+pub mod capture;
+#[cfg(all(feature = "vulkan-interop", not(any(target_arch = "wasm32", target_vendor = "apple"))))]
+pub mod interop;
 pub mod error;
+mod profiling;
+mod describe;
+mod command_dump;
+mod upload;
+mod mesh_optimize;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod recording;
+#[cfg(all(feature = "image", not(target_arch = "wasm32")))]
+pub mod testing;
+#[cfg(feature = "window")]
+pub mod window;
+#[cfg(all(feature = "window", not(target_arch = "wasm32")))]
+pub mod app;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
 
 pub use context::*;
+pub use camera::*;
+pub use globals::*;
+pub use config::*;
 pub use buffer::*;
+pub use packing::*;
+pub use streaming::*;
 pub use texture::*;
+pub use compressed_texture::*;
 pub use pipeline::*;
+pub use shader::*;
 pub use render::*;
 pub use compute::*;
+pub use batch::*;
+pub use mesh::*;
+pub use lod::*;
+pub use dynamic_mesh::*;
+pub use instance::*;
+pub use indirect::*;
+pub use material::*;
+pub use animation::*;
+pub use draw_queue::*;
+pub use asset_loader::*;
+pub use assets::*;
+pub use debug_draw::*;
+pub use polyline::*;
+pub use point_cloud::*;
+pub use debug_view::*;
+pub use billboard::*;
+pub use picking::*;
+pub use ui::*;
+#[cfg(feature = "imgui")]
+pub use imgui_backend::*;
+pub use skybox::*;
+pub use shadertoy::*;
+pub use ibl::*;
+pub use culling::*;
+pub use mipmap::*;
+pub use hiz::*;
+pub use bloom::*;
+pub use taa::*;
+pub use dither::*;
+pub use color::*;
+pub use abuffer::*;
+pub use decal::*;
+pub use profiler::*;
+pub use overlay::*;
+pub use query::*;
+pub use resources::*;
+pub use validation::*;
+#[cfg(feature = "renderdoc")]
+pub use capture::*;
+#[cfg(all(feature = "vulkan-interop", not(any(target_arch = "wasm32", target_vendor = "apple"))))]
+pub use interop::*;
+#[cfg(feature = "gltf")]
+pub use gltf_loader::*;
 pub use error::*;
+#[cfg(feature = "window")]
+pub use window::*;
+#[cfg(all(feature = "window", not(target_arch = "wasm32")))]
+pub use app::*;
+#[cfg(feature = "gamepad")]
+pub use gamepad::*;
 
 // Re-export commonly used wgpu types
 pub use wgpu::{
@@ -42,6 +200,13 @@ pub use wgpu::{
     CompareFunction,
     StencilState,
     DepthBiasState,
+    Backends,
+    Dx12Compiler,
+    Gles3MinorVersion,
+    InstanceFlags,
+    MemoryHints,
+    PresentMode,
+    PowerPreference,
 };
 
 // Re-export bytemuck for vertex data
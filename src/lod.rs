@@ -0,0 +1,97 @@
+use crate::{ Camera, CullInstance, MeshData, SubMesh };
+use glam::{ Mat4, Vec3 };
+
+/// One level of detail: the submesh drawn while the object is nearer than `distance`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodLevel {
+    pub submesh: u32,
+    pub distance: f32,
+}
+
+/// Discrete levels of detail stored as submeshes of one mesh.
+///
+/// Levels are ordered by switch distance; [`select`](Self::select) returns the
+/// first level whose distance is beyond the object, or `None` once the object
+/// is past the last one, so distant objects drop out entirely. `bias` scales
+/// every distance (above 1 keeps detailed levels longer).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshLod {
+    levels: Vec<LodLevel>,
+    bias: f32,
+}
+
+impl MeshLod {
+    pub fn new(mut levels: Vec<LodLevel>) -> Self {
+        levels.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        Self { levels, bias: 1.0 }
+    }
+
+    /// Level `i` draws submesh `i` up to `distances[i]`
+    pub fn from_distances(distances: &[f32]) -> Self {
+        Self::new(
+            distances
+                .iter()
+                .enumerate()
+                .map(|(submesh, &distance)| LodLevel { submesh: submesh as u32, distance })
+                .collect()
+        )
+    }
+
+    pub fn bias(mut self, bias: f32) -> Self {
+        self.bias = bias;
+        self
+    }
+
+    pub fn levels(&self) -> &[LodLevel] {
+        &self.levels
+    }
+
+    /// Submesh to draw for an object `distance` away from the camera
+    pub fn select(&self, distance: f32) -> Option<u32> {
+        self.levels
+            .iter()
+            .find(|level| distance < level.distance * self.bias)
+            .map(|level| level.submesh)
+    }
+
+    /// [`select`](Self::select) by the distance from the camera to a world-space point
+    pub fn select_for(&self, camera: &Camera, center: [f32; 3]) -> Option<u32> {
+        self.select(Vec3::from(center).distance(Vec3::from(camera.position)))
+    }
+
+    /// A [`GpuCuller`](crate::GpuCuller) instance drawing the level for the
+    /// distance to its bounds' center, or `None` past the last level.
+    /// Rebuild the instances with this each frame before culling.
+    pub fn cull_instance(
+        &self,
+        camera: &Camera,
+        model: [[f32; 4]; 4],
+        aabb_min: [f32; 3],
+        aabb_max: [f32; 3]
+    ) -> Option<CullInstance> {
+        let center = (Vec3::from(aabb_min) + Vec3::from(aabb_max)) * 0.5;
+        let center = Mat4::from_cols_array_2d(&model).transform_point3(center);
+        self.select_for(camera, center.to_array())
+            .map(|submesh| CullInstance::new(model, aabb_min, aabb_max, submesh))
+    }
+}
+
+impl<V> MeshData<V> where V: bytemuck::Pod {
+    /// Concatenate levels of detail into one mesh with a submesh per level,
+    /// for use with [`Mesh::with_submeshes`](crate::Mesh::with_submeshes) and [`MeshLod`].
+    /// Indices stay relative to their level through `base_vertex`, so each level
+    /// can still use 16-bit indices.
+    pub fn combine_lods(levels: &[Self]) -> (Self, Vec<SubMesh>) {
+        let mut combined = Self { vertices: Vec::new(), indices: Vec::new() };
+        let mut submeshes = Vec::with_capacity(levels.len());
+        for level in levels {
+            let start = combined.indices.len() as u32;
+            let base_vertex = combined.vertices.len() as i32;
+            combined.vertices.extend_from_slice(&level.vertices);
+            combined.indices.extend_from_slice(&level.indices);
+            let end = combined.indices.len() as u32;
+            submeshes.push(SubMesh::new(start..end).base_vertex(base_vertex));
+        }
+        (combined, submeshes)
+    }
+}
@@ -1,11 +1,4 @@
 use geepu::*;
-use winit::{
-    application::ApplicationHandler,
-    event::WindowEvent,
-    event_loop::{ ActiveEventLoop, ControlFlow, EventLoop },
-    window::{ Window, WindowId },
-};
-use std::sync::Arc;
 
 // Define a simple vertex structure
 #[repr(C)]
@@ -15,184 +8,110 @@ struct Vertex {
     color: [f32; 3],
 }
 
-struct App {
-    window: Option<Arc<Window>>,
-    context: Option<GpuContext>,
+#[derive(Default)]
+struct Triangle {
     vertex_buffer: Option<TypedBuffer<Vertex>>,
     pipeline: Option<RenderPipeline>,
 }
 
-impl Default for App {
-    fn default() -> Self {
-        Self {
-            window: None,
-            context: None,
-            vertex_buffer: None,
-            pipeline: None,
-        }
-    }
-}
+impl App for Triangle {
+    fn init(&mut self, context: &GpuContext) -> Result<()> {
+        // Create triangle vertices
+        let vertices = [
+            Vertex { position: [0.0, 0.5, 0.0], color: [1.0, 0.0, 0.0] },
+            Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 1.0, 0.0] },
+            Vertex { position: [0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0] },
+        ];
 
-impl ApplicationHandler for App {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        if self.window.is_none() {
-            let window_attributes = Window::default_attributes()
-                .with_title("Geepu Example")
-                .with_inner_size(winit::dpi::LogicalSize::new(800, 600));
-
-            let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
-
-            // Create GPU context
-            let context = pollster::block_on(GpuContext::new_with_window(window.clone())).unwrap();
-
-            // Create triangle vertices
-            let vertices = [
-                Vertex { position: [0.0, 0.5, 0.0], color: [1.0, 0.0, 0.0] },
-                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 1.0, 0.0] },
-                Vertex { position: [0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0] },
-            ];
-
-            // Create vertex buffer
-            let vertex_buffer = TypedBuffer::vertex(&context, &vertices).unwrap();
-
-            // Create vertex layout
-            let vertex_layout =
-                vertex_layout![
-                0 => VertexFormat::Float32x3, // position
-                1 => VertexFormat::Float32x3, // color
-            ];
-
-            // Simple vertex shader
-            let vertex_shader =
-                r#"
-                struct VertexInput {
-                    @location(0) position: vec3<f32>,
-                    @location(1) color: vec3<f32>,
-                }
+        // Create vertex buffer
+        let vertex_buffer = TypedBuffer::vertex(context, &vertices)?;
 
-                struct VertexOutput {
-                    @builtin(position) clip_position: vec4<f32>,
-                    @location(0) color: vec3<f32>,
-                }
+        // Create vertex layout
+        let vertex_layout =
+            vertex_layout![
+            0 => VertexFormat::Float32x3, // position
+            1 => VertexFormat::Float32x3, // color
+        ];
 
-                @vertex
-                fn vs_main(model: VertexInput) -> VertexOutput {
-                    var out: VertexOutput;
-                    out.color = model.color;
-                    out.clip_position = vec4<f32>(model.position, 1.0);
-                    return out;
-                }
-            "#;
-
-            // Simple fragment shader
-            let fragment_shader =
-                r#"
-                @fragment
-                fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-                    return vec4<f32>(in.color, 1.0);
-                }
+        // Simple vertex shader
+        let vertex_shader =
+            r#"
+            struct VertexInput {
+                @location(0) position: vec3<f32>,
+                @location(1) color: vec3<f32>,
+            }
 
-                struct VertexOutput {
-                    @builtin(position) clip_position: vec4<f32>,
-                    @location(0) color: vec3<f32>,
-                }
-            "#;
-
-            // Create render pipeline
-            let surface_format = context.surface_format().unwrap();
-            let pipeline = RenderPipeline::simple(
-                &context,
-                vertex_shader,
-                fragment_shader,
-                &[vertex_layout],
-                surface_format,
-                Some("Triangle Pipeline")
-            ).unwrap();
-
-            self.window = Some(window);
-            self.context = Some(context);
-            self.vertex_buffer = Some(vertex_buffer);
-            self.pipeline = Some(pipeline);
-        }
-    }
-
-    fn window_event(
-        &mut self,
-        event_loop: &ActiveEventLoop,
-        _window_id: WindowId,
-        event: WindowEvent
-    ) {
-        match event {
-            WindowEvent::CloseRequested => {
-                event_loop.exit();
-            }
-            WindowEvent::Resized(physical_size) => {
-                if let Some(context) = &mut self.context {
-                    context.resize(physical_size).unwrap();
-                }
+            struct VertexOutput {
+                @builtin(position) clip_position: vec4<f32>,
+                @location(0) color: vec3<f32>,
             }
-            WindowEvent::RedrawRequested => {
-                if
-                    let (Some(context), Some(vertex_buffer), Some(pipeline)) = (
-                        &self.context,
-                        &self.vertex_buffer,
-                        &self.pipeline,
-                    )
-                {
-                    // Get surface texture
-                    let output = match context.get_current_texture() {
-                        Ok(output) => output,
-                        Err(_) => {
-                            return;
-                        }
-                    };
-
-                    let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-                    // Create render commands
-                    let mut commands = RenderCommands::new(context, Some("Frame Commands"));
-
-                    // Begin render pass
-                    let color_attachments = [Some(color_attachment(&view, Some(Color::BLACK)))];
-                    let mut render_pass = commands.begin_render_pass(
-                        &color_attachments,
-                        None,
-                        Some("Main Pass")
-                    );
-
-                    // Set pipeline and draw triangle
-                    render_pass.set_pipeline(pipeline);
-                    render_pass.set_vertex_buffer(0, vertex_buffer);
-                    render_pass.draw(0..3, 0..1);
-
-                    // Finish render pass (drop it)
-                    drop(render_pass);
-
-                    // Submit commands
-                    commands.submit(context);
-
-                    // Present the frame
-                    output.present();
-                }
 
-                // Request next frame
-                if let Some(window) = &self.window {
-                    window.request_redraw();
-                }
+            @vertex
+            fn vs_main(model: VertexInput) -> VertexOutput {
+                var out: VertexOutput;
+                out.color = model.color;
+                out.clip_position = vec4<f32>(model.position, 1.0);
+                return out;
             }
-            _ => {}
-        }
+        "#;
+
+        // Simple fragment shader
+        let fragment_shader =
+            r#"
+            @fragment
+            fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+                return vec4<f32>(in.color, 1.0);
+            }
+
+            struct VertexOutput {
+                @builtin(position) clip_position: vec4<f32>,
+                @location(0) color: vec3<f32>,
+            }
+        "#;
+
+        // Create render pipeline
+        let surface_format = context.surface_format().unwrap_or(TextureFormat::Bgra8UnormSrgb);
+        let pipeline = RenderPipeline::simple_multisampled(
+            context,
+            vertex_shader,
+            fragment_shader,
+            &[vertex_layout],
+            surface_format,
+            context.sample_count(),
+            Some("Triangle Pipeline")
+        )?;
+
+        self.vertex_buffer = Some(vertex_buffer);
+        self.pipeline = Some(pipeline);
+        Ok(())
     }
-}
 
-fn main() -> Result<()> {
-    let event_loop = EventLoop::new().unwrap();
-    event_loop.set_control_flow(ControlFlow::Poll);
+    fn render(&mut self, frame: &mut Frame) -> Result<()> {
+        let (Some(vertex_buffer), Some(pipeline)) = (&self.vertex_buffer, &self.pipeline) else {
+            return Ok(());
+        };
 
-    let mut app = App::default();
-    event_loop.run_app(&mut app).unwrap();
+        // Begin render pass
+        let color_attachments = [
+            Some(frame.context.surface_attachment(&frame.view, Some(Color::BLACK)))
+        ];
+        let mut render_pass = frame.commands.begin_render_pass(
+            &color_attachments,
+            None,
+            Some("Main Pass")
+        );
 
-    Ok(())
+        // Set pipeline and draw triangle
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_vertex_buffer(0, vertex_buffer);
+        render_pass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    // The loop submits the frame's commands and presents after each render
+    WindowConfig::new("Geepu Example").size(800, 600).run(Triangle::default())
 }
 
 #[cfg(test)]
@@ -213,5 +132,1181 @@ mod tests {
         assert!(buffer.is_ok());
         let buffer = buffer.unwrap();
         assert_eq!(buffer.len(), 4);
+        assert!(buffer.write_at(&context, usize::MAX, &[1.0]).is_err());
+    }
+
+    #[test]
+    fn test_unaligned_buffer_readback() {
+        let context = pollster::block_on(GpuContext::new()).unwrap();
+        let usage = BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
+
+        // 9 bytes, padded to 12 at creation so the last element can be copied back
+        let rgb = TypedBuffer::<[u8; 3]>::empty(&context, 3, usage).unwrap();
+        assert_eq!(pollster::block_on(rgb.read_at(&context, 2)).unwrap(), [0; 3]);
+
+        let shorts = TypedBuffer::new(&context, &[1u16, 2, 3], usage).unwrap();
+        assert_eq!(pollster::block_on(shorts.read_range(&context, 1..3)).unwrap(), [2, 3]);
+        let odd = TypedBuffer::<u16>::empty(&context, 3, usage).unwrap();
+        assert_eq!(pollster::block_on(odd.read(&context)).unwrap(), [0; 3]);
+    }
+
+    #[test]
+    fn test_backends_by_name() {
+        let config = GpuConfig::new().backends_by_name("vulkan,dx12");
+        assert_eq!(config.backends, Backends::VULKAN | Backends::DX12);
+    }
+
+    #[test]
+    fn test_primitive_winding() {
+        let primitives = [
+            MeshData::cube(),
+            MeshData::sphere(8),
+            MeshData::plane(2.0),
+            MeshData::torus(1.0, 0.25, 16, 8),
+            MeshData::cylinder(0.5, 1.0, 12),
+            MeshData::fullscreen_quad(),
+        ];
+        for data in &primitives {
+            for tri in data.indices.chunks(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| data.vertices[tri[i] as usize]);
+                let e1 = [0, 1, 2].map(|i| b.position[i] - a.position[i]);
+                let e2 = [0, 1, 2].map(|i| c.position[i] - a.position[i]);
+                let face = [
+                    e1[1] * e2[2] - e1[2] * e2[1],
+                    e1[2] * e2[0] - e1[0] * e2[2],
+                    e1[0] * e2[1] - e1[1] * e2[0],
+                ];
+                let normal = [0, 1, 2].map(|i| a.normal[i] + b.normal[i] + c.normal[i]);
+                let facing: f32 = (0..3).map(|i| face[i] * normal[i]).sum();
+                // Degenerate triangles at sphere poles have no face normal
+                assert!(facing > -1e-5, "triangle {:?} is wound clockwise", tri);
+            }
+        }
+    }
+
+    #[test]
+    fn test_instance_layout_follows_mesh_vertex() {
+        let mesh = MeshVertex::layout();
+        let instance = InstanceTransform::layout();
+        assert_eq!(instance.step_mode, wgpu::VertexStepMode::Instance);
+        assert_eq!(instance.array_stride, 64);
+        let last_mesh_location = mesh.attributes.iter().map(|a| a.shader_location).max().unwrap();
+        assert_eq!(instance.attributes[0].shader_location, last_mesh_location + 1);
+    }
+
+    #[test]
+    fn test_camera_clip_space() {
+        let camera = Camera::perspective([0.0, 0.0, 5.0], [0.0; 3], 1.0, 1.0, 0.1, 100.0);
+        let m = camera.view_projection_matrix();
+        let clip = |p: [f32; 3]| {
+            let v = [0, 1, 2, 3].map(|r| (0..3).map(|c| m[c][r] * p[c]).sum::<f32>() + m[3][r]);
+            [v[0] / v[3], v[1] / v[3], v[2] / v[3]]
+        };
+        // The target lands in the centre of the screen, between the clip planes
+        let center = clip([0.0; 3]);
+        assert!(center[0].abs() < 1e-5 && center[1].abs() < 1e-5);
+        assert!(center[2] > 0.0 && center[2] < 1.0);
+        // Near plane maps to depth 0 (wgpu convention), and +Y stays up
+        assert!(clip([0.0, 0.0, 4.9])[2].abs() < 1e-4);
+        assert!(clip([0.0, 1.0, 0.0])[1] > 0.0);
+    }
+
+    #[test]
+    fn test_frustum_culls_boxes_outside_view() {
+        let camera = Camera::perspective([0.0, 0.0, 5.0], [0.0; 3], 1.0, 1.0, 0.1, 100.0);
+        let frustum = camera.frustum();
+        assert!(frustum.intersects_aabb([-1.0; 3], [1.0; 3]));
+        // Behind the camera, beyond the far plane, and far off to the side
+        assert!(!frustum.intersects_aabb([-1.0, -1.0, 6.0], [1.0, 1.0, 8.0]));
+        assert!(!frustum.intersects_aabb([-1.0, -1.0, -200.0], [1.0, 1.0, -150.0]));
+        assert!(!frustum.intersects_aabb([50.0, -1.0, -1.0], [52.0, 1.0, 1.0]));
+        // A box straddling the left plane still counts
+        assert!(frustum.intersects_aabb([-3.0, -0.5, -0.5], [-1.5, 0.5, 0.5]));
+    }
+
+    #[test]
+    fn test_draw_queue_sort_order() {
+        let mut queue = DrawQueue::new();
+        let keys = [
+            SortKey::transparent(2.0),
+            SortKey::opaque(1, 0, 5.0),
+            SortKey::transparent(8.0),
+            SortKey::opaque(0, 1, 3.0),
+            SortKey::opaque(0, 1, 1.0),
+            SortKey::opaque(0, 0, 9.0).layer(1),
+        ];
+        for key in keys {
+            queue.submit(key, |_| {});
+        }
+        let order: Vec<(u64, f32)> = queue
+            .sorted_keys()
+            .iter()
+            .map(|key| (key.pipeline, key.depth))
+            .collect();
+        // Opaque grouped by pipeline and front-to-back, then transparent back-to-front
+        assert_eq!(order, [(0, 1.0), (0, 3.0), (1, 5.0), (0, 8.0), (0, 2.0), (0, 9.0)]);
+    }
+
+    #[test]
+    fn test_draw_queue_prepass() {
+        let mut queue = DrawQueue::new();
+        queue.submit_with_prepass(SortKey::opaque(0, 0, 1.0), |_| {}, |_| {});
+        queue.submit_with_prepass(SortKey::transparent(2.0), |_| {}, |_| {});
+        queue.submit(SortKey::opaque(1, 0, 3.0), |_| {});
+        // Only opaque draws get a depth-only version
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.prepass_len(), 1);
+        assert!(MaterialShader::basic().depth_prepass);
+    }
+
+    #[test]
+    fn test_decal_shader() {
+        let shader = MaterialShader::basic_decal();
+        if let Err(e) = check_wgsl("Basic Decal", None, &shader.source) {
+            panic!("{}", e);
+        }
+        assert!(shader.is_transparent() && !shader.depth_write && !shader.depth_prepass);
+    }
+
+    #[test]
+    fn test_animation_sampling_and_joint_matrices() {
+        let joint = |parent, translation| Joint {
+            name: None,
+            parent,
+            rest: JointTransform::new(translation, [0.0, 0.0, 0.0, 1.0], [1.0; 3]),
+            inverse_bind: glam::Mat4::IDENTITY.to_cols_array_2d(),
+        };
+        let skeleton = Skeleton::new(vec![joint(None, [0.0; 3]), joint(Some(0), [0.0, 1.0, 0.0])]);
+        let clip = AnimationClip::new(
+            None,
+            vec![AnimationChannel {
+                target: 0,
+                times: vec![0.0, 2.0],
+                values: ChannelValues::Translation(vec![[0.0; 3], [4.0, 0.0, 0.0]]),
+                interpolation: Interpolation::Linear,
+            }]
+        );
+        assert_eq!(clip.duration, 2.0);
+
+        let mut pose = skeleton.rest_pose();
+        clip.sample(0.5, &mut pose);
+        assert_eq!(pose.joints[0].translation, [1.0, 0.0, 0.0]);
+        // The child follows its parent
+        let globals = skeleton.global_transforms(&pose);
+        assert_eq!(globals[1][3], [1.0, 1.0, 0.0, 1.0]);
+        // Sampling past the end clamps, and blending halfway lands in between
+        clip.sample(10.0, &mut pose);
+        let blended = skeleton.rest_pose().blend(&pose, 0.5);
+        assert_eq!(blended.joints[0].translation, [2.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_ray_picking() {
+        let camera = Camera::perspective([0.0, 0.0, 5.0], [0.0; 3], 1.0, 1.0, 0.1, 100.0);
+        let ray = camera.screen_ray(50.0, 50.0, 100, 100);
+        let hit = ray.intersect_aabb([-1.0; 3], [1.0; 3]).unwrap();
+        assert!((hit - 3.9).abs() < 1e-3);
+        // Nearest box wins; one off to the side is missed
+        let objects = [
+            (ObjectId(1), [-1.0, -1.0, -5.0], [1.0, 1.0, -4.0]),
+            (ObjectId(2), [-1.0; 3], [1.0; 3]),
+            (ObjectId(3), [5.0, 5.0, 0.0], [6.0, 6.0, 1.0]),
+        ];
+        assert_eq!(ray.pick(objects).map(|(id, _)| id), Some(ObjectId(2)));
+        assert_eq!(camera.screen_ray(0.0, 0.0, 100, 100).pick([objects[1]]), None);
+    }
+
+    #[test]
+    fn test_compute_picker_shaders() {
+        for source in ComputePicker::shader_sources() {
+            if let Err(e) = check_wgsl("Compute Picking", Some(ShaderStages::COMPUTE), &source) {
+                panic!("{}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pixel_camera() {
+        let mut camera = Camera::pixels(800, 600);
+        let clip = |camera: &Camera, p: [f32; 3]| {
+            let m = glam::Mat4::from_cols_array_2d(&camera.view_projection_matrix());
+            m.project_point3(p.into()).to_array()
+        };
+        // Top-left pixel corner maps to the top-left of clip space, Y pointing down
+        assert_eq!(clip(&camera, [0.0, 0.0, 0.0])[..2], [-1.0, 1.0]);
+        assert_eq!(clip(&camera, [800.0, 600.0, 0.0])[..2], [1.0, -1.0]);
+        // Resizing keeps one unit per pixel
+        camera.set_viewport(400, 300);
+        assert_eq!(clip(&camera, [400.0, 300.0, 0.0])[..2], [1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_nine_slice_cells() {
+        let slice = NineSlice::new([0.0, 0.0, 32.0, 32.0], [8.0, 8.0, 8.0, 8.0]);
+        let cells = slice.cells([10.0, 20.0], [100.0, 50.0], [64.0, 64.0]);
+        assert_eq!(cells.len(), 9);
+        // Corners keep their texel size, the center stretches
+        assert_eq!(cells[0], ([10.0, 20.0], [8.0, 8.0], [0.0, 0.0, 0.125, 0.125]));
+        assert_eq!(cells[4], ([18.0, 28.0], [84.0, 34.0], [0.125, 0.125, 0.375, 0.375]));
+        assert_eq!(cells[8].0, [102.0, 62.0]);
+        // Too small for the borders: they shrink and the center disappears
+        let cells = slice.cells([0.0, 0.0], [8.0, 8.0], [64.0, 64.0]);
+        assert_eq!(cells.len(), 4);
+        assert_eq!(cells[3], ([4.0, 4.0], [4.0, 4.0], [0.375, 0.375, 0.5, 0.5]));
+    }
+
+    #[test]
+    fn test_polyline_shader() {
+        if let Err(e) = check_wgsl("Polyline", None, &PolylineRenderer::shader_source()) {
+            panic!("{}", e);
+        }
+        // Matches the instance attributes: start, width, end, padding, color
+        assert_eq!(std::mem::size_of::<LineSegment>(), 48);
+    }
+
+    #[test]
+    fn test_point_cloud_chunks() {
+        if let Err(e) = check_wgsl("Point Cloud", None, &PointCloudRenderer::shader_source()) {
+            panic!("{}", e);
+        }
+        assert_eq!(std::mem::size_of::<CloudPoint>(), 20);
+
+        // Two clusters far apart end up in separate chunks
+        let points: Vec<CloudPoint> = (0..8)
+            .map(|i| {
+                let x = if i % 2 == 0 { -10.0 } else { 10.0 };
+                CloudPoint::new([x + (i as f32) * 0.01, 0.0, -5.0], 2.0, [1.0; 4])
+            })
+            .collect();
+        let (sorted, chunks) = PointCloud::partition(&points, 4);
+        assert_eq!(sorted.len(), 8);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].max[0] < 0.0 && chunks[1].min[0] > 0.0);
+        assert_eq!(chunks[1].range, 4..8);
+
+        let camera = Camera::perspective([10.0, 0.0, 0.0], [10.0, 0.0, -5.0], 1.0, 1.0, 0.1, 50.0);
+        let frustum = camera.frustum();
+        let visible: Vec<_> = chunks
+            .iter()
+            .filter(|chunk| frustum.intersects_aabb(chunk.min, chunk.max))
+            .collect();
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0].range, 4..8);
+    }
+
+    #[test]
+    fn test_timing_history_window() {
+        let mut history = TimingHistory::new(2);
+        history.record("shadow", 1.0);
+        history.record("main", 4.0);
+        history.record("shadow", 2.0);
+        history.record("shadow", 6.0);
+        let report = history.report();
+        assert_eq!(report.len(), 2);
+        // Passes keep first-seen order; only the last two shadow samples remain
+        assert_eq!(report[0].label, "shadow");
+        assert_eq!((report[0].last_ms, report[0].average_ms, report[0].max_ms), (6.0, 4.0, 6.0));
+        assert_eq!(report[0].samples, 2);
+        assert_eq!(report[1].label, "main");
+    }
+
+    #[test]
+    fn test_bench_report() {
+        let report = BenchReport::from_samples(vec![4.0, 1.0, 3.0, 2.0], true, 1000);
+        assert_eq!((report.min_ms, report.median_ms, report.mean_ms), (1.0, 2.5, 2.5));
+        assert!((report.stddev_ms - (1.25f32).sqrt()).abs() < 1e-6);
+        assert_eq!(report.samples_ms, vec![4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(report.workgroups_per_second(), 400_000.0);
+        assert_eq!(report.bandwidth_gb_per_second(1_000_000_000), 400.0);
+        assert_eq!(BenchReport::from_samples(Vec::new(), false, 1).throughput(10), 0.0);
+    }
+
+    #[test]
+    fn test_frame_stats() {
+        let mut stats = FrameStats::new(2);
+        assert_eq!(stats.fps(), 0.0);
+        stats.record(std::time::Duration::from_millis(40));
+        stats.record(std::time::Duration::from_millis(10));
+        stats.record(std::time::Duration::from_millis(30));
+        // The 40 ms frame fell out of the window
+        assert_eq!(stats.history().count(), 2);
+        assert!((stats.average_ms() - 20.0).abs() < 1e-3);
+        assert!((stats.fps() - 50.0).abs() < 1e-2);
+        assert!((stats.max_ms() - 30.0).abs() < 1e-3);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_snapshot_compare() {
+        let expected = image::RgbaImage::from_pixel(4, 4, image::Rgba([200, 40, 40, 255]));
+        let mut actual = expected.clone();
+        actual.put_pixel(1, 2, image::Rgba([201, 40, 41, 255]));
+        actual.put_pixel(3, 3, image::Rgba([40, 200, 40, 255]));
+        let diff = geepu::testing::compare_images(&actual, &expected, 0.02).unwrap();
+        // The off-by-one pixel is within tolerance, the green one is not
+        assert_eq!(diff.mismatched, 1);
+        assert_eq!(diff.image.get_pixel(3, 3).0, [255, 0, 0, 255]);
+        assert!(diff.max_difference > 0.1);
+    }
+
+    #[test]
+    fn test_leak_report_format() {
+        let live = |kind, label: &str, size_bytes| LiveResource {
+            kind,
+            label: label.to_string(),
+            size_bytes,
+            backtrace: None,
+        };
+        let report = LeakReport {
+            resources: vec![
+                live(ResourceKind::Texture, "Shadow Map", 4096),
+                live(ResourceKind::Buffer, "TypedBuffer<f32>", 16)
+            ],
+        };
+        assert_eq!(report.total_bytes(), 4112);
+        let text = report.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines, [
+            "2 live resources (4112 bytes)",
+            "  Texture \"Shadow Map\" (4096 bytes)",
+            "  Buffer \"TypedBuffer<f32>\" (16 bytes)",
+        ]);
+        assert!(LeakReport::default().is_empty());
+    }
+
+    #[test]
+    fn test_gpu_memory_usage() {
+        let tracked = GpuMemoryUsage {
+            tracked_buffer_bytes: 256,
+            tracked_texture_bytes: 4096,
+            ..Default::default()
+        };
+        assert!(!tracked.is_reported());
+        assert_eq!(tracked.total_bytes(), 4352);
+        assert_eq!(tracked.to_string(), "tracked buffers 256 bytes, tracked textures 4096 bytes");
+
+        let reported = GpuMemoryUsage {
+            buffer_bytes: Some(1024),
+            texture_bytes: Some(8192),
+            allocations: Some(3),
+            ..tracked
+        };
+        assert_eq!(reported.total_bytes(), 9216);
+        assert!(reported.to_string().starts_with("buffers 1024 bytes, textures 8192 bytes in 3"));
+    }
+
+    #[cfg(feature = "counters")]
+    #[test]
+    fn test_gpu_memory_counters() {
+        let context = pollster::block_on(GpuContext::new()).unwrap();
+        let _buffer = TypedBuffer::<f32>::empty(&context, 1024, BufferUsages::STORAGE).unwrap();
+        let usage = context.gpu_memory_usage();
+        assert!(usage.buffer_bytes.is_some_and(|bytes| bytes >= 4096));
+    }
+
+    #[test]
+    fn test_validation_error_display() {
+        let error = GeepuError::Validation {
+            message: "Buffer size 0 is invalid".to_string(),
+            label: Some("Particles".to_string()),
+            source_op: "create_buffer",
+        };
+        assert_eq!(
+            error.to_string(),
+            "Validation error in create_buffer (Particles): Buffer size 0 is invalid"
+        );
+    }
+
+    #[test]
+    fn test_shader_diagnostic() {
+        let source = "@fragment\nfn fs_main() -> @location(0) vec4<f32> {\n    let x = 1 + ;\n}\n";
+        let Err(GeepuError::ShaderError(diagnostic)) =
+            check_wgsl("Triangle", Some(ShaderStages::FRAGMENT), source) else {
+            panic!("expected a shader error");
+        };
+        let span = diagnostic.span.unwrap();
+        assert_eq!((span.start_line, span.start_column), (3, 17));
+        assert_eq!(diagnostic.excerpt, ["    let x = 1 + ;"]);
+        assert!(diagnostic.to_string().contains("--> Triangle (fragment):3:17"));
+
+        // Validation errors carry naga's error chain as notes
+        let unbound = "@fragment\nfn fs_main() -> vec4<f32> {\n    return vec4<f32>(1.0);\n}\n";
+        let Err(GeepuError::ShaderError(diagnostic)) = check_wgsl("Unbound", None, unbound) else {
+            panic!("expected a shader error");
+        };
+        assert!(diagnostic.notes.iter().any(|note| note.contains("bindings")));
+
+        let valid = "@compute @workgroup_size(1)\nfn cs_main() {}\n";
+        assert!(check_wgsl("Noop", Some(ShaderStages::COMPUTE), valid).is_ok());
+    }
+
+    #[test]
+    fn test_vertex_input_validation() {
+        let source = "struct In {\n    @location(0) position: vec3<f32>,\n    \
+            @location(1) normal: vec3<f32>,\n    @location(2) joints: vec4<u32>,\n}\n\
+            @vertex\nfn vs_main(input: In) -> @builtin(position) vec4<f32> {\n    \
+            return vec4<f32>(input.position, 1.0);\n}\n";
+        let positions = vertex_layout!(0 => Float32x3);
+        let rest = vertex_layout!(1 => Snorm8x4, 2 => Uint16x4, 3 => Float32x2);
+        let layouts = [positions.clone(), rest];
+        assert!(check_vertex_inputs("Skinned", source, "vs_main", &layouts).is_ok());
+
+        let missing = vertex_layout!(1 => Float32x2);
+        let Err(GeepuError::ShaderError(diagnostic)) =
+            check_vertex_inputs("Skinned", source, "vs_main", &[positions.clone(), missing]) else {
+            panic!("expected a missing input error");
+        };
+        assert!(diagnostic.message.contains("`joints` at @location(2) is not provided"));
+
+        let wrong = vertex_layout!(1 => Float32x3, 2 => Float32x4);
+        let Err(GeepuError::ShaderError(diagnostic)) =
+            check_vertex_inputs("Skinned", source, "vs_main", &[positions, wrong]) else {
+            panic!("expected a type mismatch error");
+        };
+        assert!(diagnostic.message.contains("`joints` at @location(2) is vec4<u32>"));
+        assert!(diagnostic.message.contains("Float32x4"));
+        assert_eq!(diagnostic.span.unwrap().start_line, 4);
+        assert_eq!(diagnostic.excerpt, ["    @location(2) joints: vec4<u32>,"]);
+    }
+
+    #[test]
+    fn test_binding_reflection() {
+        let source = r#"
+            struct Params { count: u32 }
+            @group(1) @binding(0) var<uniform> params: Params;
+            @group(0) @binding(1) var<storage, read_write> data: array<f32>;
+            @group(0) @binding(0) var<storage, read> input: array<f32>;
+            @group(1) @binding(2) var noise: texture_2d<f32>;
+            @group(1) @binding(3) var noise_sampler: sampler;
+            @group(1) @binding(4) var output: texture_storage_2d<rgba8unorm, write>;
+
+            @compute @workgroup_size(64)
+            fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+                data[id.x] = input[id.x];
+            }
+        "#;
+        let bindings = reflect_bindings(source);
+        let summary: Vec<(u32, u32, &str, ShaderBindingKind)> = bindings
+            .iter()
+            .map(|b| (b.group, b.binding, b.name.as_str(), b.kind))
+            .collect();
+        assert_eq!(summary, [
+            (0, 0, "input", ShaderBindingKind::StorageBuffer { read_only: true }),
+            (0, 1, "data", ShaderBindingKind::StorageBuffer { read_only: false }),
+            (1, 0, "params", ShaderBindingKind::UniformBuffer),
+            (1, 2, "noise", ShaderBindingKind::Texture),
+            (1, 3, "noise_sampler", ShaderBindingKind::Sampler),
+            (1, 4, "output", ShaderBindingKind::StorageTexture),
+        ]);
+        assert!(reflect_bindings("not wgsl").is_empty());
+    }
+
+    #[test]
+    fn test_layout_reflection_through_helpers() {
+        let fragment = r#"
+            @group(0) @binding(0) var albedo: texture_2d<f32>;
+            @group(0) @binding(1) var albedo_sampler: sampler;
+            @group(0) @binding(2) var lookup: texture_2d<f32>;
+
+            fn sample_at(t: texture_2d<f32>, s: sampler, uv: vec2<f32>) -> vec4<f32> {
+                return textureSample(t, s, uv);
+            }
+            fn shade(uv: vec2<f32>) -> vec4<f32> {
+                return sample_at(albedo, albedo_sampler, uv);
+            }
+            @fragment fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+                return shade(position.xy) * textureLoad(lookup, vec2<i32>(position.xy), 0);
+            }
+        "#;
+        let groups = reflect_layout_entries(&[fragment], ShaderStages::VERTEX_FRAGMENT).unwrap();
+        // Sampled through a filtering sampler two calls down
+        assert_eq!(groups[0][0].ty, wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        });
+        assert_eq!(groups[0][1].visibility, ShaderStages::FRAGMENT);
+        assert_eq!(groups[0][2].ty, wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        });
+    }
+
+    #[test]
+    fn test_layout_reflection() {
+        let vertex = r#"
+            @group(0) @binding(0) var<uniform> camera: mat4x4<f32>;
+            @vertex fn vs_main() -> @builtin(position) vec4<f32> { return camera[0]; }
+        "#;
+        let fragment = r#"
+            @group(0) @binding(0) var<uniform> camera: mat4x4<f32>;
+            @group(2) @binding(0) var albedo: texture_2d_array<f32>;
+            @group(2) @binding(1) var<storage, read_write> counts: array<atomic<u32>>;
+            @group(2) @binding(2) var albedo_sampler: sampler;
+            @group(2) @binding(3) var lookup: texture_2d<f32>;
+            @fragment fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+                atomicAdd(&counts[0], 1u);
+                let texel = textureLoad(lookup, vec2<i32>(position.xy), 0);
+                return textureSample(albedo, albedo_sampler, position.xy, 0) * texel;
+            }
+        "#;
+        let groups = reflect_layout_entries(&[vertex, fragment], ShaderStages::VERTEX_FRAGMENT)
+            .unwrap();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].len(), 1);
+        assert_eq!(groups[0][0].visibility, ShaderStages::VERTEX_FRAGMENT);
+        assert!(groups[1].is_empty());
+        let bindings: Vec<u32> = groups[2].iter().map(|entry| entry.binding).collect();
+        assert_eq!(bindings, [0, 1, 2, 3]);
+        assert_eq!(groups[2][0].ty, wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2Array,
+            multisampled: false,
+        });
+        // Only textures sampled through a filtering sampler are filterable
+        assert_eq!(groups[2][3].ty, wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        });
+        // Visibility follows the stages that use each binding
+        assert_eq!(groups[2][1].visibility, ShaderStages::FRAGMENT);
+
+        // Writable storage never reaches the vertex stage
+        let writes_in_vertex = r#"
+            @group(0) @binding(0) var<storage, read_write> counts: array<atomic<u32>>;
+            @vertex fn vs_main() -> @builtin(position) vec4<f32> {
+                atomicAdd(&counts[0], 1u);
+                return vec4<f32>(0.0);
+            }
+        "#;
+        let error = reflect_layout_entries(&[writes_in_vertex], ShaderStages::VERTEX_FRAGMENT)
+            .unwrap_err();
+        assert!(matches!(error, GeepuError::PipelineError(_)));
+
+        let storage = "@group(0) @binding(0) var out: texture_storage_2d<r32float, write>;";
+        let groups = reflect_layout_entries(&[storage], ShaderStages::COMPUTE).unwrap();
+        assert_eq!(groups[0][0].ty, wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format: wgpu::TextureFormat::R32Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        });
+        let conflicting = [vertex, "@group(0) @binding(0) var camera: sampler;"];
+        assert!(reflect_layout_entries(&conflicting, ShaderStages::VERTEX).is_err());
+    }
+
+    #[test]
+    fn test_error_context() {
+        let failed: Result<()> = Err(GeepuError::TextureError("size is zero".to_string()));
+        let error = failed.context("creating shadow map").unwrap_err();
+        assert_eq!(error.to_string(), "creating shadow map: Texture error: size is zero");
+        assert!(matches!(error.root_cause(), GeepuError::TextureError(_)));
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn test_pass_validation() {
+        let mut state = PassState::new(Some("Main Pass"));
+        let error = state.check_draw().unwrap_err();
+        assert!(error.to_string().starts_with("Validation error in draw (Main Pass): no pipeline"));
+
+        state.set_pipeline(2, 1);
+        state.set_bind_group(0);
+        let error = state.check_draw().unwrap_err();
+        assert!(error.to_string().contains("bind group 1 required by the pipeline is not set"));
+        state.set_bind_group(1);
+        assert!(state.check_draw().unwrap_err().to_string().contains("vertex buffer slot 0"));
+        state.set_vertex_buffer(0);
+        assert!(state.check_draw().is_ok());
+
+        assert!(state.check_draw_indexed(&(0..3)).is_err());
+        state.set_index_buffer(6);
+        assert!(state.check_draw_indexed(&(0..6)).is_ok());
+        let error = state.check_draw_indexed(&(3..9)).unwrap_err();
+        assert!(error.to_string().contains("indices 3..9 are out of range"));
+        #[allow(clippy::reversed_empty_ranges)]
+        let error = state.check_draw_indexed(&(4..2)).unwrap_err();
+        assert!(matches!(error, GeepuError::Validation { source_op: "draw_indexed", .. }));
+    }
+
+    #[test]
+    fn test_debug_view_shaders() {
+        let positions_only = wgpu::VertexBufferLayout {
+            array_stride: 12,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+        };
+        for view in DebugView::ALL {
+            for layout in [MeshVertex::layout(), positions_only.clone()] {
+                let Some(source) = view.shader_source(&layout) else {
+                    assert_eq!(view, DebugView::Lit);
+                    continue;
+                };
+                if let Err(e) = check_wgsl("Debug View", None, &source) {
+                    panic!("{:?}: {}", view, e);
+                }
+            }
+        }
+        assert_eq!(DebugView::Wireframe.next(), DebugView::Lit);
+    }
+
+    #[test]
+    fn test_frame_globals_layout() {
+        assert_eq!(std::mem::size_of::<GlobalsUniform>(), 32);
+        let source = format!(
+            "{}\n@group(0) @binding(0) var<uniform> globals: Globals;\n\
+             @fragment fn main() -> @location(0) vec4<f32> {{\n\
+                 return vec4<f32>(globals.mouse / globals.resolution, globals.time, 1.0);\n\
+             }}",
+            FrameGlobals::WGSL
+        );
+        if let Err(e) = check_wgsl("Globals", None, &source) {
+            panic!("{}", e);
+        }
+    }
+
+    #[test]
+    fn test_shadertoy_source() {
+        let fragment =
+            "fn main_image(frag_coord: vec2<f32>) -> vec4<f32> {\n\
+                 let uv = frag_coord / globals.resolution;\n\
+                 let last = textureSample(channel1, channel1_sampler, uv);\n\
+                 return textureSample(channel0, channel0_sampler, uv) * 0.5 + last * 0.5;\n\
+             }\n";
+        let source = Shadertoy::shader_source(fragment, 2);
+        if let Err(e) = check_wgsl("Shadertoy", None, &source) {
+            panic!("{}", e);
+        }
+        assert!(check_wgsl("Shadertoy", None, &Shadertoy::shader_source(fragment, 1)).is_err());
+    }
+
+    #[test]
+    fn test_packed_vertex_formats() {
+        assert_eq!(pack_unorm8x4([0.0, 0.5, 1.0, 2.0]), [0, 128, 255, 255]);
+        assert_eq!(pack_snorm8x4([-1.0, 0.0, 1.0, -3.0]), [-127, 0, 127, -127]);
+        assert_eq!(pack_snorm16x2([0.5, -1.0]), [16384, -32767]);
+        assert_eq!(pack_unorm16x2([1.0, 0.0]), [65535, 0]);
+
+        for value in [0.0, -0.0, 1.0, -2.5, 0.333, 65504.0, 6.1e-5, 5.96e-8, -1.0e-6] {
+            let half = f32_to_f16(value);
+            let back = f16_to_f32(half);
+            assert!((back - value).abs() <= value.abs() * 1e-3 + 6e-8, "{} -> {}", value, back);
+            assert_eq!(f32_to_f16(back), half);
+        }
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(70000.0), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+        // Halfway between 1.0 and the next half rounds to even
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 2048.0), 0x3c00);
+        assert_eq!(pack_float16x4([1.0, 2.0, 0.5, -1.0]), [0x3c00, 0x4000, 0x3800, 0xbc00]);
+
+        let layout = vertex_layout![0 => Float32x3, 1 => Unorm8x4, 2 => VertexFormat::Float16x2];
+        assert_eq!(layout.array_stride, 20);
+        let built = VertexBufferBuilder::new().float16x4(0).snorm16x2(1).unorm8x4(2).build();
+        assert_eq!(built.attributes[2].offset, 12);
+    }
+
+    #[test]
+    fn test_index_narrowing() {
+        assert_eq!(IndexBuffer::narrow(&[0, 1, 65535]), Some(vec![0u16, 1, 65535]));
+        assert_eq!(IndexBuffer::narrow(&[0, 65536]), None);
+        assert_eq!(<u16 as IndexType>::FORMAT, wgpu::IndexFormat::Uint16);
+        assert_eq!(<u32 as IndexType>::FORMAT, wgpu::IndexFormat::Uint32);
+    }
+
+    #[test]
+    fn test_mesh_optimization() {
+        let sorted_triangles = |data: &MeshData<MeshVertex>| {
+            let mut triangles: Vec<[[u32; 3]; 3]> = data.indices
+                .chunks_exact(3)
+                .map(|t| t.iter().map(|&i| data.vertices[i as usize].position.map(f32::to_bits)))
+                .map(|mut corners| [(); 3].map(|_| corners.next().unwrap()))
+                .collect();
+            for triangle in &mut triangles {
+                let first = (0..3).min_by_key(|&i| triangle[i]).unwrap();
+                triangle.rotate_left(first);
+            }
+            triangles.sort();
+            triangles
+        };
+
+        // Scramble the triangle order so there is something to optimize
+        let mut data = MeshData::sphere(24);
+        let mut triangles: Vec<Vec<u32>> = data.indices
+            .chunks_exact(3)
+            .map(<[u32]>::to_vec)
+            .collect();
+        let count = triangles.len();
+        for i in 0..count {
+            triangles.swap(i, (i * 7919) % count);
+        }
+        data.indices = triangles.concat();
+        let before = sorted_triangles(&data);
+        let scrambled = data.vertex_cache_miss_ratio(16);
+
+        let order = data.optimize();
+        assert_eq!(order.len(), data.vertices.len());
+        assert_eq!(sorted_triangles(&data), before);
+        assert!(data.vertex_cache_miss_ratio(16) < scrambled * 0.6);
+        // Vertices now appear in first-use order
+        let mut next = 0;
+        for &index in &data.indices {
+            assert!(index <= next);
+            next = next.max(index + 1);
+        }
+
+        let coarse = data.simplify(4);
+        assert!(coarse.indices.len() < data.indices.len() / 4);
+        assert!(coarse.indices.len() >= 3);
+        assert!(coarse.indices.iter().all(|&i| (i as usize) < coarse.vertices.len()));
+    }
+
+    #[cfg(feature = "optimize-meshes")]
+    #[test]
+    fn test_meshopt_simplification() {
+        let data = MeshData::sphere(32);
+        let half = data.simplify_to(0.5, 0.05);
+        assert!(half.indices.len() <= data.indices.len() / 2 + 3);
+        assert!(half.indices.len() >= 3);
+        assert!(half.vertices.len() < data.vertices.len());
+        assert!(half.indices.iter().all(|&i| (i as usize) < half.vertices.len()));
+    }
+
+    #[test]
+    fn test_lod_selection() {
+        let detailed = MeshData::sphere(16);
+        let levels = [detailed.clone(), detailed.simplify(8), detailed.simplify(4)];
+        let (combined, submeshes) = MeshData::combine_lods(&levels);
+        assert_eq!(submeshes.len(), 3);
+        assert_eq!(submeshes[1].base_vertex, detailed.vertices.len() as i32);
+        assert_eq!(submeshes[2].range.end as usize, combined.indices.len());
+        for (submesh, level) in submeshes.iter().zip(&levels) {
+            assert_eq!(submesh.range.len(), level.indices.len());
+        }
+
+        // Levels are sorted by distance whatever order they're given in
+        let lod = MeshLod::new(vec![
+            LodLevel { submesh: 2, distance: 60.0 },
+            LodLevel { submesh: 0, distance: 10.0 },
+            LodLevel { submesh: 1, distance: 25.0 }
+        ]);
+        assert_eq!(lod, MeshLod::from_distances(&[10.0, 25.0, 60.0]));
+        assert_eq!(lod.select(0.0), Some(0));
+        assert_eq!(lod.select(10.0), Some(1));
+        assert_eq!(lod.select(59.0), Some(2));
+        assert_eq!(lod.select(60.0), None);
+        assert_eq!(lod.clone().bias(2.0).select(40.0), Some(1));
+
+        let camera = Camera::perspective([0.0, 0.0, 5.0], [0.0; 3], 1.0, 1.0, 0.1, 100.0);
+        let far = glam::Mat4::from_translation(glam::Vec3::new(0.0, 0.0, -30.0));
+        let instance = lod.cull_instance(&camera, far.to_cols_array_2d(), [-1.0; 3], [1.0; 3]);
+        assert_eq!(instance.map(|i| i.submesh), Some(2));
+        let gone = glam::Mat4::from_translation(glam::Vec3::new(0.0, 0.0, -80.0));
+        assert!(lod.cull_instance(&camera, gone.to_cols_array_2d(), [-1.0; 3], [1.0; 3]).is_none());
+    }
+
+    #[test]
+    fn test_upload_chunks() {
+        let chunks: Vec<_> = upload_chunks(10, 4).collect();
+        assert_eq!(chunks, [0..4, 4..8, 8..10]);
+        // Chunk sizes round down to the copy alignment, and never to zero
+        assert_eq!(upload_chunks(12, 6).collect::<Vec<_>>(), [0..4, 4..8, 8..12]);
+        assert_eq!(upload_chunks(8, 1).count(), 2);
+        assert_eq!(upload_chunks(0, 4).count(), 0);
+        let gigabytes = 5u64 << 30;
+        let chunks: Vec<_> = upload_chunks(gigabytes, DEFAULT_UPLOAD_CHUNK).collect();
+        assert_eq!(chunks.len(), 80);
+        assert_eq!(chunks.last().unwrap().end, gigabytes);
+
+        let progress = UploadProgress { uploaded: 3, total: 4, chunk: 2, chunks: 3 };
+        assert_eq!(progress.fraction(), 0.75);
+        assert_eq!(UploadProgress { uploaded: 0, total: 0, chunk: 0, chunks: 0 }.fraction(), 1.0);
+    }
+
+    #[test]
+    fn test_field_offsets() {
+        assert_eq!(field_offset(|camera: &CameraUniform| &camera.view), Some(0));
+        assert_eq!(field_offset(|camera: &CameraUniform| &camera.projection), Some(64));
+        assert_eq!(
+            field_offset(|camera: &CameraUniform| &camera.position),
+            Some(std::mem::offset_of!(CameraUniform, position))
+        );
+        assert_eq!(field_offset(|globals: &GlobalsUniform| &globals.frame), Some(8));
+        assert_eq!(field_offset(|globals: &GlobalsUniform| &globals.mouse[1]), Some(28));
+        // Anything that isn't part of the element is rejected
+        static OUTSIDE: f32 = 0.0;
+        assert_eq!(field_offset(|_: &GlobalsUniform| &OUTSIDE), None);
+    }
+
+    #[test]
+    fn test_indirect_args_layout() {
+        assert_eq!(std::mem::size_of::<DrawIndirectArgs>(), 16);
+        assert_eq!(std::mem::size_of::<DrawIndexedIndirectArgs>(), 20);
+        assert_eq!(std::mem::size_of::<DispatchIndirectArgs>(), 12);
+        let args = IndirectBufferBuilder::new().dispatch(8, 4, 1).dispatch(1, 1, 1);
+        assert_eq!(args.len(), 2);
+        let dispatch = [DispatchIndirectArgs::new(8, 4, 1)];
+        assert_eq!(bytemuck::cast_slice::<_, u32>(&dispatch), [8, 4, 1]);
+    }
+
+    #[test]
+    fn test_query_kinds() {
+        let stats = wgpu::PipelineStatisticsTypes::VERTEX_SHADER_INVOCATIONS |
+            wgpu::PipelineStatisticsTypes::FRAGMENT_SHADER_INVOCATIONS |
+            wgpu::PipelineStatisticsTypes::COMPUTE_SHADER_INVOCATIONS;
+        assert_eq!(QueryKind::PipelineStatistics(stats).values_per_query(), 3);
+        assert_eq!(QueryKind::Timestamp.values_per_query(), 1);
+        assert_eq!(QueryKind::Occlusion.required_features(), wgpu::Features::empty());
+        assert_eq!(
+            QueryKind::PipelineStatistics(stats).required_features(),
+            wgpu::Features::PIPELINE_STATISTICS_QUERY
+        );
+    }
+
+    #[test]
+    fn test_color_spaces() {
+        for value in [0.0, 0.002, 0.2, 0.5, 1.0] {
+            assert!((srgb_to_linear(linear_to_srgb(value)) - value).abs() < 1e-5);
+        }
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+
+        let gray = Color::from_srgb_hex(0x808080);
+        assert!((gray.r - 0.2158).abs() < 1e-3 && gray.a == 1.0);
+        let back = gray.to_srgb().to_array();
+        assert!((back[0] - 128.0 / 255.0).abs() < 1e-5);
+
+        for format in [wgpu::TextureFormat::Bgra8UnormSrgb, wgpu::TextureFormat::Bgra8Unorm] {
+            let source = LinearTarget::shader_source(format);
+            if let Err(e) = check_wgsl("Linear Output", None, &source) {
+                panic!("{:?}: {}", format, e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_abuffer_shaders() {
+        if let Err(e) = check_wgsl("A-Buffer Resolve", None, &ABuffer::shader_source()) {
+            panic!("{}", e);
+        }
+        let insert = format!(
+            "{}
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) {{
+    abuffer_insert(position, vec4<f32>(1.0, 0.0, 0.0, 0.5));
+}}",
+            ABuffer::wgsl(1)
+        );
+        if let Err(e) = check_wgsl("A-Buffer Insert", None, &insert) {
+            panic!("{}", e);
+        }
+    }
+
+    #[test]
+    fn test_dither_shaders() {
+        for format in [wgpu::TextureFormat::Bgra8UnormSrgb, wgpu::TextureFormat::Rgba8Unorm] {
+            if let Err(e) = check_wgsl("Dither", None, &Dither::shader_source(format)) {
+                panic!("{:?}: {}", format, e);
+            }
+        }
+        assert_eq!(DitherSettings::default().mode, DitherMode::Noise);
+    }
+
+    #[test]
+    fn test_mip_generator_shaders() {
+        assert_eq!(max_mip_levels(256, 128), 9);
+        assert_eq!(max_mip_levels(5, 3), 3);
+        assert_eq!(max_mip_levels(0, 0), 1);
+        for filter in [MipFilter::Average, MipFilter::Min, MipFilter::Max] {
+            let source = MipGenerator::shader_source(wgpu::TextureFormat::Rgba16Float, filter)
+                .unwrap();
+            if let Err(e) = check_wgsl("Mip Generator", Some(ShaderStages::COMPUTE), &source) {
+                panic!("{:?}: {}", filter, e);
+            }
+        }
+        let srgb = MipGenerator::shader_source(wgpu::TextureFormat::Rgba8UnormSrgb, MipFilter::Max);
+        assert!(matches!(srgb, Err(GeepuError::TextureError(_))));
+    }
+
+    #[test]
+    fn test_compressed_image_headers() {
+        let put = |bytes: &mut Vec<u8>, offset: usize, value: &[u8]| {
+            bytes[offset..offset + value.len()].copy_from_slice(value);
+        };
+
+        // 8x8 BC7 with two mips: 4 blocks, then 1 block, stored smallest first
+        let mut ktx2 = vec![0u8; 128 + 80];
+        put(&mut ktx2, 0, b"\xabKTX 20\xbb\r\n\x1a\n");
+        for (offset, value) in [(12, 145u32), (20, 8), (24, 8), (36, 1), (40, 2)] {
+            put(&mut ktx2, offset, &value.to_le_bytes());
+        }
+        for (offset, value) in [(80, 144u64), (88, 64), (104, 128), (112, 16)] {
+            put(&mut ktx2, offset, &value.to_le_bytes());
+        }
+        ktx2[144] = 7;
+        let image = CompressedImage::parse(&ktx2).unwrap();
+        assert_eq!(image.format, wgpu::TextureFormat::Bc7RgbaUnorm);
+        assert_eq!(image.dimension, wgpu::TextureViewDimension::D2);
+        assert_eq!((image.mip_level_count, image.data.len(), image.data[0]), (2, 80, 7));
+        assert_eq!(image.required_features(), wgpu::Features::TEXTURE_COMPRESSION_BC);
+
+        // 4x4 DXT5 cubemap, one block per face, short by one face
+        let mut dds = vec![0u8; 128 + 16 * 5];
+        put(&mut dds, 0, b"DDS ");
+        for (offset, value) in [(12, 4u32), (16, 4), (28, 1), (80, 0x4), (112, 0xfe00)] {
+            put(&mut dds, offset, &value.to_le_bytes());
+        }
+        put(&mut dds, 84, b"DXT5");
+        assert!(matches!(CompressedImage::parse(&dds), Err(GeepuError::TextureError(_))));
+        dds.extend_from_slice(&[0; 16]);
+        let image = CompressedImage::parse(&dds).unwrap();
+        assert_eq!(image.format, wgpu::TextureFormat::Bc3RgbaUnorm);
+        assert_eq!(image.dimension, wgpu::TextureViewDimension::Cube);
+        assert_eq!(image.size.depth_or_array_layers, 6);
+        assert!(CompressedImage::parse(b"not a texture").is_err());
+    }
+
+    #[test]
+    fn test_label_prefix() {
+        let config = GpuConfig::new().label_prefix("Editor/");
+        assert_eq!(config.prefixed_label("Shadow Pass"), "Editor/Shadow Pass");
+        assert_eq!(GpuConfig::new().prefixed_label("Shadow Pass"), "Shadow Pass");
+    }
+
+    #[test]
+    fn test_present_mode_fallback() {
+        let vsync_off = GpuConfig::new().vsync(false);
+        let all = [PresentMode::Fifo, PresentMode::Immediate, PresentMode::Mailbox];
+        assert_eq!(vsync_off.present_mode_for(&all), PresentMode::Mailbox);
+        let no_mailbox = [PresentMode::Fifo, PresentMode::Immediate];
+        assert_eq!(vsync_off.present_mode_for(&no_mailbox), PresentMode::Fifo);
+        assert_eq!(GpuConfig::new().present_mode_for(&all), PresentMode::Fifo);
+        let deterministic = GpuConfig::new().vsync(false).deterministic(true);
+        assert_eq!(deterministic.present_mode_for(&all), PresentMode::Fifo);
+    }
+
+    #[test]
+    fn test_deterministic_adapter_order() {
+        let info = |name: &str, device_type, backend| wgpu::AdapterInfo {
+            name: name.to_string(),
+            vendor: 0,
+            device: 0,
+            device_type,
+            driver: String::new(),
+            driver_info: String::new(),
+            backend,
+        };
+        let discrete = info("dGPU", wgpu::DeviceType::DiscreteGpu, wgpu::Backend::Vulkan);
+        let integrated = info("iGPU", wgpu::DeviceType::IntegratedGpu, wgpu::Backend::Vulkan);
+        let gl = info("dGPU", wgpu::DeviceType::DiscreteGpu, wgpu::Backend::Gl);
+        let cpu = info("llvmpipe", wgpu::DeviceType::Cpu, wgpu::Backend::Vulkan);
+
+        let config = GpuConfig::new().deterministic(true);
+        let key = |info| config.adapter_sort_key(info);
+        assert!(key(&discrete) < key(&gl));
+        assert!(key(&gl) < key(&integrated));
+        assert!(key(&integrated) < key(&cpu));
+
+        let testing = GpuConfig::testing();
+        assert!(testing.adapter_sort_key(&cpu) < testing.adapter_sort_key(&integrated));
+    }
+
+    #[test]
+    fn test_validation_flags() {
+        let config = GpuConfig::new().validation(true).debug_labels(false);
+        assert!(config.instance_flags.contains(InstanceFlags::VALIDATION));
+        assert!(!config.instance_flags.contains(InstanceFlags::DEBUG));
+        assert_eq!(GpuConfig::new().capture_errors, cfg!(debug_assertions));
+        assert!(!GpuConfig::new().capture_errors(false).capture_errors);
+    }
+
+    #[test]
+    fn test_taa_jitter() {
+        assert_eq!(halton(1, 2), 0.5);
+        assert_eq!(halton(2, 3), 2.0 / 3.0);
+        assert_eq!(taa_jitter(0, 8, 4, 4), taa_jitter(8, 8, 4, 4));
+        for frame in 0..8 {
+            let [x, y] = taa_jitter(frame, 8, 100, 50);
+            assert!(x.abs() <= 0.01 && y.abs() <= 0.02);
+        }
+
+        let camera = Camera::perspective([0.0, 0.0, 5.0], [0.0; 3], 1.0, 1.0, 0.1, 100.0);
+        let uniform = camera.jittered_uniform([0.01, -0.02]);
+        assert_eq!(uniform.view, camera.uniform().view);
+        assert_ne!(uniform.view_projection, camera.uniform().view_projection);
+    }
+
+    #[test]
+    fn test_msaa_samples() {
+        assert_eq!(GpuConfig::new().samples, 1);
+        assert_eq!(GpuConfig::new().samples(4).samples, 4);
+        assert_eq!(GpuConfig::new().samples(0).samples, 1);
+    }
+
+    #[test]
+    fn test_renderers_match_sample_count() {
+        let config = GpuConfig::new().samples(4);
+        let context = pollster::block_on(GpuContext::new_with_config(config)).unwrap();
+        let samples = context.sample_count();
+        let color = TextureFormat::Rgba8Unorm;
+        let depth = TextureFormat::Depth32Float;
+        let [color_target, depth_target] = [color, depth].map(|format| {
+            TextureBuilder::new(4, 4)
+                .format(format)
+                .usage(wgpu::TextureUsages::RENDER_ATTACHMENT)
+                .sample_count(samples)
+                .build(&context)
+                .unwrap()
+        });
+        let camera = Camera::perspective([0.0, 0.0, 3.0], [0.0; 3], 1.0, 1.0, 0.1, 10.0);
+        let white = || Texture::from_rgba8(&context, &[255; 4], 1, 1, None).unwrap();
+
+        let mut debug = DebugDraw::new_multisampled(&context, color, Some(depth), samples).unwrap();
+        debug.debug_line([0.0; 3], [1.0; 3], [1.0; 4]);
+        debug.prepare(&context, &camera).unwrap();
+        let mut lines = PolylineRenderer::new_multisampled(&context, color, Some(depth), samples)
+            .unwrap();
+        lines.line([0.0; 3], [1.0; 3], 2.0, [1.0; 4]);
+        lines.prepare(&context, &camera).unwrap();
+        let mut ui = UiRenderer::new_multisampled(&context, None, color, Some(depth), samples)
+            .unwrap();
+        ui.rect([0.0; 2], [1.0; 2], [1.0; 4]);
+        ui.prepare(&context, &camera).unwrap();
+        let mut billboards = BillboardRenderer::new_multisampled(
+            &context,
+            None,
+            color,
+            Some(depth),
+            samples
+        ).unwrap();
+        let billboard = Billboard::new([0.0; 3], [1.0; 2]);
+        billboards.prepare(&context, &camera, &[billboard]).unwrap();
+        let mut points = PointCloudRenderer::new_multisampled(&context, color, Some(depth), samples)
+            .unwrap();
+        let cloud_points = [CloudPoint::new([0.0; 3], 1.0, [1.0; 4])];
+        let cloud = PointCloud::new(&context, &points, &cloud_points, 64).unwrap();
+        points.prepare(&context, &camera).unwrap();
+        let cubemap = Texture::create_cubemap(
+            &context,
+            1,
+            TextureFormat::Rgba8Unorm,
+            1,
+            wgpu::TextureUsages::TEXTURE_BINDING,
+            None
+        ).unwrap();
+        let skybox = Skybox::new_multisampled(&context, cubemap, color, Some(depth), samples)
+            .unwrap();
+        skybox.prepare(&context, &camera).unwrap();
+
+        let cube = Mesh::cube(&context).unwrap();
+        let params = BasicParams::default();
+        let texture = white();
+        let shader = std::sync::Arc::new(MaterialShader::basic());
+        let material = Material::new(&context, shader, &params, &[&texture]).unwrap();
+        let draws = [MaterialDraw::new(&cube, &material, glam::Mat4::IDENTITY.to_cols_array_2d())];
+        let mut materials = MaterialRenderer::new_multisampled(
+            &context,
+            color,
+            Some(depth),
+            samples
+        ).unwrap();
+        materials.prepare(&context, &draws).unwrap();
+        let decal_shader = std::sync::Arc::new(MaterialShader::basic_decal());
+        let decal_material = Material::new(&context, decal_shader, &params, &[&texture]).unwrap();
+        let decals = [Decal::new(&decal_material, glam::Mat4::IDENTITY.to_cols_array_2d())];
+        let resolved_depth = Texture::create_depth_texture(&context, 4, 4, None).unwrap();
+        let mut decal_renderer = DecalRenderer::new_multisampled(
+            &context,
+            color,
+            Some(depth),
+            samples
+        ).unwrap();
+        decal_renderer.set_depth(&context, &resolved_depth.view, 4, 4).unwrap();
+        decal_renderer.prepare(&context, &decals).unwrap();
+
+        // Drawing any of them into the 4x targets fails if its pipeline has another count
+        let result = context.capture_errors("render", None, || {
+            let mut commands = RenderCommands::new(&context, None);
+            {
+                let targets = [Some(color_attachment(&color_target.view, None))];
+                let depth = depth_stencil_attachment(&depth_target.view, Some(1.0), None);
+                let mut pass = commands.begin_render_pass(&targets, Some(depth), None);
+                debug.render(&mut pass);
+                lines.render(&mut pass);
+                ui.render(&mut pass);
+                billboards.render(&mut pass);
+                points.render(&mut pass, &cloud);
+                skybox.render(&mut pass);
+                materials.render(&mut pass, &draws);
+            }
+            {
+                let targets = [Some(color_attachment(&color_target.view, None))];
+                let depth = depth_stencil_attachment(&depth_target.view, None, None);
+                let mut pass = commands.begin_render_pass(&targets, Some(depth), None);
+                decal_renderer.render(&mut pass, &decals);
+            }
+            commands.submit(&context);
+        });
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_asset_handles() {
+        let mut assets = Assets::new();
+        let name = assets.insert(String::from("checker"));
+        let size = assets.insert(64u32);
+        assert_eq!(assets.get(&name).as_deref(), Some(&String::from("checker")));
+        assert_eq!(assets.generation(size.id()), Some(0));
+        let mut seen = 0;
+        assert!(assets.get_if_changed(&size, &mut seen).is_none());
+
+        // Clones keep the asset alive; the last drop releases it
+        let copy = name.clone();
+        drop(name);
+        assets.collect_garbage();
+        assert_eq!(assets.len(), 2);
+        drop(copy);
+        assets.collect_garbage();
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets.get(&size).as_deref(), Some(&64));
+    }
+
+    #[cfg(feature = "gamepad")]
+    #[test]
+    fn test_gamepad_deadzones() {
+        assert_eq!(stick_deadzone(glam::Vec2::new(0.1, 0.1), 0.15), glam::Vec2::ZERO);
+        // Past the deadzone the direction is kept and full deflection still reaches 1
+        let diagonal = stick_deadzone(glam::Vec2::new(0.5, 0.5), 0.15);
+        assert!((diagonal.x - diagonal.y).abs() < 1e-6);
+        assert!((stick_deadzone(glam::Vec2::new(0.0, -1.0), 0.15).y + 1.0).abs() < 1e-6);
+        assert_eq!(trigger_deadzone(0.04, 0.05), 0.0);
+        assert!((trigger_deadzone(0.525, 0.05) - 0.5).abs() < 1e-6);
+        assert_eq!(trigger_deadzone(1.0, 0.05), 1.0);
+    }
+
+    #[cfg(all(feature = "vulkan-interop", target_os = "linux"))]
+    #[test]
+    fn test_external_memory_round_trip() {
+        let config = GpuConfig::new().backends(Backends::VULKAN).external_memory(true);
+        let context = pollster::block_on(GpuContext::new_with_config(config)).unwrap();
+        let descriptor = wgpu::TextureDescriptor {
+            label: Some("Shared"),
+            size: wgpu::Extent3d { width: 64, height: 64, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        };
+        let exported = Texture::new_exportable(&context, &descriptor).unwrap();
+        assert!(exported.size >= 64 * 64 * 4);
+
+        // The exported descriptor imports back as a texture of the same image
+        let imported = unsafe {
+            Texture::from_external_memory(&context, exported.memory, &descriptor).unwrap()
+        };
+        assert_eq!(imported.texture.size(), exported.texture.texture.size());
     }
 }
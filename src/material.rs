@@ -0,0 +1,1003 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    Camera,
+    CameraUniform,
+    DebugView,
+    DrawQueue,
+    GeepuError,
+    GpuContext,
+    JointBuffer,
+    Mesh,
+    RenderPass,
+    RenderPipeline,
+    Result,
+    SkinWeights,
+    SortKey,
+    Texture,
+    TypedBuffer,
+};
+use crate::describe;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicU64, Ordering };
+use wgpu::util::DeviceExt;
+
+static NEXT_SHADER_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Per-object data bound at group 2 for every material draw
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ObjectUniform {
+    pub model: [[f32; 4]; 4],
+    /// Inverse transpose of `model`, for transforming normals
+    pub normal: [[f32; 4]; 4],
+}
+
+impl ObjectUniform {
+    pub fn new(model: [[f32; 4]; 4]) -> Self {
+        let matrix = glam::Mat4::from_cols_array_2d(&model);
+        Self {
+            model,
+            normal: matrix.inverse().transpose().to_cols_array_2d(),
+        }
+    }
+}
+
+/// A material shader variant: WGSL source plus the pipeline state it is drawn with.
+///
+/// The source is prefixed with [`MaterialShader::PRELUDE`], which declares the
+/// camera at group 0 and the object transform at group 2. Group 1 belongs to the
+/// material: its parameter block at binding 0, then a texture and sampler pair
+/// per texture starting at binding 1 (2D unless set through
+/// [`textures`](Self::textures)). Entry points are `vs_main` and `fs_main`.
+#[derive(Debug)]
+pub struct MaterialShader {
+    id: u64,
+    pub label: String,
+    pub source: String,
+    /// View dimension of each texture binding
+    pub textures: Vec<wgpu::TextureViewDimension>,
+    pub blend: Option<wgpu::BlendState>,
+    pub cull_mode: Option<wgpu::Face>,
+    pub depth_write: bool,
+    /// Whether draws bind a [`JointBuffer`] at group 3 and [`SkinWeights`] in vertex slot 1
+    pub skinned: bool,
+    /// Whether opaque draws take part in the renderer's depth pre-pass; turn it
+    /// off for shaders that `discard`, whose depth the vertex stage alone can't know
+    pub depth_prepass: bool,
+}
+
+impl MaterialShader {
+    /// Declarations shared by every material shader
+    pub const PRELUDE: &'static str =
+        r#"
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(2) @binding(0) var<uniform> object: Object;
+"#;
+
+    /// A shader variant from WGSL source that samples `texture_count` textures
+    pub fn new(label: impl Into<String>, source: &str, texture_count: u32) -> Self {
+        Self {
+            id: NEXT_SHADER_ID.fetch_add(1, Ordering::Relaxed),
+            label: label.into(),
+            source: format!("{}{}{}", Camera::WGSL, Self::PRELUDE, source),
+            textures: vec![wgpu::TextureViewDimension::D2; texture_count as usize],
+            blend: Some(wgpu::BlendState::REPLACE),
+            cull_mode: Some(wgpu::Face::Back),
+            depth_write: true,
+            skinned: false,
+            depth_prepass: true,
+        }
+    }
+
+    /// Simple shader for [`MeshVertex`](crate::MeshVertex) meshes: a base color times
+    /// one texture, shaded with a fixed directional light. Parameters are [`BasicParams`].
+    pub fn basic() -> Self {
+        Self::new("Basic Material", BASIC_SHADER, 1)
+    }
+
+    /// Metallic-roughness PBR shader for [`MeshVertex`](crate::MeshVertex) meshes lit by a
+    /// fixed directional light. Parameters are [`PbrParams`]; textures are base color,
+    /// metallic-roughness (B = metallic, G = roughness), occlusion (R) and emissive.
+    pub fn pbr() -> Self {
+        Self::new("PBR Material", &format!("{}{}{}", PBR_SHADER, PBR_VERTEX, PBR_FLAT_AMBIENT), 4)
+    }
+
+    /// Set the view dimension of every texture binding (e.g. cube maps)
+    pub fn textures(mut self, dimensions: Vec<wgpu::TextureViewDimension>) -> Self {
+        self.textures = dimensions;
+        self
+    }
+
+    pub fn blend(mut self, blend: Option<wgpu::BlendState>) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn depth_write(mut self, depth_write: bool) -> Self {
+        self.depth_write = depth_write;
+        self
+    }
+
+    pub fn skinned(mut self, skinned: bool) -> Self {
+        self.skinned = skinned;
+        self
+    }
+
+    pub fn depth_prepass(mut self, depth_prepass: bool) -> Self {
+        self.depth_prepass = depth_prepass;
+        self
+    }
+
+    /// Unique id used to key cached pipelines
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Whether the shader blends with what is already in the target
+    pub fn is_transparent(&self) -> bool {
+        self.blend.is_some_and(|blend| blend != wgpu::BlendState::REPLACE)
+    }
+
+    /// Layout of the material bind group (group 1)
+    fn bind_group_layout(&self, context: &GpuContext) -> Arc<wgpu::BindGroupLayout> {
+        let visibility = wgpu::ShaderStages::VERTEX_FRAGMENT;
+        let mut builder = BindGroupLayoutBuilder::new().uniform_buffer(0, visibility);
+        for (i, &dimension) in self.textures.iter().enumerate() {
+            let binding = 1 + (i as u32) * 2;
+            builder = builder
+                .texture(
+                    binding,
+                    visibility,
+                    wgpu::TextureSampleType::Float { filterable: true },
+                    dimension,
+                    false
+                )
+                .sampler(binding + 1, visibility, wgpu::SamplerBindingType::Filtering);
+        }
+        builder.build(context, Some(&format!("{} Bind Group Layout", self.label)))
+    }
+}
+
+/// Parameters for [`MaterialShader::basic`]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BasicParams {
+    pub base_color: [f32; 4],
+}
+
+impl Default for BasicParams {
+    fn default() -> Self {
+        Self { base_color: [1.0; 4] }
+    }
+}
+
+/// Parameters for [`MaterialShader::pbr`], following glTF's metallic-roughness model
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PbrParams {
+    pub base_color: [f32; 4],
+    /// Emissive color in RGB; A is unused
+    pub emissive: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub occlusion_strength: f32,
+    /// Fragments with base color alpha below this are discarded (0 disables)
+    pub alpha_cutoff: f32,
+}
+
+impl Default for PbrParams {
+    fn default() -> Self {
+        Self {
+            base_color: [1.0; 4],
+            emissive: [0.0; 4],
+            metallic: 1.0,
+            roughness: 1.0,
+            occlusion_strength: 1.0,
+            alpha_cutoff: 0.0,
+        }
+    }
+}
+
+/// A shader variant with its parameter block and textures bound
+pub struct Material {
+    pub shader: Arc<MaterialShader>,
+    pub params: wgpu::Buffer,
+    pub bind_group_layout: Arc<wgpu::BindGroupLayout>,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Material {
+    /// Create a material from a shader, a `Pod` parameter block and its textures
+    pub fn new<P>(
+        context: &GpuContext,
+        shader: Arc<MaterialShader>,
+        params: &P,
+        textures: &[&Texture]
+    ) -> Result<Self>
+        where P: bytemuck::Pod
+    {
+        if textures.len() != shader.textures.len() {
+            return Err(
+                GeepuError::PipelineError(
+                    format!(
+                        "Material shader '{}' expects {} textures, got {}",
+                        shader.label,
+                        shader.textures.len(),
+                        textures.len()
+                    )
+                )
+            );
+        }
+
+        let params = context.device.create_buffer_init(
+            &(wgpu::util::BufferInitDescriptor {
+                label: Some(&context.label(&format!("{} Params", shader.label))),
+                contents: bytemuck::bytes_of(params),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            })
+        );
+        let bind_group_layout = shader.bind_group_layout(context);
+
+        let mut builder = BindGroupBuilder::new(&bind_group_layout).buffer(0, &params);
+        for (i, texture) in textures.iter().enumerate() {
+            let binding = 1 + (i as u32) * 2;
+            builder = builder
+                .texture_view(binding, &texture.view)
+                .sampler(binding + 1, &texture.sampler);
+        }
+        let bind_group = builder.build(context, Some(&format!("{} Bind Group", shader.label)));
+
+        Ok(Self {
+            shader,
+            params,
+            bind_group_layout,
+            bind_group,
+        })
+    }
+
+    /// Upload a new parameter block (must be the same size as the original)
+    pub fn set_params<P>(&self, context: &GpuContext, params: &P) -> Result<()>
+        where P: bytemuck::Pod
+    {
+        let bytes = bytemuck::bytes_of(params);
+        if (bytes.len() as u64) != self.params.size() {
+            return Err(
+                GeepuError::BufferError(
+                    format!(
+                        "Material parameters are {} bytes, expected {}",
+                        bytes.len(),
+                        self.params.size()
+                    )
+                )
+            );
+        }
+        context.write_buffer(&self.params, 0, bytes);
+        Ok(())
+    }
+}
+
+/// One draw: a mesh rendered with a material at a model transform
+pub struct MaterialDraw<'a, V> {
+    pub mesh: &'a Mesh<V>,
+    pub material: &'a Material,
+    pub transform: [[f32; 4]; 4],
+    /// Skinning inputs, required by [`skinned`](MaterialShader::skinned) shaders
+    pub skin: Option<MaterialSkin<'a>>,
+}
+
+impl<'a, V> MaterialDraw<'a, V> {
+    pub fn new(mesh: &'a Mesh<V>, material: &'a Material, transform: [[f32; 4]; 4]) -> Self {
+        Self { mesh, material, transform, skin: None }
+    }
+
+    /// Skin the mesh with per-vertex `weights` and the matrices in `joints`
+    pub fn skinned(
+        mut self,
+        weights: &'a TypedBuffer<SkinWeights>,
+        joints: &'a JointBuffer
+    ) -> Self {
+        self.skin = Some(MaterialSkin { weights, joints });
+        self
+    }
+}
+
+/// Per-vertex weights and joint matrices of a skinned draw
+#[derive(Clone, Copy)]
+pub struct MaterialSkin<'a> {
+    pub weights: &'a TypedBuffer<SkinWeights>,
+    pub joints: &'a JointBuffer,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    shader: u64,
+    array_stride: u64,
+    step_mode: wgpu::VertexStepMode,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+
+impl PipelineKey {
+    fn new(shader: &MaterialShader, layout: &wgpu::VertexBufferLayout) -> Self {
+        Self::for_shader(shader.id(), layout)
+    }
+
+    fn for_shader(shader: u64, layout: &wgpu::VertexBufferLayout) -> Self {
+        Self {
+            shader,
+            array_stride: layout.array_stride,
+            step_mode: layout.step_mode,
+            attributes: layout.attributes.to_vec(),
+        }
+    }
+}
+
+/// Draws (mesh, material, transform) triples, compiling one pipeline per
+/// material shader and vertex layout on first use.
+///
+/// Call [`prepare`](Self::prepare) before the render pass to build missing
+/// pipelines and upload transforms, then [`render`](Self::render) inside it with
+/// the same draw list. [`set_debug_view`](Self::set_debug_view) swaps the
+/// materials for a [`DebugView`] from the next `prepare` on.
+///
+/// With [`set_depth_prepass`](Self::set_depth_prepass), `prepare` also builds
+/// a fragment-less pipeline per opaque material, recorded by
+/// [`render_prepass`](Self::render_prepass) (or through
+/// [`DrawQueue::flush_prepass`]) into a depth-only pass before the main one;
+/// the main pipelines then test `LessEqual` so hidden fragments are never shaded.
+pub struct MaterialRenderer {
+    color_format: wgpu::TextureFormat,
+    depth_format: Option<wgpu::TextureFormat>,
+    samples: u32,
+    camera: TypedBuffer<CameraUniform>,
+    camera_layout: Arc<wgpu::BindGroupLayout>,
+    camera_bind_group: wgpu::BindGroup,
+    object_layout: Arc<wgpu::BindGroupLayout>,
+    objects: wgpu::Buffer,
+    object_bind_group: wgpu::BindGroup,
+    object_stride: u64,
+    joint_layout: Arc<wgpu::BindGroupLayout>,
+    pipelines: HashMap<PipelineKey, RenderPipeline>,
+    debug_view: DebugView,
+    /// Pipelines of the debug views, keyed by the view's index in [`DebugView::ALL`]
+    debug_pipelines: HashMap<PipelineKey, RenderPipeline>,
+    depth_prepass: bool,
+    prepass_pipelines: HashMap<PipelineKey, RenderPipeline>,
+}
+
+impl MaterialRenderer {
+    /// Create a renderer targeting the given color (and optional depth) formats
+    pub fn new(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>
+    ) -> Result<Self> {
+        Self::new_multisampled(context, color_format, depth_format, 1)
+    }
+
+    /// [`new`](Self::new) for targets with `samples` samples per pixel, e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    pub fn new_multisampled(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        samples: u32
+    ) -> Result<Self> {
+        let camera = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let camera_layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::VERTEX_FRAGMENT)
+            .build(context, Some("Material Camera Layout"));
+        let camera_bind_group = BindGroupBuilder::new(&camera_layout)
+            .buffer(0, camera.buffer())
+            .build(context, Some("Material Camera Bind Group"));
+
+        let object_size = std::mem::size_of::<ObjectUniform>() as u64;
+        let alignment = context.device.limits().min_uniform_buffer_offset_alignment as u64;
+        let object_stride = object_size.next_multiple_of(alignment);
+        let object_layout = BindGroupLayoutBuilder::new()
+            .dynamic_uniform_buffer(0, wgpu::ShaderStages::VERTEX_FRAGMENT, object_size)
+            .build(context, Some("Material Object Layout"));
+        let (objects, object_bind_group) = Self::create_objects(
+            context,
+            &object_layout,
+            object_stride,
+            1
+        );
+
+        Ok(Self {
+            color_format,
+            depth_format,
+            samples,
+            camera,
+            camera_layout,
+            camera_bind_group,
+            object_layout,
+            objects,
+            object_bind_group,
+            object_stride,
+            joint_layout: JointBuffer::bind_group_layout(context),
+            pipelines: HashMap::new(),
+            debug_view: DebugView::Lit,
+            debug_pipelines: HashMap::new(),
+            depth_prepass: false,
+            prepass_pipelines: HashMap::new(),
+        })
+    }
+
+    /// Upload the camera used by subsequent renders
+    pub fn set_camera(&self, context: &GpuContext, camera: &Camera) -> Result<()> {
+        camera.update_buffer(context, &self.camera)
+    }
+
+    /// Upload camera matrices directly, e.g. from [`Taa`](crate::Taa)'s jittered camera
+    pub fn set_camera_uniform(&self, context: &GpuContext, camera: &CameraUniform) -> Result<()> {
+        self.camera.write(context, std::slice::from_ref(camera))
+    }
+
+    /// Number of pipelines compiled so far
+    pub fn pipeline_count(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    /// Shade subsequent draws with `view`; its pipelines are built by the next
+    /// [`prepare`](Self::prepare)
+    pub fn set_debug_view(&mut self, context: &GpuContext, view: DebugView) -> Result<()> {
+        let missing = view.required_features() - context.device.features();
+        if !missing.is_empty() {
+            return Err(GeepuError::MissingFeatures(missing));
+        }
+        self.debug_view = view;
+        Ok(())
+    }
+
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+    }
+
+    /// Render opaque materials into a depth pre-pass first. Pipelines are
+    /// rebuilt by the next [`prepare`](Self::prepare); needs a depth format.
+    pub fn set_depth_prepass(&mut self, enabled: bool) -> Result<()> {
+        if enabled && self.depth_format.is_none() {
+            return Err(
+                GeepuError::PipelineError(
+                    "Depth pre-pass needs a renderer with a depth format".to_string()
+                )
+            );
+        }
+        if enabled != self.depth_prepass {
+            self.depth_prepass = enabled;
+            self.pipelines.clear();
+            self.prepass_pipelines.clear();
+        }
+        Ok(())
+    }
+
+    pub fn depth_prepass(&self) -> bool {
+        self.depth_prepass
+    }
+
+    /// Compile pipelines the draws need and upload their transforms
+    pub fn prepare<V>(&mut self, context: &GpuContext, draws: &[MaterialDraw<V>]) -> Result<()>
+        where V: bytemuck::Pod
+    {
+        for draw in draws {
+            let key = PipelineKey::new(&draw.material.shader, &draw.mesh.layout);
+            if !self.pipelines.contains_key(&key) {
+                let pipeline = self.create_pipeline(
+                    context,
+                    draw.material,
+                    &draw.mesh.layout,
+                    false
+                );
+                self.pipelines.insert(key.clone(), pipeline);
+            }
+            if self.uses_prepass(draw.material) && !self.prepass_pipelines.contains_key(&key) {
+                let layout = &draw.mesh.layout;
+                let pipeline = self.create_pipeline(context, draw.material, layout, true);
+                self.prepass_pipelines.insert(key, pipeline);
+            }
+            let key = self.debug_key(&draw.mesh.layout);
+            if self.debug_view != DebugView::Lit && !self.debug_pipelines.contains_key(&key) {
+                let layout = &draw.mesh.layout;
+                if let Some(pipeline) = self.create_debug_pipeline(context, layout) {
+                    self.debug_pipelines.insert(key, pipeline);
+                }
+            }
+        }
+
+        let required = (draws.len().max(1) as u64) * self.object_stride;
+        if required > self.objects.size() {
+            let capacity = draws.len().next_power_of_two();
+            let (objects, bind_group) = Self::create_objects(
+                context,
+                &self.object_layout,
+                self.object_stride,
+                capacity
+            );
+            self.objects = objects;
+            self.object_bind_group = bind_group;
+        }
+
+        let mut data = vec![0u8; draws.len() * self.object_stride as usize];
+        for (i, draw) in draws.iter().enumerate() {
+            let offset = i * (self.object_stride as usize);
+            let object = ObjectUniform::new(draw.transform);
+            data[offset..offset + std::mem::size_of::<ObjectUniform>()].copy_from_slice(
+                bytemuck::bytes_of(&object)
+            );
+        }
+        context.write_buffer(&self.objects, 0, &data);
+        Ok(())
+    }
+
+    /// Record the draws into a render pass; `draws` must match the last `prepare` call
+    pub fn render<'a, V>(&'a self, pass: &mut RenderPass<'a>, draws: &[MaterialDraw<'a, V>])
+        where V: bytemuck::Pod
+    {
+        for (i, draw) in draws.iter().enumerate() {
+            let key = PipelineKey::new(&draw.material.shader, &draw.mesh.layout);
+            let Some(pipeline) = self.pipelines.get(&key) else {
+                continue;
+            };
+            let offset = ((i as u64) * self.object_stride) as u32;
+            self.record(pass, pipeline, offset, draw.mesh, draw.material, draw.skin);
+        }
+    }
+
+    /// Record the depth-only versions of the opaque draws into a pass with
+    /// only a depth attachment, before [`render`](Self::render) in the main pass
+    /// (which must load that depth). Does nothing without
+    /// [`set_depth_prepass`](Self::set_depth_prepass).
+    pub fn render_prepass<'a, V>(&'a self, pass: &mut RenderPass<'a>, draws: &[MaterialDraw<'a, V>])
+        where V: bytemuck::Pod
+    {
+        if self.debug_view != DebugView::Lit {
+            return;
+        }
+        for (i, draw) in draws.iter().enumerate() {
+            let key = PipelineKey::new(&draw.material.shader, &draw.mesh.layout);
+            let Some(pipeline) = self.prepass_pipelines.get(&key) else {
+                continue;
+            };
+            let offset = ((i as u64) * self.object_stride) as u32;
+            self.record_depth(pass, pipeline, offset, draw.mesh, draw.material, draw.skin);
+        }
+    }
+
+    /// Queue the draws into a [`DrawQueue`] instead of recording them directly,
+    /// keyed by shader and material and sorted by depth from `camera`;
+    /// `draws` must match the last `prepare` call
+    pub fn queue<'a, V>(
+        &'a self,
+        queue: &mut DrawQueue<'a>,
+        camera: &Camera,
+        draws: &[MaterialDraw<'a, V>]
+    )
+        where V: bytemuck::Pod
+    {
+        for (i, draw) in draws.iter().enumerate() {
+            let key = PipelineKey::new(&draw.material.shader, &draw.mesh.layout);
+            let Some(pipeline) = self.pipelines.get(&key) else {
+                continue;
+            };
+            let [x, y, z, _] = draw.transform[3];
+            let depth = camera.view_depth([x, y, z]);
+            let sort_key = if draw.material.shader.is_transparent() {
+                SortKey::transparent(depth)
+            } else {
+                let material = std::ptr::from_ref(draw.material) as u64;
+                SortKey::opaque(draw.material.shader.id(), material, depth)
+            };
+            let offset = ((i as u64) * self.object_stride) as u32;
+            let (mesh, material, skin) = (draw.mesh, draw.material, draw.skin);
+            let draw = move |pass: &mut RenderPass<'a>| {
+                self.record(pass, pipeline, offset, mesh, material, skin);
+            };
+            match self.prepass_pipelines.get(&key) {
+                Some(depth) if self.debug_view == DebugView::Lit => {
+                    let depth_only = move |pass: &mut RenderPass<'a>| {
+                        self.record_depth(pass, depth, offset, mesh, material, skin);
+                    };
+                    queue.submit_with_prepass(sort_key, depth_only, draw);
+                }
+                _ => queue.submit(sort_key, draw),
+            }
+        }
+    }
+
+    fn uses_prepass(&self, material: &Material) -> bool {
+        self.depth_prepass && material.shader.depth_prepass && !material.shader.is_transparent()
+    }
+
+    /// Record one prepared draw, through the debug view's pipeline when one is active
+    fn record<'a, V>(
+        &'a self,
+        pass: &mut RenderPass<'a>,
+        pipeline: &'a RenderPipeline,
+        offset: u32,
+        mesh: &'a Mesh<V>,
+        material: &'a Material,
+        skin: Option<MaterialSkin<'a>>
+    )
+        where V: bytemuck::Pod
+    {
+        let debug = match self.debug_view {
+            DebugView::Lit => None,
+            _ => self.debug_pipelines.get(&self.debug_key(&mesh.layout)),
+        };
+        pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        if debug.is_none() || self.debug_view == DebugView::Wireframe {
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(1, &material.bind_group, &[]);
+            pass.set_bind_group(2, &self.object_bind_group, &[offset]);
+            if let Some(skin) = skin {
+                pass.set_bind_group(3, &skin.joints.bind_group, &[]);
+                pass.set_vertex_buffer(1, skin.weights);
+            }
+            pass.draw_mesh(mesh);
+        }
+        if let Some(debug) = debug {
+            pass.set_pipeline(debug);
+            pass.set_bind_group(1, &self.object_bind_group, &[offset]);
+            pass.draw_mesh(mesh);
+        }
+    }
+
+    /// Record one prepared draw through its depth-only pipeline
+    fn record_depth<'a, V>(
+        &'a self,
+        pass: &mut RenderPass<'a>,
+        pipeline: &'a RenderPipeline,
+        offset: u32,
+        mesh: &'a Mesh<V>,
+        material: &'a Material,
+        skin: Option<MaterialSkin<'a>>
+    )
+        where V: bytemuck::Pod
+    {
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        pass.set_bind_group(1, &material.bind_group, &[]);
+        pass.set_bind_group(2, &self.object_bind_group, &[offset]);
+        if let Some(skin) = skin {
+            pass.set_bind_group(3, &skin.joints.bind_group, &[]);
+            pass.set_vertex_buffer(1, skin.weights);
+        }
+        pass.draw_mesh(mesh);
+    }
+
+    fn debug_key(&self, layout: &wgpu::VertexBufferLayout) -> PipelineKey {
+        let index = DebugView::ALL.iter().position(|&view| view == self.debug_view);
+        PipelineKey::for_shader(index.unwrap_or(0) as u64, layout)
+    }
+
+    pub(crate) fn create_objects(
+        context: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        stride: u64,
+        capacity: usize
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = context.device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some(&context.label("Material Object Buffer")),
+                size: stride * (capacity as u64),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        );
+        let bind_group = BindGroupBuilder::new(layout)
+            .buffer_range(0, &buffer, 0, Some(std::mem::size_of::<ObjectUniform>() as u64))
+            .build(context, Some("Material Object Bind Group"));
+        (buffer, bind_group)
+    }
+
+    /// Pipeline of the current debug view, with the object transform at group 1
+    fn create_debug_pipeline(
+        &self,
+        context: &GpuContext,
+        vertex_layout: &wgpu::VertexBufferLayout
+    ) -> Option<RenderPipeline> {
+        let view = self.debug_view;
+        let source = view.shader_source(vertex_layout)?;
+        let label = context.label(&format!("Debug View {:?}", view));
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&label),
+                bind_group_layouts: &[&self.camera_layout, &self.object_layout],
+                push_constant_ranges: &[],
+            })
+        );
+
+        let descriptor = wgpu::RenderPipelineDescriptor {
+            label: Some(&label),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: std::slice::from_ref(vertex_layout),
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: self.color_format,
+                        blend: view.blend(),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                ],
+                compilation_options: Default::default(),
+            }),
+            primitive: view.primitive(),
+            depth_stencil: self.depth_format.map(|format| view.depth_stencil(format)),
+            multisample: wgpu::MultisampleState {
+                count: self.samples,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        };
+        log::debug!("{}", describe::render_pipeline(&descriptor, 2));
+        let pipeline = context.device.create_render_pipeline(&descriptor);
+
+        Some(RenderPipeline {
+            pipeline,
+            bind_group_layouts: Vec::new(),
+            vertex_buffer_count: 1,
+            bindings: Vec::new(),
+        })
+    }
+
+    /// Pipeline of a material, or its fragment-less depth pre-pass version
+    fn create_pipeline(
+        &self,
+        context: &GpuContext,
+        material: &Material,
+        vertex_layout: &wgpu::VertexBufferLayout,
+        depth_only: bool
+    ) -> RenderPipeline {
+        let shader = &material.shader;
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&shader.label),
+            source: wgpu::ShaderSource::Wgsl(shader.source.as_str().into()),
+        });
+
+        let mut bind_group_layouts: Vec<&wgpu::BindGroupLayout> = vec![
+            &self.camera_layout,
+            &material.bind_group_layout,
+            &self.object_layout
+        ];
+        let mut buffers = vec![vertex_layout.clone()];
+        if shader.skinned {
+            bind_group_layouts.push(&self.joint_layout);
+            buffers.push(SkinWeights::layout());
+        }
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&shader.label),
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: &[],
+            })
+        );
+        let color_targets = [
+            Some(wgpu::ColorTargetState {
+                format: self.color_format,
+                blend: shader.blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+        ];
+        // Main pipelines draw on top of the pre-pass's identical depth
+        let depth_compare = if !depth_only && self.uses_prepass(material) {
+            wgpu::CompareFunction::LessEqual
+        } else {
+            wgpu::CompareFunction::Less
+        };
+
+        let descriptor = wgpu::RenderPipelineDescriptor {
+            label: Some(&shader.label),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &buffers,
+                compilation_options: Default::default(),
+            },
+            fragment: (!depth_only).then(|| wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &color_targets,
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: shader.cull_mode,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: self.depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: shader.depth_write || depth_only,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: self.samples,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        };
+        log::debug!("{}", describe::render_pipeline(&descriptor, bind_group_layouts.len()));
+        let pipeline = context.device.create_render_pipeline(&descriptor);
+
+        RenderPipeline {
+            pipeline,
+            bind_group_layouts: Vec::new(),
+            vertex_buffer_count: buffers.len(),
+            bindings: Vec::new(),
+        }
+    }
+}
+
+const BASIC_SHADER: &str =
+    r#"
+struct BasicParams {
+    base_color: vec4<f32>,
+}
+
+@group(1) @binding(0) var<uniform> params: BasicParams;
+@group(1) @binding(1) var base_texture: texture_2d<f32>;
+@group(1) @binding(2) var base_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let world = object.model * vec4<f32>(in.position, 1.0);
+    out.clip_position = camera.view_projection * world;
+    out.normal = (object.normal * vec4<f32>(in.normal, 0.0)).xyz;
+    out.uv = in.uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let light = normalize(vec3<f32>(0.4, 1.0, 0.6));
+    let diffuse = max(dot(normalize(in.normal), light), 0.0) * 0.8 + 0.2;
+    let color = params.base_color * textureSample(base_texture, base_sampler, in.uv);
+    return vec4<f32>(color.rgb * diffuse, color.a);
+}
+"#;
+
+/// PBR shader body without a vertex stage; the ambient term comes from an `ambient`
+/// function appended after it
+pub(crate) const PBR_SHADER: &str =
+    r#"
+const PI: f32 = 3.14159265;
+const LIGHT_DIRECTION: vec3<f32> = vec3<f32>(0.4, 1.0, 0.6);
+const LIGHT_COLOR: vec3<f32> = vec3<f32>(3.0, 3.0, 3.0);
+
+struct PbrParams {
+    base_color: vec4<f32>,
+    emissive: vec4<f32>,
+    metallic: f32,
+    roughness: f32,
+    occlusion_strength: f32,
+    alpha_cutoff: f32,
+}
+
+@group(1) @binding(0) var<uniform> params: PbrParams;
+@group(1) @binding(1) var base_color_texture: texture_2d<f32>;
+@group(1) @binding(2) var base_color_sampler: sampler;
+@group(1) @binding(3) var metallic_roughness_texture: texture_2d<f32>;
+@group(1) @binding(4) var metallic_roughness_sampler: sampler;
+@group(1) @binding(5) var occlusion_texture: texture_2d<f32>;
+@group(1) @binding(6) var occlusion_sampler: sampler;
+@group(1) @binding(7) var emissive_texture: texture_2d<f32>;
+@group(1) @binding(8) var emissive_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let base = params.base_color * textureSample(base_color_texture, base_color_sampler, in.uv);
+    let metallic_roughness = textureSample(metallic_roughness_texture, metallic_roughness_sampler, in.uv);
+    let occlusion = textureSample(occlusion_texture, occlusion_sampler, in.uv).r;
+    let emissive = params.emissive.rgb * textureSample(emissive_texture, emissive_sampler, in.uv).rgb;
+    if (base.a < params.alpha_cutoff) {
+        discard;
+    }
+
+    let metallic = params.metallic * metallic_roughness.b;
+    let roughness = clamp(params.roughness * metallic_roughness.g, 0.04, 1.0);
+    let ao = mix(1.0, occlusion, params.occlusion_strength);
+
+    let n = normalize(in.normal);
+    let v = normalize(camera.position.xyz - in.world_position);
+    let l = normalize(LIGHT_DIRECTION);
+    let h = normalize(v + l);
+    let n_dot_l = max(dot(n, l), 0.0);
+    let n_dot_v = max(dot(n, v), 1e-4);
+    let n_dot_h = max(dot(n, h), 0.0);
+    let v_dot_h = max(dot(v, h), 0.0);
+
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    let d = a2 / (PI * denom * denom);
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let g = n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+    let f0 = mix(vec3<f32>(0.04), base.rgb, metallic);
+    let f = f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+
+    let specular = d * g * f / max(4.0 * n_dot_v * n_dot_l, 1e-4);
+    let diffuse = (1.0 - f) * (1.0 - metallic) * base.rgb / PI;
+    let direct = (diffuse + specular) * LIGHT_COLOR * n_dot_l;
+    let indirect = ambient(n, v, base.rgb, metallic, roughness) * ao;
+    return vec4<f32>(direct + indirect + emissive, base.a);
+}
+"#;
+
+/// Vertex stage of the PBR shader for [`MeshVertex`](crate::MeshVertex) meshes
+pub(crate) const PBR_VERTEX: &str =
+    r#"
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let world = object.model * vec4<f32>(in.position, 1.0);
+    out.clip_position = camera.view_projection * world;
+    out.world_position = world.xyz;
+    out.normal = (object.normal * vec4<f32>(in.normal, 0.0)).xyz;
+    out.uv = in.uv;
+    return out;
+}
+"#;
+
+pub(crate) const PBR_FLAT_AMBIENT: &str =
+    r#"
+fn ambient(n: vec3<f32>, v: vec3<f32>, albedo: vec3<f32>, metallic: f32, roughness: f32) -> vec3<f32> {
+    return vec3<f32>(0.03) * albedo;
+}
+"#;
@@ -0,0 +1,319 @@
+use crate::{ GeepuError, GpuContext, Result, TypedBuffer };
+use std::any::Any;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Standard vertex used by the built-in primitive generators
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+impl MeshVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2];
+
+    pub fn new(position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> Self {
+        Self { position, normal, uv }
+    }
+
+    /// Vertex layout: position at location 0, normal at 1, uv at 2
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// CPU-side indexed geometry that can be uploaded as a [`Mesh`]
+#[derive(Debug, Clone, Default)]
+pub struct MeshData<V> {
+    pub vertices: Vec<V>,
+    pub indices: Vec<u32>,
+}
+
+impl<V> MeshData<V> where V: bytemuck::Pod {
+    /// Upload the geometry as a mesh with the given vertex layout
+    pub fn upload(
+        &self,
+        context: &GpuContext,
+        layout: wgpu::VertexBufferLayout<'static>
+    ) -> Result<Mesh<V>> {
+        Mesh::new(context, &self.vertices, Some(&self.indices), layout)
+    }
+}
+
+/// Element types an index buffer can hold
+pub trait IndexType: bytemuck::Pod {
+    const FORMAT: wgpu::IndexFormat;
+}
+
+impl IndexType for u16 {
+    const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint16;
+}
+
+impl IndexType for u32 {
+    const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint32;
+}
+
+/// An index buffer in whichever format its indices fit
+pub enum IndexBuffer {
+    Uint16(TypedBuffer<u16>),
+    Uint32(TypedBuffer<u32>),
+}
+
+impl IndexBuffer {
+    /// Upload `indices` as 16-bit indices when they all fit, 32-bit otherwise
+    pub fn new(context: &GpuContext, indices: &[u32]) -> Result<Self> {
+        match Self::narrow(indices) {
+            Some(narrow) => Ok(Self::Uint16(TypedBuffer::index(context, &narrow)?)),
+            None => Ok(Self::Uint32(TypedBuffer::index(context, indices)?)),
+        }
+    }
+
+    /// `indices` converted to 16 bits, if none is larger than `u16::MAX`
+    pub fn narrow(indices: &[u32]) -> Option<Vec<u16>> {
+        indices
+            .iter()
+            .map(|&index| u16::try_from(index).ok())
+            .collect()
+    }
+
+    pub fn format(&self) -> wgpu::IndexFormat {
+        match self {
+            Self::Uint16(_) => wgpu::IndexFormat::Uint16,
+            Self::Uint32(_) => wgpu::IndexFormat::Uint32,
+        }
+    }
+
+    /// Number of indices
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Uint16(buffer) => buffer.len(),
+            Self::Uint32(buffer) => buffer.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn size_bytes(&self) -> u64 {
+        match self {
+            Self::Uint16(buffer) => buffer.size_bytes(),
+            Self::Uint32(buffer) => buffer.size_bytes(),
+        }
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        match self {
+            Self::Uint16(buffer) => buffer.buffer(),
+            Self::Uint32(buffer) => buffer.buffer(),
+        }
+    }
+}
+
+/// A part of a mesh drawn with a single draw call
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubMesh {
+    /// Index range for indexed meshes, vertex range otherwise
+    pub range: Range<u32>,
+    /// Value added to each index before fetching the vertex
+    pub base_vertex: i32,
+}
+
+impl SubMesh {
+    pub fn new(range: Range<u32>) -> Self {
+        Self { range, base_vertex: 0 }
+    }
+
+    pub fn base_vertex(mut self, base_vertex: i32) -> Self {
+        self.base_vertex = base_vertex;
+        self
+    }
+}
+
+/// GPU geometry: a vertex buffer, optional index buffer, its vertex layout and submesh ranges
+pub struct Mesh<V> {
+    pub vertices: TypedBuffer<V>,
+    pub indices: Option<IndexBuffer>,
+    pub layout: wgpu::VertexBufferLayout<'static>,
+    pub submeshes: Vec<SubMesh>,
+}
+
+impl<V> Mesh<V> where V: bytemuck::Pod {
+    /// Upload vertices (and optional indices) as a mesh with a single submesh covering everything.
+    /// Indices are stored as 16-bit when they all fit, as for meshes of up to 65536 vertices.
+    pub fn new(
+        context: &GpuContext,
+        vertices: &[V],
+        indices: Option<&[u32]>,
+        layout: wgpu::VertexBufferLayout<'static>
+    ) -> Result<Self> {
+        let vertices = TypedBuffer::vertex(context, vertices)?;
+        let indices = indices.map(|indices| IndexBuffer::new(context, indices)).transpose()?;
+        let count = indices.as_ref().map_or(vertices.len(), |indices| indices.len()) as u32;
+
+        Ok(Self {
+            vertices,
+            indices,
+            layout,
+            submeshes: vec![SubMesh::new(0..count)],
+        })
+    }
+
+    /// Replace the submesh list, checking every range against the buffer it indexes
+    pub fn with_submeshes(mut self, submeshes: Vec<SubMesh>) -> Result<Self> {
+        let count = self.element_count();
+        let out_of_bounds = submeshes
+            .iter()
+            .find(|s| s.range.start > s.range.end || s.range.end > count);
+        if let Some(bad) = out_of_bounds {
+            return Err(
+                GeepuError::BufferError(
+                    format!("Submesh range {:?} is out of bounds for {} elements", bad.range, count)
+                )
+            );
+        }
+        self.submeshes = submeshes;
+        Ok(self)
+    }
+
+    /// Whether the mesh is drawn with an index buffer
+    pub fn is_indexed(&self) -> bool {
+        self.indices.is_some()
+    }
+
+    /// Number of vertices in the vertex buffer
+    pub fn vertex_count(&self) -> u32 {
+        self.vertices.len() as u32
+    }
+
+    /// Number of indices, or 0 for non-indexed meshes
+    pub fn index_count(&self) -> u32 {
+        self.indices.as_ref().map_or(0, |indices| indices.len() as u32)
+    }
+
+    /// Format of the index buffer, for indexed meshes
+    pub fn index_format(&self) -> Option<wgpu::IndexFormat> {
+        self.indices.as_ref().map(IndexBuffer::format)
+    }
+
+    /// Number of elements submesh ranges refer to (indices or vertices)
+    fn element_count(&self) -> u32 {
+        if self.is_indexed() { self.index_count() } else { self.vertex_count() }
+    }
+}
+
+/// What drawing a [`Mesh`] needs, independent of its vertex type
+trait RegisteredMesh: Any {
+    fn vertex_buffer(&self) -> &wgpu::Buffer;
+    fn index_buffer(&self) -> Option<&IndexBuffer>;
+    fn submeshes(&self) -> &[SubMesh];
+    fn layout(&self) -> &wgpu::VertexBufferLayout<'static>;
+}
+
+impl<V> RegisteredMesh for Mesh<V> where V: bytemuck::Pod {
+    fn vertex_buffer(&self) -> &wgpu::Buffer {
+        self.vertices.buffer()
+    }
+
+    fn index_buffer(&self) -> Option<&IndexBuffer> {
+        self.indices.as_ref()
+    }
+
+    fn submeshes(&self) -> &[SubMesh] {
+        &self.submeshes
+    }
+
+    fn layout(&self) -> &wgpu::VertexBufferLayout<'static> {
+        &self.layout
+    }
+}
+
+/// Meshes of any vertex type stored by name.
+///
+/// Simple apps [`add_mesh`](Self::add_mesh) their geometry once and draw it
+/// with [`RenderPassGuard::draw_mesh`](crate::RenderPassGuard::draw_mesh),
+/// without touching vertex or index buffers.
+#[derive(Default)]
+pub struct MeshRegistry {
+    meshes: HashMap<String, Box<dyn RegisteredMesh>>,
+}
+
+impl MeshRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Upload vertices (and optional indices) as the mesh `name`, replacing any earlier one
+    pub fn add_mesh<V>(
+        &mut self,
+        context: &GpuContext,
+        name: impl Into<String>,
+        vertices: &[V],
+        indices: Option<&[u32]>,
+        layout: wgpu::VertexBufferLayout<'static>
+    ) -> Result<()>
+        where V: bytemuck::Pod
+    {
+        self.insert(name, Mesh::new(context, vertices, indices, layout)?);
+        Ok(())
+    }
+
+    /// Store an uploaded mesh as `name`, replacing any earlier one
+    pub fn insert<V>(&mut self, name: impl Into<String>, mesh: Mesh<V>) where V: bytemuck::Pod {
+        self.meshes.insert(name.into(), Box::new(mesh));
+    }
+
+    /// The mesh `name`, if it was registered with vertex type `V`
+    pub fn get<V>(&self, name: &str) -> Option<&Mesh<V>> where V: bytemuck::Pod {
+        let mesh: &dyn Any = self.meshes.get(name)?.as_ref();
+        mesh.downcast_ref()
+    }
+
+    /// Vertex layout of the mesh `name`, for building a pipeline that draws it
+    pub fn layout(&self, name: &str) -> Option<&wgpu::VertexBufferLayout<'static>> {
+        self.meshes.get(name).map(|mesh| mesh.layout())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.meshes.remove(name).is_some()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.meshes.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.meshes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty()
+    }
+
+    /// Bind the mesh `name` and draw each of its submeshes
+    pub(crate) fn draw(&self, pass: &mut wgpu::RenderPass<'_>, name: &str) -> Result<()> {
+        let mesh = self.meshes
+            .get(name)
+            .ok_or_else(|| GeepuError::BufferError(format!("No mesh registered as `{}`", name)))?;
+        pass.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
+        if let Some(indices) = mesh.index_buffer() {
+            pass.set_index_buffer(indices.buffer().slice(..), indices.format());
+        }
+        for submesh in mesh.submeshes() {
+            if mesh.index_buffer().is_some() {
+                pass.draw_indexed(submesh.range.clone(), submesh.base_vertex, 0..1);
+            } else {
+                pass.draw(submesh.range.clone(), 0..1);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{ MeshData, MeshVertex };
+use std::collections::{ HashMap, HashSet };
+
+/// Post-transform cache size the triangle order is tuned for
+#[cfg(not(feature = "optimize-meshes"))]
+const CACHE_SIZE: usize = 32;
+
+/// Mesh optimizations for imported geometry, run before upload.
+///
+/// [`optimize_vertex_cache`](Self::optimize_vertex_cache) reorders triangles
+/// so vertices are reused while still in the GPU's post-transform cache;
+/// [`optimize_vertex_fetch`](Self::optimize_vertex_fetch) then reorders
+/// vertices into the order they are first used, so fetches walk memory
+/// linearly. [`simplify_clustered`](Self::simplify_clustered) builds a coarser
+/// version for distant levels of detail.
+///
+/// With the `optimize-meshes` feature the triangle reordering runs through
+/// meshoptimizer (the `meshopt` crate), and `MeshData<MeshVertex>` gains its
+/// edge-collapse [`simplify_to`](MeshData::simplify_to).
+impl<V> MeshData<V> where V: bytemuck::Pod {
+    /// Reorder triangles for cache reuse, then vertices for fetch locality.
+    /// Returns the vertex order, as [`optimize_vertex_fetch`](Self::optimize_vertex_fetch) does.
+    pub fn optimize(&mut self) -> Vec<u32> {
+        self.optimize_vertex_cache();
+        self.optimize_vertex_fetch()
+    }
+
+    /// Reorder triangles to reduce vertex shader invocations (meshoptimizer
+    /// with `optimize-meshes`, Forsyth's linear-speed algorithm otherwise)
+    pub fn optimize_vertex_cache(&mut self) {
+        let triangle_count = self.indices.len() / 3;
+        if triangle_count == 0 {
+            return;
+        }
+        #[cfg(feature = "optimize-meshes")]
+        {
+            let vertex_count = self.vertices.len();
+            let mut output = meshopt::optimize_vertex_cache(
+                &self.indices[..triangle_count * 3],
+                vertex_count
+            );
+            output.extend_from_slice(&self.indices[triangle_count * 3..]);
+            self.indices = output;
+        }
+        #[cfg(not(feature = "optimize-meshes"))]
+        self.optimize_vertex_cache_forsyth(triangle_count);
+    }
+
+    #[cfg(not(feature = "optimize-meshes"))]
+    fn optimize_vertex_cache_forsyth(&mut self, triangle_count: usize) {
+        let vertex_count = self.vertices.len();
+        let indices = &self.indices[..triangle_count * 3];
+
+        // Triangles using each vertex, as ranges into `adjacency`; the live
+        // part of each range shrinks as its triangles are emitted
+        let mut remaining = vec![0u32; vertex_count];
+        for &index in indices {
+            remaining[index as usize] += 1;
+        }
+        let mut offsets = Vec::with_capacity(vertex_count);
+        let mut total = 0;
+        for &count in &remaining {
+            offsets.push(total);
+            total += count as usize;
+        }
+        let mut adjacency = vec![0u32; total];
+        let mut filled = vec![0usize; vertex_count];
+        for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+            for &vertex in corners {
+                let vertex = vertex as usize;
+                adjacency[offsets[vertex] + filled[vertex]] = triangle as u32;
+                filled[vertex] += 1;
+            }
+        }
+
+        let mut scores: Vec<f32> = remaining
+            .iter()
+            .map(|&count| vertex_score(None, count))
+            .collect();
+        let triangle_score = |scores: &[f32], triangle: usize| -> f32 {
+            indices[triangle * 3..triangle * 3 + 3]
+                .iter()
+                .map(|&vertex| scores[vertex as usize])
+                .sum()
+        };
+        let mut emitted = vec![false; triangle_count];
+        let mut best = (0..triangle_count).max_by(|&a, &b| {
+            triangle_score(&scores, a).total_cmp(&triangle_score(&scores, b))
+        });
+        let mut cursor = 0;
+        let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+        let mut output = Vec::with_capacity(indices.len());
+
+        loop {
+            let triangle = match best {
+                Some(triangle) => triangle,
+                None => {
+                    // Nothing in the cache has triangles left: take the next unused one
+                    while cursor < triangle_count && emitted[cursor] {
+                        cursor += 1;
+                    }
+                    if cursor == triangle_count {
+                        break;
+                    }
+                    cursor
+                }
+            };
+            emitted[triangle] = true;
+            let corners = &indices[triangle * 3..triangle * 3 + 3];
+            output.extend_from_slice(corners);
+
+            for &vertex in corners {
+                let vertex = vertex as usize;
+                let start = offsets[vertex];
+                let live = &mut adjacency[start..start + (remaining[vertex] as usize)];
+                if let Some(position) = live.iter().position(|&t| t == (triangle as u32)) {
+                    let last = live.len() - 1;
+                    live.swap(position, last);
+                    remaining[vertex] -= 1;
+                }
+            }
+
+            let mut next_cache: Vec<u32> = corners.to_vec();
+            next_cache.extend(cache.iter().filter(|vertex| !corners.contains(vertex)));
+            for (position, &vertex) in next_cache.iter().enumerate() {
+                let position = (position < CACHE_SIZE).then_some(position);
+                scores[vertex as usize] = vertex_score(position, remaining[vertex as usize]);
+            }
+            next_cache.truncate(CACHE_SIZE);
+            cache = next_cache;
+
+            best = None;
+            let mut best_score = -1.0;
+            for &vertex in &cache {
+                let start = offsets[vertex as usize];
+                for &t in &adjacency[start..start + (remaining[vertex as usize] as usize)] {
+                    let score = triangle_score(&scores, t as usize);
+                    if score > best_score {
+                        best_score = score;
+                        best = Some(t as usize);
+                    }
+                }
+            }
+        }
+
+        output.extend_from_slice(&self.indices[triangle_count * 3..]);
+        self.indices = output;
+    }
+
+    /// Reorder vertices into the order the index buffer first uses them,
+    /// dropping unused ones. Returns the old index of each new vertex, to
+    /// reorder parallel vertex streams (e.g. skin weights) the same way.
+    pub fn optimize_vertex_fetch(&mut self) -> Vec<u32> {
+        let mut remap = vec![u32::MAX; self.vertices.len()];
+        let mut order = Vec::with_capacity(self.vertices.len());
+        for index in &mut self.indices {
+            let old = *index as usize;
+            if remap[old] == u32::MAX {
+                remap[old] = order.len() as u32;
+                order.push(*index);
+            }
+            *index = remap[old];
+        }
+        self.vertices = order
+            .iter()
+            .map(|&index| self.vertices[index as usize])
+            .collect();
+        order
+    }
+
+    /// A coarser copy made by merging the vertices in each cell of a
+    /// `grid`³ grid over the bounds into one and dropping collapsed triangles.
+    /// `position` reads a vertex's position.
+    pub fn simplify_clustered(&self, grid: u32, position: impl Fn(&V) -> [f32; 3]) -> Self {
+        let grid = grid.max(1);
+        let positions: Vec<[f32; 3]> = self.vertices.iter().map(&position).collect();
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for p in &positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+        let extent = (0..3).map(|axis| max[axis] - min[axis]).fold(0.0, f32::max);
+        let cell_size = (extent / (grid as f32)).max(f32::EPSILON);
+        let cell_of = |p: &[f32; 3]| {
+            [0, 1, 2].map(|axis| {
+                (((p[axis] - min[axis]) / cell_size) as u32).min(grid - 1)
+            })
+        };
+
+        // Each cell is represented by its vertex closest to the cell's mean
+        let mut cells: HashMap<[u32; 3], ([f32; 3], u32)> = HashMap::new();
+        for p in &positions {
+            let (sum, count) = cells.entry(cell_of(p)).or_insert(([0.0; 3], 0));
+            for axis in 0..3 {
+                sum[axis] += p[axis];
+            }
+            *count += 1;
+        }
+        let mut representatives: HashMap<[u32; 3], (f32, u32)> = HashMap::new();
+        for (index, p) in positions.iter().enumerate() {
+            let cell = cell_of(p);
+            let (sum, count) = cells[&cell];
+            let distance: f32 = (0..3)
+                .map(|axis| (p[axis] - sum[axis] / (count as f32)).powi(2))
+                .sum();
+            let entry = representatives.entry(cell).or_insert((f32::MAX, index as u32));
+            if distance < entry.0 {
+                *entry = (distance, index as u32);
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut indices = Vec::new();
+        for corners in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| {
+                representatives[&cell_of(&positions[corners[i] as usize])].1
+            });
+            if a == b || b == c || a == c {
+                continue;
+            }
+            // Rotate so the smallest index leads, keeping the winding
+            let key = if a < b && a < c {
+                [a, b, c]
+            } else if b < c {
+                [b, c, a]
+            } else {
+                [c, a, b]
+            };
+            if seen.insert(key) {
+                indices.extend_from_slice(&[a, b, c]);
+            }
+        }
+
+        let mut simplified = Self { vertices: self.vertices.clone(), indices };
+        simplified.optimize_vertex_fetch();
+        simplified
+    }
+
+    /// Average vertex shader invocations per triangle for a FIFO
+    /// post-transform cache of `cache_size` entries (lower is better, 0.5-3.0)
+    pub fn vertex_cache_miss_ratio(&self, cache_size: usize) -> f32 {
+        let triangles = self.indices.len() / 3;
+        if triangles == 0 {
+            return 0.0;
+        }
+        let mut cache = std::collections::VecDeque::with_capacity(cache_size);
+        let mut misses = 0;
+        for index in &self.indices {
+            if !cache.contains(index) {
+                misses += 1;
+                if cache.len() == cache_size {
+                    cache.pop_front();
+                }
+                cache.push_back(*index);
+            }
+        }
+        (misses as f32) / (triangles as f32)
+    }
+}
+
+impl MeshData<MeshVertex> {
+    /// [`simplify_clustered`](MeshData::simplify_clustered) by vertex position
+    pub fn simplify(&self, grid: u32) -> Self {
+        self.simplify_clustered(grid, |vertex| vertex.position)
+    }
+
+    /// A copy with about `ratio` of the triangles, collapsing edges with
+    /// meshoptimizer while keeping the surface within `error` (relative to
+    /// the mesh extent, e.g. 0.01) of the original
+    #[cfg(feature = "optimize-meshes")]
+    pub fn simplify_to(&self, ratio: f32, error: f32) -> Self {
+        let stride = std::mem::size_of::<MeshVertex>();
+        let adapter = meshopt::VertexDataAdapter
+            ::new(bytemuck::cast_slice(&self.vertices), stride, 0)
+            .expect("MeshVertex data is evenly strided");
+        let triangle_count = self.indices.len() / 3;
+        let target = (((triangle_count as f32) * ratio.clamp(0.0, 1.0)) as usize) * 3;
+        let indices = meshopt::simplify(
+            &self.indices[..triangle_count * 3],
+            &adapter,
+            target,
+            error
+        );
+        let mut simplified = Self { vertices: self.vertices.clone(), indices };
+        simplified.optimize_vertex_fetch();
+        simplified
+    }
+}
+
+/// Forsyth's vertex score: recently used vertices and vertices with few
+/// triangles left score higher; vertices with none left never get picked
+#[cfg(not(feature = "optimize-meshes"))]
+fn vertex_score(cache_position: Option<usize>, remaining: u32) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        // The last triangle's vertices score lower so strips don't backtrack
+        Some(position) if position < 3 => 0.75,
+        Some(position) => {
+            let scale = 1.0 / ((CACHE_SIZE - 3) as f32);
+            (1.0 - ((position - 3) as f32) * scale).powf(1.5)
+        }
+    };
+    cache_score + 2.0 * (remaining as f32).powf(-0.5)
+}
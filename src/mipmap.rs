@@ -0,0 +1,247 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    ComputePass,
+    ComputePipeline,
+    GeepuError,
+    GpuContext,
+    Result,
+    Texture,
+};
+use std::ops::Range;
+use std::sync::Arc;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Number of mip levels in a full chain down to 1x1
+pub fn max_mip_levels(width: u32, height: u32) -> u32 {
+    width.max(height).max(1).ilog2() + 1
+}
+
+/// How a [`MipGenerator`] reduces each 2x2 block of the level above
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MipFilter {
+    /// Box filter, for color mips and bloom chains
+    Average,
+    /// Smallest value, e.g. the farthest depth of a reversed-Z Hi-Z chain
+    Min,
+    /// Largest value, e.g. the farthest depth of a Hi-Z chain
+    Max,
+}
+
+impl MipFilter {
+    /// WGSL expression combining texels `a` and `b`
+    fn reduce_wgsl(self) -> &'static str {
+        match self {
+            Self::Average => "a + b",
+            Self::Min => "min(a, b)",
+            Self::Max => "max(a, b)",
+        }
+    }
+}
+
+/// WGSL name of a storage texture format, or `None` when `format` cannot be
+/// written from a compute shader without extra features
+pub fn storage_format_wgsl(format: wgpu::TextureFormat) -> Option<&'static str> {
+    use wgpu::TextureFormat as Format;
+
+    Some(match format {
+        Format::Rgba8Unorm => "rgba8unorm",
+        Format::Rgba8Snorm => "rgba8snorm",
+        Format::Rgba16Float => "rgba16float",
+        Format::Rgba32Float => "rgba32float",
+        Format::R32Float => "r32float",
+        Format::Rg32Float => "rg32float",
+        _ => {
+            return None;
+        }
+    })
+}
+
+/// Downsample kernel writing one mip level from the one above in a compute pass.
+///
+/// [`generate`](Self::generate) fills a whole chain, [`downsample`](Self::downsample)
+/// a single level (or any two views of matching format), so bloom chains and
+/// custom reductions such as Hi-Z min/max mips can be built on the GPU. Odd
+/// sizes fold the leftover row and column into the last texel, so min/max
+/// chains stay conservative. Textures need `TEXTURE_BINDING` and
+/// `STORAGE_BINDING` usage and a format from [`storage_format_wgsl`]; sRGB
+/// formats cannot be storage textures.
+pub struct MipGenerator {
+    pipeline: ComputePipeline,
+    layout: Arc<wgpu::BindGroupLayout>,
+    format: wgpu::TextureFormat,
+    filter: MipFilter,
+}
+
+impl MipGenerator {
+    pub fn new(
+        context: &GpuContext,
+        format: wgpu::TextureFormat,
+        filter: MipFilter
+    ) -> Result<Self> {
+        let source = Self::shader_source(format, filter)?;
+        let layout = BindGroupLayoutBuilder::new()
+            .texture(
+                0,
+                wgpu::ShaderStages::COMPUTE,
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .storage_texture(
+                1,
+                wgpu::ShaderStages::COMPUTE,
+                wgpu::StorageTextureAccess::WriteOnly,
+                format,
+                wgpu::TextureViewDimension::D2
+            )
+            .build(context, Some("Mip Generator Layout"));
+        let pipeline = ComputePipeline::new(
+            context,
+            &source,
+            vec![layout.clone()],
+            Some("Mip Generator Pipeline")
+        )?;
+        Ok(Self { pipeline, layout, format, filter })
+    }
+
+    /// The kernel's WGSL: `source` is the level above, `destination` the level written
+    pub fn shader_source(format: wgpu::TextureFormat, filter: MipFilter) -> Result<String> {
+        let name = storage_format_wgsl(format).ok_or_else(|| {
+            GeepuError::TextureError(format!("{:?} cannot be a storage texture", format))
+        })?;
+        Ok(
+            DOWNSAMPLE_SHADER.replace("{format}", name)
+                .replace("{reduce}", filter.reduce_wgsl())
+                .replace("{average}", if filter == MipFilter::Average { "true" } else { "false" })
+        )
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn filter(&self) -> MipFilter {
+        self.filter
+    }
+
+    /// Record one dispatch writing `target` from `source`, where `target` is
+    /// a single-level view `target_size` texels large (see [`Texture::mip_view`])
+    pub fn downsample(
+        &self,
+        context: &GpuContext,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        target: &wgpu::TextureView,
+        target_size: (u32, u32)
+    ) {
+        let bind_group = BindGroupBuilder::new(&self.layout)
+            .texture_view(0, source)
+            .texture_view(1, target)
+            .build(context, Some("Mip Generator Bind Group"));
+        let mut pass = ComputePass::new(encoder, Some(&context.label("Mip Downsample")));
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(
+            target_size.0.div_ceil(WORKGROUP_SIZE),
+            target_size.1.div_ceil(WORKGROUP_SIZE),
+            1
+        );
+    }
+
+    /// Record the dispatches filling mip levels `1..` of `texture` from level 0
+    pub fn generate(
+        &self,
+        context: &GpuContext,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &Texture
+    ) -> Result<()> {
+        self.generate_levels(context, encoder, texture, 1..texture.mip_level_count())
+    }
+
+    /// Record the dispatches filling each of `levels` from the level above it,
+    /// in order, e.g. `1..5` for the first four levels of a bloom chain
+    pub fn generate_levels(
+        &self,
+        context: &GpuContext,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &Texture,
+        levels: Range<u32>
+    ) -> Result<()> {
+        self.check(texture, &levels)?;
+        let mut source = texture.mip_view(levels.start - 1);
+        for level in levels {
+            let target = texture.mip_view(level);
+            self.downsample(context, encoder, &source, &target, texture.mip_size(level));
+            source = target;
+        }
+        Ok(())
+    }
+
+    fn check(&self, texture: &Texture, levels: &Range<u32>) -> Result<()> {
+        let required = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING;
+        let error = if texture.format() != self.format {
+            format!(
+                "Texture is {:?}, mip generator was created for {:?}",
+                texture.format(),
+                self.format
+            )
+        } else if !texture.texture.usage().contains(required) {
+            "Mip generation needs TEXTURE_BINDING and STORAGE_BINDING usage".to_string()
+        } else if levels.start == 0 || levels.end > texture.mip_level_count() {
+            format!(
+                "Cannot generate mip levels {:?} of a texture with {} levels",
+                levels,
+                texture.mip_level_count()
+            )
+        } else {
+            return Ok(());
+        };
+        Err(GeepuError::TextureError(error))
+    }
+}
+
+const DOWNSAMPLE_SHADER: &str =
+    r#"
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var destination: texture_storage_2d<{format}, write>;
+
+const AVERAGE: bool = {average};
+
+fn reduce(a: vec4<f32>, b: vec4<f32>) -> vec4<f32> {
+    return {reduce};
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(destination);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    let source_size = textureDimensions(source);
+    let start = min(id.xy * 2u, source_size - 1u);
+    // The last texel also covers the row and column left over by odd sizes
+    var end = min(start + 2u, source_size);
+    if (id.x == size.x - 1u) {
+        end.x = source_size.x;
+    }
+    if (id.y == size.y - 1u) {
+        end.y = source_size.y;
+    }
+
+    var value = textureLoad(source, start, 0);
+    for (var y = start.y; y < end.y; y++) {
+        for (var x = start.x; x < end.x; x++) {
+            if (x != start.x || y != start.y) {
+                value = reduce(value, textureLoad(source, vec2<u32>(x, y), 0));
+            }
+        }
+    }
+    if (AVERAGE) {
+        let count = (end - start).x * (end - start).y;
+        value /= f32(count);
+    }
+    textureStore(destination, id.xy, value);
+}
+"#;
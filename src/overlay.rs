@@ -0,0 +1,347 @@
+use crate::{ Camera, GpuContext, PassTiming, RectStyle, RenderPass, Result, UiRenderer };
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Frame time above which a graph bar turns yellow (60 Hz)
+const TARGET_MS: f32 = 1000.0 / 60.0;
+/// Frame time above which a graph bar turns red (30 Hz)
+const SLOW_MS: f32 = 1000.0 / 30.0;
+
+const TEXT_COLOR: [f32; 4] = [0.92, 0.92, 0.92, 1.0];
+const DIM_COLOR: [f32; 4] = [0.6, 0.6, 0.65, 1.0];
+const GOOD_COLOR: [f32; 4] = [0.3, 0.85, 0.4, 1.0];
+const WARN_COLOR: [f32; 4] = [0.95, 0.8, 0.25, 1.0];
+const BAD_COLOR: [f32; 4] = [0.95, 0.3, 0.25, 1.0];
+
+/// Rolling window of CPU frame times
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    window: usize,
+    frames: VecDeque<f32>,
+}
+
+impl FrameStats {
+    /// Keep the last `window` frame times
+    pub fn new(window: usize) -> Self {
+        Self { window: window.max(1), frames: VecDeque::with_capacity(window) }
+    }
+
+    /// Add the duration of one frame
+    pub fn record(&mut self, delta: Duration) {
+        if self.frames.len() == self.window {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(delta.as_secs_f32() * 1000.0);
+    }
+
+    /// Most recent frame time in milliseconds
+    pub fn last_ms(&self) -> f32 {
+        self.frames.back().copied().unwrap_or(0.0)
+    }
+
+    pub fn average_ms(&self) -> f32 {
+        if self.frames.is_empty() {
+            return 0.0;
+        }
+        self.frames.iter().sum::<f32>() / (self.frames.len() as f32)
+    }
+
+    pub fn max_ms(&self) -> f32 {
+        self.frames.iter().copied().fold(0.0, f32::max)
+    }
+
+    /// Frames per second over the window
+    pub fn fps(&self) -> f32 {
+        let average = self.average_ms();
+        if average > 0.0 { 1000.0 / average } else { 0.0 }
+    }
+
+    /// Frame times in milliseconds, oldest first
+    pub fn history(&self) -> impl Iterator<Item = f32> + '_ {
+        self.frames.iter().copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+/// Corner panel with FPS, a frame time graph, GPU pass timings and GPU memory.
+///
+/// Feed it with [`record_frame`](Self::record_frame) once per frame and,
+/// optionally, [`set_pass_timings`](Self::set_pass_timings) from a
+/// [`GpuProfiler`](crate::GpuProfiler) report; [`toggle`](Self::toggle) shows
+/// and hides it. Text uses a built-in 3x5 pixel font, so no font assets are
+/// needed. Memory comes from [`GpuContext::gpu_memory_usage`], falling back to
+/// geepu's tracked estimate when wgpu's counters are unavailable.
+pub struct StatsOverlay {
+    ui: UiRenderer,
+    stats: FrameStats,
+    passes: Vec<PassTiming>,
+    visible: bool,
+    position: [f32; 2],
+    scale: f32,
+}
+
+impl StatsOverlay {
+    /// Create a hidden overlay keeping 120 frames of history
+    pub fn new(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>
+    ) -> Result<Self> {
+        Self::new_multisampled(context, color_format, depth_format, 1)
+    }
+
+    /// [`new`](Self::new) for targets with `samples` samples per pixel, e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    pub fn new_multisampled(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        samples: u32
+    ) -> Result<Self> {
+        Ok(Self {
+            ui: UiRenderer::new_multisampled(
+                context,
+                None,
+                color_format,
+                depth_format,
+                samples
+            )?,
+            stats: FrameStats::new(120),
+            passes: Vec::new(),
+            visible: false,
+            position: [8.0, 8.0],
+            scale: 2.0,
+        })
+    }
+
+    /// Place the panel's top-left corner, in the pixels of the camera given to `prepare`
+    pub fn with_position(mut self, position: [f32; 2]) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Size of one font pixel (default 2)
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale.max(1.0);
+        self
+    }
+
+    /// Show the overlay if hidden, hide it if shown
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Record how long the last frame took
+    pub fn record_frame(&mut self, delta: Duration) {
+        self.stats.record(delta);
+    }
+
+    /// Replace the GPU pass timings shown, usually with `profiler.report()`
+    pub fn set_pass_timings(&mut self, timings: Vec<PassTiming>) {
+        self.passes = timings;
+    }
+
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// Lay out and upload the panel; draws nothing while hidden
+    pub fn prepare(&mut self, context: &GpuContext, camera: &Camera) -> Result<()> {
+        if self.visible {
+            self.layout(context.gpu_memory_usage().total_bytes());
+        }
+        self.ui.prepare(context, camera)
+    }
+
+    /// Draw the panel uploaded by the last [`prepare`](Self::prepare)
+    pub fn render<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        self.ui.render(pass);
+    }
+
+    fn layout(&mut self, memory_bytes: u64) {
+        let px = self.scale;
+        let padding = 4.0 * px;
+        let line_height = 7.0 * px;
+        let graph_size = [64.0 * px, 20.0 * px];
+
+        let frame_color = Self::frame_color(self.stats.average_ms());
+        let mut header = vec![(
+            format!("FPS {:.0}  {:.2} MS", self.stats.fps(), self.stats.average_ms()),
+            frame_color,
+        )];
+        let mut footer = Vec::new();
+        if !self.passes.is_empty() {
+            let gpu_ms: f32 = self.passes
+                .iter()
+                .map(|pass| pass.last_ms)
+                .sum();
+            footer.push((format!("GPU {:.2} MS", gpu_ms), TEXT_COLOR));
+            for pass in &self.passes {
+                footer.push((format!(" {} {:.2}", pass.label, pass.average_ms), DIM_COLOR));
+            }
+        }
+        if memory_bytes > 0 {
+            let megabytes = (memory_bytes as f64) / (1024.0 * 1024.0);
+            header.push((format!("MEM {:.1} MB", megabytes), TEXT_COLOR));
+        }
+
+        let text_width = header
+            .iter()
+            .chain(footer.iter())
+            .map(|(line, _)| text_width(line) * px)
+            .fold(0.0, f32::max);
+        let width = text_width.max(graph_size[0]) + 2.0 * padding;
+        let lines = (header.len() + footer.len()) as f32;
+        let height = lines * line_height + graph_size[1] + 2.0 * padding + px;
+
+        let [x, y] = self.position;
+        self.ui.styled_rect(
+            [x, y],
+            [width, height],
+            RectStyle::new([0.05, 0.05, 0.07, 0.8])
+                .radius(2.0 * px)
+                .border(1.0, [1.0, 1.0, 1.0, 0.15])
+        );
+
+        let mut cursor = [x + padding, y + padding];
+        for (line, color) in &header {
+            self.text(cursor, line, *color);
+            cursor[1] += line_height;
+        }
+        self.graph(cursor, graph_size);
+        cursor[1] += graph_size[1] + 2.0 * px;
+        for (line, color) in &footer {
+            self.text(cursor, line, *color);
+            cursor[1] += line_height;
+        }
+    }
+
+    /// Bars of the frame time history, scaled so 30 Hz (or the slowest frame) fills the graph
+    fn graph(&mut self, min: [f32; 2], size: [f32; 2]) {
+        self.ui.rect(min, size, [0.0, 0.0, 0.0, 0.35]);
+
+        let ceiling = self.stats.max_ms().max(SLOW_MS);
+        let bar_width = size[0] / (self.stats.window as f32);
+        let bottom = min[1] + size[1];
+        let bars: Vec<f32> = self.stats.history().collect();
+        for (index, ms) in bars.into_iter().enumerate() {
+            let height = (ms / ceiling).min(1.0) * size[1];
+            self.ui.rect(
+                [min[0] + (index as f32) * bar_width, bottom - height],
+                [bar_width, height],
+                Self::frame_color(ms)
+            );
+        }
+
+        let target = bottom - (TARGET_MS / ceiling) * size[1];
+        self.ui.rect([min[0], target], [size[0], 1.0], [1.0, 1.0, 1.0, 0.4]);
+    }
+
+    /// Draw `text` with the built-in font, merging each glyph row into runs of pixels
+    fn text(&mut self, min: [f32; 2], text: &str, color: [f32; 4]) {
+        let px = self.scale;
+        for (index, c) in text.chars().enumerate() {
+            let bits = glyph(c);
+            let origin = min[0] + (index as f32) * 4.0 * px;
+            for row in 0..5 {
+                let row_bits = (bits >> (12 - 3 * row)) & 0b111;
+                let mut column = 0;
+                while column < 3 {
+                    if row_bits & (0b100 >> column) == 0 {
+                        column += 1;
+                        continue;
+                    }
+                    let start = column;
+                    while column < 3 && row_bits & (0b100 >> column) != 0 {
+                        column += 1;
+                    }
+                    self.ui.rect(
+                        [origin + (start as f32) * px, min[1] + (row as f32) * px],
+                        [((column - start) as f32) * px, px],
+                        color
+                    );
+                }
+            }
+        }
+    }
+
+    fn frame_color(ms: f32) -> [f32; 4] {
+        if ms <= TARGET_MS * 1.05 {
+            GOOD_COLOR
+        } else if ms <= SLOW_MS * 1.05 {
+            WARN_COLOR
+        } else {
+            BAD_COLOR
+        }
+    }
+}
+
+/// Width of `text` in font pixels (3 wide glyphs with 1 pixel spacing)
+fn text_width(text: &str) -> f32 {
+    let count = text.chars().count() as f32;
+    (count * 4.0 - 1.0).max(0.0)
+}
+
+/// 3x5 bitmap of an upper-case glyph, rows top to bottom, 3 bits per row.
+/// Lower-case letters map to upper case; unknown characters are blank.
+fn glyph(c: char) -> u16 {
+    match c.to_ascii_uppercase() {
+        'A' => 0b010_101_111_101_101,
+        'B' => 0b110_101_110_101_110,
+        'C' => 0b011_100_100_100_011,
+        'D' => 0b110_101_101_101_110,
+        'E' => 0b111_100_110_100_111,
+        'F' => 0b111_100_110_100_100,
+        'G' => 0b011_100_101_101_011,
+        'H' => 0b101_101_111_101_101,
+        'I' => 0b111_010_010_010_111,
+        'J' => 0b001_001_001_101_010,
+        'K' => 0b101_101_110_101_101,
+        'L' => 0b100_100_100_100_111,
+        'M' => 0b101_111_111_101_101,
+        'N' => 0b110_101_101_101_101,
+        'O' => 0b010_101_101_101_010,
+        'P' => 0b110_101_110_100_100,
+        'Q' => 0b010_101_101_110_011,
+        'R' => 0b110_101_110_101_101,
+        'S' => 0b011_100_010_001_110,
+        'T' => 0b111_010_010_010_010,
+        'U' => 0b101_101_101_101_111,
+        'V' => 0b101_101_101_101_010,
+        'W' => 0b101_101_111_111_101,
+        'X' => 0b101_101_010_101_101,
+        'Y' => 0b101_101_010_010_010,
+        'Z' => 0b111_001_010_100_111,
+        '0' => 0b111_101_101_101_111,
+        '1' => 0b010_110_010_010_111,
+        '2' => 0b110_001_010_100_111,
+        '3' => 0b110_001_010_001_110,
+        '4' => 0b101_101_111_001_001,
+        '5' => 0b111_100_110_001_110,
+        '6' => 0b011_100_111_101_111,
+        '7' => 0b111_001_010_010_010,
+        '8' => 0b111_101_111_101_111,
+        '9' => 0b111_101_111_001_110,
+        '.' => 0b000_000_000_000_010,
+        ',' => 0b000_000_000_010_100,
+        ':' => 0b000_010_000_010_000,
+        '-' => 0b000_000_111_000_000,
+        '_' => 0b000_000_000_000_111,
+        '/' => 0b001_001_010_100_100,
+        '%' => 0b101_001_010_100_101,
+        '(' | '<' | '[' => 0b001_010_010_010_001,
+        ')' | '>' | ']' => 0b100_010_010_010_100,
+        _ => 0,
+    }
+}
@@ -0,0 +1,88 @@
+//! Conversions from `f32` data into packed vertex formats.
+//!
+//! Each function produces the bytes one attribute of the matching
+//! [`VertexFormat`](wgpu::VertexFormat) reads, so vertex structs can store
+//! `[u8; 4]` colors or `[u16; 4]` half floats instead of full `f32`s.
+
+/// Color or other 0..1 data as `Unorm8x4`, clamped
+pub fn pack_unorm8x4(value: [f32; 4]) -> [u8; 4] {
+    value.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+/// Normals, tangents or other -1..1 data as `Snorm8x4`, clamped
+pub fn pack_snorm8x4(value: [f32; 4]) -> [i8; 4] {
+    value.map(|v| (v.clamp(-1.0, 1.0) * 127.0).round() as i8)
+}
+
+/// Texture coordinates or other 0..1 data as `Unorm16x2`, clamped
+pub fn pack_unorm16x2(value: [f32; 2]) -> [u16; 2] {
+    value.map(|v| (v.clamp(0.0, 1.0) * 65535.0).round() as u16)
+}
+
+/// Octahedral normals or other -1..1 data as `Snorm16x2`, clamped
+pub fn pack_snorm16x2(value: [f32; 2]) -> [i16; 2] {
+    value.map(|v| (v.clamp(-1.0, 1.0) * 32767.0).round() as i16)
+}
+
+/// Two half floats for `Float16x2`
+pub fn pack_float16x2(value: [f32; 2]) -> [u16; 2] {
+    value.map(f32_to_f16)
+}
+
+/// Four half floats for `Float16x4`
+pub fn pack_float16x4(value: [f32; 4]) -> [u16; 4] {
+    value.map(f32_to_f16)
+}
+
+/// Convert to IEEE 754 half precision bits, rounding to nearest even.
+/// Values too large for a half become infinity.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    // Infinity stays infinity, NaN stays a (quiet) NaN
+    if exponent == 0xff {
+        return sign | 0x7c00 | (if mantissa != 0 { 0x200 } else { 0 });
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exponent <= 0 {
+        // Subnormal half, or zero when even the largest subnormal rounding misses
+        if half_exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - half_exponent) as u32;
+        let half = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round = remainder > halfway || (remainder == halfway && (half & 1) == 1);
+        return sign | ((half + (round as u32)) as u16);
+    }
+
+    // A carry out of the mantissa correctly bumps the exponent, up to infinity
+    let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1fff;
+    let round = remainder > 0x1000 || (remainder == 0x1000 && (half & 1) == 1);
+    sign | ((half + (round as u32)) as u16)
+}
+
+/// Convert IEEE 754 half precision bits to `f32` (exact)
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+    match exponent {
+        0 => {
+            let magnitude = (mantissa as f32) * f32::from_bits(0x3380_0000); // 2^-24
+            f32::from_bits(sign | magnitude.to_bits())
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+    }
+}
@@ -0,0 +1,697 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    Camera,
+    CameraUniform,
+    ComputePass,
+    ComputePipeline,
+    GeepuError,
+    GpuContext,
+    Mesh,
+    RenderPass,
+    RenderPipeline,
+    Result,
+    StagingBuffer,
+    Texture,
+    TypedBuffer,
+};
+use glam::{ Mat4, Vec3 };
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+const PICK_WORKGROUP_SIZE: u32 = 64;
+
+/// Application-chosen identifier of a pickable object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(pub u32);
+
+/// A pickable mesh instance
+pub struct PickDraw<'a, V> {
+    pub id: ObjectId,
+    pub mesh: &'a Mesh<V>,
+    pub transform: [[f32; 4]; 4],
+}
+
+impl<'a, V> PickDraw<'a, V> {
+    pub fn new(id: ObjectId, mesh: &'a Mesh<V>, transform: [[f32; 4]; 4]) -> Self {
+        Self { id, mesh, transform }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PickObject {
+    model: [[f32; 4]; 4],
+    id: u32,
+    _padding: [u32; 3],
+}
+
+/// GPU picking through an object-ID render target.
+///
+/// [`render`](Self::render) draws every object's id (offset by one, so 0 means
+/// background) into an offscreen `R32Uint` target with its own depth buffer, and
+/// [`pick`](Self::pick) reads back the texel under a pixel. Only vertex
+/// location 0 (`Float32x3` position) of the meshes is used.
+pub struct Picker {
+    ids: Texture,
+    depth: Texture,
+    camera: TypedBuffer<CameraUniform>,
+    camera_bind_group: wgpu::BindGroup,
+    object_layout: Arc<wgpu::BindGroupLayout>,
+    objects: wgpu::Buffer,
+    object_bind_group: wgpu::BindGroup,
+    object_stride: u64,
+    pipeline_layout: wgpu::PipelineLayout,
+    module: wgpu::ShaderModule,
+    pipelines: HashMap<(u64, u64), RenderPipeline>,
+}
+
+impl Picker {
+    /// Create a picker whose ID target matches a `width` x `height` viewport
+    pub fn new(context: &GpuContext, width: u32, height: u32) -> Result<Self> {
+        let (ids, depth) = Self::create_targets(context, width, height)?;
+
+        let camera = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let camera_layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::VERTEX)
+            .build(context, Some("Picking Camera Layout"));
+        let camera_bind_group = BindGroupBuilder::new(&camera_layout)
+            .buffer(0, camera.buffer())
+            .build(context, Some("Picking Camera Bind Group"));
+
+        let object_size = std::mem::size_of::<PickObject>() as u64;
+        let alignment = context.device.limits().min_uniform_buffer_offset_alignment as u64;
+        let object_stride = object_size.next_multiple_of(alignment);
+        let object_layout = BindGroupLayoutBuilder::new()
+            .dynamic_uniform_buffer(0, wgpu::ShaderStages::VERTEX_FRAGMENT, object_size)
+            .build(context, Some("Picking Object Layout"));
+        let (objects, object_bind_group) = Self::create_objects(
+            context,
+            &object_layout,
+            object_stride,
+            1
+        );
+
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&context.label("Picking Shader")),
+            source: wgpu::ShaderSource::Wgsl(format!("{}{}", Camera::WGSL, SHADER).into()),
+        });
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&context.label("Picking Pipeline Layout")),
+                bind_group_layouts: &[&camera_layout, &object_layout],
+                push_constant_ranges: &[],
+            })
+        );
+
+        Ok(Self {
+            ids,
+            depth,
+            camera,
+            camera_bind_group,
+            object_layout,
+            objects,
+            object_bind_group,
+            object_stride,
+            pipeline_layout,
+            module,
+            pipelines: HashMap::new(),
+        })
+    }
+
+    /// Resize the ID target (call on window resize)
+    pub fn resize(&mut self, context: &GpuContext, width: u32, height: u32) -> Result<()> {
+        (self.ids, self.depth) = Self::create_targets(context, width, height)?;
+        Ok(())
+    }
+
+    /// Size of the ID target
+    pub fn size(&self) -> (u32, u32) {
+        self.ids.size()
+    }
+
+    /// Render the object ids seen from `camera` and submit the work
+    pub fn render<V>(
+        &mut self,
+        context: &GpuContext,
+        camera: &Camera,
+        draws: &[PickDraw<V>]
+    ) -> Result<()>
+        where V: bytemuck::Pod
+    {
+        camera.update_buffer(context, &self.camera)?;
+        for draw in draws {
+            let key = Self::pipeline_key(&draw.mesh.layout);
+            if !self.pipelines.contains_key(&key) {
+                let pipeline = self.create_pipeline(context, key);
+                self.pipelines.insert(key, pipeline);
+            }
+        }
+
+        let required = (draws.len().max(1) as u64) * self.object_stride;
+        if required > self.objects.size() {
+            (self.objects, self.object_bind_group) = Self::create_objects(
+                context,
+                &self.object_layout,
+                self.object_stride,
+                draws.len().next_power_of_two()
+            );
+        }
+        let mut data = vec![0u8; draws.len() * self.object_stride as usize];
+        for (i, draw) in draws.iter().enumerate() {
+            let offset = i * (self.object_stride as usize);
+            let object = PickObject {
+                model: draw.transform,
+                id: draw.id.0.wrapping_add(1),
+                _padding: [0; 3],
+            };
+            data[offset..offset + std::mem::size_of::<PickObject>()].copy_from_slice(
+                bytemuck::bytes_of(&object)
+            );
+        }
+        context.write_buffer(&self.objects, 0, &data);
+
+        let mut encoder = context.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some(&context.label("Picking Encoder")),
+            })
+        );
+        {
+            let color_attachments = [
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.ids.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+            ];
+            let mut pass = RenderPass::new(
+                &mut encoder,
+                &color_attachments,
+                Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                Some(&context.label("Picking Pass"))
+            );
+            pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            for (i, draw) in draws.iter().enumerate() {
+                pass.set_pipeline(&self.pipelines[&Self::pipeline_key(&draw.mesh.layout)]);
+                pass.set_bind_group(
+                    1,
+                    &self.object_bind_group,
+                    &[((i as u64) * self.object_stride) as u32]
+                );
+                pass.draw_mesh(draw.mesh);
+            }
+        }
+        context.submit(std::iter::once(encoder.finish()));
+        Ok(())
+    }
+
+    /// Object under pixel (`x`, `y`) of the last [`render`](Self::render), with
+    /// the origin at the top-left
+    pub async fn pick(&self, context: &GpuContext, x: u32, y: u32) -> Result<Option<ObjectId>> {
+        let (width, height) = self.size();
+        if x >= width || y >= height {
+            return Ok(None);
+        }
+
+        let staging = StagingBuffer::new(context, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64)?;
+        let mut encoder = context.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some(&context.label("Picking Readback Encoder")),
+            })
+        );
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.ids.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: staging.buffer(),
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            }
+        );
+        context.submit(std::iter::once(encoder.finish()));
+
+        let texel = staging.read_data::<u32>(context).await?[0];
+        Ok(texel.checked_sub(1).map(ObjectId))
+    }
+
+    fn create_targets(context: &GpuContext, width: u32, height: u32) -> Result<(Texture, Texture)> {
+        let (width, height) = (width.max(1), height.max(1));
+        let ids = Texture::create_empty(
+            context,
+            width,
+            height,
+            ID_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            Some("Picking ID Target")
+        )?;
+        let depth = Texture::create_depth_texture(context, width, height, Some("Picking Depth"))?;
+        Ok((ids, depth))
+    }
+
+    fn create_objects(
+        context: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        stride: u64,
+        capacity: usize
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = context.device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some(&context.label("Picking Object Buffer")),
+                size: stride * (capacity as u64),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        );
+        let bind_group = BindGroupBuilder::new(layout)
+            .buffer_range(0, &buffer, 0, Some(std::mem::size_of::<PickObject>() as u64))
+            .build(context, Some("Picking Object Bind Group"));
+        (buffer, bind_group)
+    }
+
+    /// Vertex stride and position offset, the only parts of a mesh layout picking uses
+    fn pipeline_key(layout: &wgpu::VertexBufferLayout) -> (u64, u64) {
+        let position = layout.attributes
+            .iter()
+            .find(|attribute| attribute.shader_location == 0)
+            .map_or(0, |attribute| attribute.offset);
+        (layout.array_stride, position)
+    }
+
+    fn create_pipeline(
+        &self,
+        context: &GpuContext,
+        (stride, offset): (u64, u64)
+    ) -> RenderPipeline {
+        let pipeline = context.device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some(&context.label("Picking Pipeline")),
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.module,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        wgpu::VertexBufferLayout {
+                            array_stride: stride,
+                            step_mode: wgpu::VertexStepMode::Vertex,
+                            attributes: &[
+                                wgpu::VertexAttribute {
+                                    format: wgpu::VertexFormat::Float32x3,
+                                    offset,
+                                    shader_location: 0,
+                                },
+                            ],
+                        },
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: ID_FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                // The ID and depth targets are the picker's own, never multisampled
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        );
+
+        RenderPipeline {
+            pipeline,
+            bind_group_layouts: Vec::new(),
+            vertex_buffer_count: 1,
+            bindings: Vec::new(),
+        }
+    }
+}
+
+/// A pickable instance for [`ComputePicker`]: its model matrix and local-space bounds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickInstance {
+    pub id: ObjectId,
+    pub model: [[f32; 4]; 4],
+    pub aabb_min: [f32; 3],
+    pub aabb_max: [f32; 3],
+}
+
+impl PickInstance {
+    pub fn new(
+        id: ObjectId,
+        model: [[f32; 4]; 4],
+        aabb_min: [f32; 3],
+        aabb_max: [f32; 3]
+    ) -> Self {
+        Self { id, model, aabb_min, aabb_max }
+    }
+}
+
+/// The GPU copy of a [`PickInstance`], with the model matrix inverted so the
+/// ray can be moved into the box's space
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PickBounds {
+    inverse_model: [[f32; 4]; 4],
+    aabb_min: [f32; 3],
+    id: u32,
+    aabb_max: [f32; 3],
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PickRay {
+    origin: [f32; 3],
+    instance_count: u32,
+    direction: [f32; 3],
+    _padding: u32,
+}
+
+/// GPU picking by ray casting against instance bounds in a compute pass.
+///
+/// An alternative to [`Picker`] for scenes with many instances: nothing is
+/// rasterized, so picking does not wait for an ID target to be drawn and
+/// copied, and only eight bytes come back. Each instance's bounds are
+/// transformed by its model matrix (an oriented box), the nearest hit
+/// distance is found with an atomic min, and a second dispatch picks the
+/// lowest id among the instances at that distance. Like [`Ray::pick`] the
+/// result is only as tight as the bounds.
+pub struct ComputePicker {
+    instances: TypedBuffer<PickBounds>,
+    instance_count: u32,
+    ray: TypedBuffer<PickRay>,
+    hit: TypedBuffer<u32>,
+    bind_group: wgpu::BindGroup,
+    nearest_pipeline: ComputePipeline,
+    identify_pipeline: ComputePipeline,
+}
+
+impl ComputePicker {
+    /// Create a picker for `instances`; later updates must fit in as many
+    pub fn new(context: &GpuContext, instances: &[PickInstance]) -> Result<Self> {
+        let storage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
+        let instance_buffer = TypedBuffer::empty(context, instances.len().max(1), storage)?;
+        let ray = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let hit = TypedBuffer::new(
+            context,
+            &[u32::MAX; 2],
+            storage | wgpu::BufferUsages::COPY_SRC
+        )?;
+
+        let layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::COMPUTE)
+            .storage_buffer(1, wgpu::ShaderStages::COMPUTE, true)
+            .storage_buffer(2, wgpu::ShaderStages::COMPUTE, false)
+            .build(context, Some("Compute Picking Layout"));
+        let bind_group = BindGroupBuilder::new(&layout)
+            .buffer(0, ray.buffer())
+            .buffer(1, instance_buffer.buffer())
+            .buffer(2, hit.buffer())
+            .build(context, Some("Compute Picking Bind Group"));
+        let [nearest_source, identify_source] = Self::shader_sources();
+        let nearest_pipeline = ComputePipeline::new(
+            context,
+            &nearest_source,
+            vec![layout.clone()],
+            Some("Compute Picking Nearest Pipeline")
+        )?;
+        let identify_pipeline = ComputePipeline::new(
+            context,
+            &identify_source,
+            vec![layout],
+            Some("Compute Picking Identify Pipeline")
+        )?;
+
+        let mut picker = Self {
+            instances: instance_buffer,
+            instance_count: 0,
+            ray,
+            hit,
+            bind_group,
+            nearest_pipeline,
+            identify_pipeline,
+        };
+        picker.update_instances(context, instances)?;
+        Ok(picker)
+    }
+
+    /// WGSL of the two passes: nearest distance, then lowest id at that distance
+    pub fn shader_sources() -> [String; 2] {
+        [PICK_NEAREST, PICK_IDENTIFY].map(|pass| format!("{}{}", PICK_SHADER, pass))
+    }
+
+    /// Replace the instances, which must fit in the count the picker was created with
+    pub fn update_instances(
+        &mut self,
+        context: &GpuContext,
+        instances: &[PickInstance]
+    ) -> Result<()> {
+        if instances.len() > self.instances.len() {
+            return Err(
+                GeepuError::BufferError(
+                    format!(
+                        "{} pick instances exceed the picker's capacity of {}",
+                        instances.len(),
+                        self.instances.len()
+                    )
+                )
+            );
+        }
+        let bounds: Vec<PickBounds> = instances
+            .iter()
+            .map(|instance| PickBounds {
+                inverse_model: Mat4::from_cols_array_2d(&instance.model)
+                    .inverse()
+                    .to_cols_array_2d(),
+                aabb_min: instance.aabb_min,
+                id: instance.id.0,
+                aabb_max: instance.aabb_max,
+                _padding: 0,
+            })
+            .collect();
+        self.instances.write(context, &bounds)?;
+        self.instance_count = instances.len() as u32;
+        Ok(())
+    }
+
+    /// Record the picking passes for `ray` into `encoder`. The result is
+    /// reset through the queue, so pick at most once per submission.
+    pub fn record(
+        &self,
+        context: &GpuContext,
+        encoder: &mut wgpu::CommandEncoder,
+        ray: &Ray
+    ) -> Result<()> {
+        self.hit.write(context, &[u32::MAX; 2])?;
+        self.ray.write(
+            context,
+            &[
+                PickRay {
+                    origin: ray.origin,
+                    instance_count: self.instance_count,
+                    direction: ray.direction,
+                    _padding: 0,
+                },
+            ]
+        )?;
+        if self.instance_count == 0 {
+            return Ok(());
+        }
+
+        let workgroups = self.instance_count.div_ceil(PICK_WORKGROUP_SIZE);
+        let mut pass = ComputePass::new(encoder, Some(&context.label("Compute Picking Pass")));
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_pipeline(&self.nearest_pipeline);
+        pass.dispatch_workgroups(workgroups, 1, 1);
+        pass.set_pipeline(&self.identify_pipeline);
+        pass.dispatch_workgroups(workgroups, 1, 1);
+        Ok(())
+    }
+
+    /// Nearest instance hit by `ray`, with its distance along the ray
+    pub async fn pick(&self, context: &GpuContext, ray: &Ray) -> Result<Option<(ObjectId, f32)>> {
+        let mut encoder = context.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some(&context.label("Compute Picking Encoder")),
+            })
+        );
+        self.record(context, &mut encoder, ray)?;
+        let staging = StagingBuffer::new(context, self.hit.size_bytes())?;
+        staging.copy_from_buffer(&mut encoder, self.hit.buffer(), None);
+        context.submit(std::iter::once(encoder.finish()));
+
+        let hit = staging.read_data::<u32>(context).await?;
+        if hit[0] == u32::MAX {
+            return Ok(None);
+        }
+        Ok(Some((ObjectId(hit[1]), f32::from_bits(hit[0]))))
+    }
+}
+
+/// A half-line in world space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: [f32; 3],
+    /// Unit direction
+    pub direction: [f32; 3],
+}
+
+impl Ray {
+    pub fn new(origin: [f32; 3], direction: [f32; 3]) -> Self {
+        let direction = Vec3::from(direction).normalize_or_zero().to_array();
+        Self { origin, direction }
+    }
+
+    /// Distance along the ray to an axis-aligned box, or `None` if it misses
+    /// (0 when the origin is inside)
+    pub fn intersect_aabb(&self, min: [f32; 3], max: [f32; 3]) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            let inverse = 1.0 / self.direction[axis];
+            let a = (min[axis] - self.origin[axis]) * inverse;
+            let b = (max[axis] - self.origin[axis]) * inverse;
+            // NaN from a zero direction inside the slab leaves the bounds unchanged
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        (near <= far).then_some(near)
+    }
+
+    /// Nearest of `objects` (id and world-space bounds) hit by the ray, with its distance.
+    ///
+    /// A CPU fallback for [`Picker`] when a GPU round trip is not wanted.
+    pub fn pick<I>(&self, objects: I) -> Option<(ObjectId, f32)>
+        where I: IntoIterator<Item = (ObjectId, [f32; 3], [f32; 3])>
+    {
+        objects
+            .into_iter()
+            .filter_map(|(id, min, max)| self.intersect_aabb(min, max).map(|t| (id, t)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+const SHADER: &str =
+    r#"
+struct PickObject {
+    model: mat4x4<f32>,
+    id: u32,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> object: PickObject;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return camera.view_projection * object.model * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) u32 {
+    return object.id;
+}
+"#;
+
+const PICK_SHADER: &str =
+    r#"
+struct PickBounds {
+    inverse_model: mat4x4<f32>,
+    aabb_min: vec3<f32>,
+    id: u32,
+    aabb_max: vec3<f32>,
+}
+
+struct PickRay {
+    origin: vec3<f32>,
+    instance_count: u32,
+    direction: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> ray: PickRay;
+@group(0) @binding(1) var<storage, read> instances: array<PickBounds>;
+// Bits of the nearest distance, then the id hit there
+@group(0) @binding(2) var<storage, read_write> hit: array<atomic<u32>, 2>;
+
+// Distance along the ray to the instance's box, or -1 on a miss. The model
+// transform is linear, so distances in the box's space match world space.
+fn hit_distance(instance: PickBounds) -> f32 {
+    let origin = (instance.inverse_model * vec4<f32>(ray.origin, 1.0)).xyz;
+    let direction = (instance.inverse_model * vec4<f32>(ray.direction, 0.0)).xyz;
+    let safe = select(direction, vec3<f32>(1e-30), direction == vec3<f32>(0.0));
+    let a = (instance.aabb_min - origin) / safe;
+    let b = (instance.aabb_max - origin) / safe;
+    let lower = min(a, b);
+    let upper = max(a, b);
+    let near = max(max(max(lower.x, lower.y), lower.z), 0.0);
+    let far = min(min(upper.x, upper.y), upper.z);
+    return select(-1.0, near, near <= far);
+}
+"#;
+
+// Non-negative floats order like their bits, so the atomic min works on them
+const PICK_NEAREST: &str =
+    r#"
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= ray.instance_count) {
+        return;
+    }
+    let distance = hit_distance(instances[id.x]);
+    if (distance >= 0.0) {
+        atomicMin(&hit[0], bitcast<u32>(distance));
+    }
+}
+"#;
+
+const PICK_IDENTIFY: &str =
+    r#"
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= ray.instance_count) {
+        return;
+    }
+    let instance = instances[id.x];
+    let distance = hit_distance(instance);
+    if (distance >= 0.0 && bitcast<u32>(distance) == atomicLoad(&hit[0])) {
+        atomicMin(&hit[1], instance.id);
+    }
+}
+"#;
@@ -1,14 +1,24 @@
-use crate::{ GpuContext, Result, TypedBuffer };
+use crate::{ GeepuError, GpuContext, Result, ShaderBinding, ShaderBindingKind, TypedBuffer };
+use crate::describe;
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
 use wgpu::{ ShaderStages, TextureSampleType, TextureViewDimension, SamplerBindingType };
 
 /// A wrapper around render pipeline with convenient creation methods
 pub struct RenderPipeline {
     pub pipeline: wgpu::RenderPipeline,
-    pub bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    pub bind_group_layouts: Vec<Arc<wgpu::BindGroupLayout>>,
+    /// Number of vertex buffers the pipeline reads, checked by pass validation
+    pub vertex_buffer_count: usize,
+    pub(crate) bindings: Vec<ShaderBinding>,
 }
 
 impl RenderPipeline {
-    /// Create a render pipeline from shader source
+    /// Create a render pipeline from shader source.
+    ///
+    /// With no `bind_group_layouts`, the layouts are derived from the shaders'
+    /// bindings (see [`reflect_bind_group_layouts`]); not on the web.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         context: &GpuContext,
         vertex_shader: &str,
@@ -16,40 +26,83 @@ impl RenderPipeline {
         vertex_layouts: &[wgpu::VertexBufferLayout],
         color_targets: &[Option<wgpu::ColorTargetState>],
         depth_stencil: Option<wgpu::DepthStencilState>,
-        bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+        bind_group_layouts: Vec<Arc<wgpu::BindGroupLayout>>,
         label: Option<&str>
     ) -> Result<Self> {
-        let vertex_module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Vertex Shader"),
-            source: wgpu::ShaderSource::Wgsl(vertex_shader.into()),
-        });
+        Self::new_multisampled(
+            context,
+            vertex_shader,
+            fragment_shader,
+            vertex_layouts,
+            color_targets,
+            depth_stencil,
+            1,
+            bind_group_layouts,
+            label
+        )
+    }
+
+    /// [`new`](Self::new) for targets with `samples` samples per pixel, e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_multisampled(
+        context: &GpuContext,
+        vertex_shader: &str,
+        fragment_shader: Option<&str>,
+        vertex_layouts: &[wgpu::VertexBufferLayout],
+        color_targets: &[Option<wgpu::ColorTargetState>],
+        depth_stencil: Option<wgpu::DepthStencilState>,
+        samples: u32,
+        bind_group_layouts: Vec<Arc<wgpu::BindGroupLayout>>,
+        label: Option<&str>
+    ) -> Result<Self> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let name = label.unwrap_or("Render Pipeline");
+            crate::check_wgsl(name, Some(ShaderStages::VERTEX), vertex_shader)?;
+            crate::check_vertex_inputs(name, vertex_shader, "vs_main", vertex_layouts)?;
+            if let Some(fragment_shader) = fragment_shader {
+                crate::check_wgsl(name, Some(ShaderStages::FRAGMENT), fragment_shader)?;
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let bind_group_layouts = if bind_group_layouts.is_empty() {
+            let mut sources = vec![vertex_shader];
+            sources.extend(fragment_shader);
+            reflect_bind_group_layouts(context, &sources, ShaderStages::VERTEX_FRAGMENT, label)?
+        } else {
+            bind_group_layouts
+        };
+
+        let pipeline = context.capture_errors("create_render_pipeline", label, || {
+            let vertex_module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&context.label("Vertex Shader")),
+                source: wgpu::ShaderSource::Wgsl(vertex_shader.into()),
+            });
 
-        let fragment_module = if let Some(fragment_shader) = fragment_shader {
-            Some(
+            let fragment_module = fragment_shader.map(|fragment_shader| {
                 context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                    label: Some("Fragment Shader"),
+                    label: Some(&context.label("Fragment Shader")),
                     source: wgpu::ShaderSource::Wgsl(fragment_shader.into()),
                 })
-            )
-        } else {
-            None
-        };
+            });
 
-        let bind_group_layout_refs: Vec<&wgpu::BindGroupLayout> = bind_group_layouts
-            .iter()
-            .collect();
+            let bind_group_layout_refs: Vec<&wgpu::BindGroupLayout> = bind_group_layouts
+                .iter()
+                .map(|layout| layout.as_ref())
+                .collect();
 
-        let pipeline_layout = context.device.create_pipeline_layout(
-            &(wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &bind_group_layout_refs,
-                push_constant_ranges: &[],
-            })
-        );
+            let pipeline_layout = context.device.create_pipeline_layout(
+                &(wgpu::PipelineLayoutDescriptor {
+                    label: Some(&context.label("Render Pipeline Layout")),
+                    bind_group_layouts: &bind_group_layout_refs,
+                    push_constant_ranges: &[],
+                })
+            );
 
-        let pipeline = context.device.create_render_pipeline(
-            &(wgpu::RenderPipelineDescriptor {
-                label,
+            let label = context.prefixed_label(label);
+            let descriptor = wgpu::RenderPipelineDescriptor {
+                label: label.as_deref(),
                 layout: Some(&pipeline_layout),
                 vertex: wgpu::VertexState {
                     module: &vertex_module,
@@ -74,22 +127,59 @@ impl RenderPipeline {
                 },
                 depth_stencil,
                 multisample: wgpu::MultisampleState {
-                    count: 1,
+                    count: samples,
                     mask: !0,
                     alpha_to_coverage_enabled: false,
                 },
                 multiview: None,
                 cache: None,
-            })
-        );
+            };
+            log::debug!("{}", describe::render_pipeline(&descriptor, bind_group_layouts.len()));
+            context.device.create_render_pipeline(&descriptor)
+        })?;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let bindings = {
+            // Shared declarations appear in both stages; keep one of each
+            let mut bindings = crate::reflect_bindings(vertex_shader);
+            bindings.extend(fragment_shader.map(crate::reflect_bindings).unwrap_or_default());
+            bindings.sort_by_key(|binding| (binding.group, binding.binding));
+            bindings.dedup_by_key(|binding| (binding.group, binding.binding));
+            bindings
+        };
+        #[cfg(target_arch = "wasm32")]
+        let bindings = Vec::new();
 
         Ok(Self {
             pipeline,
             bind_group_layouts,
+            vertex_buffer_count: vertex_layouts.len(),
+            bindings,
         })
     }
 
-    /// Create a simple render pipeline with common defaults
+    /// The resource bindings of the vertex and fragment shaders, from
+    /// reflection (empty on the web and for pipelines geepu builds internally)
+    pub fn bindings(&self) -> &[ShaderBinding] {
+        &self.bindings
+    }
+
+    /// The binding the shaders declare as `name`
+    pub fn binding(&self, name: &str) -> Option<&ShaderBinding> {
+        self.bindings.iter().find(|binding| binding.name == name)
+    }
+
+    /// Build one bind group per layout from resources named as in the shaders,
+    /// like [`ComputePipeline::bind`]
+    pub fn bind(
+        &self,
+        context: &GpuContext,
+        resources: &[(&str, &dyn BindingSource)]
+    ) -> Result<Vec<wgpu::BindGroup>> {
+        bind_by_name(context, "Render", &self.bindings, &self.bind_group_layouts, resources)
+    }
+
+    /// Create a simple single-sampled render pipeline with common defaults
     pub fn simple(
         context: &GpuContext,
         vertex_shader: &str,
@@ -97,6 +187,28 @@ impl RenderPipeline {
         vertex_layouts: &[wgpu::VertexBufferLayout],
         surface_format: wgpu::TextureFormat,
         label: Option<&str>
+    ) -> Result<Self> {
+        Self::simple_multisampled(
+            context,
+            vertex_shader,
+            fragment_shader,
+            vertex_layouts,
+            surface_format,
+            1,
+            label
+        )
+    }
+
+    /// [`simple`](Self::simple) for targets with `samples` samples per pixel,
+    /// e.g. [`GpuContext::sample_count`] for passes into the surface
+    pub fn simple_multisampled(
+        context: &GpuContext,
+        vertex_shader: &str,
+        fragment_shader: &str,
+        vertex_layouts: &[wgpu::VertexBufferLayout],
+        surface_format: wgpu::TextureFormat,
+        samples: u32,
+        label: Option<&str>
     ) -> Result<Self> {
         let color_targets = &[
             Some(wgpu::ColorTargetState {
@@ -106,66 +218,499 @@ impl RenderPipeline {
             }),
         ];
 
-        Self::new(
+        Self::new_multisampled(
             context,
             vertex_shader,
             Some(fragment_shader),
             vertex_layouts,
             color_targets,
             None,
+            samples,
             vec![],
             label
         )
     }
+
+    /// Create a fragment-less pipeline that only writes depth, for a depth
+    /// pre-pass. Use the same vertex shader, layouts and bind groups as the
+    /// main pipeline so both produce identical depth, and give the main
+    /// pipeline a `LessEqual` depth test.
+    pub fn depth_only(
+        context: &GpuContext,
+        vertex_shader: &str,
+        vertex_layouts: &[wgpu::VertexBufferLayout],
+        depth_format: wgpu::TextureFormat,
+        bind_group_layouts: Vec<Arc<wgpu::BindGroupLayout>>,
+        label: Option<&str>
+    ) -> Result<Self> {
+        Self::new(
+            context,
+            vertex_shader,
+            None,
+            vertex_layouts,
+            &[],
+            Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            bind_group_layouts,
+            label
+        )
+    }
 }
 
 /// A wrapper around compute pipeline
 pub struct ComputePipeline {
     pub pipeline: wgpu::ComputePipeline,
-    pub bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    pub bind_group_layouts: Vec<Arc<wgpu::BindGroupLayout>>,
+    bindings: Vec<ShaderBinding>,
 }
 
 impl ComputePipeline {
-    /// Create a compute pipeline from shader source
+    /// Create a compute pipeline from shader source, deriving the layouts from
+    /// the shader when `bind_group_layouts` is empty (like [`RenderPipeline::new`])
     pub fn new(
         context: &GpuContext,
         shader_source: &str,
-        bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+        bind_group_layouts: Vec<Arc<wgpu::BindGroupLayout>>,
         label: Option<&str>
     ) -> Result<Self> {
-        let shader_module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Compute Shader"),
-            source: wgpu::ShaderSource::Wgsl(shader_source.into()),
-        });
+        #[cfg(not(target_arch = "wasm32"))]
+        crate::check_wgsl(
+            label.unwrap_or("Compute Pipeline"),
+            Some(ShaderStages::COMPUTE),
+            shader_source
+        )?;
+        #[cfg(not(target_arch = "wasm32"))]
+        let bind_group_layouts = if bind_group_layouts.is_empty() {
+            reflect_bind_group_layouts(context, &[shader_source], ShaderStages::COMPUTE, label)
+                .map_err(GeepuError::into_compute)?
+        } else {
+            bind_group_layouts
+        };
 
-        let bind_group_layout_refs: Vec<&wgpu::BindGroupLayout> = bind_group_layouts
-            .iter()
-            .collect();
+        let pipeline = context.capture_errors("create_compute_pipeline", label, || {
+            let shader_module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&context.label("Compute Shader")),
+                source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+            });
 
-        let pipeline_layout = context.device.create_pipeline_layout(
-            &(wgpu::PipelineLayoutDescriptor {
-                label: Some("Compute Pipeline Layout"),
-                bind_group_layouts: &bind_group_layout_refs,
-                push_constant_ranges: &[],
-            })
-        );
+            let bind_group_layout_refs: Vec<&wgpu::BindGroupLayout> = bind_group_layouts
+                .iter()
+                .map(|layout| layout.as_ref())
+                .collect();
+
+            let pipeline_layout = context.device.create_pipeline_layout(
+                &(wgpu::PipelineLayoutDescriptor {
+                    label: Some(&context.label("Compute Pipeline Layout")),
+                    bind_group_layouts: &bind_group_layout_refs,
+                    push_constant_ranges: &[],
+                })
+            );
 
-        let pipeline = context.device.create_compute_pipeline(
-            &(wgpu::ComputePipelineDescriptor {
-                label,
+            let label = context.prefixed_label(label);
+            let descriptor = wgpu::ComputePipelineDescriptor {
+                label: label.as_deref(),
                 layout: Some(&pipeline_layout),
                 module: &shader_module,
                 entry_point: "cs_main",
                 compilation_options: Default::default(),
                 cache: None,
-            })
-        );
+            };
+            log::debug!("{}", describe::compute_pipeline(&descriptor, bind_group_layouts.len()));
+            context.device.create_compute_pipeline(&descriptor)
+        }).map_err(GeepuError::into_compute)?;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let bindings = crate::reflect_bindings(shader_source);
+        #[cfg(target_arch = "wasm32")]
+        let bindings = Vec::new();
 
         Ok(Self {
             pipeline,
             bind_group_layouts,
+            bindings,
         })
     }
+
+    /// The shader's resource bindings, from reflection (empty on the web,
+    /// where shaders are not parsed)
+    pub fn bindings(&self) -> &[ShaderBinding] {
+        &self.bindings
+    }
+
+    /// The binding the shader declares as `name`
+    pub fn binding(&self, name: &str) -> Option<&ShaderBinding> {
+        self.bindings.iter().find(|binding| binding.name == name)
+    }
+
+    /// Build one bind group per layout from resources named as in the shader,
+    /// e.g. `pipeline.bind(&context, &[("data", &data), ("params", &params)])`.
+    ///
+    /// Fails naming the binding when a name is unknown, a shader binding is
+    /// left out, or a resource is the wrong kind (a sampler for a buffer).
+    pub fn bind(
+        &self,
+        context: &GpuContext,
+        resources: &[(&str, &dyn BindingSource)]
+    ) -> Result<Vec<wgpu::BindGroup>> {
+        bind_by_name(context, "Compute", &self.bindings, &self.bind_group_layouts, resources)
+    }
+}
+
+/// Bind group layouts for the bindings `sources` declare, one per group (see
+/// [`reflect_layout_entries`](crate::reflect_layout_entries)). Like
+/// [`BindGroupLayoutBuilder::build`] layouts they are shared per context.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn reflect_bind_group_layouts(
+    context: &GpuContext,
+    sources: &[&str],
+    visibility: ShaderStages,
+    label: Option<&str>
+) -> Result<Vec<Arc<wgpu::BindGroupLayout>>> {
+    let name = label.unwrap_or("Reflected");
+    Ok(
+        crate::reflect_layout_entries(sources, visibility)?
+            .into_iter()
+            .enumerate()
+            .map(|(group, entries)| {
+                let label = format!("{} Layout {}", name, group);
+                BindGroupLayoutBuilder { entries }.build(context, Some(&label))
+            })
+            .collect()
+    )
+}
+
+/// Shared body of the pipelines' `bind`: match named resources to reflected
+/// bindings and create a bind group per layout
+fn bind_by_name(
+    context: &GpuContext,
+    kind: &str,
+    bindings: &[ShaderBinding],
+    layouts: &[Arc<wgpu::BindGroupLayout>],
+    resources: &[(&str, &dyn BindingSource)]
+) -> Result<Vec<wgpu::BindGroup>> {
+    let mut groups: Vec<Vec<wgpu::BindGroupEntry>> = layouts
+        .iter()
+        .map(|_| Vec::new())
+        .collect();
+    for &(name, source) in resources {
+        let binding = bindings.iter().find(|binding| binding.name == name).ok_or_else(|| {
+            let names: Vec<&str> = bindings
+                .iter()
+                .map(|binding| binding.name.as_str())
+                .collect();
+            GeepuError::PipelineError(
+                format!("Shader has no binding named `{}` (has {})", name, names.join(", "))
+            )
+        })?;
+        let resource = source.binding_resource();
+        if !resource_matches(binding.kind, &resource) {
+            return Err(
+                GeepuError::PipelineError(
+                    format!(
+                        "Binding `{}` (@group({}) @binding({})) expects a {:?}",
+                        name,
+                        binding.group,
+                        binding.binding,
+                        binding.kind
+                    )
+                )
+            );
+        }
+        let entries = groups.get_mut(binding.group as usize).ok_or_else(|| {
+            GeepuError::PipelineError(
+                format!(
+                    "Binding `{}` is in @group({}) but the pipeline has {} bind group layouts",
+                    name,
+                    binding.group,
+                    layouts.len()
+                )
+            )
+        })?;
+        entries.push(wgpu::BindGroupEntry { binding: binding.binding, resource });
+    }
+
+    if let Some(missing) = bindings.iter().find(|binding| {
+        groups
+            .get(binding.group as usize)
+            .is_some_and(|entries| entries.iter().all(|entry| entry.binding != binding.binding))
+    }) {
+        return Err(
+            GeepuError::PipelineError(
+                format!(
+                    "No resource given for binding `{}` (@group({}) @binding({}))",
+                    missing.name,
+                    missing.group,
+                    missing.binding
+                )
+            )
+        );
+    }
+
+    Ok(
+        groups
+            .into_iter()
+            .zip(layouts)
+            .enumerate()
+            .map(|(group, (entries, layout))| {
+                let label = format!("{} Bind Group {}", kind, group);
+                log::debug!("{}", describe::bind_group(Some(&label), &entries));
+                context.device.create_bind_group(
+                    &(wgpu::BindGroupDescriptor {
+                        label: Some(&context.label(&label)),
+                        layout,
+                        entries: &entries,
+                    })
+                )
+            })
+            .collect()
+    )
+}
+
+/// Whether `resource` can fill a binding of `kind`
+fn resource_matches(kind: ShaderBindingKind, resource: &wgpu::BindingResource) -> bool {
+    use wgpu::BindingResource as Resource;
+    matches!(
+        (kind, resource),
+        (ShaderBindingKind::UniformBuffer, Resource::Buffer(_)) |
+            (ShaderBindingKind::StorageBuffer { .. }, Resource::Buffer(_)) |
+            (ShaderBindingKind::Texture, Resource::TextureView(_)) |
+            (ShaderBindingKind::StorageTexture, Resource::TextureView(_)) |
+            (ShaderBindingKind::Sampler, Resource::Sampler(_)) |
+            (ShaderBindingKind::Other, _)
+    )
+}
+
+/// A resource that can be bound by name with [`ComputePipeline::bind`],
+/// [`RenderPipeline::bind`] or a [`BindingRegistry`]
+pub trait BindingSource {
+    fn binding_resource(&self) -> wgpu::BindingResource<'_>;
+}
+
+impl BindingSource for wgpu::Buffer {
+    fn binding_resource(&self) -> wgpu::BindingResource<'_> {
+        self.as_entire_binding()
+    }
+}
+
+impl<T> BindingSource for TypedBuffer<T> where T: bytemuck::Pod {
+    fn binding_resource(&self) -> wgpu::BindingResource<'_> {
+        self.buffer().as_entire_binding()
+    }
+}
+
+impl BindingSource for wgpu::TextureView {
+    fn binding_resource(&self) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::TextureView(self)
+    }
+}
+
+impl BindingSource for wgpu::Sampler {
+    fn binding_resource(&self) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Sampler(self)
+    }
+}
+
+/// A pipeline whose shader bindings are known by name
+pub trait ReflectedPipeline {
+    fn bindings(&self) -> &[ShaderBinding];
+
+    fn bind(
+        &self,
+        context: &GpuContext,
+        resources: &[(&str, &dyn BindingSource)]
+    ) -> Result<Vec<wgpu::BindGroup>>;
+}
+
+impl ReflectedPipeline for RenderPipeline {
+    fn bindings(&self) -> &[ShaderBinding] {
+        RenderPipeline::bindings(self)
+    }
+
+    fn bind(
+        &self,
+        context: &GpuContext,
+        resources: &[(&str, &dyn BindingSource)]
+    ) -> Result<Vec<wgpu::BindGroup>> {
+        RenderPipeline::bind(self, context, resources)
+    }
+}
+
+impl ReflectedPipeline for ComputePipeline {
+    fn bindings(&self) -> &[ShaderBinding] {
+        ComputePipeline::bindings(self)
+    }
+
+    fn bind(
+        &self,
+        context: &GpuContext,
+        resources: &[(&str, &dyn BindingSource)]
+    ) -> Result<Vec<wgpu::BindGroup>> {
+        ComputePipeline::bind(self, context, resources)
+    }
+}
+
+/// Named resources shared between pipelines.
+///
+/// Register uniforms, storage buffers, textures and samplers once under the
+/// names the shaders use, then [`create_bind_groups`](Self::create_bind_groups)
+/// builds any pipeline's bind groups from them, indexed by group.
+#[derive(Default)]
+pub struct BindingRegistry<'a> {
+    resources: HashMap<String, &'a dyn BindingSource>,
+}
+
+impl<'a> BindingRegistry<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `resource` as `name`, replacing any earlier one
+    pub fn register(&mut self, name: impl Into<String>, resource: &'a dyn BindingSource) {
+        self.resources.insert(name.into(), resource);
+    }
+
+    /// Builder form of [`register`](Self::register)
+    pub fn with(mut self, name: impl Into<String>, resource: &'a dyn BindingSource) -> Self {
+        self.register(name, resource);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&'a dyn BindingSource> {
+        self.resources.get(name).copied()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.resources.contains_key(name)
+    }
+
+    /// Build `pipeline`'s bind groups from the resources registered as `names`,
+    /// or as every binding its shaders declare when `names` is empty
+    pub fn create_bind_groups(
+        &self,
+        context: &GpuContext,
+        pipeline: &impl ReflectedPipeline,
+        names: &[&str]
+    ) -> Result<Vec<wgpu::BindGroup>> {
+        let names: Vec<&str> = if names.is_empty() {
+            pipeline
+                .bindings()
+                .iter()
+                .map(|binding| binding.name.as_str())
+                .collect()
+        } else {
+            names.to_vec()
+        };
+        let resources = names
+            .into_iter()
+            .map(|name| {
+                let resource = self.get(name).ok_or_else(|| {
+                    GeepuError::PipelineError(format!("No resource registered as `{}`", name))
+                })?;
+                Ok((name, resource))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        pipeline.bind(context, &resources)
+    }
+}
+
+/// Everything a [`PipelineCache`] entry is built from, except the label
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RenderPipelineKey {
+    vertex_shader: String,
+    fragment_shader: Option<String>,
+    vertex_layouts: Vec<(u64, wgpu::VertexStepMode, Vec<wgpu::VertexAttribute>)>,
+    color_targets: Vec<Option<wgpu::ColorTargetState>>,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    samples: u32,
+    bind_group_layouts: Vec<wgpu::Id<wgpu::BindGroupLayout>>,
+}
+
+/// Render pipelines shared by everything that asks for the same state.
+///
+/// [`render_pipeline`](Self::render_pipeline) takes the arguments of
+/// [`RenderPipeline::new_multisampled`] and only builds a pipeline the first
+/// time a combination of shaders, vertex layouts, color targets (with their
+/// blending), depth state, sample count and bind group layouts is seen, so
+/// creating pipelines per object or per frame costs a hash lookup. The label
+/// of the first request is kept.
+#[derive(Default)]
+pub struct PipelineCache {
+    pipelines: Mutex<HashMap<RenderPipelineKey, Arc<RenderPipeline>>>,
+}
+
+impl PipelineCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached pipeline for this state, built on first use
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_pipeline(
+        &self,
+        context: &GpuContext,
+        vertex_shader: &str,
+        fragment_shader: Option<&str>,
+        vertex_layouts: &[wgpu::VertexBufferLayout],
+        color_targets: &[Option<wgpu::ColorTargetState>],
+        depth_stencil: Option<wgpu::DepthStencilState>,
+        samples: u32,
+        bind_group_layouts: Vec<Arc<wgpu::BindGroupLayout>>,
+        label: Option<&str>
+    ) -> Result<Arc<RenderPipeline>> {
+        let key = RenderPipelineKey {
+            vertex_shader: vertex_shader.to_string(),
+            fragment_shader: fragment_shader.map(str::to_string),
+            vertex_layouts: vertex_layouts
+                .iter()
+                .map(|layout| (layout.array_stride, layout.step_mode, layout.attributes.to_vec()))
+                .collect(),
+            color_targets: color_targets.to_vec(),
+            depth_stencil: depth_stencil.clone(),
+            samples,
+            bind_group_layouts: bind_group_layouts
+                .iter()
+                .map(|layout| layout.global_id())
+                .collect(),
+        };
+        if let Some(pipeline) = self.pipelines.lock().unwrap().get(&key) {
+            return Ok(pipeline.clone());
+        }
+
+        let pipeline = Arc::new(
+            RenderPipeline::new_multisampled(
+                context,
+                vertex_shader,
+                fragment_shader,
+                vertex_layouts,
+                color_targets,
+                depth_stencil,
+                samples,
+                bind_group_layouts,
+                label
+            )?
+        );
+        self.pipelines.lock().unwrap().insert(key, pipeline.clone());
+        Ok(pipeline)
+    }
+
+    /// Number of distinct pipelines built
+    pub fn len(&self) -> usize {
+        self.pipelines.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop every cached pipeline, e.g. after shaders were reloaded
+    pub fn clear(&self) {
+        self.pipelines.lock().unwrap().clear();
+    }
 }
 
 /// Builder for creating bind group layouts
@@ -195,6 +740,26 @@ impl BindGroupLayoutBuilder {
         self
     }
 
+    /// Add a uniform buffer binding bound with a dynamic offset per draw
+    pub fn dynamic_uniform_buffer(
+        mut self,
+        binding: u32,
+        visibility: wgpu::ShaderStages,
+        min_size: u64
+    ) -> Self {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: wgpu::BufferSize::new(min_size),
+            },
+            count: None,
+        });
+        self
+    }
+
     /// Add a storage buffer binding
     pub fn storage_buffer(
         mut self,
@@ -237,6 +802,39 @@ impl BindGroupLayoutBuilder {
         self
     }
 
+    /// Add a filterable float cube texture binding, for skyboxes and environment maps
+    pub fn texture_cube(self, binding: u32, visibility: wgpu::ShaderStages) -> Self {
+        self.texture(
+            binding,
+            visibility,
+            wgpu::TextureSampleType::Float { filterable: true },
+            wgpu::TextureViewDimension::Cube,
+            false
+        )
+    }
+
+    /// Add a storage texture binding, e.g. a single mip level written by a compute shader
+    pub fn storage_texture(
+        mut self,
+        binding: u32,
+        visibility: wgpu::ShaderStages,
+        access: wgpu::StorageTextureAccess,
+        format: wgpu::TextureFormat,
+        view_dimension: wgpu::TextureViewDimension
+    ) -> Self {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::StorageTexture {
+                access,
+                format,
+                view_dimension,
+            },
+            count: None,
+        });
+        self
+    }
+
     /// Add a sampler binding
     pub fn sampler(
         mut self,
@@ -253,14 +851,22 @@ impl BindGroupLayoutBuilder {
         self
     }
 
-    /// Build the bind group layout
-    pub fn build(self, context: &GpuContext, label: Option<&str>) -> wgpu::BindGroupLayout {
-        context.device.create_bind_group_layout(
-            &(wgpu::BindGroupLayoutDescriptor {
-                label,
-                entries: &self.entries,
-            })
-        )
+    /// Build the bind group layout.
+    ///
+    /// Layouts are deduplicated per context: while a layout with the same
+    /// entries is alive it is returned instead of creating another, keeping
+    /// the label it was first created with. Bind groups made against either
+    /// are then interchangeable in pipelines using the layout.
+    pub fn build(self, context: &GpuContext, label: Option<&str>) -> Arc<wgpu::BindGroupLayout> {
+        context.shared_bind_group_layout(&self.entries, || {
+            log::debug!("{}", describe::bind_group_layout(label, &self.entries));
+            context.device.create_bind_group_layout(
+                &(wgpu::BindGroupLayoutDescriptor {
+                    label: context.prefixed_label(label).as_deref(),
+                    entries: &self.entries,
+                })
+            )
+        })
     }
 }
 
@@ -306,7 +912,7 @@ impl<'a> BindGroupBuilder<'a> {
             resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
                 buffer,
                 offset,
-                size: size.map(wgpu::BufferSize::new).flatten(),
+                size: size.and_then(wgpu::BufferSize::new),
             }),
         });
         self
@@ -332,9 +938,10 @@ impl<'a> BindGroupBuilder<'a> {
 
     /// Build the bind group
     pub fn build(self, context: &GpuContext, label: Option<&str>) -> wgpu::BindGroup {
+        log::debug!("{}", describe::bind_group(label, &self.entries));
         context.device.create_bind_group(
             &(wgpu::BindGroupDescriptor {
-                label,
+                label: context.prefixed_label(label).as_deref(),
                 layout: self.layout,
                 entries: &self.entries,
             })
@@ -403,6 +1010,7 @@ pub struct PipelineBuilder<'a> {
     layouts: &'a [wgpu::VertexBufferLayout<'static>],
     uniforms: Vec<&'a wgpu::Buffer>,
     textures: Vec<&'a crate::texture::Texture>,
+    samples: u32,
     label: Option<&'a str>,
 }
 
@@ -421,6 +1029,7 @@ impl<'a> PipelineBuilder<'a> {
             layouts,
             uniforms: Vec::new(),
             textures: Vec::new(),
+            samples: 1,
             label: None,
         }
     }
@@ -431,12 +1040,25 @@ impl<'a> PipelineBuilder<'a> {
         self
     }
 
+    /// Add the [`FrameGlobals`](crate::FrameGlobals) uniform (binding index assigned automatically)
+    pub fn globals(mut self, globals: &'a crate::FrameGlobals) -> Self {
+        self.uniforms.push(globals.buffer().buffer());
+        self
+    }
+
     /// Add a texture (binding index assigned automatically)
     pub fn texture(mut self, texture: &'a crate::texture::Texture) -> Self {
         self.textures.push(texture);
         self
     }
 
+    /// Samples per pixel of the target (1 by default), e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    pub fn samples(mut self, count: u32) -> Self {
+        self.samples = count;
+        self
+    }
+
     /// Set an optional label for pipeline and resources
     pub fn label(mut self, label: &'a str) -> Self {
         self.label = Some(label);
@@ -486,12 +1108,13 @@ impl<'a> PipelineBuilder<'a> {
         let bind_group = group_builder.build(self.context, self.label);
 
         // Create the render pipeline
-        let pipeline = RenderPipeline::simple(
+        let pipeline = RenderPipeline::simple_multisampled(
             self.context,
             self.vs_src,
             self.fs_src.unwrap(),
             self.layouts,
             surface_format,
+            self.samples,
             self.label
         )?;
 
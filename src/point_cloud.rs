@@ -0,0 +1,444 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    Camera,
+    CameraUniform,
+    Frustum,
+    GpuContext,
+    RenderPass,
+    RenderPipeline,
+    Result,
+    TypedBuffer,
+};
+use crate::packing::pack_unorm8x4;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Default number of points per culling chunk
+pub const POINT_CLOUD_CHUNK_SIZE: usize = 4096;
+
+/// One point of a [`PointCloud`], 20 bytes
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CloudPoint {
+    pub position: [f32; 3],
+    /// Diameter in pixels
+    pub size: f32,
+    /// RGBA8 color, see [`pack_unorm8x4`]
+    pub color: [u8; 4],
+}
+
+impl CloudPoint {
+    pub fn new(position: [f32; 3], size: f32, color: [f32; 4]) -> Self {
+        Self { position, size, color: pack_unorm8x4(color) }
+    }
+}
+
+/// A run of spatially close points culled as a unit
+#[derive(Clone, Debug, PartialEq)]
+pub struct PointChunk {
+    /// Indices into the partitioned points
+    pub range: Range<u32>,
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+/// Settings shared by every cloud a [`PointCloudRenderer`] draws
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointCloudSettings {
+    /// Multiplier on every point's size
+    pub size_scale: f32,
+    /// Discard the corners of each point's quad so it draws as a disc
+    pub round: bool,
+}
+
+impl PointCloudSettings {
+    pub fn new() -> Self {
+        Self { size_scale: 1.0, round: true }
+    }
+}
+
+impl Default for PointCloudSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PointView {
+    viewport: [f32; 2],
+    size_scale: f32,
+    round: u32,
+}
+
+struct PointPage {
+    _points: TypedBuffer<CloudPoint>,
+    bind_group: wgpu::BindGroup,
+    first: u32,
+}
+
+/// Points resident on the GPU, split into chunks for frustum culling.
+///
+/// The points are reordered along a Morton curve so each chunk of
+/// `chunk_size` points covers a compact box. They live in storage buffers
+/// the vertex shader reads directly, so there is no vertex layout and clouds
+/// larger than one storage binding allows are spread over several buffers.
+pub struct PointCloud {
+    pages: Vec<PointPage>,
+    chunks: Vec<PointChunk>,
+    len: usize,
+}
+
+impl PointCloud {
+    /// Upload `points` for drawing with `renderer`
+    pub fn new(
+        context: &GpuContext,
+        renderer: &PointCloudRenderer,
+        points: &[CloudPoint],
+        chunk_size: usize
+    ) -> Result<Self> {
+        let chunk_size = chunk_size.max(1);
+        let (points, chunks) = Self::partition(points, chunk_size);
+
+        let limits = context.device.limits();
+        let binding_size = (limits.max_storage_buffer_binding_size as u64).min(
+            limits.max_buffer_size
+        );
+        let max_points = (binding_size as usize) / std::mem::size_of::<CloudPoint>();
+        let page_size = ((max_points / chunk_size) * chunk_size).max(chunk_size);
+
+        let mut pages = Vec::new();
+        for (i, page) in points.chunks(page_size).enumerate() {
+            let points = TypedBuffer::storage(context, page)?;
+            let bind_group = BindGroupBuilder::new(&renderer.points_layout)
+                .buffer(0, points.buffer())
+                .build(context, Some("Point Cloud Bind Group"));
+            pages.push(PointPage { _points: points, bind_group, first: (i * page_size) as u32 });
+        }
+
+        Ok(Self { pages, chunks, len: points.len() })
+    }
+
+    /// Reorder `points` along a Morton curve and split them into chunks of
+    /// `chunk_size` with their bounds
+    pub fn partition(
+        points: &[CloudPoint],
+        chunk_size: usize
+    ) -> (Vec<CloudPoint>, Vec<PointChunk>) {
+        let chunk_size = chunk_size.max(1);
+        let (min, max) = bounds(points);
+        let extent = [0, 1, 2].map(|i| (max[i] - min[i]).max(f32::EPSILON));
+        let mut sorted: Vec<(u32, CloudPoint)> = points
+            .iter()
+            .map(|point| {
+                let cell = [0, 1, 2].map(|i| {
+                    let t = (point.position[i] - min[i]) / extent[i];
+                    (t.clamp(0.0, 1.0) * 1023.0) as u32
+                });
+                (morton(cell), *point)
+            })
+            .collect();
+        sorted.sort_by_key(|&(code, _)| code);
+        let sorted: Vec<CloudPoint> = sorted
+            .into_iter()
+            .map(|(_, point)| point)
+            .collect();
+
+        let chunks = sorted
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let start = (i * chunk_size) as u32;
+                let (min, max) = bounds(chunk);
+                PointChunk { range: start..start + (chunk.len() as u32), min, max }
+            })
+            .collect();
+        (sorted, chunks)
+    }
+
+    /// Number of points
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn chunks(&self) -> &[PointChunk] {
+        &self.chunks
+    }
+
+    /// Number of storage buffers the points are spread over
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Chunks at least partly inside `frustum`
+    pub fn visible_chunks<'a>(
+        &'a self,
+        frustum: &'a Frustum
+    ) -> impl Iterator<Item = &'a PointChunk> + 'a {
+        self.chunks.iter().filter(|chunk| frustum.intersects_aabb(chunk.min, chunk.max))
+    }
+}
+
+/// Draws [`PointCloud`]s, e.g. LiDAR scans, as screen-aligned squares or
+/// discs of a fixed pixel size.
+///
+/// Each point is an instance the vertex shader pulls from the cloud's
+/// storage buffer and expands into a quad. Chunks outside the camera passed
+/// to [`prepare`](Self::prepare) are skipped. Points are opaque and write
+/// depth. Reading storage buffers from the vertex stage is not available on
+/// WebGL.
+pub struct PointCloudRenderer {
+    camera: TypedBuffer<CameraUniform>,
+    view: TypedBuffer<PointView>,
+    bind_group: wgpu::BindGroup,
+    points_layout: Arc<wgpu::BindGroupLayout>,
+    pipeline: RenderPipeline,
+    viewport: [f32; 2],
+    frustum: Frustum,
+    pub settings: PointCloudSettings,
+}
+
+impl PointCloudRenderer {
+    /// Create a point renderer for the given color (and optional depth) formats
+    pub fn new(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>
+    ) -> Result<Self> {
+        Self::new_multisampled(context, color_format, depth_format, 1)
+    }
+
+    /// [`new`](Self::new) for targets with `samples` samples per pixel, e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    pub fn new_multisampled(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        samples: u32
+    ) -> Result<Self> {
+        let camera = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let view = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::VERTEX)
+            .uniform_buffer(1, wgpu::ShaderStages::VERTEX_FRAGMENT)
+            .build(context, Some("Point Cloud View Layout"));
+        let points_layout = BindGroupLayoutBuilder::new()
+            .storage_buffer(0, wgpu::ShaderStages::VERTEX, true)
+            .build(context, Some("Point Cloud Layout"));
+        let bind_group = BindGroupBuilder::new(&layout)
+            .buffer(0, camera.buffer())
+            .buffer(1, view.buffer())
+            .build(context, Some("Point Cloud View Bind Group"));
+
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&context.label("Point Cloud Shader")),
+            source: wgpu::ShaderSource::Wgsl(Self::shader_source().into()),
+        });
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&context.label("Point Cloud Pipeline Layout")),
+                bind_group_layouts: &[&layout, &points_layout],
+                push_constant_ranges: &[],
+            })
+        );
+        let pipeline = context.device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some(&context.label("Point Cloud Pipeline")),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: color_format,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: samples,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            })
+        );
+
+        Ok(Self {
+            camera,
+            view,
+            bind_group,
+            points_layout: points_layout.clone(),
+            pipeline: RenderPipeline {
+                pipeline,
+                bind_group_layouts: vec![layout, points_layout],
+                vertex_buffer_count: 0,
+                bindings: Vec::new(),
+            },
+            viewport: [1.0, 1.0],
+            frustum: Frustum { planes: [[0.0; 4]; 6] },
+            settings: PointCloudSettings::default(),
+        })
+    }
+
+    /// The point shader's WGSL
+    pub fn shader_source() -> String {
+        format!("{}{}", Camera::WGSL, SHADER)
+    }
+
+    /// Set the size in pixels of the target points are drawn into (call on resize)
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = [width.max(1) as f32, height.max(1) as f32];
+    }
+
+    /// Upload the camera and settings, and keep the camera's frustum for culling
+    pub fn prepare(&mut self, context: &GpuContext, camera: &Camera) -> Result<()> {
+        camera.update_buffer(context, &self.camera)?;
+        self.frustum = camera.frustum();
+        let view = PointView {
+            viewport: self.viewport,
+            size_scale: self.settings.size_scale,
+            round: self.settings.round as u32,
+        };
+        self.view.write(context, &[view])
+    }
+
+    /// Draw the chunks of `cloud` inside the last prepared camera's frustum,
+    /// returning how many were drawn
+    pub fn render<'a>(&'a self, pass: &mut RenderPass<'a>, cloud: &'a PointCloud) -> usize {
+        if cloud.is_empty() {
+            return 0;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        let mut drawn = 0;
+        let mut bound = None;
+        for chunk in cloud.visible_chunks(&self.frustum) {
+            let page = cloud.pages
+                .iter()
+                .rposition(|page| page.first <= chunk.range.start)
+                .unwrap_or(0);
+            if bound != Some(page) {
+                pass.set_bind_group(1, &cloud.pages[page].bind_group, &[]);
+                bound = Some(page);
+            }
+            let first = cloud.pages[page].first;
+            pass.draw(0..6, chunk.range.start - first..chunk.range.end - first);
+            drawn += 1;
+        }
+        drawn
+    }
+}
+
+fn bounds(points: &[CloudPoint]) -> ([f32; 3], [f32; 3]) {
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for point in points {
+        for i in 0..3 {
+            min[i] = min[i].min(point.position[i]);
+            max[i] = max[i].max(point.position[i]);
+        }
+    }
+    (min, max)
+}
+
+/// Interleave the low 10 bits of each coordinate
+fn morton(cell: [u32; 3]) -> u32 {
+    let spread = |mut v: u32| {
+        v &= 0x3ff;
+        v = (v | (v << 16)) & 0x030000ff;
+        v = (v | (v << 8)) & 0x0300f00f;
+        v = (v | (v << 4)) & 0x030c30c3;
+        (v | (v << 2)) & 0x09249249
+    };
+    spread(cell[0]) | (spread(cell[1]) << 1) | (spread(cell[2]) << 2)
+}
+
+const SHADER: &str =
+    r#"
+struct PointView {
+    viewport: vec2<f32>,
+    size_scale: f32,
+    round: u32,
+}
+
+// Scalar fields keep the 20 byte stride of the Rust struct
+struct CloudPoint {
+    x: f32,
+    y: f32,
+    z: f32,
+    size: f32,
+    color: u32,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var<uniform> point_view: PointView;
+@group(1) @binding(0) var<storage, read> points: array<CloudPoint>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // -1..1 across the point's quad
+    @location(0) local: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    @builtin(instance_index) instance: u32
+) -> VertexOutput {
+    var out: VertexOutput;
+    let point = points[instance];
+    let clip = camera.view_projection * vec4<f32>(point.x, point.y, point.z, 1.0);
+    if (clip.w <= 0.0) {
+        out.clip_position = vec4<f32>(0.0);
+        return out;
+    }
+
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0)
+    );
+    let corner = corners[index];
+    let size = max(point.size * point_view.size_scale, 1.0);
+    let offset = corner * size / point_view.viewport * clip.w;
+
+    out.clip_position = vec4<f32>(clip.xy + offset, clip.zw);
+    out.local = corner;
+    out.color = unpack4x8unorm(point.color);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (point_view.round != 0u && dot(in.local, in.local) > 1.0) {
+        discard;
+    }
+    return in.color;
+}
+"#;
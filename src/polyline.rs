@@ -0,0 +1,307 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    Camera,
+    CameraUniform,
+    DynamicMesh,
+    GpuContext,
+    RenderPass,
+    RenderPipeline,
+    Result,
+    TypedBuffer,
+};
+
+/// One segment of a wide line, expanded into a quad in the vertex shader
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineSegment {
+    pub start: [f32; 3],
+    /// Width in pixels
+    pub width: f32,
+    pub end: [f32; 3],
+    _padding: f32,
+    pub color: [f32; 4],
+}
+
+impl LineSegment {
+    pub fn new(start: [f32; 3], end: [f32; 3], width: f32, color: [f32; 4]) -> Self {
+        Self { start, width, end, _padding: 0.0, color }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineView {
+    viewport: [f32; 2],
+    _padding: [f32; 2],
+}
+
+/// Immediate-mode lines of any pixel width.
+///
+/// Hardware lines are one pixel wide and aliased. Here every segment is an
+/// instance that the vertex shader expands into a screen-space quad around
+/// the projected endpoints; the fragment shader cuts a capsule out of it with
+/// a one pixel anti-aliased edge. Caps are round, and consecutive segments of
+/// a [`polyline`](Self::polyline) overlap in round joins (translucent lines
+/// are blended twice there). Lines thinner than a pixel fade out instead of
+/// breaking up.
+///
+/// Positions are in the coordinates of the camera passed to
+/// [`prepare`](Self::prepare): world space for 3D overlays, or pixels with
+/// [`Camera::pixels`]. Lines are depth-tested but do not write depth.
+pub struct PolylineRenderer {
+    segments: DynamicMesh<LineSegment>,
+    camera: TypedBuffer<CameraUniform>,
+    view: TypedBuffer<LineView>,
+    bind_group: wgpu::BindGroup,
+    pipeline: RenderPipeline,
+    viewport: [f32; 2],
+}
+
+impl PolylineRenderer {
+    /// Create a line renderer for the given color (and optional depth) formats
+    pub fn new(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>
+    ) -> Result<Self> {
+        Self::new_multisampled(context, color_format, depth_format, 1)
+    }
+
+    /// [`new`](Self::new) for targets with `samples` samples per pixel, e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    pub fn new_multisampled(
+        context: &GpuContext,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        samples: u32
+    ) -> Result<Self> {
+        let camera = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let view = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::VERTEX)
+            .uniform_buffer(1, wgpu::ShaderStages::VERTEX)
+            .build(context, Some("Polyline Layout"));
+        let bind_group = BindGroupBuilder::new(&layout)
+            .buffer(0, camera.buffer())
+            .buffer(1, view.buffer())
+            .build(context, Some("Polyline Bind Group"));
+
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&context.label("Polyline Shader")),
+            source: wgpu::ShaderSource::Wgsl(Self::shader_source().into()),
+        });
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&context.label("Polyline Pipeline Layout")),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            })
+        );
+        let pipeline = context.device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some(&context.label("Polyline Pipeline")),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<LineSegment>() as u64,
+                            step_mode: wgpu::VertexStepMode::Instance,
+                            attributes: &wgpu::vertex_attr_array![
+                                0 => Float32x3,
+                                1 => Float32,
+                                2 => Float32x3,
+                                3 => Float32,
+                                4 => Float32x4
+                            ],
+                        },
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: color_format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: samples,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            })
+        );
+
+        Ok(Self {
+            segments: DynamicMesh::new(context, 256)?,
+            camera,
+            view,
+            bind_group,
+            pipeline: RenderPipeline {
+                pipeline,
+                bind_group_layouts: vec![layout],
+                vertex_buffer_count: 1,
+                bindings: Vec::new(),
+            },
+            viewport: [1.0, 1.0],
+        })
+    }
+
+    /// The line shader's WGSL
+    pub fn shader_source() -> String {
+        format!("{}{}", Camera::WGSL, SHADER)
+    }
+
+    /// Set the size in pixels of the target lines are drawn into (call on resize)
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = [width.max(1) as f32, height.max(1) as f32];
+    }
+
+    /// Draw a line from `start` to `end`, `width` pixels wide
+    pub fn line(&mut self, start: [f32; 3], end: [f32; 3], width: f32, color: [f32; 4]) {
+        self.segments.push(LineSegment::new(start, end, width, color));
+    }
+
+    /// Draw connected segments through `points` with round joins; repeat the
+    /// first point at the end to close the shape
+    pub fn polyline(&mut self, points: &[[f32; 3]], width: f32, color: [f32; 4]) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], width, color);
+        }
+    }
+
+    /// Number of segments accumulated for the current frame
+    pub fn segment_count(&self) -> usize {
+        self.segments.pending().len()
+    }
+
+    /// Upload this frame's segments and camera, then clear the list for the next frame
+    pub fn prepare(&mut self, context: &GpuContext, camera: &Camera) -> Result<()> {
+        camera.update_buffer(context, &self.camera)?;
+        self.view.write(context, &[LineView { viewport: self.viewport, _padding: [0.0; 2] }])?;
+        self.segments.upload(context)
+    }
+
+    /// Draw the segments uploaded by the last [`prepare`](Self::prepare)
+    pub fn render<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        let count = self.segments.vertex_count();
+        if count == 0 {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.segments.buffer());
+        pass.draw(0..6, 0..count);
+    }
+}
+
+const SHADER: &str =
+    r#"
+struct LineView {
+    viewport: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var<uniform> line_view: LineView;
+
+struct SegmentInput {
+    @location(0) start: vec3<f32>,
+    @location(1) width: f32,
+    @location(2) end: vec3<f32>,
+    @location(4) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Pixels along and across the segment, from its start
+    @location(0) @interpolate(linear) local: vec2<f32>,
+    // Segment length and half width in pixels
+    @location(1) @interpolate(flat) shape: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+const NEAR_W: f32 = 1e-5;
+
+// Move `p` towards `q` until it is in front of the camera
+fn clip_to_near(p: vec4<f32>, q: vec4<f32>) -> vec4<f32> {
+    if (p.w >= NEAR_W) {
+        return p;
+    }
+    return mix(p, q, (NEAR_W - p.w) / (q.w - p.w));
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, in: SegmentInput) -> VertexOutput {
+    var out: VertexOutput;
+    let start = camera.view_projection * vec4<f32>(in.start, 1.0);
+    let end = camera.view_projection * vec4<f32>(in.end, 1.0);
+    if (start.w < NEAR_W && end.w < NEAR_W) {
+        out.clip_position = vec4<f32>(0.0);
+        return out;
+    }
+    let a = clip_to_near(start, end);
+    let b = clip_to_near(end, start);
+
+    let half_viewport = line_view.viewport * 0.5;
+    let screen_a = a.xy / a.w * half_viewport;
+    let screen_b = b.xy / b.w * half_viewport;
+    let delta = screen_b - screen_a;
+    let span = length(delta);
+    var direction = vec2<f32>(1.0, 0.0);
+    if (span > 1e-4) {
+        direction = delta / span;
+    }
+    let normal = vec2<f32>(-direction.y, direction.x);
+
+    // Thin lines are drawn one pixel wide and faded instead
+    let radius = max(in.width, 1.0) * 0.5;
+    let extent = radius + 1.0;
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 0.0)
+    );
+    let corner = corners[index];
+    let along = mix(-extent, span + extent, corner.x);
+    let across = mix(-extent, extent, corner.y);
+    let screen = screen_a + direction * along + normal * across;
+    let clip = mix(a, b, corner.x);
+
+    out.clip_position = vec4<f32>(screen / half_viewport * clip.w, clip.z, clip.w);
+    out.local = vec2<f32>(along, across);
+    out.shape = vec2<f32>(span, radius);
+    out.color = vec4<f32>(in.color.rgb, in.color.a * min(in.width, 1.0));
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let nearest = vec2<f32>(clamp(in.local.x, 0.0, in.shape.x), 0.0);
+    let distance = length(in.local - nearest) - in.shape.y;
+    let coverage = clamp(0.5 - distance, 0.0, 1.0);
+    if (coverage <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
+"#;
@@ -0,0 +1,205 @@
+use crate::{ GpuContext, Mesh, MeshData, MeshVertex, Result };
+use std::f32::consts::{ PI, TAU };
+
+/// Procedural primitives. All are centred on the origin, wound counter-clockwise
+/// when seen from outside, and use UVs with `v` growing downwards.
+impl MeshData<MeshVertex> {
+    /// Unit cube spanning -0.5..0.5 on every axis, with per-face normals
+    pub fn cube() -> Self {
+        let faces: [([f32; 3], [f32; 3]); 6] = [
+            ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+            ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+            ([0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+            ([0.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
+        ];
+
+        let mut data = Self::default();
+        for (normal, up) in faces {
+            let right = cross(up, normal);
+            let base = data.vertices.len() as u32;
+            for (x, y, uv) in [
+                (-0.5, -0.5, [0.0, 1.0]),
+                (0.5, -0.5, [1.0, 1.0]),
+                (0.5, 0.5, [1.0, 0.0]),
+                (-0.5, 0.5, [0.0, 0.0]),
+            ] {
+                let position = [0, 1, 2].map(|i| normal[i] * 0.5 + right[i] * x + up[i] * y);
+                data.vertices.push(MeshVertex::new(position, normal, uv));
+            }
+            data.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        data
+    }
+
+    /// UV sphere of radius 0.5 with `subdivisions` rings and twice as many segments
+    pub fn sphere(subdivisions: u32) -> Self {
+        let rings = subdivisions.max(2);
+        let segments = rings * 2;
+
+        let mut data = Self::default();
+        for ring in 0..=rings {
+            let v = (ring as f32) / (rings as f32);
+            let phi = v * PI;
+            for segment in 0..=segments {
+                let u = (segment as f32) / (segments as f32);
+                let theta = u * TAU;
+                let normal = [phi.sin() * theta.cos(), phi.cos(), -phi.sin() * theta.sin()];
+                data.vertices.push(MeshVertex::new(normal.map(|n| n * 0.5), normal, [u, v]));
+            }
+        }
+        data.indices = grid_indices(rings, segments);
+        data
+    }
+
+    /// Square of side `size` in the XZ plane, facing +Y
+    pub fn plane(size: f32) -> Self {
+        let h = size * 0.5;
+        let normal = [0.0, 1.0, 0.0];
+        Self {
+            vertices: vec![
+                MeshVertex::new([-h, 0.0, h], normal, [0.0, 1.0]),
+                MeshVertex::new([h, 0.0, h], normal, [1.0, 1.0]),
+                MeshVertex::new([h, 0.0, -h], normal, [1.0, 0.0]),
+                MeshVertex::new([-h, 0.0, -h], normal, [0.0, 0.0])
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+        }
+    }
+
+    /// Torus around the Y axis: `radius` to the tube centre, `tube_radius` of the tube
+    pub fn torus(radius: f32, tube_radius: f32, segments: u32, sides: u32) -> Self {
+        let segments = segments.max(3);
+        let sides = sides.max(3);
+
+        let mut data = Self::default();
+        for segment in 0..=segments {
+            let u = (segment as f32) / (segments as f32);
+            let theta = u * TAU;
+            for side in 0..=sides {
+                let v = (side as f32) / (sides as f32);
+                let phi = v * TAU;
+                let normal = [phi.cos() * theta.cos(), phi.sin(), -phi.cos() * theta.sin()];
+                let position = [
+                    radius * theta.cos() + tube_radius * normal[0],
+                    tube_radius * normal[1],
+                    -radius * theta.sin() + tube_radius * normal[2],
+                ];
+                data.vertices.push(MeshVertex::new(position, normal, [u, v]));
+            }
+        }
+        data.indices = grid_indices(segments, sides);
+        data
+    }
+
+    /// Capped cylinder along the Y axis
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> Self {
+        let segments = segments.max(3);
+        let half = height * 0.5;
+
+        let mut data = Self::default();
+        // Side wall: two rows of vertices, bottom then top
+        for (row, y) in [(0.0, -half), (1.0, half)] {
+            for segment in 0..=segments {
+                let u = (segment as f32) / (segments as f32);
+                let theta = u * TAU;
+                let normal = [theta.cos(), 0.0, -theta.sin()];
+                let position = [radius * normal[0], y, radius * normal[2]];
+                data.vertices.push(MeshVertex::new(position, normal, [u, 1.0 - row]));
+            }
+        }
+        let row = segments + 1;
+        for segment in 0..segments {
+            let (a, b) = (segment, segment + 1);
+            data.indices.extend_from_slice(&[a, b, row + b, a, row + b, row + a]);
+        }
+
+        // Caps: a centre vertex fanned out to a ring
+        for (y, ny) in [(half, 1.0), (-half, -1.0)] {
+            let normal = [0.0, ny, 0.0];
+            let center = data.vertices.len() as u32;
+            data.vertices.push(MeshVertex::new([0.0, y, 0.0], normal, [0.5, 0.5]));
+            for segment in 0..=segments {
+                let theta = ((segment as f32) / (segments as f32)) * TAU;
+                let (x, z) = (theta.cos(), -theta.sin());
+                let uv = [0.5 + x * 0.5, 0.5 + z * 0.5 * ny];
+                data.vertices.push(MeshVertex::new([radius * x, y, radius * z], normal, uv));
+            }
+            for segment in 0..segments {
+                let (a, b) = (center + 1 + segment, center + 2 + segment);
+                if ny > 0.0 {
+                    data.indices.extend_from_slice(&[center, a, b]);
+                } else {
+                    data.indices.extend_from_slice(&[center, b, a]);
+                }
+            }
+        }
+        data
+    }
+
+    /// Quad covering clip space (-1..1 in X and Y at z = 0), facing the camera
+    pub fn fullscreen_quad() -> Self {
+        let normal = [0.0, 0.0, 1.0];
+        Self {
+            vertices: vec![
+                MeshVertex::new([-1.0, -1.0, 0.0], normal, [0.0, 1.0]),
+                MeshVertex::new([1.0, -1.0, 0.0], normal, [1.0, 1.0]),
+                MeshVertex::new([1.0, 1.0, 0.0], normal, [1.0, 0.0]),
+                MeshVertex::new([-1.0, 1.0, 0.0], normal, [0.0, 0.0])
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+        }
+    }
+}
+
+/// GPU versions of the procedural primitives, using [`MeshVertex::layout`]
+impl Mesh<MeshVertex> {
+    pub fn cube(context: &GpuContext) -> Result<Self> {
+        MeshData::cube().upload(context, MeshVertex::layout())
+    }
+
+    pub fn sphere(context: &GpuContext, subdivisions: u32) -> Result<Self> {
+        MeshData::sphere(subdivisions).upload(context, MeshVertex::layout())
+    }
+
+    pub fn plane(context: &GpuContext, size: f32) -> Result<Self> {
+        MeshData::plane(size).upload(context, MeshVertex::layout())
+    }
+
+    pub fn torus(
+        context: &GpuContext,
+        radius: f32,
+        tube_radius: f32,
+        segments: u32,
+        sides: u32
+    ) -> Result<Self> {
+        MeshData::torus(radius, tube_radius, segments, sides).upload(context, MeshVertex::layout())
+    }
+
+    pub fn cylinder(context: &GpuContext, radius: f32, height: f32, segments: u32) -> Result<Self> {
+        MeshData::cylinder(radius, height, segments).upload(context, MeshVertex::layout())
+    }
+
+    pub fn fullscreen_quad(context: &GpuContext) -> Result<Self> {
+        MeshData::fullscreen_quad().upload(context, MeshVertex::layout())
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+/// Indices for a (rows + 1) x (columns + 1) vertex grid laid out row by row
+fn grid_indices(rows: u32, columns: u32) -> Vec<u32> {
+    let stride = columns + 1;
+    let mut indices = Vec::with_capacity((rows * columns * 6) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = row * stride + column;
+            let b = a + stride;
+            indices.extend_from_slice(&[a, b, b + 1, a, b + 1, a + 1]);
+        }
+    }
+    indices
+}
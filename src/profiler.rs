@@ -0,0 +1,338 @@
+use crate::{ GeepuError, GpuContext, Result };
+use crate::profiling::profile_scope;
+#[cfg(any(feature = "tracy", feature = "puffin"))]
+use crate::profiling::{ GpuZone, GpuZoneSink };
+use std::collections::VecDeque;
+use std::sync::{ Arc, Mutex };
+
+/// Frames of timestamps that can be in flight before new scopes are dropped
+const READBACK_FRAMES: usize = 3;
+
+/// Rolling GPU time statistics for one profiled pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassTiming {
+    pub label: String,
+    /// Time of the most recent sample in milliseconds
+    pub last_ms: f32,
+    pub average_ms: f32,
+    pub max_ms: f32,
+    /// Number of samples in the window
+    pub samples: usize,
+}
+
+/// Per-pass rolling window of GPU times, in first-seen order
+#[derive(Debug, Clone)]
+pub struct TimingHistory {
+    window: usize,
+    passes: Vec<(String, VecDeque<f32>)>,
+}
+
+impl TimingHistory {
+    /// Keep the last `window` samples of each pass
+    pub fn new(window: usize) -> Self {
+        Self { window: window.max(1), passes: Vec::new() }
+    }
+
+    /// Add a sample for `label`
+    pub fn record(&mut self, label: &str, milliseconds: f32) {
+        let index = match self.passes.iter().position(|(name, _)| name == label) {
+            Some(index) => index,
+            None => {
+                self.passes.push((label.to_string(), VecDeque::with_capacity(self.window)));
+                self.passes.len() - 1
+            }
+        };
+        let samples = &mut self.passes[index].1;
+        if samples.len() == self.window {
+            samples.pop_front();
+        }
+        samples.push_back(milliseconds);
+    }
+
+    /// Statistics for every pass seen so far
+    pub fn report(&self) -> Vec<PassTiming> {
+        self.passes
+            .iter()
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|(label, samples)| PassTiming {
+                label: label.clone(),
+                last_ms: *samples.back().unwrap(),
+                average_ms: samples.iter().sum::<f32>() / (samples.len() as f32),
+                max_ms: samples.iter().copied().fold(0.0, f32::max),
+                samples: samples.len(),
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.passes.clear();
+    }
+}
+
+type MapResult = Arc<Mutex<Option<std::result::Result<(), wgpu::BufferAsyncError>>>>;
+
+enum ReadbackState {
+    Free,
+    /// Resolved into the buffer by a recorded (maybe not yet submitted) copy
+    Copied,
+    Mapping(MapResult),
+}
+
+struct Readback {
+    buffer: wgpu::Buffer,
+    labels: Vec<String>,
+    state: ReadbackState,
+}
+
+struct ProfilerQueries {
+    query_set: wgpu::QuerySet,
+    resolve: wgpu::Buffer,
+    readbacks: Vec<Readback>,
+    max_scopes: u32,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+}
+
+/// GPU timestamp profiler with a rolling per-pass report.
+///
+/// Passes begun through
+/// [`begin_profiled_render_pass`](crate::RenderCommands::begin_profiled_render_pass) or
+/// [`begin_profiled_compute_pass`](crate::ComputeCommands::begin_profiled_compute_pass)
+/// get timestamp writes at their start and end. Each frame, record
+/// [`resolve`](Self::resolve) after the last profiled pass, submit, then call
+/// [`end_frame`](Self::end_frame), which collects finished frames without
+/// blocking (results lag a frame or two).
+///
+/// Without `wgpu::Features::TIMESTAMP_QUERY` on the device the profiler is
+/// disabled: passes run unprofiled and the report stays empty.
+pub struct GpuProfiler {
+    queries: Option<ProfilerQueries>,
+    labels: Vec<String>,
+    history: TimingHistory,
+    #[cfg(any(feature = "tracy", feature = "puffin"))]
+    zones: GpuZoneSink,
+}
+
+impl GpuProfiler {
+    /// Create a profiler for up to `max_scopes` passes per frame, averaging over 60 frames
+    pub fn new(context: &GpuContext, max_scopes: u32) -> Result<Self> {
+        if max_scopes == 0 || max_scopes > wgpu::QUERY_SET_MAX_QUERIES / 2 {
+            return Err(
+                GeepuError::Other(
+                    format!(
+                        "Profiler scopes must be between 1 and {}, got {}",
+                        wgpu::QUERY_SET_MAX_QUERIES / 2,
+                        max_scopes
+                    )
+                )
+            );
+        }
+
+        let count = max_scopes * 2;
+        let queries = context.device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let size = (count as u64) * (wgpu::QUERY_SIZE as u64);
+                let readbacks = (0..READBACK_FRAMES)
+                    .map(|_| Readback {
+                        buffer: context.device.create_buffer(
+                            &(wgpu::BufferDescriptor {
+                                label: Some(&context.label("Profiler Readback")),
+                                size,
+                                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                                mapped_at_creation: false,
+                            })
+                        ),
+                        labels: Vec::new(),
+                        state: ReadbackState::Free,
+                    })
+                    .collect();
+                ProfilerQueries {
+                    query_set: context.device.create_query_set(
+                        &(wgpu::QuerySetDescriptor {
+                            label: Some(&context.label("Profiler Timestamps")),
+                            ty: wgpu::QueryType::Timestamp,
+                            count,
+                        })
+                    ),
+                    resolve: context.device.create_buffer(
+                        &(wgpu::BufferDescriptor {
+                            label: Some(&context.label("Profiler Resolve")),
+                            size,
+                            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                            mapped_at_creation: false,
+                        })
+                    ),
+                    readbacks,
+                    max_scopes,
+                    period: context.queue.get_timestamp_period(),
+                }
+            });
+
+        Ok(Self {
+            queries,
+            labels: Vec::new(),
+            history: TimingHistory::new(60),
+            #[cfg(any(feature = "tracy", feature = "puffin"))]
+            zones: GpuZoneSink::default(),
+        })
+    }
+
+    /// Change how many frames the report averages over
+    pub fn with_window(mut self, frames: usize) -> Self {
+        self.history = TimingHistory::new(frames);
+        self
+    }
+
+    /// Whether the device supports timestamp queries
+    pub fn is_enabled(&self) -> bool {
+        self.queries.is_some()
+    }
+
+    /// Reserve the timestamp pair for a pass named `label`
+    pub(crate) fn begin_scope(&mut self, label: &str) -> Option<u32> {
+        let queries = self.queries.as_ref()?;
+        let scope = self.labels.len() as u32;
+        if scope >= queries.max_scopes {
+            return None;
+        }
+        self.labels.push(label.to_string());
+        Some(scope)
+    }
+
+    pub(crate) fn render_timestamp_writes(
+        &self,
+        scope: Option<u32>
+    ) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        let (queries, scope) = (self.queries.as_ref()?, scope?);
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &queries.query_set,
+            beginning_of_pass_write_index: Some(scope * 2),
+            end_of_pass_write_index: Some(scope * 2 + 1),
+        })
+    }
+
+    pub(crate) fn compute_timestamp_writes(
+        &self,
+        scope: Option<u32>
+    ) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        let (queries, scope) = (self.queries.as_ref()?, scope?);
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set: &queries.query_set,
+            beginning_of_pass_write_index: Some(scope * 2),
+            end_of_pass_write_index: Some(scope * 2 + 1),
+        })
+    }
+
+    /// Resolve this frame's timestamps into a readback buffer.
+    ///
+    /// Record after the last profiled pass of the frame. If every readback
+    /// buffer is still in flight the frame's timings are dropped.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let labels = std::mem::take(&mut self.labels);
+        let Some(queries) = self.queries.as_mut() else {
+            return;
+        };
+        if labels.is_empty() {
+            return;
+        }
+        let Some(readback) = queries.readbacks
+            .iter_mut()
+            .find(|readback| matches!(readback.state, ReadbackState::Free)) else {
+            return;
+        };
+
+        let count = (labels.len() as u32) * 2;
+        encoder.resolve_query_set(&queries.query_set, 0..count, &queries.resolve, 0);
+        let size = (count as u64) * (wgpu::QUERY_SIZE as u64);
+        encoder.copy_buffer_to_buffer(&queries.resolve, 0, &readback.buffer, 0, size);
+        readback.labels = labels;
+        readback.state = ReadbackState::Copied;
+    }
+
+    /// Start reading back submitted frames and record any that have arrived.
+    ///
+    /// Call once per frame after submitting the commands holding
+    /// [`resolve`](Self::resolve).
+    pub fn end_frame(&mut self, context: &GpuContext) {
+        profile_scope!("geepu::profiler_readback");
+        let Some(queries) = self.queries.as_mut() else {
+            return;
+        };
+
+        for readback in &mut queries.readbacks {
+            if matches!(readback.state, ReadbackState::Copied) {
+                let result: MapResult = Arc::default();
+                let callback_result = result.clone();
+                let size = (readback.labels.len() as u64) * 2 * (wgpu::QUERY_SIZE as u64);
+                readback.buffer.slice(..size).map_async(wgpu::MapMode::Read, move |r| {
+                    *callback_result.lock().unwrap() = Some(r);
+                });
+                readback.state = ReadbackState::Mapping(result);
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        context.device.poll(wgpu::Maintain::Poll);
+        #[cfg(target_arch = "wasm32")]
+        let _ = context;
+
+        for readback in &mut queries.readbacks {
+            let ReadbackState::Mapping(result) = &readback.state else {
+                continue;
+            };
+            let Some(result) = result.lock().unwrap().take() else {
+                continue;
+            };
+            if result.is_ok() {
+                let size = (readback.labels.len() as u64) * 2 * (wgpu::QUERY_SIZE as u64);
+                let data = readback.buffer.slice(..size).get_mapped_range();
+                let ticks: &[u64] = bytemuck::cast_slice(&data);
+                #[cfg(any(feature = "tracy", feature = "puffin"))]
+                {
+                    let zones: Vec<GpuZone<'_>> = readback.labels
+                        .iter()
+                        .zip(ticks.chunks_exact(2))
+                        .map(|(label, pair)| GpuZone { label, begin: pair[0], end: pair[1] })
+                        .collect();
+                    self.zones.frame(&zones, queries.period);
+                }
+                for (label, pair) in readback.labels.iter().zip(ticks.chunks_exact(2)) {
+                    let nanoseconds = (pair[1].saturating_sub(pair[0]) as f64) *
+                        (queries.period as f64);
+                    let milliseconds = (nanoseconds / 1_000_000.0) as f32;
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        target: "geepu::profiler",
+                        pass = %label,
+                        gpu_ms = milliseconds
+                    );
+                    self.history.record(label, milliseconds);
+                }
+                drop(data);
+                readback.buffer.unmap();
+            }
+            readback.labels.clear();
+            readback.state = ReadbackState::Free;
+        }
+    }
+
+    /// Rolling GPU time per pass, in the order passes were first profiled
+    pub fn report(&self) -> Vec<PassTiming> {
+        self.history.report()
+    }
+
+    /// Sum of the passes' most recent GPU times
+    pub fn frame_ms(&self) -> f32 {
+        self.report()
+            .iter()
+            .map(|timing| timing.last_ms)
+            .sum()
+    }
+
+    /// Forget all recorded timings
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+}
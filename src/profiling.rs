@@ -0,0 +1,129 @@
+//! Glue for external CPU/GPU profilers (Tracy via the `tracy` feature, puffin
+//! via the `puffin` feature). Everything here compiles to nothing when both
+//! features are off.
+
+/// Open a CPU profiling scope lasting until the end of the enclosing block
+macro_rules! profile_scope {
+    ($name:expr) => {
+        $crate::profiling::profile_scope!($name, "")
+    };
+    ($name:expr, $data:expr) => {
+        #[cfg(feature = "puffin")]
+        puffin::profile_scope!($name, $data);
+        #[cfg(feature = "tracy")]
+        let _tracy_span = tracy_client::Client::running().map(|client| {
+            let span = client.span_alloc(Some($name), module_path!(), file!(), line!(), 0);
+            let data: &str = $data;
+            if !data.is_empty() {
+                span.emit_text(data);
+            }
+            span
+        });
+    };
+}
+
+pub(crate) use profile_scope;
+
+/// One resolved GPU pass: label and begin/end timestamps in ticks
+#[cfg(any(feature = "tracy", feature = "puffin"))]
+pub(crate) struct GpuZone<'a> {
+    pub label: &'a str,
+    pub begin: u64,
+    pub end: u64,
+}
+
+/// Forwards resolved GPU pass timings to the enabled external profilers
+#[cfg(any(feature = "tracy", feature = "puffin"))]
+#[derive(Default)]
+pub(crate) struct GpuZoneSink {
+    #[cfg(feature = "tracy")]
+    tracy: Option<tracy_client::GpuContext>,
+    #[cfg(feature = "puffin")]
+    puffin_scopes: std::collections::HashMap<String, puffin::ScopeId>,
+}
+
+#[cfg(any(feature = "tracy", feature = "puffin"))]
+impl GpuZoneSink {
+    /// Report one frame's zones; `period` is nanoseconds per tick
+    pub(crate) fn frame(&mut self, zones: &[GpuZone<'_>], period: f32) {
+        if zones.is_empty() {
+            return;
+        }
+        #[cfg(feature = "tracy")]
+        self.tracy_frame(zones, period);
+        #[cfg(feature = "puffin")]
+        self.puffin_frame(zones, period);
+    }
+
+    #[cfg(feature = "tracy")]
+    fn tracy_frame(&mut self, zones: &[GpuZone<'_>], period: f32) {
+        let Some(client) = tracy_client::Client::running() else {
+            return;
+        };
+        if self.tracy.is_none() {
+            // Calibrate with the first timestamp seen; zones then land on a
+            // timeline offset by the readback latency
+            self.tracy = client
+                .new_gpu_context(
+                    Some("geepu"),
+                    tracy_client::GpuContextType::Invalid,
+                    zones[0].begin as i64,
+                    period
+                )
+                .ok();
+        }
+        let Some(context) = &self.tracy else {
+            return;
+        };
+        for zone in zones {
+            let Ok(mut span) = context.span_alloc(zone.label, "", file!(), line!()) else {
+                return;
+            };
+            span.end_zone();
+            span.upload_timestamp_start(zone.begin as i64);
+            span.upload_timestamp_end(zone.end.max(zone.begin) as i64);
+        }
+    }
+
+    #[cfg(feature = "puffin")]
+    fn puffin_frame(&mut self, zones: &[GpuZone<'_>], period: f32) {
+        if !puffin::are_scopes_on() {
+            return;
+        }
+        // puffin has no GPU clock, so place the frame's zones (keeping their
+        // relative timing) to end at the moment they were read back
+        let to_ns = |ticks: u64| ((ticks as f64) * (period as f64)) as i64;
+        let first = zones[0].begin;
+        let last = zones
+            .iter()
+            .map(|zone| zone.end)
+            .max()
+            .unwrap_or(first);
+        let origin = puffin::now_ns() - to_ns(last.saturating_sub(first));
+
+        let mut stream = puffin::StreamInfo::default();
+        let mut cursor = i64::MIN;
+        for zone in zones {
+            let id = match self.puffin_scopes.get(zone.label) {
+                Some(id) => *id,
+                None => {
+                    let details = puffin::ScopeDetails::from_scope_name(zone.label.to_string());
+                    let id = puffin::GlobalProfiler::lock().register_user_scopes(&[details])[0];
+                    self.puffin_scopes.insert(zone.label.to_string(), id);
+                    id
+                }
+            };
+            let start = (origin + to_ns(zone.begin.saturating_sub(first))).max(cursor);
+            let stop = (origin + to_ns(zone.end.saturating_sub(first))).max(start);
+            let (offset, _) = stream.stream.begin_scope(|| start, id, "");
+            stream.stream.end_scope(offset, stop);
+            stream.num_scopes += 1;
+            stream.depth = 1;
+            stream.range_ns = (stream.range_ns.0.min(start), stream.range_ns.1.max(stop));
+            cursor = stop;
+        }
+
+        let info = puffin::ThreadInfo { start_time_ns: None, name: "GPU".to_string() };
+        puffin::GlobalProfiler::lock().report_user_scopes(info, &stream.as_stream_into_ref());
+    }
+}
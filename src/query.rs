@@ -0,0 +1,286 @@
+use crate::{ GeepuError, GpuContext, Result, StagingBuffer };
+use std::ops::Range;
+
+/// What a [`QuerySet`] measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryKind {
+    /// GPU timestamps, read back in nanoseconds
+    Timestamp,
+    /// Samples passing the depth test between begin/end occlusion query
+    Occlusion,
+    /// The selected pipeline counters between begin/end statistics query
+    PipelineStatistics(wgpu::PipelineStatisticsTypes),
+}
+
+impl QueryKind {
+    /// Number of `u64` results each query resolves to
+    pub fn values_per_query(self) -> u32 {
+        match self {
+            QueryKind::PipelineStatistics(types) => types.bits().count_ones(),
+            _ => 1,
+        }
+    }
+
+    /// Device features the query type needs
+    pub fn required_features(self) -> wgpu::Features {
+        match self {
+            QueryKind::Timestamp => wgpu::Features::TIMESTAMP_QUERY,
+            QueryKind::Occlusion => wgpu::Features::empty(),
+            QueryKind::PipelineStatistics(_) => wgpu::Features::PIPELINE_STATISTICS_QUERY,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            QueryKind::Timestamp => "Timestamp",
+            QueryKind::Occlusion => "Occlusion",
+            QueryKind::PipelineStatistics(_) => "Pipeline Statistics",
+        }
+    }
+}
+
+/// A query set that hands out slots and reads back only the ones used.
+///
+/// [`allocate`](Self::allocate) a slot per query, write it from a pass (e.g.
+/// [`RenderPass::write_timestamp`](crate::RenderPass::write_timestamp)), then
+/// record [`RenderCommands::resolve_queries`](crate::RenderCommands::resolve_queries)
+/// before submitting and [`read`](Self::read) afterwards.
+/// [`reset`](Self::reset) frees every slot for the next frame.
+pub struct QuerySet {
+    query_set: wgpu::QuerySet,
+    kind: QueryKind,
+    resolve: wgpu::Buffer,
+    staging: StagingBuffer,
+    capacity: u32,
+    used: u32,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+}
+
+impl QuerySet {
+    /// Create a set of `capacity` queries of `kind`
+    pub fn new(context: &GpuContext, kind: QueryKind, capacity: u32) -> Result<Self> {
+        if capacity == 0 || capacity > wgpu::QUERY_SET_MAX_QUERIES {
+            return Err(
+                GeepuError::Other(
+                    format!(
+                        "{} query count must be between 1 and {}, got {}",
+                        kind.name(),
+                        wgpu::QUERY_SET_MAX_QUERIES,
+                        capacity
+                    )
+                )
+            );
+        }
+        let missing = kind.required_features() - context.device.features();
+        if !missing.is_empty() {
+            return Err(GeepuError::MissingFeatures(missing));
+        }
+
+        let ty = match kind {
+            QueryKind::Timestamp => wgpu::QueryType::Timestamp,
+            QueryKind::Occlusion => wgpu::QueryType::Occlusion,
+            QueryKind::PipelineStatistics(types) => wgpu::QueryType::PipelineStatistics(types),
+        };
+        let size =
+            (capacity as u64) * (kind.values_per_query() as u64) * (wgpu::QUERY_SIZE as u64);
+        let query_set = context.device.create_query_set(
+            &(wgpu::QuerySetDescriptor {
+                label: Some(&context.label(&format!("{} Queries", kind.name()))),
+                ty,
+                count: capacity,
+            })
+        );
+        let resolve = context.device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some(&context.label(&format!("{} Query Resolve", kind.name()))),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            })
+        );
+
+        Ok(Self {
+            query_set,
+            kind,
+            resolve,
+            staging: StagingBuffer::new(context, size)?,
+            capacity,
+            used: 0,
+            period: context.queue.get_timestamp_period(),
+        })
+    }
+
+    pub fn kind(&self) -> QueryKind {
+        self.kind
+    }
+
+    /// Number of queries in the set
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Number of slots handed out since the last [`reset`](Self::reset)
+    pub fn used(&self) -> u32 {
+        self.used
+    }
+
+    /// Take the next free slot
+    pub fn allocate(&mut self) -> Result<u32> {
+        Ok(self.allocate_range(1)?.start)
+    }
+
+    /// Take `count` consecutive slots, e.g. a begin/end timestamp pair
+    pub fn allocate_range(&mut self, count: u32) -> Result<Range<u32>> {
+        let start = self.used;
+        let end = start
+            .checked_add(count)
+            .filter(|&end| end <= self.capacity)
+            .ok_or_else(|| {
+                GeepuError::Other(
+                    format!(
+                        "{} query set is full ({} of {} used, {} requested)",
+                        self.kind.name(),
+                        self.used,
+                        self.capacity,
+                        count
+                    )
+                )
+            })?;
+        self.used = end;
+        Ok(start..end)
+    }
+
+    /// Free every slot, typically at the start of a frame
+    pub fn reset(&mut self) {
+        self.used = 0;
+    }
+
+    /// Record copying the used slots' results into the readback buffer.
+    /// Does nothing when no slot is in use.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.used == 0 {
+            return;
+        }
+        encoder.resolve_query_set(&self.query_set, 0..self.used, &self.resolve, 0);
+        self.staging.copy_from_buffer(encoder, &self.resolve, Some(self.resolved_size()));
+    }
+
+    /// Read the resolved results of the used slots, [`values_per_query`]
+    /// values each
+    ///
+    /// [`values_per_query`]: QueryKind::values_per_query
+    pub async fn read(&self, context: &GpuContext) -> Result<Vec<u64>> {
+        let mut values: Vec<u64> = self.staging.read_data(context).await?;
+        values.truncate((self.resolved_size() / (wgpu::QUERY_SIZE as u64)) as usize);
+        Ok(values)
+    }
+
+    /// Read timestamp results in nanoseconds
+    pub async fn read_timestamps(&self, context: &GpuContext) -> Result<Vec<f64>> {
+        self.expect_kind(matches!(self.kind, QueryKind::Timestamp))?;
+        let period = self.period as f64;
+        Ok(
+            self
+                .read(context).await?
+                .into_iter()
+                .map(|ticks| (ticks as f64) * period)
+                .collect()
+        )
+    }
+
+    /// Read pipeline statistics, one entry per query with the counters in
+    /// `PipelineStatisticsTypes` bit order
+    pub async fn read_statistics(&self, context: &GpuContext) -> Result<Vec<Vec<u64>>> {
+        self.expect_kind(matches!(self.kind, QueryKind::PipelineStatistics(_)))?;
+        let per_query = self.kind.values_per_query() as usize;
+        Ok(
+            self
+                .read(context).await?
+                .chunks_exact(per_query)
+                .map(<[u64]>::to_vec)
+                .collect()
+        )
+    }
+
+    /// The wrapped wgpu query set
+    pub fn raw(&self) -> &wgpu::QuerySet {
+        &self.query_set
+    }
+
+    fn resolved_size(&self) -> u64 {
+        (self.used as u64) * (self.kind.values_per_query() as u64) * (wgpu::QUERY_SIZE as u64)
+    }
+
+    fn expect_kind(&self, matches: bool) -> Result<()> {
+        if matches {
+            Ok(())
+        } else {
+            Err(GeepuError::Other(format!("Not supported by a {:?} query set", self.kind)))
+        }
+    }
+}
+
+impl AsRef<QuerySet> for QuerySet {
+    fn as_ref(&self) -> &QuerySet {
+        self
+    }
+}
+
+/// A set of occlusion queries with a readback path for their results.
+///
+/// Attach it to a pass with
+/// [`begin_occlusion_pass`](crate::RenderCommands::begin_occlusion_pass), wrap
+/// draws (typically cheap bounding boxes) in
+/// [`begin_occlusion_query`](crate::RenderPass::begin_occlusion_query) /
+/// [`end_occlusion_query`](crate::RenderPass::end_occlusion_query), then
+/// [`resolve`](Self::resolve) after the pass and [`read`](Self::read) after
+/// submitting. A non-zero result means some samples passed the depth test.
+pub struct OcclusionQueries {
+    queries: QuerySet,
+}
+
+impl OcclusionQueries {
+    /// Create `count` occlusion queries
+    pub fn new(context: &GpuContext, count: u32) -> Result<Self> {
+        let mut queries = QuerySet::new(context, QueryKind::Occlusion, count)?;
+        queries.allocate_range(count)?;
+        Ok(Self { queries })
+    }
+
+    /// Number of queries in the set
+    pub fn len(&self) -> u32 {
+        self.queries.capacity()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.capacity() == 0
+    }
+
+    /// Record copying every query's result into the readback buffer
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.queries.resolve(encoder);
+    }
+
+    /// Read the resolved results: the number of samples that passed per query
+    pub async fn read(&self, context: &GpuContext) -> Result<Vec<u64>> {
+        self.queries.read(context).await
+    }
+
+    /// Read the resolved results as per-query visibility
+    pub async fn read_visible(&self, context: &GpuContext) -> Result<Vec<bool>> {
+        Ok(
+            self
+                .read(context).await?
+                .into_iter()
+                .map(|samples| samples > 0)
+                .collect()
+        )
+    }
+}
+
+impl AsRef<QuerySet> for OcclusionQueries {
+    fn as_ref(&self) -> &QuerySet {
+        &self.queries
+    }
+}
@@ -0,0 +1,186 @@
+//! Offscreen animation export.
+//!
+//! [`record`] renders a fixed number of frames at a fixed frame rate into an
+//! offscreen target, reads each one back and hands it to a [`FrameSink`]: a
+//! numbered PNG sequence, ffmpeg, or any command reading raw RGBA frames from
+//! its standard input. Frame times advance by exactly `1 / fps`, independent
+//! of how long rendering takes, so simulations come out at the intended speed.
+
+use crate::{ GeepuError, GpuContext, RenderCommands, Result, StagingBuffer, Texture };
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{ Child, Command, Stdio };
+
+/// Format of the offscreen target frames are rendered into
+pub const RECORDING_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Time of the frame being recorded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameTime {
+    /// Frame number, from 0
+    pub index: u32,
+    /// Seconds since the first frame
+    pub time: f32,
+    /// Seconds per frame
+    pub delta: f32,
+}
+
+/// Receives each frame's index and RGBA8 pixels
+pub type FrameCallback = Box<dyn FnMut(u32, &[u8]) -> Result<()>>;
+
+/// Where recorded frames go
+pub enum FrameSink {
+    /// `frame_00000.png`, `frame_00001.png`, ... in a directory, created if missing
+    #[cfg(feature = "image")]
+    PngSequence(PathBuf),
+    /// An H.264 video at the given path, encoded by `ffmpeg` from `PATH`
+    Ffmpeg(PathBuf),
+    /// A command reading tightly packed RGBA8 frames from its standard input
+    Pipe(Command),
+    /// A function called with each frame's index and RGBA8 pixels
+    Callback(FrameCallback),
+}
+
+impl FrameSink {
+    /// The `ffmpeg` invocation [`FrameSink::Ffmpeg`] runs
+    pub fn ffmpeg_command(output: &std::path::Path, size: (u32, u32), fps: f32) -> Command {
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-loglevel", "error", "-y", "-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", size.0, size.1), "-r", &fps.to_string()])
+            .args(["-i", "-", "-c:v", "libx264", "-pix_fmt", "yuv420p"])
+            .arg(output);
+        command
+    }
+}
+
+/// Rows copied out of an RGBA8 texture, padded to the alignment texture copies need
+pub(crate) struct TargetReadback {
+    staging: StagingBuffer,
+    width: u32,
+    height: u32,
+    padded_row_bytes: u32,
+}
+
+impl TargetReadback {
+    pub(crate) fn new(context: &GpuContext, width: u32, height: u32) -> Result<Self> {
+        let padded_row_bytes = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let staging = StagingBuffer::new(context, (padded_row_bytes as u64) * (height as u64))?;
+        Ok(Self { staging, width, height, padded_row_bytes })
+    }
+
+    /// Record copying `texture` into the staging buffer
+    pub(crate) fn copy_from(&self, encoder: &mut wgpu::CommandEncoder, texture: &Texture) {
+        encoder.copy_texture_to_buffer(
+            texture.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: self.staging.buffer(),
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_row_bytes),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            }
+        );
+    }
+
+    /// Wait for the copy and return the pixels with the row padding removed
+    pub(crate) fn read(&self, context: &GpuContext) -> Result<Vec<u8>> {
+        let padded: Vec<u8> = pollster::block_on(self.staging.read_data(context))?;
+        let row_bytes = (self.width * 4) as usize;
+        Ok(
+            padded
+                .chunks_exact(self.padded_row_bytes as usize)
+                .flat_map(|row| &row[..row_bytes])
+                .copied()
+                .collect()
+        )
+    }
+}
+
+/// Render `frames` frames of `size` at `fps` with `render` and write them to `sink`.
+///
+/// `render` records into the commands, typically one pass clearing and
+/// drawing to the target's view, using pipelines that output
+/// [`RECORDING_FORMAT`]; the commands are submitted afterwards.
+pub fn record<F>(
+    context: &GpuContext,
+    size: (u32, u32),
+    frames: u32,
+    fps: f32,
+    mut sink: FrameSink,
+    mut render: F
+) -> Result<()>
+    where F: FnMut(&mut RenderCommands, &Texture, FrameTime)
+{
+    let (width, height) = size;
+    let target = Texture::create_empty(
+        context,
+        width,
+        height,
+        RECORDING_FORMAT,
+        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        Some("Recording Target")
+    )?;
+    let readback = TargetReadback::new(context, width, height)?;
+
+    let mut encoder = match &mut sink {
+        FrameSink::Ffmpeg(path) => Some(spawn(FrameSink::ffmpeg_command(path, size, fps))?),
+        FrameSink::Pipe(command) => Some(spawn(std::mem::replace(command, Command::new("")))?),
+        _ => None,
+    };
+    #[cfg(feature = "image")]
+    if let FrameSink::PngSequence(directory) = &sink {
+        std::fs::create_dir_all(directory).map_err(|e| io_error(directory.display(), e))?;
+    }
+
+    let delta = 1.0 / fps;
+    for index in 0..frames {
+        let time = FrameTime { index, time: (index as f32) * delta, delta };
+        let mut commands = RenderCommands::new(context, Some("Recording"));
+        render(&mut commands, &target, time);
+        readback.copy_from(commands.encoder(), &target);
+        commands.submit(context);
+        let pixels = readback.read(context)?;
+
+        if let Some(child) = &mut encoder {
+            let stdin = child.stdin.as_mut().expect("encoder stdin is piped");
+            stdin.write_all(&pixels).map_err(|e| io_error("encoder", e))?;
+            continue;
+        }
+        match &mut sink {
+            #[cfg(feature = "image")]
+            FrameSink::PngSequence(directory) => {
+                let path = directory.join(format!("frame_{:05}.png", index));
+                image::save_buffer(&path, &pixels, width, height, image::ExtendedColorType::Rgba8)?;
+            }
+            FrameSink::Callback(callback) => callback(index, &pixels)?,
+            _ => {}
+        }
+    }
+
+    if let Some(mut child) = encoder {
+        drop(child.stdin.take());
+        let status = child.wait().map_err(|e| io_error("encoder", e))?;
+        if !status.success() {
+            return Err(GeepuError::Other(format!("Frame encoder exited with {}", status)));
+        }
+    }
+    Ok(())
+}
+
+fn spawn(mut command: Command) -> Result<Child> {
+    command
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| io_error(format!("{:?}", command.get_program()), e))
+}
+
+fn io_error(what: impl std::fmt::Display, e: std::io::Error) -> GeepuError {
+    GeepuError::Other(format!("Failed to write frames to {}: {}", what, e))
+}
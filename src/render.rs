@@ -1,8 +1,30 @@
-use crate::{ GpuContext, RenderPipeline, TypedBuffer, Result };
+use crate::{
+    DrawIndexedIndirectArgs,
+    DrawIndirectArgs,
+    GeepuError,
+    GpuContext,
+    GpuProfiler,
+    IndexBuffer,
+    IndexType,
+    InstanceBuffer,
+    Mesh,
+    MeshRegistry,
+    PassState,
+    QuerySet,
+    RenderPipeline,
+    TypedBuffer,
+    Result,
+};
+use crate::command_dump::{ self, describe_buffer, quoted, CommandDump };
+use crate::profiling::profile_scope;
 
 /// A high-level render pass wrapper
 pub struct RenderPass<'a> {
     pass: wgpu::RenderPass<'a>,
+    features: wgpu::Features,
+    debug_group: bool,
+    validation: Option<PassState>,
+    dump: Option<CommandDump>,
 }
 
 impl<'a> RenderPass<'a> {
@@ -12,27 +34,99 @@ impl<'a> RenderPass<'a> {
         color_attachments: &'a [Option<wgpu::RenderPassColorAttachment<'a>>],
         depth_stencil_attachment: Option<wgpu::RenderPassDepthStencilAttachment<'a>>,
         label: Option<&str>
+    ) -> Self {
+        Self::begin(encoder, color_attachments, depth_stencil_attachment, None, None, label)
+    }
+
+    fn begin(
+        encoder: &'a mut wgpu::CommandEncoder,
+        color_attachments: &'a [Option<wgpu::RenderPassColorAttachment<'a>>],
+        depth_stencil_attachment: Option<wgpu::RenderPassDepthStencilAttachment<'a>>,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'a>>,
+        occlusion_query_set: Option<&'a wgpu::QuerySet>,
+        label: Option<&str>
     ) -> Self {
         let pass = encoder.begin_render_pass(
             &(wgpu::RenderPassDescriptor {
                 label,
                 color_attachments,
                 depth_stencil_attachment,
-                occlusion_query_set: None,
-                timestamp_writes: None,
+                occlusion_query_set,
+                timestamp_writes,
             })
         );
 
-        Self { pass }
+        Self {
+            pass,
+            features: wgpu::Features::empty(),
+            debug_group: false,
+            validation: None,
+            dump: None,
+        }
+    }
+
+    /// Wrap the rest of the pass in a debug group named `label`, popped on drop
+    fn with_debug_group(mut self, label: Option<&str>, enabled: bool) -> Self {
+        if let (Some(label), true) = (label, enabled) {
+            self.pass.push_debug_group(label);
+            self.debug_group = true;
+        }
+        self
+    }
+
+    /// Track bound state so the `try_` methods can check it before encoding
+    fn with_validation(mut self, label: Option<&str>, enabled: bool) -> Self {
+        if enabled {
+            self.validation = Some(PassState::new(label));
+        }
+        self
+    }
+
+    /// Record the pass into a frame dump, if one is in progress
+    fn with_dump(mut self, dump: Option<CommandDump>) -> Self {
+        self.dump = dump;
+        self
+    }
+
+    fn record(&self, line: impl FnOnce() -> String) {
+        if let Some(dump) = &self.dump {
+            dump.record(format!("    {}", line()));
+        }
+    }
+
+    /// Declare the device features available to this pass.
+    ///
+    /// Passes started with [`RenderCommands::begin_render_pass`] get the device's
+    /// features automatically; ones built with [`new`](Self::new) assume none.
+    pub fn with_features(mut self, features: wgpu::Features) -> Self {
+        self.features = features;
+        self
     }
 
     /// Set the render pipeline
     pub fn set_pipeline(&mut self, pipeline: &'a RenderPipeline) {
+        if let Some(state) = &mut self.validation {
+            state.set_pipeline(pipeline.bind_group_layouts.len(), pipeline.vertex_buffer_count);
+        }
+        self.record(|| {
+            format!(
+                "set_pipeline {:?} ({} bind group layouts, {} vertex buffers)",
+                pipeline.pipeline.global_id(),
+                pipeline.bind_group_layouts.len(),
+                pipeline.vertex_buffer_count
+            )
+        });
         self.pass.set_pipeline(&pipeline.pipeline);
     }
 
     /// Set a bind group
     pub fn set_bind_group(&mut self, index: u32, bind_group: &'a wgpu::BindGroup, offsets: &[u32]) {
+        if let Some(state) = &mut self.validation {
+            state.set_bind_group(index);
+        }
+        self.record(|| {
+            format!("set_bind_group {} {:?} offsets {:?}", index, bind_group.global_id(), offsets)
+        });
         self.pass.set_bind_group(index, bind_group, offsets);
     }
 
@@ -40,18 +134,111 @@ impl<'a> RenderPass<'a> {
     pub fn set_vertex_buffer<T>(&mut self, slot: u32, buffer: &'a TypedBuffer<T>)
         where T: bytemuck::Pod
     {
+        if let Some(state) = &mut self.validation {
+            state.set_vertex_buffer(slot);
+        }
+        self.record(|| format!("set_vertex_buffer {} {}", slot, describe_buffer(buffer)));
         self.pass.set_vertex_buffer(slot, buffer.buffer().slice(..));
     }
 
-    /// Set index buffer
-    pub fn set_index_buffer<T>(&mut self, buffer: &'a TypedBuffer<T>, format: wgpu::IndexFormat)
+    /// Set a vertex buffer to a range of elements of a typed buffer
+    pub fn set_vertex_buffer_range<T>(
+        &mut self,
+        slot: u32,
+        buffer: &'a TypedBuffer<T>,
+        range: std::ops::Range<usize>
+    )
         where T: bytemuck::Pod
     {
-        self.pass.set_index_buffer(buffer.buffer().slice(..), format);
+        let size = std::mem::size_of::<T>() as u64;
+        let slice = buffer.buffer().slice((range.start as u64) * size..(range.end as u64) * size);
+        if let Some(state) = &mut self.validation {
+            state.set_vertex_buffer(slot);
+        }
+        self.record(|| {
+            format!("set_vertex_buffer {} {} elements {:?}", slot, describe_buffer(buffer), range)
+        });
+        self.pass.set_vertex_buffer(slot, slice);
+    }
+
+    /// Set index buffer, in the format matching its element type
+    pub fn set_index_buffer<T>(&mut self, buffer: &'a TypedBuffer<T>) where T: IndexType {
+        self.set_index_slice(buffer.buffer(), buffer.len() as u64, T::FORMAT, || {
+            describe_buffer(buffer)
+        });
+    }
+
+    /// Set a mesh index buffer of either format
+    pub fn set_mesh_index_buffer(&mut self, indices: &'a IndexBuffer) {
+        let describe = || match indices {
+            IndexBuffer::Uint16(buffer) => describe_buffer(buffer),
+            IndexBuffer::Uint32(buffer) => describe_buffer(buffer),
+        };
+        self.set_index_slice(indices.buffer(), indices.len() as u64, indices.format(), describe);
+    }
+
+    fn set_index_slice(
+        &mut self,
+        buffer: &'a wgpu::Buffer,
+        count: u64,
+        format: wgpu::IndexFormat,
+        describe: impl FnOnce() -> String
+    ) {
+        if let Some(state) = &mut self.validation {
+            state.set_index_buffer(count);
+        }
+        self.record(|| format!("set_index_buffer {} {:?}", describe(), format));
+        self.pass.set_index_buffer(buffer.slice(..), format);
+    }
+
+    /// Restrict drawing to a rectangle of the target, in pixels
+    pub fn set_scissor_rect(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        self.record(|| format!("set_scissor_rect {} {} {}x{}", x, y, width, height));
+        self.pass.set_scissor_rect(x, y, width, height);
+    }
+
+    /// Start counting samples that pass the depth test into query `index`.
+    ///
+    /// The pass must have been begun with
+    /// [`RenderCommands::begin_occlusion_pass`].
+    pub fn begin_occlusion_query(&mut self, index: u32) {
+        self.record(|| format!("begin_occlusion_query {}", index));
+        self.pass.begin_occlusion_query(index);
+    }
+
+    /// End the occlusion query started by [`begin_occlusion_query`](Self::begin_occlusion_query)
+    pub fn end_occlusion_query(&mut self) {
+        self.record(|| "end_occlusion_query".to_string());
+        self.pass.end_occlusion_query();
+    }
+
+    /// Write a GPU timestamp into slot `index` of a timestamp [`QuerySet`].
+    /// Needs `TIMESTAMP_QUERY_INSIDE_PASSES`.
+    pub fn write_timestamp(&mut self, queries: &QuerySet, index: u32) -> Result<()> {
+        self.require(wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES)?;
+        self.record(|| format!("write_timestamp {:?} {}", queries.raw().global_id(), index));
+        self.pass.write_timestamp(queries.raw(), index);
+        Ok(())
+    }
+
+    /// Start counting pipeline statistics into slot `index` of `queries`
+    pub fn begin_pipeline_statistics_query(&mut self, queries: &'a QuerySet, index: u32) {
+        self.record(|| {
+            format!("begin_pipeline_statistics_query {:?} {}", queries.raw().global_id(), index)
+        });
+        self.pass.begin_pipeline_statistics_query(queries.raw(), index);
+    }
+
+    /// End the query started by
+    /// [`begin_pipeline_statistics_query`](Self::begin_pipeline_statistics_query)
+    pub fn end_pipeline_statistics_query(&mut self) {
+        self.record(|| "end_pipeline_statistics_query".to_string());
+        self.pass.end_pipeline_statistics_query();
     }
 
     /// Draw primitives
     pub fn draw(&mut self, vertices: std::ops::Range<u32>, instances: std::ops::Range<u32>) {
+        self.record(|| format!("draw vertices {:?} instances {:?}", vertices, instances));
         self.pass.draw(vertices, instances);
     }
 
@@ -62,13 +249,255 @@ impl<'a> RenderPass<'a> {
         base_vertex: i32,
         instances: std::ops::Range<u32>
     ) {
+        self.record(|| {
+            format!(
+                "draw_indexed indices {:?} base_vertex {} instances {:?}",
+                indices,
+                base_vertex,
+                instances
+            )
+        });
         self.pass.draw_indexed(indices, base_vertex, instances);
     }
+
+    /// Like [`draw`](Self::draw), but first checks that a pipeline, its bind
+    /// groups and its vertex buffers are bound when
+    /// [`GpuConfig::validate_commands`](crate::GpuConfig::validate_commands) is on
+    pub fn try_draw(
+        &mut self,
+        vertices: std::ops::Range<u32>,
+        instances: std::ops::Range<u32>
+    ) -> Result<()> {
+        if let Some(state) = &self.validation {
+            state.check_draw()?;
+        }
+        self.draw(vertices, instances);
+        Ok(())
+    }
+
+    /// Like [`draw_indexed`](Self::draw_indexed), additionally checking that
+    /// `indices` lies within the bound index buffer
+    pub fn try_draw_indexed(
+        &mut self,
+        indices: std::ops::Range<u32>,
+        base_vertex: i32,
+        instances: std::ops::Range<u32>
+    ) -> Result<()> {
+        if let Some(state) = &self.validation {
+            state.check_draw_indexed(&indices)?;
+        }
+        self.draw_indexed(indices, base_vertex, instances);
+        Ok(())
+    }
+
+    /// Draw primitives with the arguments at `index` in an indirect buffer
+    pub fn draw_indirect(&mut self, args: &'a TypedBuffer<DrawIndirectArgs>, index: usize) {
+        self.record(|| format!("draw_indirect {} index {}", describe_buffer(args), index));
+        self.pass.draw_indirect(args.buffer(), Self::args_offset::<DrawIndirectArgs>(index));
+    }
+
+    /// Draw indexed primitives with the arguments at `index` in an indirect buffer
+    pub fn draw_indexed_indirect(
+        &mut self,
+        args: &'a TypedBuffer<DrawIndexedIndirectArgs>,
+        index: usize
+    ) {
+        let offset = Self::args_offset::<DrawIndexedIndirectArgs>(index);
+        self.record(|| format!("draw_indexed_indirect {} index {}", describe_buffer(args), index));
+        self.pass.draw_indexed_indirect(args.buffer(), offset);
+    }
+
+    /// Issue `count` indirect draws starting at `first`, in a single call when
+    /// `Features::MULTI_DRAW_INDIRECT` is available and one call per draw otherwise
+    pub fn multi_draw_indirect(
+        &mut self,
+        args: &'a TypedBuffer<DrawIndirectArgs>,
+        first: usize,
+        count: u32
+    ) {
+        if self.features.contains(wgpu::Features::MULTI_DRAW_INDIRECT) {
+            let offset = Self::args_offset::<DrawIndirectArgs>(first);
+            self.record(|| {
+                let args = describe_buffer(args);
+                format!("multi_draw_indirect {} first {} count {}", args, first, count)
+            });
+            self.pass.multi_draw_indirect(args.buffer(), offset, count);
+        } else {
+            for index in first..first + (count as usize) {
+                self.draw_indirect(args, index);
+            }
+        }
+    }
+
+    /// Indexed version of [`multi_draw_indirect`](Self::multi_draw_indirect)
+    pub fn multi_draw_indexed_indirect(
+        &mut self,
+        args: &'a TypedBuffer<DrawIndexedIndirectArgs>,
+        first: usize,
+        count: u32
+    ) {
+        if self.features.contains(wgpu::Features::MULTI_DRAW_INDIRECT) {
+            let offset = Self::args_offset::<DrawIndexedIndirectArgs>(first);
+            self.record(|| {
+                let args = describe_buffer(args);
+                format!("multi_draw_indexed_indirect {} first {} count {}", args, first, count)
+            });
+            self.pass.multi_draw_indexed_indirect(args.buffer(), offset, count);
+        } else {
+            for index in first..first + (count as usize) {
+                self.draw_indexed_indirect(args, index);
+            }
+        }
+    }
+
+    /// Issue up to `max_count` indirect draws starting at `first`, with the actual
+    /// count read on the GPU from `count_buffer[count_index]`.
+    ///
+    /// Requires `Features::MULTI_DRAW_INDIRECT_COUNT`.
+    pub fn multi_draw_indirect_count(
+        &mut self,
+        args: &'a TypedBuffer<DrawIndirectArgs>,
+        first: usize,
+        count_buffer: &'a TypedBuffer<u32>,
+        count_index: usize,
+        max_count: u32
+    ) -> Result<()> {
+        self.require(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT)?;
+        self.record(|| {
+            format!(
+                "multi_draw_indirect_count {} first {} count from {} index {} max {}",
+                describe_buffer(args),
+                first,
+                describe_buffer(count_buffer),
+                count_index,
+                max_count
+            )
+        });
+        self.pass.multi_draw_indirect_count(
+            args.buffer(),
+            Self::args_offset::<DrawIndirectArgs>(first),
+            count_buffer.buffer(),
+            Self::args_offset::<u32>(count_index),
+            max_count
+        );
+        Ok(())
+    }
+
+    /// Indexed version of [`multi_draw_indirect_count`](Self::multi_draw_indirect_count)
+    pub fn multi_draw_indexed_indirect_count(
+        &mut self,
+        args: &'a TypedBuffer<DrawIndexedIndirectArgs>,
+        first: usize,
+        count_buffer: &'a TypedBuffer<u32>,
+        count_index: usize,
+        max_count: u32
+    ) -> Result<()> {
+        self.require(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT)?;
+        self.record(|| {
+            format!(
+                "multi_draw_indexed_indirect_count {} first {} count from {} index {} max {}",
+                describe_buffer(args),
+                first,
+                describe_buffer(count_buffer),
+                count_index,
+                max_count
+            )
+        });
+        self.pass.multi_draw_indexed_indirect_count(
+            args.buffer(),
+            Self::args_offset::<DrawIndexedIndirectArgs>(first),
+            count_buffer.buffer(),
+            Self::args_offset::<u32>(count_index),
+            max_count
+        );
+        Ok(())
+    }
+
+    /// Bind a mesh's buffers and draw all of its submeshes
+    pub fn draw_mesh<V>(&mut self, mesh: &'a Mesh<V>) where V: bytemuck::Pod {
+        self.bind_mesh(mesh);
+        for submesh in &mesh.submeshes {
+            self.draw_submesh_range(mesh, submesh.range.clone(), submesh.base_vertex, 0..1);
+        }
+    }
+
+    /// Draw every submesh of a mesh once per instance, with the instance data in slot 1
+    pub fn draw_mesh_instanced<V, T>(&mut self, mesh: &'a Mesh<V>, instances: &'a InstanceBuffer<T>)
+        where V: bytemuck::Pod, T: bytemuck::Pod
+    {
+        if instances.is_empty() {
+            return;
+        }
+        self.bind_mesh(mesh);
+        self.set_vertex_buffer(1, instances.buffer());
+        let count = instances.len() as u32;
+        for submesh in &mesh.submeshes {
+            self.draw_submesh_range(mesh, submesh.range.clone(), submesh.base_vertex, 0..count);
+        }
+    }
+
+    /// Bind a mesh's buffers and draw one of its submeshes
+    pub fn draw_submesh<V>(&mut self, mesh: &'a Mesh<V>, index: usize) where V: bytemuck::Pod {
+        self.bind_mesh(mesh);
+        let submesh = &mesh.submeshes[index];
+        self.draw_submesh_range(mesh, submesh.range.clone(), submesh.base_vertex, 0..1);
+    }
+
+    fn require(&self, features: wgpu::Features) -> Result<()> {
+        if self.features.contains(features) {
+            Ok(())
+        } else {
+            Err(GeepuError::MissingFeatures(features - self.features))
+        }
+    }
+
+    fn args_offset<T>(index: usize) -> u64 {
+        (index * std::mem::size_of::<T>()) as u64
+    }
+
+    pub(crate) fn bind_mesh<V>(&mut self, mesh: &'a Mesh<V>) where V: bytemuck::Pod {
+        self.set_vertex_buffer(0, &mesh.vertices);
+        if let Some(indices) = &mesh.indices {
+            self.set_mesh_index_buffer(indices);
+        }
+    }
+
+    fn draw_submesh_range<V>(
+        &mut self,
+        mesh: &Mesh<V>,
+        range: std::ops::Range<u32>,
+        base_vertex: i32,
+        instances: std::ops::Range<u32>
+    )
+        where V: bytemuck::Pod
+    {
+        if mesh.is_indexed() {
+            self.draw_indexed(range, base_vertex, instances);
+        } else {
+            self.draw(range, instances);
+        }
+    }
+}
+
+impl Drop for RenderPass<'_> {
+    fn drop(&mut self) {
+        if self.debug_group {
+            self.pass.pop_debug_group();
+        }
+        if let Some(dump) = &self.dump {
+            dump.record("  end render pass".to_string());
+        }
+    }
 }
 
 /// A high-level render command builder
 pub struct RenderCommands {
     encoder: wgpu::CommandEncoder,
+    features: wgpu::Features,
+    label_prefix: Option<String>,
+    debug_groups: bool,
+    validate_commands: bool,
+    dump: Option<CommandDump>,
 }
 
 impl RenderCommands {
@@ -76,21 +505,136 @@ impl RenderCommands {
     pub fn new(context: &GpuContext, label: Option<&str>) -> Self {
         let encoder = context.device.create_command_encoder(
             &(wgpu::CommandEncoderDescriptor {
-                label,
+                label: context.prefixed_label(label).as_deref(),
             })
         );
+        let dump = context.command_recorder();
+        if let Some(dump) = &dump {
+            dump.record(format!("render commands {}", quoted(label)));
+        }
 
-        Self { encoder }
+        Self {
+            encoder,
+            features: context.device.features(),
+            label_prefix: context.config.label_prefix.clone(),
+            debug_groups: context.config.debug_groups,
+            validate_commands: context.config.validate_commands,
+            dump,
+        }
+    }
+
+    fn pass_label(&self, label: Option<&str>) -> Option<String> {
+        label.map(|label| format!("{}{}", self.label_prefix.as_deref().unwrap_or(""), label))
+    }
+
+    fn record(&self, line: impl FnOnce() -> String) {
+        if let Some(dump) = &self.dump {
+            dump.record(format!("  {}", line()));
+        }
+    }
+
+    /// Dump the start of a pass and hand the dump on to it
+    fn record_pass(
+        &self,
+        label: Option<&str>,
+        color_attachments: &[Option<wgpu::RenderPassColorAttachment>],
+        depth_stencil_attachment: &Option<wgpu::RenderPassDepthStencilAttachment>
+    ) -> Option<CommandDump> {
+        self.record(|| {
+            let targets: Vec<String> = color_attachments
+                .iter()
+                .map(|attachment| {
+                    attachment.as_ref().map_or("none".to_string(), |attachment| {
+                        format!("{:?} {:?}", attachment.view.global_id(), attachment.ops.load)
+                    })
+                })
+                .collect();
+            let depth = depth_stencil_attachment.as_ref().map_or("none".to_string(), |depth| {
+                format!("{:?}", depth.view.global_id())
+            });
+            format!(
+                "begin render pass {} color [{}] depth {}",
+                quoted(label),
+                targets.join(", "),
+                depth
+            )
+        });
+        self.dump.clone()
     }
 
-    /// Begin a render pass
+    /// Begin a render pass.
+    ///
+    /// A labeled pass is wrapped in a debug group of the same name unless
+    /// [`GpuConfig::debug_groups`](crate::GpuConfig::debug_groups) turned them off.
     pub fn begin_render_pass<'a>(
         &'a mut self,
         color_attachments: &'a [Option<wgpu::RenderPassColorAttachment<'a>>],
         depth_stencil_attachment: Option<wgpu::RenderPassDepthStencilAttachment<'a>>,
         label: Option<&str>
     ) -> RenderPass<'a> {
-        RenderPass::new(&mut self.encoder, color_attachments, depth_stencil_attachment, label)
+        let label = self.pass_label(label);
+        let dump = self.record_pass(label.as_deref(), color_attachments, &depth_stencil_attachment);
+        RenderPass::new(
+            &mut self.encoder,
+            color_attachments,
+            depth_stencil_attachment,
+            label.as_deref()
+        )
+            .with_features(self.features)
+            .with_debug_group(label.as_deref(), self.debug_groups)
+            .with_validation(label.as_deref(), self.validate_commands)
+            .with_dump(dump)
+    }
+
+    /// Begin a render pass timed by `profiler` under `label`
+    pub fn begin_profiled_render_pass<'a>(
+        &'a mut self,
+        profiler: &'a mut GpuProfiler,
+        color_attachments: &'a [Option<wgpu::RenderPassColorAttachment<'a>>],
+        depth_stencil_attachment: Option<wgpu::RenderPassDepthStencilAttachment<'a>>,
+        label: &str
+    ) -> RenderPass<'a> {
+        let scope = profiler.begin_scope(label);
+        let profiler: &'a GpuProfiler = profiler;
+        let label = self.pass_label(Some(label));
+        let dump = self.record_pass(label.as_deref(), color_attachments, &depth_stencil_attachment);
+        RenderPass::begin(
+            &mut self.encoder,
+            color_attachments,
+            depth_stencil_attachment,
+            profiler.render_timestamp_writes(scope),
+            None,
+            label.as_deref()
+        )
+            .with_features(self.features)
+            .with_debug_group(label.as_deref(), self.debug_groups)
+            .with_validation(label.as_deref(), self.validate_commands)
+            .with_dump(dump)
+    }
+
+    /// Begin a render pass that can record into `queries`, an
+    /// [`OcclusionQueries`](crate::OcclusionQueries) or an occlusion [`QuerySet`]
+    pub fn begin_occlusion_pass<'a>(
+        &'a mut self,
+        queries: &'a impl AsRef<QuerySet>,
+        color_attachments: &'a [Option<wgpu::RenderPassColorAttachment<'a>>],
+        depth_stencil_attachment: Option<wgpu::RenderPassDepthStencilAttachment<'a>>,
+        label: Option<&str>
+    ) -> RenderPass<'a> {
+        let label = self.pass_label(label);
+        let dump = self.record_pass(label.as_deref(), color_attachments, &depth_stencil_attachment);
+        RenderPass::begin(
+            &mut self.encoder,
+            color_attachments,
+            depth_stencil_attachment,
+            None,
+            Some(queries.as_ref().raw()),
+            label.as_deref()
+        )
+            .with_features(self.features)
+            .with_debug_group(label.as_deref(), self.debug_groups)
+            .with_validation(label.as_deref(), self.validate_commands)
+            .with_dump(dump)
     }
 
     /// Copy buffer to buffer
@@ -102,6 +646,15 @@ impl RenderCommands {
         destination_offset: u64,
         copy_size: u64
     ) {
+        self.record(|| {
+            command_dump::buffer_to_buffer(
+                source,
+                source_offset,
+                destination,
+                destination_offset,
+                copy_size
+            )
+        });
         self.encoder.copy_buffer_to_buffer(
             source,
             source_offset,
@@ -118,6 +671,7 @@ impl RenderCommands {
         destination: wgpu::ImageCopyTexture,
         copy_size: wgpu::Extent3d
     ) {
+        self.record(|| command_dump::buffer_to_texture(&source, &destination, copy_size));
         self.encoder.copy_buffer_to_texture(source, destination, copy_size);
     }
 
@@ -128,12 +682,45 @@ impl RenderCommands {
         destination: wgpu::ImageCopyBuffer,
         copy_size: wgpu::Extent3d
     ) {
+        self.record(|| command_dump::texture_to_buffer(&source, &destination, copy_size));
         self.encoder.copy_texture_to_buffer(source, destination, copy_size);
     }
 
+    /// Record resolving the used slots of `queries` for [`QuerySet::read`]
+    pub fn resolve_queries(&mut self, queries: &QuerySet) {
+        self.record(|| {
+            format!("resolve_queries {:?} 0..{}", queries.raw().global_id(), queries.used())
+        });
+        queries.resolve(&mut self.encoder);
+    }
+
     /// Finish and submit commands
     pub fn submit(self, context: &GpuContext) {
-        context.queue.submit(std::iter::once(self.encoder.finish()));
+        profile_scope!("geepu::submit");
+        self.record(|| "submit".to_string());
+        context.submit(std::iter::once(self.encoder.finish()));
+    }
+
+    /// Finish recording without submitting, to submit together with other
+    /// commands through a [`CommandBatch`](crate::CommandBatch)
+    pub fn finish(self) -> wgpu::CommandBuffer {
+        self.record(|| "finish".to_string());
+        self.encoder.finish()
+    }
+
+    /// Finish and submit commands, calling `callback` once the GPU has
+    /// finished executing them.
+    ///
+    /// The callback runs from a later submit or device poll, natively, and
+    /// from the browser's event loop on the web; keep it short, e.g. to flag a
+    /// buffer as free for reuse or a readback as ready.
+    pub fn submit_with_callback(
+        self,
+        context: &GpuContext,
+        callback: impl FnOnce() + Send + 'static
+    ) {
+        self.submit(context);
+        context.queue.on_submitted_work_done(callback);
     }
 
     /// Get the underlying encoder (for advanced usage)
@@ -142,6 +729,138 @@ impl RenderCommands {
     }
 }
 
+/// A render pass straight into the context's surface for the current frame.
+///
+/// The guard acquires the surface texture, creates its view and opens a pass
+/// clearing it (or loading it without a clear color). Record with the forwarding
+/// methods, or [`render_pass`](Self::render_pass) for anything else; dropping the
+/// guard (or [`finish`](Self::finish)) ends the pass, submits it and presents.
+/// Resources only need to be alive while they are being recorded.
+pub struct RenderPassGuard<'c> {
+    context: &'c GpuContext,
+    pass: Option<wgpu::RenderPass<'static>>,
+    encoder: Option<wgpu::CommandEncoder>,
+    view: wgpu::TextureView,
+    output: Option<wgpu::SurfaceTexture>,
+}
+
+impl<'c> RenderPassGuard<'c> {
+    /// Acquire the surface texture and begin a pass into it, with the
+    /// context's depth buffer attached when it has one (see
+    /// [`GpuContext::enable_depth`]). With MSAA the pass draws into the
+    /// context's multisampled target, resolved into the surface texture.
+    pub fn new(context: &'c GpuContext, clear_color: Option<wgpu::Color>) -> Result<Self> {
+        let output = context.get_current_texture()?;
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let label = context.prefixed_label(Some("Surface Pass"));
+        let mut encoder = context.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: label.as_deref(),
+            })
+        );
+        let pass = encoder
+            .begin_render_pass(
+                &(wgpu::RenderPassDescriptor {
+                    label: label.as_deref(),
+                    color_attachments: &[Some(context.surface_attachment(&view, clear_color))],
+                    depth_stencil_attachment: context.depth_attachment(),
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                })
+            )
+            .forget_lifetime();
+
+        Ok(Self {
+            context,
+            pass: Some(pass),
+            encoder: Some(encoder),
+            view,
+            output: Some(output),
+        })
+    }
+
+    /// View of the surface texture being rendered to
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// The underlying wgpu pass, for commands the guard doesn't forward
+    pub fn render_pass(&mut self) -> &mut wgpu::RenderPass<'static> {
+        self.pass.as_mut().expect("surface pass is open until the guard is dropped")
+    }
+
+    /// Set the render pipeline
+    pub fn set_pipeline(&mut self, pipeline: &RenderPipeline) {
+        self.render_pass().set_pipeline(&pipeline.pipeline);
+    }
+
+    /// Set a bind group
+    pub fn set_bind_group(&mut self, index: u32, bind_group: &wgpu::BindGroup, offsets: &[u32]) {
+        self.render_pass().set_bind_group(index, bind_group, offsets);
+    }
+
+    /// Set vertex buffer
+    pub fn set_vertex_buffer<T>(&mut self, slot: u32, buffer: &TypedBuffer<T>)
+        where T: bytemuck::Pod
+    {
+        self.render_pass().set_vertex_buffer(slot, buffer.buffer().slice(..));
+    }
+
+    /// Set index buffer, in the format matching its element type
+    pub fn set_index_buffer<T>(&mut self, buffer: &TypedBuffer<T>) where T: IndexType {
+        self.render_pass().set_index_buffer(buffer.buffer().slice(..), T::FORMAT);
+    }
+
+    /// Set a mesh index buffer of either format
+    pub fn set_mesh_index_buffer(&mut self, indices: &IndexBuffer) {
+        self.render_pass().set_index_buffer(indices.buffer().slice(..), indices.format());
+    }
+
+    /// Draw primitives
+    pub fn draw(&mut self, vertices: std::ops::Range<u32>, instances: std::ops::Range<u32>) {
+        self.render_pass().draw(vertices, instances);
+    }
+
+    /// Draw indexed primitives
+    pub fn draw_indexed(
+        &mut self,
+        indices: std::ops::Range<u32>,
+        base_vertex: i32,
+        instances: std::ops::Range<u32>
+    ) {
+        self.render_pass().draw_indexed(indices, base_vertex, instances);
+    }
+
+    /// Bind and draw the mesh registered in `meshes` as `name`
+    pub fn draw_mesh(&mut self, meshes: &MeshRegistry, name: &str) -> Result<()> {
+        meshes.draw(self.render_pass(), name)
+    }
+
+    /// End the pass, submit it and present the surface texture
+    pub fn finish(self) {}
+
+    fn end(&mut self) {
+        // The pass has to end before its encoder can finish
+        drop(self.pass.take());
+        if let Some(encoder) = self.encoder.take() {
+            self.context.submit(std::iter::once(encoder.finish()));
+        }
+        if let Some(output) = self.output.take() {
+            #[cfg(feature = "window")]
+            if let Some(window) = &self.context.window {
+                window.pre_present_notify();
+            }
+            output.present();
+        }
+    }
+}
+
+impl Drop for RenderPassGuard<'_> {
+    fn drop(&mut self) {
+        self.end();
+    }
+}
+
 /// Simple render target helper
 pub struct RenderTarget {
     pub texture: crate::Texture,
@@ -183,7 +902,7 @@ impl RenderTarget {
     pub fn color_attachment(
         &self,
         clear_color: Option<wgpu::Color>
-    ) -> wgpu::RenderPassColorAttachment {
+    ) -> wgpu::RenderPassColorAttachment<'_> {
         wgpu::RenderPassColorAttachment {
             view: &self.texture.view,
             resolve_target: None,
@@ -202,7 +921,7 @@ impl RenderTarget {
     pub fn depth_stencil_attachment(
         &self,
         clear_depth: Option<f32>
-    ) -> Option<wgpu::RenderPassDepthStencilAttachment> {
+    ) -> Option<wgpu::RenderPassDepthStencilAttachment<'_>> {
         self.depth_texture.as_ref().map(|depth| wgpu::RenderPassDepthStencilAttachment {
             view: &depth.view,
             depth_ops: Some(wgpu::Operations {
@@ -227,7 +946,7 @@ impl RenderTarget {
 pub fn color_attachment(
     view: &wgpu::TextureView,
     clear_color: Option<wgpu::Color>
-) -> wgpu::RenderPassColorAttachment {
+) -> wgpu::RenderPassColorAttachment<'_> {
     wgpu::RenderPassColorAttachment {
         view,
         resolve_target: None,
@@ -247,7 +966,7 @@ pub fn depth_stencil_attachment(
     view: &wgpu::TextureView,
     clear_depth: Option<f32>,
     clear_stencil: Option<u32>
-) -> wgpu::RenderPassDepthStencilAttachment {
+) -> wgpu::RenderPassDepthStencilAttachment<'_> {
     wgpu::RenderPassDepthStencilAttachment {
         view,
         depth_ops: clear_depth.map(|depth| wgpu::Operations {
@@ -260,3 +979,9 @@ pub fn depth_stencil_attachment(
         }),
     }
 }
+
+impl From<RenderCommands> for wgpu::CommandBuffer {
+    fn from(commands: RenderCommands) -> Self {
+        commands.finish()
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{ Arc, Mutex, Weak };
+
+/// Kind of a geepu-owned GPU resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Buffer,
+    StagingBuffer,
+    Texture,
+}
+
+/// A tracked resource that has not been dropped yet
+#[derive(Debug, Clone)]
+pub struct LiveResource {
+    pub kind: ResourceKind,
+    pub label: String,
+    pub size_bytes: u64,
+    /// Where the resource was created; captured in debug builds when `RUST_BACKTRACE` is set
+    pub backtrace: Option<String>,
+}
+
+/// Resources still alive when the report was taken, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct LeakReport {
+    pub resources: Vec<LiveResource>,
+}
+
+impl LeakReport {
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    /// Combined size of the live resources
+    pub fn total_bytes(&self) -> u64 {
+        self.resources
+            .iter()
+            .map(|resource| resource.size_bytes)
+            .sum()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} live resources ({} bytes)", self.resources.len(), self.total_bytes())?;
+        for resource in &self.resources {
+            write!(
+                f,
+                "\n  {:?} \"{}\" ({} bytes)",
+                resource.kind,
+                resource.label,
+                resource.size_bytes
+            )?;
+            if let Some(backtrace) = &resource.backtrace {
+                for line in backtrace.lines() {
+                    write!(f, "\n      {}", line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// GPU memory in use, from wgpu's allocation counters and geepu's own tracking
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GpuMemoryUsage {
+    /// Memory the backend allocated for buffers; `None` without the `counters`
+    /// feature or when the backend does not report allocations
+    pub buffer_bytes: Option<u64>,
+    /// Memory the backend allocated for textures
+    pub texture_bytes: Option<u64>,
+    /// Number of backend memory allocations
+    pub allocations: Option<u64>,
+    /// Estimated size of the live buffers geepu tracks, staging buffers included
+    pub tracked_buffer_bytes: u64,
+    /// Estimated size of the live textures geepu tracks
+    pub tracked_texture_bytes: u64,
+}
+
+impl GpuMemoryUsage {
+    pub(crate) fn new(counters: &wgpu::HalCounters, tracker: &ResourceTracker) -> Self {
+        let read = |value: isize| value.max(0) as u64;
+        let allocations = read(counters.memory_allocations.read());
+        let buffers = read(counters.buffer_memory.read());
+        let textures = read(counters.texture_memory.read());
+        let reported = allocations > 0 || buffers > 0 || textures > 0;
+        let tracked_buffers = tracker.live_bytes(ResourceKind::Buffer);
+        let tracked_staging = tracker.live_bytes(ResourceKind::StagingBuffer);
+        Self {
+            buffer_bytes: reported.then_some(buffers),
+            texture_bytes: reported.then_some(textures),
+            allocations: reported.then_some(allocations),
+            tracked_buffer_bytes: tracked_buffers + tracked_staging,
+            tracked_texture_bytes: tracker.live_bytes(ResourceKind::Texture),
+        }
+    }
+
+    /// Whether the backend's counters are available
+    pub fn is_reported(&self) -> bool {
+        self.allocations.is_some()
+    }
+
+    /// Total memory as reported by the backend, or geepu's estimate when it reports nothing
+    pub fn total_bytes(&self) -> u64 {
+        match (self.buffer_bytes, self.texture_bytes) {
+            (Some(buffers), Some(textures)) => buffers + textures,
+            _ => self.tracked_buffer_bytes + self.tracked_texture_bytes,
+        }
+    }
+}
+
+impl fmt::Display for GpuMemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let (Some(buffers), Some(textures)) = (self.buffer_bytes, self.texture_bytes) {
+            write!(
+                f,
+                "buffers {} bytes, textures {} bytes in {} allocations; ",
+                buffers,
+                textures,
+                self.allocations.unwrap_or(0)
+            )?;
+        }
+        write!(
+            f,
+            "tracked buffers {} bytes, tracked textures {} bytes",
+            self.tracked_buffer_bytes,
+            self.tracked_texture_bytes
+        )
+    }
+}
+
+struct Entry {
+    kind: ResourceKind,
+    label: String,
+    size_bytes: u64,
+    #[cfg(debug_assertions)]
+    backtrace: Option<std::backtrace::Backtrace>,
+}
+
+#[derive(Default)]
+struct TrackerState {
+    next_id: u64,
+    live: BTreeMap<u64, Entry>,
+}
+
+/// Registry of the resources a [`GpuContext`](crate::GpuContext) created;
+/// inert when tracking is disabled
+#[derive(Clone, Default)]
+pub(crate) struct ResourceTracker {
+    state: Option<Arc<Mutex<TrackerState>>>,
+}
+
+impl ResourceTracker {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self { state: enabled.then(Default::default) }
+    }
+
+    /// Register a resource; it stays in the report until the handle is dropped
+    pub(crate) fn track(
+        &self,
+        kind: ResourceKind,
+        label: &str,
+        size_bytes: u64
+    ) -> Option<TrackedResource> {
+        let state = self.state.as_ref()?;
+        let mut guard = state.lock().unwrap();
+        let id = guard.next_id;
+        guard.next_id += 1;
+        guard.live.insert(id, Entry {
+            kind,
+            label: label.to_string(),
+            size_bytes,
+            #[cfg(debug_assertions)]
+            backtrace: Some(std::backtrace::Backtrace::capture()).filter(|backtrace| {
+                backtrace.status() == std::backtrace::BacktraceStatus::Captured
+            }),
+        });
+        Some(TrackedResource { state: Arc::downgrade(state), id })
+    }
+
+    /// Combined size of the live resources of one kind
+    pub(crate) fn live_bytes(&self, kind: ResourceKind) -> u64 {
+        let Some(state) = &self.state else {
+            return 0;
+        };
+        state
+            .lock()
+            .unwrap()
+            .live.values()
+            .filter(|entry| entry.kind == kind)
+            .map(|entry| entry.size_bytes)
+            .sum()
+    }
+
+    pub(crate) fn report(&self) -> LeakReport {
+        let Some(state) = &self.state else {
+            return LeakReport::default();
+        };
+        let resources = state
+            .lock()
+            .unwrap()
+            .live.values()
+            .map(|entry| LiveResource {
+                kind: entry.kind,
+                label: entry.label.clone(),
+                size_bytes: entry.size_bytes,
+                #[cfg(debug_assertions)]
+                backtrace: entry.backtrace.as_ref().map(|backtrace| backtrace.to_string()),
+                #[cfg(not(debug_assertions))]
+                backtrace: None,
+            })
+            .collect();
+        LeakReport { resources }
+    }
+}
+
+/// Removes its resource from the tracker when dropped
+pub(crate) struct TrackedResource {
+    state: Weak<Mutex<TrackerState>>,
+    id: u64,
+}
+
+impl Drop for TrackedResource {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.upgrade() {
+            state.lock().unwrap().live.remove(&self.id);
+        }
+    }
+}
+
+/// Approximate memory of a texture with all its layers and mip levels
+pub(crate) fn texture_size_bytes(texture: &wgpu::Texture) -> u64 {
+    let format = texture.format();
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(4) as u64;
+    let size = texture.size();
+    (0..texture.mip_level_count())
+        .map(|level| {
+            let mip = size.mip_level_size(level, texture.dimension());
+            let blocks_x = mip.width.div_ceil(block_width) as u64;
+            let blocks_y = mip.height.div_ceil(block_height) as u64;
+            blocks_x * blocks_y * (mip.depth_or_array_layers as u64) * block_size
+        })
+        .sum::<u64>() * (texture.sample_count() as u64)
+}
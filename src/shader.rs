@@ -0,0 +1,861 @@
+use crate::{ GeepuError, Result };
+use std::fmt;
+
+/// Line/column range in shader source; 1-based, end column exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceSpan {
+    pub start_line: u32,
+    pub start_column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+}
+
+impl SourceSpan {
+    /// Span of the bytes `offset..offset + length` of `source`
+    pub fn from_offsets(source: &str, offset: usize, length: usize) -> Self {
+        let position = |offset: usize| {
+            let prefix = &source[..offset.min(source.len())];
+            let line = prefix.matches('\n').count() as u32 + 1;
+            let line_start = prefix.rfind('\n').map_or(0, |index| index + 1);
+            (line, (prefix.len() - line_start) as u32 + 1)
+        };
+        let (start_line, start_column) = position(offset);
+        let (end_line, end_column) = position(offset + length);
+        Self { start_line, start_column, end_line, end_column }
+    }
+}
+
+/// A shader compilation error pointing at the offending source.
+///
+/// Displays like a rustc diagnostic:
+///
+/// ```text
+/// error: expected expression, found ';'
+///  --> Triangle (fragment):3:17
+///   |
+/// 3 |     let x = 1 + ;
+///   |                 ^ expected expression
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderDiagnostic {
+    /// Name of the shader, usually its pipeline label
+    pub shader: String,
+    pub stage: Option<wgpu::ShaderStages>,
+    pub message: String,
+    pub span: Option<SourceSpan>,
+    /// What the span refers to, e.g. `"expected expression"`
+    pub span_label: String,
+    /// The source lines covered by `span`
+    pub excerpt: Vec<String>,
+    /// naga's chain of underlying errors and secondary labels, outermost first
+    pub notes: Vec<String>,
+}
+
+impl ShaderDiagnostic {
+    pub fn new(
+        shader: impl Into<String>,
+        stage: Option<wgpu::ShaderStages>,
+        message: impl Into<String>
+    ) -> Self {
+        Self {
+            shader: shader.into(),
+            stage,
+            message: message.into(),
+            span: None,
+            span_label: String::new(),
+            excerpt: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    /// Point the diagnostic at `offset..offset + length` of `source`
+    pub fn with_span(
+        mut self,
+        source: &str,
+        offset: usize,
+        length: usize,
+        label: impl Into<String>
+    ) -> Self {
+        let span = SourceSpan::from_offsets(source, offset, length);
+        self.excerpt = source
+            .lines()
+            .skip((span.start_line - 1) as usize)
+            .take((span.end_line - span.start_line + 1) as usize)
+            .map(str::to_string)
+            .collect();
+        self.span = Some(span);
+        self.span_label = label.into();
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error: {}", self.message)?;
+        let stage = match self.stage {
+            Some(stage) if stage == wgpu::ShaderStages::VERTEX => " (vertex)",
+            Some(stage) if stage == wgpu::ShaderStages::FRAGMENT => " (fragment)",
+            Some(stage) if stage == wgpu::ShaderStages::COMPUTE => " (compute)",
+            _ => "",
+        };
+
+        let gutter = match self.span {
+            Some(span) => span.end_line.to_string().len(),
+            None => 1,
+        };
+        let blank = " ".repeat(gutter);
+        match self.span {
+            Some(span) => {
+                write!(
+                    f,
+                    "\n{}--> {}{}:{}:{}",
+                    blank,
+                    self.shader,
+                    stage,
+                    span.start_line,
+                    span.start_column
+                )?;
+                write!(f, "\n{} |", blank)?;
+                for (index, line) in self.excerpt.iter().enumerate() {
+                    let number = span.start_line + (index as u32);
+                    write!(f, "\n{:>width$} | {}", number, line, width = gutter)?;
+                }
+                // Underline the first line of the span
+                let start = span.start_column as usize;
+                let end = if span.end_line == span.start_line {
+                    span.end_column as usize
+                } else {
+                    self.excerpt.first().map_or(start, |line| line.len() + 1)
+                };
+                let carets = "^".repeat(end.saturating_sub(start).max(1));
+                write!(f, "\n{} | {}{}", blank, " ".repeat(start - 1), carets)?;
+                if !self.span_label.is_empty() {
+                    write!(f, " {}", self.span_label)?;
+                }
+            }
+            None => write!(f, "\n{}--> {}{}", blank, self.shader, stage)?,
+        }
+        for note in &self.notes {
+            write!(f, "\n{} = note: {}", blank, note)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse and validate WGSL with naga, reporting the first error as a
+/// [`GeepuError::ShaderError`] with its source location.
+///
+/// Pipeline constructors run this before handing shaders to wgpu, whose own
+/// errors carry no structure. Not available on the web, where the browser
+/// compiles shaders.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn check_wgsl(shader: &str, stage: Option<wgpu::ShaderStages>, source: &str) -> Result<()> {
+    use wgpu::naga;
+
+    let module = naga::front::wgsl::parse_str(source).map_err(|error| {
+        let mut diagnostic = ShaderDiagnostic::new(shader, stage, error.message());
+        for (span, label) in error.labels() {
+            diagnostic = match span.to_range() {
+                Some(range) if diagnostic.span.is_none() => {
+                    diagnostic.with_span(source, range.start, range.len(), label)
+                }
+                _ => diagnostic.with_note(label),
+            };
+        }
+        GeepuError::ShaderError(Box::new(diagnostic))
+    })?;
+
+    let mut validator = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all()
+    );
+    validator.validate(&module).map_err(|error| {
+        let mut diagnostic = ShaderDiagnostic::new(shader, stage, error.as_inner().to_string());
+        for (span, label) in error.spans() {
+            diagnostic = match span.to_range() {
+                Some(range) if diagnostic.span.is_none() => {
+                    diagnostic.with_span(source, range.start, range.len(), label.clone())
+                }
+                _ => diagnostic.with_note(label.clone()),
+            };
+        }
+        let mut cause = std::error::Error::source(error.as_inner());
+        while let Some(inner) = cause {
+            diagnostic = diagnostic.with_note(inner.to_string());
+            cause = inner.source();
+        }
+        GeepuError::ShaderError(Box::new(diagnostic))
+    })?;
+
+    Ok(())
+}
+
+/// Kind of resource a shader binding expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderBindingKind {
+    UniformBuffer,
+    StorageBuffer {
+        read_only: bool,
+    },
+    Texture,
+    StorageTexture,
+    Sampler,
+    /// Binding arrays and acceleration structures
+    Other,
+}
+
+/// A `@group`/`@binding` resource declared by a shader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderBinding {
+    pub group: u32,
+    pub binding: u32,
+    pub name: String,
+    pub kind: ShaderBindingKind,
+}
+
+/// The resource bindings `source` declares, ordered by group then binding.
+/// Empty when the source does not parse.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn reflect_bindings(source: &str) -> Vec<ShaderBinding> {
+    use wgpu::naga;
+
+    let Ok(module) = naga::front::wgsl::parse_str(source) else {
+        return Vec::new();
+    };
+    let mut bindings: Vec<ShaderBinding> = module.global_variables
+        .iter()
+        .filter_map(|(_, global)| {
+            let resource = global.binding.as_ref()?;
+            let kind = match (global.space, &module.types[global.ty].inner) {
+                (naga::AddressSpace::Uniform, _) => ShaderBindingKind::UniformBuffer,
+                (naga::AddressSpace::Storage { access }, _) => {
+                    let read_only = !access.contains(naga::StorageAccess::STORE);
+                    ShaderBindingKind::StorageBuffer { read_only }
+                }
+                (_, naga::TypeInner::Image { class: naga::ImageClass::Storage { .. }, .. }) => {
+                    ShaderBindingKind::StorageTexture
+                }
+                (_, naga::TypeInner::Image { .. }) => ShaderBindingKind::Texture,
+                (_, naga::TypeInner::Sampler { .. }) => ShaderBindingKind::Sampler,
+                _ => ShaderBindingKind::Other,
+            };
+            Some(ShaderBinding {
+                group: resource.group,
+                binding: resource.binding,
+                name: global.name.clone().unwrap_or_default(),
+                kind,
+            })
+        })
+        .collect();
+    bindings.sort_by_key(|binding| (binding.group, binding.binding));
+    bindings
+}
+
+/// Bind group layout entries for the bindings `sources` declare, indexed by
+/// group (groups no shader uses are empty). Bindings declared by several
+/// sources are merged.
+///
+/// Each entry is visible to the stages (within `visibility`) whose entry points
+/// use it; bindings no entry point uses get the stages of their module's entry
+/// points. Writable storage buffers and textures used by a vertex entry point
+/// fail with a [`GeepuError::PipelineError`], as do binding arrays and other
+/// resources without a layout equivalent. Float textures are filterable when a
+/// shader samples them through a filtering sampler, and buffers have no
+/// minimum binding size.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn reflect_layout_entries(
+    sources: &[&str],
+    visibility: wgpu::ShaderStages
+) -> Result<Vec<Vec<wgpu::BindGroupLayoutEntry>>> {
+    use wgpu::naga;
+
+    let modules = sources
+        .iter()
+        .map(|source| {
+            let module = naga::front::wgsl::parse_str(source).map_err(|error| {
+                GeepuError::PipelineError(format!("Cannot reflect shader: {}", error.message()))
+            })?;
+            let mut validator = naga::valid::Validator::new(
+                naga::valid::ValidationFlags::all(),
+                naga::valid::Capabilities::all()
+            );
+            let info = validator.validate(&module).map_err(|error| {
+                GeepuError::PipelineError(format!("Cannot reflect shader: {}", error.as_inner()))
+            })?;
+            Ok((module, info))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let filtered: Vec<(u32, u32)> = modules
+        .iter()
+        .flat_map(|(module, _)| filtered_textures(module))
+        .collect();
+
+    let mut groups: Vec<Vec<wgpu::BindGroupLayoutEntry>> = Vec::new();
+    for (module, info) in &modules {
+        let module_stages = module.entry_points
+            .iter()
+            .fold(wgpu::ShaderStages::NONE, |stages, entry| stages | shader_stage(entry.stage));
+        for (handle, global) in module.global_variables.iter() {
+            let Some(resource) = &global.binding else {
+                continue;
+            };
+            let name = global.name.as_deref().unwrap_or_default();
+            let filterable = filtered.contains(&(resource.group, resource.binding));
+            let Some((ty, writable)) = layout_binding_type(module, global, filterable) else {
+                return Err(
+                    GeepuError::PipelineError(
+                        format!(
+                            "Binding `{}` (@group({}) @binding({})) has no layout equivalent",
+                            name,
+                            resource.group,
+                            resource.binding
+                        )
+                    )
+                );
+            };
+            let used_by = module.entry_points
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| !info.get_entry_point(index)[handle].is_empty())
+                .fold(wgpu::ShaderStages::NONE, |stages, (_, entry)| {
+                    stages | shader_stage(entry.stage)
+                });
+            if writable && used_by.contains(wgpu::ShaderStages::VERTEX) {
+                let message = format!(
+                    "Writable binding `{}` (@group({}) @binding({})) is used by a vertex shader",
+                    name,
+                    resource.group,
+                    resource.binding
+                );
+                return Err(GeepuError::PipelineError(message));
+            }
+            let visibility = match used_by {
+                wgpu::ShaderStages::NONE if writable =>
+                    module_stages.intersection(visibility) - wgpu::ShaderStages::VERTEX,
+                wgpu::ShaderStages::NONE if module_stages.is_empty() => visibility,
+                wgpu::ShaderStages::NONE => module_stages.intersection(visibility),
+                used_by => used_by.intersection(visibility),
+            };
+
+            let group = resource.group as usize;
+            if groups.len() <= group {
+                groups.resize_with(group + 1, Vec::new);
+            }
+            match groups[group].iter_mut().find(|entry| entry.binding == resource.binding) {
+                Some(entry) if entry.ty == ty => entry.visibility |= visibility,
+                Some(_) => {
+                    return Err(
+                        GeepuError::PipelineError(
+                            format!(
+                                "Binding `{}` (@group({}) @binding({})) is declared twice",
+                                name,
+                                resource.group,
+                                resource.binding
+                            )
+                        )
+                    );
+                }
+                None =>
+                    groups[group].push(wgpu::BindGroupLayoutEntry {
+                        binding: resource.binding,
+                        visibility,
+                        ty,
+                        count: None,
+                    }),
+            }
+        }
+    }
+    for entries in &mut groups {
+        entries.sort_by_key(|entry| entry.binding);
+    }
+    Ok(groups)
+}
+
+/// Pipeline stage of a naga entry point
+#[cfg(not(target_arch = "wasm32"))]
+fn shader_stage(stage: wgpu::naga::ShaderStage) -> wgpu::ShaderStages {
+    match stage {
+        wgpu::naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+        wgpu::naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+        wgpu::naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+    }
+}
+
+/// `(group, binding)` of every texture `module` samples through a filtering
+/// sampler, found from the image/sampler pair of its sample expressions.
+/// Pairs passed into helper functions are followed back through their call
+/// sites to the globals.
+#[cfg(not(target_arch = "wasm32"))]
+fn filtered_textures(module: &wgpu::naga::Module) -> Vec<(u32, u32)> {
+    use wgpu::naga;
+
+    let mut pairs = Vec::new();
+    for (callee, function) in functions_with_handles(module) {
+        for (_, expression) in function.expressions.iter() {
+            let naga::Expression::ImageSample { image, sampler, .. } = *expression else {
+                continue;
+            };
+            if
+                let (Some(image), Some(sampler)) = (
+                    sample_operand(function, image),
+                    sample_operand(function, sampler),
+                )
+            {
+                sampled_globals(module, callee, image, sampler, &mut pairs);
+            }
+        }
+    }
+
+    let mut filtered = Vec::new();
+    for (image, sampler) in pairs {
+        let sampler = &module.types[module.global_variables[sampler].ty].inner;
+        if !matches!(sampler, naga::TypeInner::Sampler { comparison: false }) {
+            continue;
+        }
+        if let Some(resource) = &module.global_variables[image].binding {
+            filtered.push((resource.group, resource.binding));
+        }
+    }
+    filtered
+}
+
+/// Image or sampler operand of a sample expression
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy)]
+enum SampleOperand {
+    Global(wgpu::naga::Handle<wgpu::naga::GlobalVariable>),
+    /// Argument of the function the sample is in
+    Argument(u32),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn sample_operand(
+    function: &wgpu::naga::Function,
+    expression: wgpu::naga::Handle<wgpu::naga::Expression>
+) -> Option<SampleOperand> {
+    match function.expressions[expression] {
+        wgpu::naga::Expression::GlobalVariable(global) => Some(SampleOperand::Global(global)),
+        wgpu::naga::Expression::FunctionArgument(index) => Some(SampleOperand::Argument(index)),
+        _ => None,
+    }
+}
+
+/// Every function of `module` with its handle; entry points have none
+#[cfg(not(target_arch = "wasm32"))]
+fn functions_with_handles(
+    module: &wgpu::naga::Module
+) -> impl Iterator<
+    Item = (Option<wgpu::naga::Handle<wgpu::naga::Function>>, &wgpu::naga::Function)
+> {
+    module.functions
+        .iter()
+        .map(|(handle, function)| (Some(handle), function))
+        .chain(module.entry_points.iter().map(|entry| (None, &entry.function)))
+}
+
+/// Resolve an image/sampler pair used in `callee` to the globals it samples,
+/// following arguments back through every call of `callee`
+#[cfg(not(target_arch = "wasm32"))]
+fn sampled_globals(
+    module: &wgpu::naga::Module,
+    callee: Option<wgpu::naga::Handle<wgpu::naga::Function>>,
+    image: SampleOperand,
+    sampler: SampleOperand,
+    pairs: &mut Vec<(
+        wgpu::naga::Handle<wgpu::naga::GlobalVariable>,
+        wgpu::naga::Handle<wgpu::naga::GlobalVariable>,
+    )>
+) {
+    if let (SampleOperand::Global(image), SampleOperand::Global(sampler)) = (image, sampler) {
+        pairs.push((image, sampler));
+        return;
+    }
+    // Entry point arguments are never textures or samplers
+    let Some(callee) = callee else {
+        return;
+    };
+    for (caller, function) in functions_with_handles(module) {
+        let mut calls = Vec::new();
+        collect_calls(&function.body, &mut calls);
+        for arguments in calls
+            .into_iter()
+            .filter(|(target, _)| *target == callee)
+            .map(|(_, arguments)| arguments) {
+            // Naga modules have no recursion, so this walks up the call graph and stops
+            let resolve = |operand| {
+                match operand {
+                    SampleOperand::Argument(index) =>
+                        sample_operand(function, arguments[index as usize]),
+                    global => Some(global),
+                }
+            };
+            if let (Some(image), Some(sampler)) = (resolve(image), resolve(sampler)) {
+                sampled_globals(module, caller, image, sampler, pairs);
+            }
+        }
+    }
+}
+
+/// Target and arguments of every call in `block`, nested blocks included
+#[cfg(not(target_arch = "wasm32"))]
+fn collect_calls<'a>(
+    block: &'a wgpu::naga::Block,
+    calls: &mut Vec<(
+        wgpu::naga::Handle<wgpu::naga::Function>,
+        &'a [wgpu::naga::Handle<wgpu::naga::Expression>],
+    )>
+) {
+    use wgpu::naga::Statement;
+
+    for statement in block.iter() {
+        match statement {
+            Statement::Call { function, arguments, .. } => calls.push((*function, arguments)),
+            Statement::Block(body) => collect_calls(body, calls),
+            Statement::If { accept, reject, .. } => {
+                collect_calls(accept, calls);
+                collect_calls(reject, calls);
+            }
+            Statement::Switch { cases, .. } => {
+                for case in cases {
+                    collect_calls(&case.body, calls);
+                }
+            }
+            Statement::Loop { body, continuing, .. } => {
+                collect_calls(body, calls);
+                collect_calls(continuing, calls);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Layout binding type of a resource global, and whether shaders can write it
+#[cfg(not(target_arch = "wasm32"))]
+fn layout_binding_type(
+    module: &wgpu::naga::Module,
+    global: &wgpu::naga::GlobalVariable,
+    filterable: bool
+) -> Option<(wgpu::BindingType, bool)> {
+    use wgpu::naga;
+    use wgpu::{ BindingType, BufferBindingType, StorageTextureAccess, TextureViewDimension };
+
+    Some(match (global.space, &module.types[global.ty].inner) {
+        (naga::AddressSpace::Uniform, _) => {
+            let ty = BufferBindingType::Uniform;
+            (BindingType::Buffer { ty, has_dynamic_offset: false, min_binding_size: None }, false)
+        }
+        (naga::AddressSpace::Storage { access }, _) => {
+            let read_only = !access.contains(naga::StorageAccess::STORE);
+            let ty = BufferBindingType::Storage { read_only };
+            let binding = BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            };
+            (binding, !read_only)
+        }
+        (_, naga::TypeInner::Image { dim, arrayed, class }) => {
+            let view_dimension = match (dim, arrayed) {
+                (naga::ImageDimension::D1, _) => TextureViewDimension::D1,
+                (naga::ImageDimension::D2, false) => TextureViewDimension::D2,
+                (naga::ImageDimension::D2, true) => TextureViewDimension::D2Array,
+                (naga::ImageDimension::D3, _) => TextureViewDimension::D3,
+                (naga::ImageDimension::Cube, false) => TextureViewDimension::Cube,
+                (naga::ImageDimension::Cube, true) => TextureViewDimension::CubeArray,
+            };
+            match *class {
+                naga::ImageClass::Sampled { kind, multi } => {
+                    let sample_type = match kind {
+                        naga::ScalarKind::Float => {
+                            wgpu::TextureSampleType::Float { filterable: filterable && !multi }
+                        }
+                        naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                        naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                        _ => {
+                            return None;
+                        }
+                    };
+                    let multisampled = multi;
+                    (BindingType::Texture { sample_type, view_dimension, multisampled }, false)
+                }
+                naga::ImageClass::Depth { multi } => {
+                    let sample_type = wgpu::TextureSampleType::Depth;
+                    let multisampled = multi;
+                    (BindingType::Texture { sample_type, view_dimension, multisampled }, false)
+                }
+                naga::ImageClass::Storage { format, access } => {
+                    let load = access.contains(naga::StorageAccess::LOAD);
+                    let store = access.contains(naga::StorageAccess::STORE);
+                    let access = match (load, store) {
+                        (true, true) => StorageTextureAccess::ReadWrite,
+                        (false, true) => StorageTextureAccess::WriteOnly,
+                        _ => StorageTextureAccess::ReadOnly,
+                    };
+                    let format = storage_texture_format(format);
+                    (BindingType::StorageTexture { access, format, view_dimension }, store)
+                }
+            }
+        }
+        (_, naga::TypeInner::Sampler { comparison }) => {
+            let ty = if *comparison {
+                wgpu::SamplerBindingType::Comparison
+            } else {
+                wgpu::SamplerBindingType::Filtering
+            };
+            (BindingType::Sampler(ty), false)
+        }
+        _ => {
+            return None;
+        }
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn storage_texture_format(format: wgpu::naga::StorageFormat) -> wgpu::TextureFormat {
+    use wgpu::naga::StorageFormat as Storage;
+    use wgpu::TextureFormat as Format;
+
+    match format {
+        Storage::R8Unorm => Format::R8Unorm,
+        Storage::R8Snorm => Format::R8Snorm,
+        Storage::R8Uint => Format::R8Uint,
+        Storage::R8Sint => Format::R8Sint,
+        Storage::R16Uint => Format::R16Uint,
+        Storage::R16Sint => Format::R16Sint,
+        Storage::R16Float => Format::R16Float,
+        Storage::Rg8Unorm => Format::Rg8Unorm,
+        Storage::Rg8Snorm => Format::Rg8Snorm,
+        Storage::Rg8Uint => Format::Rg8Uint,
+        Storage::Rg8Sint => Format::Rg8Sint,
+        Storage::R32Uint => Format::R32Uint,
+        Storage::R32Sint => Format::R32Sint,
+        Storage::R32Float => Format::R32Float,
+        Storage::Rg16Uint => Format::Rg16Uint,
+        Storage::Rg16Sint => Format::Rg16Sint,
+        Storage::Rg16Float => Format::Rg16Float,
+        Storage::Rgba8Unorm => Format::Rgba8Unorm,
+        Storage::Rgba8Snorm => Format::Rgba8Snorm,
+        Storage::Rgba8Uint => Format::Rgba8Uint,
+        Storage::Rgba8Sint => Format::Rgba8Sint,
+        Storage::Bgra8Unorm => Format::Bgra8Unorm,
+        Storage::Rgb10a2Uint => Format::Rgb10a2Uint,
+        Storage::Rgb10a2Unorm => Format::Rgb10a2Unorm,
+        Storage::Rg11b10Float => Format::Rg11b10Float,
+        Storage::Rg32Uint => Format::Rg32Uint,
+        Storage::Rg32Sint => Format::Rg32Sint,
+        Storage::Rg32Float => Format::Rg32Float,
+        Storage::Rgba16Uint => Format::Rgba16Uint,
+        Storage::Rgba16Sint => Format::Rgba16Sint,
+        Storage::Rgba16Float => Format::Rgba16Float,
+        Storage::Rgba32Uint => Format::Rgba32Uint,
+        Storage::Rgba32Sint => Format::Rgba32Sint,
+        Storage::Rgba32Float => Format::Rgba32Float,
+        Storage::R16Unorm => Format::R16Unorm,
+        Storage::R16Snorm => Format::R16Snorm,
+        Storage::Rg16Unorm => Format::Rg16Unorm,
+        Storage::Rg16Snorm => Format::Rg16Snorm,
+        Storage::Rgba16Unorm => Format::Rgba16Unorm,
+        Storage::Rgba16Snorm => Format::Rgba16Snorm,
+    }
+}
+
+/// Check that `layouts` provide every `@location` input of the vertex entry
+/// point `entry_point`, with a matching scalar type (float formats for `f32`
+/// inputs, `Uint*` for `u32`, `Sint*` for `i32`).
+///
+/// Fails with a [`GeepuError::ShaderError`] naming the first mismatched input.
+/// Like wgpu, extra attributes and differing component counts are allowed;
+/// the latter is logged, as the shader then reads filled-in defaults. Shaders
+/// that fail to parse or lack the entry point are left to [`check_wgsl`] and wgpu.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn check_vertex_inputs(
+    shader: &str,
+    source: &str,
+    entry_point: &str,
+    layouts: &[wgpu::VertexBufferLayout]
+) -> Result<()> {
+    use wgpu::naga;
+
+    let Ok(module) = naga::front::wgsl::parse_str(source) else {
+        return Ok(());
+    };
+    let Some(entry) = module.entry_points
+        .iter()
+        .find(|entry| entry.stage == naga::ShaderStage::Vertex && entry.name == entry_point) else {
+        return Ok(());
+    };
+
+    // (name, location, type) of each input, unpacking struct arguments
+    let mut inputs = Vec::new();
+    for argument in &entry.function.arguments {
+        match (&argument.binding, &module.types[argument.ty].inner) {
+            (Some(naga::Binding::Location { location, .. }), inner) => {
+                inputs.push((argument.name.clone().unwrap_or_default(), *location, inner));
+            }
+            (None, naga::TypeInner::Struct { members, .. }) => {
+                for member in members {
+                    if let Some(naga::Binding::Location { location, .. }) = member.binding {
+                        let inner = &module.types[member.ty].inner;
+                        inputs.push((member.name.clone().unwrap_or_default(), location, inner));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut attributes = std::collections::HashMap::new();
+    for (buffer, layout) in layouts.iter().enumerate() {
+        for attribute in layout.attributes {
+            let location = attribute.shader_location;
+            if let Some((other, _)) = attributes.insert(location, (buffer, attribute.format)) {
+                let message = format!(
+                    "@location({}) is provided by both vertex buffer {} and vertex buffer {}",
+                    location,
+                    other,
+                    buffer
+                );
+                let diagnostic = ShaderDiagnostic::new(
+                    shader,
+                    Some(wgpu::ShaderStages::VERTEX),
+                    message
+                );
+                return Err(GeepuError::ShaderError(Box::new(diagnostic)));
+            }
+        }
+    }
+
+    for (name, location, inner) in inputs {
+        let (kind, components) = match inner {
+            naga::TypeInner::Scalar(scalar) => (scalar.kind, 1),
+            naga::TypeInner::Vector { size, scalar } => (scalar.kind, *size as u32),
+            _ => continue,
+        };
+        let shader_type = wgsl_type_name(kind, components);
+        let error = |message: String, note: Option<String>| {
+            let mut diagnostic = ShaderDiagnostic::new(
+                shader,
+                Some(wgpu::ShaderStages::VERTEX),
+                message
+            );
+            if let Some((offset, length)) = location_span(source, location, &name) {
+                diagnostic = diagnostic.with_span(source, offset, length, shader_type.clone());
+            }
+            if let Some(note) = note {
+                diagnostic = diagnostic.with_note(note);
+            }
+            GeepuError::ShaderError(Box::new(diagnostic))
+        };
+
+        let Some(&(buffer, format)) = attributes.get(&location) else {
+            return Err(
+                error(
+                    format!(
+                        "vertex input `{}` at @location({}) is not provided by any vertex buffer",
+                        name,
+                        location
+                    ),
+                    Some(format!("{} vertex buffer layouts were given", layouts.len()))
+                )
+            );
+        };
+        let (format_kind, format_components) = vertex_format_type(format);
+        if format_kind != kind {
+            return Err(
+                error(
+                    format!(
+                        "vertex input `{}` at @location({}) is {}, but buffer {} provides {:?}",
+                        name,
+                        location,
+                        shader_type,
+                        buffer,
+                        format
+                    ),
+                    Some(
+                        format!(
+                            "{:?} is read as {} in the shader",
+                            format,
+                            wgsl_type_name(format_kind, format_components)
+                        )
+                    )
+                )
+            );
+        }
+        if format_components != components {
+            log::warn!(
+                "{}: vertex input `{}` at @location({}) is {} but vertex buffer {} provides {:?}",
+                shader,
+                name,
+                location,
+                shader_type,
+                buffer,
+                format
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Scalar kind and component count a vertex format is read as
+#[cfg(not(target_arch = "wasm32"))]
+fn vertex_format_type(format: wgpu::VertexFormat) -> (wgpu::naga::ScalarKind, u32) {
+    use wgpu::naga::ScalarKind;
+    use wgpu::VertexFormat::*;
+    match format {
+        Uint32 => (ScalarKind::Uint, 1),
+        Uint8x2 | Uint16x2 | Uint32x2 => (ScalarKind::Uint, 2),
+        Uint32x3 => (ScalarKind::Uint, 3),
+        Uint8x4 | Uint16x4 | Uint32x4 => (ScalarKind::Uint, 4),
+        Sint32 => (ScalarKind::Sint, 1),
+        Sint8x2 | Sint16x2 | Sint32x2 => (ScalarKind::Sint, 2),
+        Sint32x3 => (ScalarKind::Sint, 3),
+        Sint8x4 | Sint16x4 | Sint32x4 => (ScalarKind::Sint, 4),
+        Float32 | Float64 => (ScalarKind::Float, 1),
+        Unorm8x2 | Snorm8x2 | Unorm16x2 | Snorm16x2 | Float16x2 | Float32x2 | Float64x2 => {
+            (ScalarKind::Float, 2)
+        }
+        Float32x3 | Float64x3 => (ScalarKind::Float, 3),
+        Unorm8x4
+        | Snorm8x4
+        | Unorm16x4
+        | Snorm16x4
+        | Float16x4
+        | Float32x4
+        | Float64x4
+        | Unorm10_10_10_2 => (ScalarKind::Float, 4),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn wgsl_type_name(kind: wgpu::naga::ScalarKind, components: u32) -> String {
+    let scalar = match kind {
+        wgpu::naga::ScalarKind::Sint => "i32",
+        wgpu::naga::ScalarKind::Uint => "u32",
+        wgpu::naga::ScalarKind::Bool => "bool",
+        _ => "f32",
+    };
+    match components {
+        1 => scalar.to_string(),
+        n => format!("vec{}<{}>", n, scalar),
+    }
+}
+
+/// Byte range of `@location(N) name` in `source`, for pointing at an input
+#[cfg(not(target_arch = "wasm32"))]
+fn location_span(source: &str, location: u32, name: &str) -> Option<(usize, usize)> {
+    let attribute = format!("@location({})", location);
+    source.match_indices(&attribute).find_map(|(offset, _)| {
+        let rest = &source[offset + attribute.len()..];
+        let trimmed = rest.trim_start();
+        let after = trimmed.strip_prefix(name)?;
+        if !after.trim_start().starts_with(':') {
+            return None;
+        }
+        Some((offset, attribute.len() + (rest.len() - trimmed.len()) + name.len()))
+    })
+}
@@ -0,0 +1,370 @@
+use crate::{
+    check_wgsl,
+    color_attachment,
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    FrameGlobals,
+    GpuContext,
+    RenderCommands,
+    RenderPipeline,
+    Result,
+    Texture,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Format of the textures a [`Shadertoy`] reading its previous frame renders into
+pub const FEEDBACK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// An input a [`Shadertoy`] shader samples as `channelN` with `channelN_sampler`
+pub enum ShadertoyChannel {
+    Texture(Arc<Texture>),
+    /// What the shader output last frame, black on the first frame and after a resize
+    PreviousFrame,
+}
+
+/// Ping-pong targets for shaders reading their own output
+struct Feedback {
+    targets: [Texture; 2],
+    /// Channel bind group used while rendering into `targets[i]`
+    channel_groups: [wgpu::BindGroup; 2],
+    /// Bind group copying `targets[i]` to the output
+    blit_groups: [wgpu::BindGroup; 2],
+    blit: RenderPipeline,
+    current: usize,
+}
+
+/// Runs a Shadertoy-style fragment shader over the whole target every frame.
+///
+/// The shader defines `fn main_image(frag_coord: vec2<f32>) -> vec4<f32>`,
+/// where `frag_coord` is in pixels from the bottom-left corner. It can use
+/// `globals` (see [`FrameGlobals`]) and the `channelN` textures it was created
+/// with; [`ShadertoyChannel::PreviousFrame`] feeds the last output back in
+/// for simulations and trails.
+pub struct Shadertoy {
+    globals: FrameGlobals,
+    channels: Vec<ShadertoyChannel>,
+    channel_layout: Arc<wgpu::BindGroupLayout>,
+    /// Channel bind group when no channel reads the previous frame
+    channel_group: Option<wgpu::BindGroup>,
+    pipeline: RenderPipeline,
+    feedback: Option<Feedback>,
+    format: wgpu::TextureFormat,
+    size: (u32, u32),
+}
+
+impl Shadertoy {
+    /// Run `fragment_source` into targets of `format`
+    pub fn new(
+        context: &GpuContext,
+        fragment_source: &str,
+        format: wgpu::TextureFormat
+    ) -> Result<Self> {
+        Self::with_channels(context, fragment_source, Vec::new(), format)
+    }
+
+    /// Run `fragment_source` with `channels` bound as `channel0`, `channel1`, ...
+    pub fn with_channels(
+        context: &GpuContext,
+        fragment_source: &str,
+        channels: Vec<ShadertoyChannel>,
+        format: wgpu::TextureFormat
+    ) -> Result<Self> {
+        let source = Self::shader_source(fragment_source, channels.len());
+        check_wgsl("Shadertoy", None, &source)?;
+
+        let globals = FrameGlobals::new(context)?;
+        let mut layout_builder = BindGroupLayoutBuilder::new();
+        for index in 0..channels.len() as u32 {
+            layout_builder = layout_builder
+                .texture(
+                    index * 2,
+                    wgpu::ShaderStages::FRAGMENT,
+                    wgpu::TextureSampleType::Float { filterable: true },
+                    wgpu::TextureViewDimension::D2,
+                    false
+                )
+                .sampler(
+                    index * 2 + 1,
+                    wgpu::ShaderStages::FRAGMENT,
+                    wgpu::SamplerBindingType::Filtering
+                );
+        }
+        let channel_layout = layout_builder.build(context, Some("Shadertoy Channels"));
+
+        let feedback = channels
+            .iter()
+            .any(|channel| matches!(channel, ShadertoyChannel::PreviousFrame));
+        let layouts = vec![globals.layout().clone(), channel_layout.clone()];
+        let pipeline = fullscreen_pipeline(
+            context,
+            &source,
+            layouts,
+            (if feedback { FEEDBACK_FORMAT } else { format }).into(),
+            "Shadertoy"
+        );
+
+        let mut shadertoy = Self {
+            globals,
+            channels,
+            channel_layout,
+            channel_group: None,
+            pipeline,
+            feedback: None,
+            format,
+            size: context.size(),
+        };
+        if feedback {
+            shadertoy.feedback = Some(shadertoy.create_feedback(context)?);
+        } else {
+            shadertoy.channel_group = Some(shadertoy.channel_group(context, None));
+        }
+        Ok(shadertoy)
+    }
+
+    /// The complete WGSL module run for `fragment_source` with `channel_count` channels
+    pub fn shader_source(fragment_source: &str, channel_count: usize) -> String {
+        let mut source = String::from(FrameGlobals::WGSL);
+        source.push_str("@group(0) @binding(0) var<uniform> globals: Globals;\n");
+        for index in 0..channel_count {
+            source.push_str(
+                &format!(
+                    "@group(1) @binding({}) var channel{}: texture_2d<f32>;\n\
+                     @group(1) @binding({}) var channel{}_sampler: sampler;\n",
+                    index * 2,
+                    index,
+                    index * 2 + 1,
+                    index
+                )
+            );
+        }
+        source.push_str(fragment_source);
+        source.push_str(FULLSCREEN_VERTEX);
+        source.push_str(SHADERTOY_FRAGMENT);
+        source
+    }
+
+    /// Track the cursor for `globals.mouse`
+    #[cfg(feature = "window")]
+    pub fn handle_event(&mut self, event: &winit::event::WindowEvent) {
+        self.globals.handle_event(event);
+    }
+
+    pub fn globals(&self) -> &FrameGlobals {
+        &self.globals
+    }
+
+    /// Set the mouse position directly, e.g. when rendering offscreen
+    pub fn globals_mut(&mut self) -> &mut FrameGlobals {
+        &mut self.globals
+    }
+
+    /// Advance time by `delta` and follow the context's size. Call once per frame.
+    pub fn update(&mut self, context: &GpuContext, delta: Duration) -> Result<()> {
+        self.update_with_resolution(context, delta, context.size())
+    }
+
+    /// [`update`](Self::update) for a render target other than the surface
+    pub fn update_with_resolution(
+        &mut self,
+        context: &GpuContext,
+        delta: Duration,
+        resolution: (u32, u32)
+    ) -> Result<()> {
+        if resolution != self.size {
+            self.size = resolution;
+            if self.feedback.is_some() {
+                self.feedback = Some(self.create_feedback(context)?);
+            }
+        }
+        self.globals.update_with_resolution(context, delta, resolution)
+    }
+
+    /// Run the shader over `target`
+    pub fn render(&mut self, commands: &mut RenderCommands, target: &wgpu::TextureView) {
+        let Some(feedback) = &mut self.feedback else {
+            let color = [Some(color_attachment(target, None))];
+            let mut pass = commands.begin_render_pass(&color, None, Some("Shadertoy"));
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, self.globals.bind_group(), &[]);
+            if let Some(group) = &self.channel_group {
+                pass.set_bind_group(1, group, &[]);
+            }
+            pass.draw(0..3, 0..1);
+            return;
+        };
+
+        let current = feedback.current;
+        {
+            let color = [Some(color_attachment(&feedback.targets[current].view, None))];
+            let mut pass = commands.begin_render_pass(&color, None, Some("Shadertoy"));
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, self.globals.bind_group(), &[]);
+            pass.set_bind_group(1, &feedback.channel_groups[current], &[]);
+            pass.draw(0..3, 0..1);
+        }
+        {
+            let color = [Some(color_attachment(target, None))];
+            let mut pass = commands.begin_render_pass(&color, None, Some("Shadertoy Output"));
+            pass.set_pipeline(&feedback.blit);
+            pass.set_bind_group(0, &feedback.blit_groups[current], &[]);
+            pass.draw(0..3, 0..1);
+        }
+        feedback.current = 1 - current;
+    }
+
+    /// Bind the channels, with `previous` standing in for the previous frame
+    fn channel_group(&self, context: &GpuContext, previous: Option<&Texture>) -> wgpu::BindGroup {
+        let mut builder = BindGroupBuilder::new(&self.channel_layout);
+        for (index, channel) in self.channels.iter().enumerate() {
+            let texture = match channel {
+                ShadertoyChannel::Texture(texture) => texture.as_ref(),
+                ShadertoyChannel::PreviousFrame => previous.expect("feedback target"),
+            };
+            builder = builder
+                .texture_view((index * 2) as u32, &texture.view)
+                .sampler((index * 2 + 1) as u32, &texture.sampler);
+        }
+        builder.build(context, Some("Shadertoy Channels"))
+    }
+
+    fn create_feedback(&self, context: &GpuContext) -> Result<Feedback> {
+        let (width, height) = self.size;
+        let create_target = || {
+            Texture::create_empty(
+                context,
+                width.max(1),
+                height.max(1),
+                FEEDBACK_FORMAT,
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                Some("Shadertoy Feedback")
+            )
+        };
+        let targets = [create_target()?, create_target()?];
+
+        let blit_layout = BindGroupLayoutBuilder::new()
+            .texture(
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .sampler(1, wgpu::ShaderStages::FRAGMENT, wgpu::SamplerBindingType::Filtering)
+            .build(context, Some("Shadertoy Output"));
+        let blit_group = |target: &Texture| {
+            BindGroupBuilder::new(&blit_layout)
+                .texture_view(0, &target.view)
+                .sampler(1, &target.sampler)
+                .build(context, Some("Shadertoy Output"))
+        };
+        let blit_groups = [blit_group(&targets[0]), blit_group(&targets[1])];
+        let channel_groups = [
+            self.channel_group(context, Some(&targets[1])),
+            self.channel_group(context, Some(&targets[0])),
+        ];
+        let blit = fullscreen_pipeline(
+            context,
+            &format!("{}{}", FULLSCREEN_VERTEX, BLIT_FRAGMENT),
+            vec![blit_layout],
+            self.format.into(),
+            "Shadertoy Output"
+        );
+
+        Ok(Feedback { targets, channel_groups, blit_groups, blit, current: 0 })
+    }
+}
+
+/// A pipeline drawing a fullscreen triangle with `vs_main` and `fs_main` from
+/// `source` into a single-sampled target (post passes run after the MSAA resolve)
+pub(crate) fn fullscreen_pipeline(
+    context: &GpuContext,
+    source: &str,
+    bind_group_layouts: Vec<Arc<wgpu::BindGroupLayout>>,
+    target: wgpu::ColorTargetState,
+    label: &str
+) -> RenderPipeline {
+    let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(&context.label(label)),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+    let layout_refs: Vec<&wgpu::BindGroupLayout> = bind_group_layouts
+        .iter()
+        .map(|layout| layout.as_ref())
+        .collect();
+    let pipeline_layout = context.device.create_pipeline_layout(
+        &(wgpu::PipelineLayoutDescriptor {
+            label: Some(&context.label(label)),
+            bind_group_layouts: &layout_refs,
+            push_constant_ranges: &[],
+        })
+    );
+    let pipeline = context.device.create_render_pipeline(
+        &(wgpu::RenderPipelineDescriptor {
+            label: Some(&context.label(label)),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(target)],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    );
+    RenderPipeline {
+        pipeline,
+        bind_group_layouts,
+        vertex_buffer_count: 0,
+        bindings: Vec::new(),
+    }
+}
+
+pub(crate) const FULLSCREEN_VERTEX: &str =
+    r#"
+struct FullscreenOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((vertex << 1u) & 2u), f32(vertex & 2u));
+    var out: FullscreenOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+"#;
+
+/// Appended after the user's `main_image`, following [`FULLSCREEN_VERTEX`]
+const SHADERTOY_FRAGMENT: &str =
+    r#"
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    // Shadertoy puts the origin at the bottom-left pixel
+    let position = in.clip_position.xy;
+    return main_image(vec2<f32>(position.x, globals.resolution.y - position.y));
+}
+"#;
+
+const BLIT_FRAGMENT: &str =
+    r#"
+@group(0) @binding(0) var source_texture: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSample(source_texture, source_sampler, in.uv);
+}
+"#;
@@ -0,0 +1,398 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    Camera,
+    GpuContext,
+    RenderPass,
+    RenderPipeline,
+    Result,
+    Texture,
+    TypedBuffer,
+};
+
+/// Format of cubemaps produced by [`Skybox::equirect_to_cubemap`]
+pub const CUBEMAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyboxUniform {
+    /// Inverse of the projection times the rotation-only view
+    inverse_view_projection: [[f32; 4]; 4],
+}
+
+/// Draws a cubemap behind the scene at maximum depth.
+///
+/// Render it after opaque geometry in the same pass: it only covers pixels
+/// where nothing closer was drawn, so the depth buffer must be cleared to 1.0.
+pub struct Skybox {
+    pub cubemap: Texture,
+    uniform: TypedBuffer<SkyboxUniform>,
+    bind_group: wgpu::BindGroup,
+    pipeline: RenderPipeline,
+}
+
+impl Skybox {
+    /// Create a skybox from a filterable cube texture (see [`Texture::create_cubemap`]
+    /// and [`Texture::cubemap_from_bytes`])
+    pub fn new(
+        context: &GpuContext,
+        cubemap: Texture,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>
+    ) -> Result<Self> {
+        Self::new_multisampled(context, cubemap, color_format, depth_format, 1)
+    }
+
+    /// [`new`](Self::new) for targets with `samples` samples per pixel, e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    pub fn new_multisampled(
+        context: &GpuContext,
+        cubemap: Texture,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        samples: u32
+    ) -> Result<Self> {
+        let uniform = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::VERTEX)
+            .texture_cube(1, wgpu::ShaderStages::FRAGMENT)
+            .sampler(2, wgpu::ShaderStages::FRAGMENT, wgpu::SamplerBindingType::Filtering)
+            .build(context, Some("Skybox Bind Group Layout"));
+        let bind_group = BindGroupBuilder::new(&layout)
+            .buffer(0, uniform.buffer())
+            .texture_view(1, &cubemap.view)
+            .sampler(2, &cubemap.sampler)
+            .build(context, Some("Skybox Bind Group"));
+
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&context.label("Skybox Shader")),
+            source: wgpu::ShaderSource::Wgsl(SKYBOX_SHADER.into()),
+        });
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&context.label("Skybox Pipeline Layout")),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            })
+        );
+        let pipeline = context.device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some(&context.label("Skybox Pipeline")),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: color_format,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: samples,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            })
+        );
+
+        Ok(Self {
+            cubemap,
+            uniform,
+            bind_group,
+            pipeline: RenderPipeline {
+                pipeline,
+                bind_group_layouts: vec![layout],
+                vertex_buffer_count: 0,
+                bindings: Vec::new(),
+            },
+        })
+    }
+
+    /// Create a skybox from an equirectangular (latitude-longitude) environment map,
+    /// typically an HDR image, converted to a cubemap with `face_size` pixel faces
+    pub fn from_equirectangular(
+        context: &GpuContext,
+        equirect: &Texture,
+        face_size: u32,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>
+    ) -> Result<Self> {
+        let cubemap = Self::equirect_to_cubemap(context, equirect, face_size)?;
+        Self::new(context, cubemap, color_format, depth_format)
+    }
+
+    /// Convert an equirectangular environment map into a [`CUBEMAP_FORMAT`] cubemap
+    pub fn equirect_to_cubemap(
+        context: &GpuContext,
+        equirect: &Texture,
+        face_size: u32
+    ) -> Result<Texture> {
+        let cubemap = Texture::create_cubemap(
+            context,
+            face_size,
+            CUBEMAP_FORMAT,
+            1,
+            wgpu::TextureUsages::TEXTURE_BINDING |
+                wgpu::TextureUsages::RENDER_ATTACHMENT |
+                wgpu::TextureUsages::COPY_SRC |
+                wgpu::TextureUsages::COPY_DST,
+            Some("Environment Cubemap")
+        )?;
+
+        // Float32 sources are only filterable with FLOAT32_FILTERABLE
+        let filterable = matches!(
+            equirect.format().sample_type(None, Some(context.device.features())),
+            Some(wgpu::TextureSampleType::Float { filterable: true })
+        );
+        let (sampler_type, filter) = if filterable {
+            (wgpu::SamplerBindingType::Filtering, wgpu::FilterMode::Linear)
+        } else {
+            (wgpu::SamplerBindingType::NonFiltering, wgpu::FilterMode::Nearest)
+        };
+        let sampler = context.device.create_sampler(
+            &(wgpu::SamplerDescriptor {
+                label: Some(&context.label("Equirect Sampler")),
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                mag_filter: filter,
+                min_filter: filter,
+                ..Default::default()
+            })
+        );
+
+        let layout = BindGroupLayoutBuilder::new()
+            .texture(
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable },
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .sampler(1, wgpu::ShaderStages::FRAGMENT, sampler_type)
+            .build(context, Some("Equirect Bind Group Layout"));
+        let bind_group = BindGroupBuilder::new(&layout)
+            .texture_view(0, &equirect.view)
+            .sampler(1, &sampler)
+            .build(context, Some("Equirect Bind Group"));
+
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&context.label("Equirect To Cubemap Shader")),
+            source: wgpu::ShaderSource::Wgsl(
+                format!("{}{}", CUBE_FACE_WGSL, EQUIRECT_SHADER).into()
+            ),
+        });
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&context.label("Equirect To Cubemap Layout")),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            })
+        );
+        let pipeline = context.device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some(&context.label("Equirect To Cubemap Pipeline")),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[Some(CUBEMAP_FORMAT.into())],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                // Renders into the cubemap faces, not the surface
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        );
+
+        render_cube_faces(context, &cubemap, 1, &pipeline, &bind_group, "Equirect To Cubemap");
+
+        Ok(cubemap)
+    }
+
+    /// Upload the camera's orientation and projection
+    pub fn prepare(&self, context: &GpuContext, camera: &Camera) -> Result<()> {
+        let mut view = camera.view();
+        view.w_axis = glam::Vec4::W;
+        let inverse = (camera.proj() * view).inverse();
+        self.uniform.write(
+            context,
+            &[
+                SkyboxUniform {
+                    inverse_view_projection: inverse.to_cols_array_2d(),
+                },
+            ]
+        )
+    }
+
+    /// Draw the skybox into the current pass
+    pub fn render<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// Run a `CUBE_FACE_WGSL` pipeline over every face of the first `mip_levels` mips
+/// of `cubemap`. The draw's instance index is `mip * 6 + face`.
+pub(crate) fn render_cube_faces(
+    context: &GpuContext,
+    cubemap: &Texture,
+    mip_levels: u32,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    label: &str
+) {
+    let mut encoder = context.device.create_command_encoder(
+        &(wgpu::CommandEncoderDescriptor {
+            label: Some(label),
+        })
+    );
+    for mip in 0..mip_levels {
+        for face in 0..6 {
+            let view = cubemap.texture.create_view(
+                &(wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_mip_level: mip,
+                    mip_level_count: Some(1),
+                    base_array_layer: face,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            );
+            let mut pass = encoder.begin_render_pass(
+                &(wgpu::RenderPassDescriptor {
+                    label: Some(label),
+                    color_attachments: &[
+                        Some(wgpu::RenderPassColorAttachment {
+                            view: &view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                store: wgpu::StoreOp::Store,
+                            },
+                        }),
+                    ],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                })
+            );
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            let instance = mip * 6 + face;
+            pass.draw(0..3, instance..instance + 1);
+        }
+    }
+    context.submit(std::iter::once(encoder.finish()));
+}
+
+/// `cube_direction` maps a face index (+X, -X, +Y, -Y, +Z, -Z) and texture
+/// coordinates (v down) to a direction. The vertex shader draws one face with a
+/// fullscreen triangle and passes the instance index through as `face`.
+pub(crate) const CUBE_FACE_WGSL: &str =
+    r#"
+fn cube_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let s = uv.x * 2.0 - 1.0;
+    let t = uv.y * 2.0 - 1.0;
+    switch face {
+        case 0u: { return normalize(vec3<f32>(1.0, -t, -s)); }
+        case 1u: { return normalize(vec3<f32>(-1.0, -t, s)); }
+        case 2u: { return normalize(vec3<f32>(s, 1.0, t)); }
+        case 3u: { return normalize(vec3<f32>(s, -1.0, -t)); }
+        case 4u: { return normalize(vec3<f32>(s, -t, 1.0)); }
+        default: { return normalize(vec3<f32>(-s, -t, -1.0)); }
+    }
+}
+
+struct FaceOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) face: u32,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32, @builtin(instance_index) face: u32) -> FaceOutput {
+    let uv = vec2<f32>(f32((vertex << 1u) & 2u), f32(vertex & 2u));
+    var out: FaceOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    out.face = face;
+    return out;
+}
+"#;
+
+const EQUIRECT_SHADER: &str =
+    r#"
+const PI: f32 = 3.14159265;
+
+@group(0) @binding(0) var equirect_texture: texture_2d<f32>;
+@group(0) @binding(1) var equirect_sampler: sampler;
+
+@fragment
+fn fs_main(in: FaceOutput) -> @location(0) vec4<f32> {
+    let dir = cube_direction(in.face, in.uv);
+    let uv = vec2<f32>(atan2(dir.z, dir.x) / (2.0 * PI) + 0.5, acos(clamp(dir.y, -1.0, 1.0)) / PI);
+    return vec4<f32>(textureSampleLevel(equirect_texture, equirect_sampler, uv, 0.0).rgb, 1.0);
+}
+"#;
+
+const SKYBOX_SHADER: &str =
+    r#"
+struct Skybox {
+    inverse_view_projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> skybox: Skybox;
+@group(0) @binding(1) var sky_texture: texture_cube<f32>;
+@group(0) @binding(2) var sky_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex << 1u) & 2u), f32(vertex & 2u));
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    var out: VertexOutput;
+    // z = w puts the sky at the far plane
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    out.direction = skybox.inverse_view_projection * vec4<f32>(ndc, 1.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = in.direction.xyz / in.direction.w;
+    return textureSample(sky_texture, sky_sampler, direction);
+}
+"#;
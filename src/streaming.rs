@@ -0,0 +1,150 @@
+use crate::{ GpuContext, GeepuError, Result, TypedBuffer };
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicU8, Ordering };
+
+const IN_USE: u8 = 0;
+const MAPPING: u8 = 1;
+const MAPPED: u8 = 2;
+const FAILED: u8 = 3;
+
+/// A staging buffer that stays mapped between uses
+struct Slot {
+    buffer: wgpu::Buffer,
+    state: Arc<AtomicU8>,
+}
+
+/// A GPU buffer refilled every frame straight from CPU-visible memory, for
+/// streaming audio, video frames or particle data.
+///
+/// [`write`](Self::write) hands out the mapped memory of a staging buffer to
+/// fill in place, then copies it into the GPU buffer on the GPU, so the data
+/// is never copied on the CPU the way `queue.write_buffer` does. Used staging
+/// buffers are mapped again in the background at the next write; when none is
+/// ready yet another one is created, so the pool settles at the number of
+/// frames in flight.
+pub struct StreamingBuffer<T> {
+    buffer: TypedBuffer<T>,
+    slots: Vec<Slot>,
+    len: usize,
+}
+
+impl<T> StreamingBuffer<T> where T: bytemuck::Pod {
+    /// Create a buffer of `capacity` elements with `usage` (`COPY_DST` is added)
+    pub fn new(context: &GpuContext, capacity: usize, usage: wgpu::BufferUsages) -> Result<Self> {
+        if !(std::mem::size_of::<T>() as u64).is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT) {
+            return Err(
+                GeepuError::BufferError(
+                    "Streamed element size must be a multiple of 4 bytes".to_string()
+                )
+            );
+        }
+        Ok(Self {
+            buffer: TypedBuffer::empty(context, capacity, usage | wgpu::BufferUsages::COPY_DST)?,
+            slots: Vec::new(),
+            len: 0,
+        })
+    }
+
+    /// Fill the first `len` elements for this frame.
+    ///
+    /// `fill` receives mapped staging memory holding whatever was written to
+    /// it last time it was used. The copy into [`buffer`](Self::buffer) is
+    /// batched with the context's other uploads, or submitted immediately
+    /// when [`GpuConfig::batch_uploads`](crate::GpuConfig::batch_uploads) is
+    /// off. Call at most once per frame.
+    pub fn write(
+        &mut self,
+        context: &GpuContext,
+        len: usize,
+        fill: impl FnOnce(&mut [T])
+    ) -> Result<()> {
+        if len > self.buffer.len() {
+            return Err(GeepuError::BufferError("Data size exceeds buffer capacity".to_string()));
+        }
+        self.len = len;
+        if len == 0 {
+            return Ok(());
+        }
+
+        self.recycle(context);
+        let index = match
+            self.slots.iter().position(|slot| slot.state.load(Ordering::Acquire) == MAPPED)
+        {
+            Some(index) => index,
+            None => {
+                self.slots.push(self.create_slot(context));
+                self.slots.len() - 1
+            }
+        };
+
+        let slot = &self.slots[index];
+        let size = (len * std::mem::size_of::<T>()) as u64;
+        {
+            let mut mapped = slot.buffer.slice(..size).get_mapped_range_mut();
+            fill(bytemuck::cast_slice_mut(&mut mapped));
+        }
+        slot.buffer.unmap();
+        slot.state.store(IN_USE, Ordering::Release);
+        context.copy_buffer(&slot.buffer, self.buffer.buffer(), size);
+        Ok(())
+    }
+
+    /// The GPU buffer holding the last write
+    pub fn buffer(&self) -> &TypedBuffer<T> {
+        &self.buffer
+    }
+
+    /// Number of elements written by the last write
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of elements the buffer holds
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Number of staging buffers allocated so far
+    pub fn staging_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Start mapping the staging buffers used by earlier frames
+    fn recycle(&mut self, context: &GpuContext) {
+        self.slots.retain(|slot| slot.state.load(Ordering::Acquire) != FAILED);
+        if !self.slots.iter().any(|slot| slot.state.load(Ordering::Acquire) == IN_USE) {
+            return;
+        }
+
+        // The last copy has to be submitted before its source can be mapped
+        context.flush_uploads();
+        for slot in &self.slots {
+            if slot.state.load(Ordering::Acquire) != IN_USE {
+                continue;
+            }
+            slot.state.store(MAPPING, Ordering::Release);
+            let state = slot.state.clone();
+            slot.buffer.slice(..).map_async(wgpu::MapMode::Write, move |result| {
+                state.store(if result.is_ok() { MAPPED } else { FAILED }, Ordering::Release);
+            });
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        context.device.poll(wgpu::Maintain::Poll);
+    }
+
+    fn create_slot(&self, context: &GpuContext) -> Slot {
+        let buffer = context.device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some(&context.label("StreamingBuffer Staging")),
+                size: self.buffer.size_bytes().max(wgpu::COPY_BUFFER_ALIGNMENT),
+                usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            })
+        );
+        Slot { buffer, state: Arc::new(AtomicU8::new(MAPPED)) }
+    }
+}
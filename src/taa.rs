@@ -0,0 +1,347 @@
+use crate::{
+    check_wgsl,
+    color_attachment,
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    Camera,
+    CameraUniform,
+    GpuContext,
+    RenderCommands,
+    RenderPipeline,
+    Result,
+    Texture,
+    TextureBuilder,
+    TypedBuffer,
+};
+use crate::shadertoy::{ fullscreen_pipeline, FULLSCREEN_VERTEX };
+use glam::Mat4;
+use std::sync::Arc;
+
+/// Format of velocity targets read by [`Taa::resolve`]
+pub const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+/// Element `index` of the Halton low-discrepancy sequence in `base`, in 0..1
+pub fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * ((index % base) as f32);
+        index /= base;
+    }
+    result
+}
+
+/// Sub-pixel offset for `frame` of a `width` x `height` target, in clip-space
+/// units for [`Camera::jittered_uniform`]. Cycles through the first `phases`
+/// points of the Halton (2, 3) sequence, each within half a pixel.
+pub fn taa_jitter(frame: u64, phases: u32, width: u32, height: u32) -> [f32; 2] {
+    let index = ((frame % (phases.max(1) as u64)) as u32) + 1;
+    let x = halton(index, 2) - 0.5;
+    let y = halton(index, 3) - 0.5;
+    [(x * 2.0) / (width.max(1) as f32), (y * 2.0) / (height.max(1) as f32)]
+}
+
+/// Parameters of a [`Taa`] resolve
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TaaSettings {
+    /// Weight of the history in each resolved pixel; higher is smoother but
+    /// slower to follow changes
+    pub feedback: f32,
+    /// Jitter positions cycled through before repeating
+    pub jitter_phases: u32,
+}
+
+impl TaaSettings {
+    pub fn new() -> Self {
+        Self { feedback: 0.9, jitter_phases: 8 }
+    }
+}
+
+impl Default for TaaSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const HISTORY_VALID: u32 = 1;
+const HAS_VELOCITY: u32 = 2;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct TaaParams {
+    /// Current NDC to previous clip space
+    reprojection: [[f32; 4]; 4],
+    feedback: f32,
+    flags: u32,
+    _padding: [u32; 2],
+}
+
+/// Temporal anti-aliasing: accumulates jittered frames into a history texture.
+///
+/// Each frame, render the scene with [`jittered_uniform`](Self::jittered_uniform)
+/// as the camera, then [`resolve`](Self::resolve) the color and depth into the
+/// next history texture, read back through [`output`](Self::output). History is
+/// reprojected with the camera's motion from depth; moving objects can write
+/// their own motion to a [`VELOCITY_FORMAT`] target with
+/// [`Taa::VELOCITY_WGSL`] and pass it in instead. Reprojected history is
+/// clamped to the current frame's 3x3 neighborhood to limit ghosting.
+pub struct Taa {
+    settings: TaaSettings,
+    params: TypedBuffer<TaaParams>,
+    layout: Arc<wgpu::BindGroupLayout>,
+    sampler: wgpu::Sampler,
+    pipeline: RenderPipeline,
+    history: [Texture; 2],
+    /// Index of the history texture the last resolve wrote
+    current: usize,
+    /// Bound when no velocity target is given
+    no_velocity: Texture,
+    previous_view_projection: Option<Mat4>,
+    frame: u64,
+    format: wgpu::TextureFormat,
+    size: (u32, u32),
+}
+
+impl Taa {
+    /// WGSL helper for velocity targets: `taa_velocity(clip, previous_clip)`
+    /// takes the unjittered clip positions of a vertex this frame and last
+    /// frame and returns the motion [`resolve`](Self::resolve) expects
+    pub const VELOCITY_WGSL: &'static str =
+        r#"
+fn taa_velocity(clip: vec4<f32>, previous_clip: vec4<f32>) -> vec2<f32> {
+    let motion = clip.xy / clip.w - previous_clip.xy / previous_clip.w;
+    return motion * vec2<f32>(0.5, -0.5);
+}
+"#;
+
+    /// TAA for `width` x `height` images of `format`, e.g. `Rgba16Float`
+    pub fn new(
+        context: &GpuContext,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        settings: TaaSettings
+    ) -> Result<Self> {
+        let filterable = wgpu::TextureSampleType::Float { filterable: true };
+        let (fragment, d2) = (wgpu::ShaderStages::FRAGMENT, wgpu::TextureViewDimension::D2);
+        let layout = BindGroupLayoutBuilder::new()
+            .texture(0, fragment, filterable, d2, false)
+            .texture(1, fragment, filterable, d2, false)
+            .texture(2, fragment, wgpu::TextureSampleType::Depth, d2, false)
+            .texture(3, fragment, filterable, d2, false)
+            .sampler(4, fragment, wgpu::SamplerBindingType::Filtering)
+            .uniform_buffer(5, fragment)
+            .build(context, Some("TAA Layout"));
+        let sampler = context.device.create_sampler(
+            &(wgpu::SamplerDescriptor {
+                label: Some(&context.label("TAA Sampler")),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            })
+        );
+
+        let source = format!("{}{}", FULLSCREEN_VERTEX, RESOLVE_FRAGMENT);
+        check_wgsl("TAA Resolve", None, &source)?;
+        let pipeline = fullscreen_pipeline(
+            context,
+            &source,
+            vec![layout.clone()],
+            format.into(),
+            "TAA Resolve"
+        );
+        let no_velocity = TextureBuilder::new(1, 1)
+            .format(VELOCITY_FORMAT)
+            .label("TAA No Velocity")
+            .build(context)?;
+
+        Ok(Self {
+            settings,
+            params: TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?,
+            layout,
+            sampler,
+            pipeline,
+            history: Self::create_history(context, width, height, format)?,
+            current: 0,
+            no_velocity,
+            previous_view_projection: None,
+            frame: 0,
+            format,
+            size: (width, height),
+        })
+    }
+
+    fn create_history(
+        context: &GpuContext,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat
+    ) -> Result<[Texture; 2]> {
+        let create = || {
+            TextureBuilder::new(width, height)
+                .format(format)
+                .usage(
+                    wgpu::TextureUsages::RENDER_ATTACHMENT |
+                        wgpu::TextureUsages::TEXTURE_BINDING |
+                        wgpu::TextureUsages::COPY_SRC
+                )
+                .label("TAA History")
+                .build(context)
+        };
+        Ok([create()?, create()?])
+    }
+
+    /// Follow a new image size, discarding the history
+    pub fn resize(&mut self, context: &GpuContext, width: u32, height: u32) -> Result<()> {
+        if self.size != (width, height) {
+            self.size = (width, height);
+            self.history = Self::create_history(context, width, height, self.format)?;
+            self.reset();
+        }
+        Ok(())
+    }
+
+    /// Discard the history, e.g. after a camera cut
+    pub fn reset(&mut self) {
+        self.previous_view_projection = None;
+    }
+
+    pub fn settings(&self) -> &TaaSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: TaaSettings) {
+        self.settings = settings;
+    }
+
+    /// Clip-space jitter of the frame being rendered (see [`taa_jitter`])
+    pub fn jitter(&self) -> [f32; 2] {
+        taa_jitter(self.frame, self.settings.jitter_phases, self.size.0, self.size.1)
+    }
+
+    /// `camera`'s matrices with this frame's [`jitter`](Self::jitter), to draw the scene with
+    pub fn jittered_uniform(&self, camera: &Camera) -> CameraUniform {
+        camera.jittered_uniform(self.jitter())
+    }
+
+    /// The image written by the last [`resolve`](Self::resolve)
+    pub fn output(&self) -> &Texture {
+        &self.history[self.current]
+    }
+
+    /// Blend `color`, rendered with [`jittered_uniform`](Self::jittered_uniform)
+    /// of `camera`, with the history into [`output`](Self::output) and advance
+    /// the jitter. `depth` is the single-sampled depth buffer drawn with it;
+    /// `velocity`, if given, replaces camera reprojection (see
+    /// [`Taa::VELOCITY_WGSL`]).
+    pub fn resolve(
+        &mut self,
+        context: &GpuContext,
+        commands: &mut RenderCommands,
+        camera: &Camera,
+        color: &wgpu::TextureView,
+        depth: &wgpu::TextureView,
+        velocity: Option<&wgpu::TextureView>
+    ) -> Result<()> {
+        let view_projection = camera.proj() * camera.view();
+        let mut flags = 0;
+        if self.previous_view_projection.is_some() {
+            flags |= HISTORY_VALID;
+        }
+        if velocity.is_some() {
+            flags |= HAS_VELOCITY;
+        }
+        let previous = self.previous_view_projection.unwrap_or(view_projection);
+        self.params.write(
+            context,
+            &[
+                TaaParams {
+                    reprojection: (previous * view_projection.inverse()).to_cols_array_2d(),
+                    feedback: self.settings.feedback,
+                    flags,
+                    _padding: [0; 2],
+                },
+            ]
+        )?;
+
+        let next = 1 - self.current;
+        let bind_group = BindGroupBuilder::new(&self.layout)
+            .texture_view(0, color)
+            .texture_view(1, &self.history[self.current].view)
+            .texture_view(2, depth)
+            .texture_view(3, velocity.unwrap_or(&self.no_velocity.view))
+            .sampler(4, &self.sampler)
+            .buffer(5, self.params.buffer())
+            .build(context, Some("TAA Bind Group"));
+        {
+            let target = [Some(color_attachment(&self.history[next].view, None))];
+            let mut pass = commands.begin_render_pass(&target, None, Some("TAA Resolve"));
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        self.current = next;
+        self.previous_view_projection = Some(view_projection);
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+const RESOLVE_FRAGMENT: &str =
+    r#"
+struct TaaParams {
+    reprojection: mat4x4<f32>,
+    feedback: f32,
+    flags: u32,
+    _padding: vec2<u32>,
+}
+
+const HISTORY_VALID: u32 = 1u;
+const HAS_VELOCITY: u32 = 2u;
+
+@group(0) @binding(0) var current: texture_2d<f32>;
+@group(0) @binding(1) var history: texture_2d<f32>;
+@group(0) @binding(2) var depth: texture_depth_2d;
+@group(0) @binding(3) var velocity: texture_2d<f32>;
+@group(0) @binding(4) var history_sampler: sampler;
+@group(0) @binding(5) var<uniform> params: TaaParams;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(current));
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let color = textureLoad(current, pixel, 0);
+
+    // History is clamped to the range of the current 3x3 neighborhood
+    var low = color.rgb;
+    var high = color.rgb;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let texel = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let neighbor = textureLoad(current, texel, 0).rgb;
+            low = min(low, neighbor);
+            high = max(high, neighbor);
+        }
+    }
+
+    var previous_uv: vec2<f32>;
+    if ((params.flags & HAS_VELOCITY) != 0u) {
+        previous_uv = in.uv - textureLoad(velocity, pixel, 0).xy;
+    } else {
+        let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+        let previous = params.reprojection * vec4<f32>(ndc, textureLoad(depth, pixel, 0), 1.0);
+        previous_uv = vec2<f32>(previous.x, -previous.y) / previous.w * 0.5 + 0.5;
+    }
+    let on_screen = all(previous_uv >= vec2<f32>(0.0)) && all(previous_uv <= vec2<f32>(1.0));
+    if ((params.flags & HISTORY_VALID) == 0u || !on_screen) {
+        return color;
+    }
+
+    let previous = textureSampleLevel(history, history_sampler, previous_uv, 0.0).rgb;
+    return vec4<f32>(mix(color.rgb, clamp(previous, low, high), params.feedback), color.a);
+}
+"#;
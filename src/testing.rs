@@ -0,0 +1,181 @@
+//! Golden-image snapshot testing.
+//!
+//! [`assert_render_matches`] renders offscreen, reads the image back and
+//! compares it against a committed PNG with a perceptual (YIQ) difference.
+//! A missing golden image is recorded from the render; set
+//! `GEEPU_UPDATE_GOLDEN=1` to re-record existing ones after an intended change.
+
+use crate::recording::TargetReadback;
+use crate::{ GeepuError, GpuContext, RenderCommands, Result, Texture };
+use image::RgbaImage;
+use std::path::{ Path, PathBuf };
+
+/// Format of the offscreen target snapshots are rendered into
+pub const SNAPSHOT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Largest possible YIQ difference between two colors
+const MAX_YIQ_DELTA: f32 = 35215.0;
+
+/// Result of comparing a render against its golden image
+pub struct ImageDiff {
+    /// Pixels whose difference exceeds the tolerance
+    pub mismatched: usize,
+    /// Largest per-pixel difference, from 0 (identical) to 1 (black vs white)
+    pub max_difference: f32,
+    /// The expected image faded to gray with mismatched pixels in red
+    pub image: RgbaImage,
+}
+
+/// Render into a `width` x `height` [`SNAPSHOT_FORMAT`] target and read it back.
+///
+/// `render` records into the commands, typically one pass clearing and drawing
+/// to the target's view; the commands are submitted afterwards.
+pub fn render_offscreen<F>(
+    context: &GpuContext,
+    width: u32,
+    height: u32,
+    render: F
+) -> Result<RgbaImage>
+    where F: FnOnce(&mut RenderCommands, &Texture)
+{
+    let target = Texture::create_empty(
+        context,
+        width,
+        height,
+        SNAPSHOT_FORMAT,
+        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        Some("Snapshot Target")
+    )?;
+
+    let readback = TargetReadback::new(context, width, height)?;
+    let mut commands = RenderCommands::new(context, Some("Snapshot"));
+    render(&mut commands, &target);
+    readback.copy_from(commands.encoder(), &target);
+    commands.submit(context);
+
+    let pixels = readback.read(context)?;
+    RgbaImage::from_raw(width, height, pixels).ok_or_else(|| {
+        GeepuError::TextureError("Snapshot readback has the wrong size".to_string())
+    })
+}
+
+/// Compare two images; pixels differing by more than `tolerance` (0..=1) count as mismatched
+pub fn compare_images(
+    actual: &RgbaImage,
+    expected: &RgbaImage,
+    tolerance: f32
+) -> Result<ImageDiff> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(
+            GeepuError::Other(
+                format!(
+                    "Image size {:?} does not match expected {:?}",
+                    actual.dimensions(),
+                    expected.dimensions()
+                )
+            )
+        );
+    }
+
+    let mut image = RgbaImage::new(expected.width(), expected.height());
+    let mut mismatched = 0;
+    let mut max_difference: f32 = 0.0;
+    for ((a, e), out) in actual.pixels().zip(expected.pixels()).zip(image.pixels_mut()) {
+        let difference = yiq_difference(a.0, e.0);
+        max_difference = max_difference.max(difference);
+        if difference > tolerance {
+            mismatched += 1;
+            out.0 = [255, 0, 0, 255];
+        } else {
+            let [y, _, _] = yiq(blend_white(e.0));
+            let gray = (255.0 - (255.0 - y) * 0.25) as u8;
+            out.0 = [gray, gray, gray, 255];
+        }
+    }
+
+    Ok(ImageDiff { mismatched, max_difference, image })
+}
+
+/// Render with `render` and assert the result matches the PNG at `golden`.
+///
+/// On failure the render and a diff image are written next to the golden image
+/// as `<name>.actual.png` and `<name>.diff.png`, then the test panics.
+pub fn assert_render_matches<F>(
+    context: &GpuContext,
+    size: (u32, u32),
+    render: F,
+    golden: impl AsRef<Path>,
+    tolerance: f32
+)
+    where F: FnOnce(&mut RenderCommands, &Texture)
+{
+    let golden = golden.as_ref();
+    let actual = render_offscreen(context, size.0, size.1, render).unwrap_or_else(|e| {
+        panic!("Failed to render snapshot {}: {}", golden.display(), e)
+    });
+
+    let update = std::env::var_os("GEEPU_UPDATE_GOLDEN").is_some_and(|value| value != "0");
+    if update || !golden.exists() {
+        if let Some(parent) = golden.parent() {
+            std::fs::create_dir_all(parent).ok();
+        }
+        actual.save(golden).unwrap_or_else(|e| {
+            panic!("Failed to write golden image {}: {}", golden.display(), e)
+        });
+        eprintln!("Recorded golden image {}", golden.display());
+        return;
+    }
+
+    let expected = image
+        ::open(golden)
+        .unwrap_or_else(|e| panic!("Failed to read golden image {}: {}", golden.display(), e))
+        .to_rgba8();
+    let diff = compare_images(&actual, &expected, tolerance).unwrap_or_else(|e| {
+        panic!("Snapshot {} does not match: {}", golden.display(), e)
+    });
+    if diff.mismatched == 0 {
+        return;
+    }
+
+    let actual_path = sibling(golden, "actual");
+    let diff_path = sibling(golden, "diff");
+    actual.save(&actual_path).ok();
+    diff.image.save(&diff_path).ok();
+    panic!(
+        "Snapshot {} differs in {} pixels (max difference {:.3}, tolerance {:.3}); see {} and {}",
+        golden.display(),
+        diff.mismatched,
+        diff.max_difference,
+        tolerance,
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+/// `golden/triangle.png` -> `golden/triangle.<suffix>.png`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+/// Perceptual difference of two sRGB pixels in 0..=1, after blending both over white
+fn yiq_difference(a: [u8; 4], b: [u8; 4]) -> f32 {
+    let [ya, ia, qa] = yiq(blend_white(a));
+    let [yb, ib, qb] = yiq(blend_white(b));
+    let (y, i, q) = (ya - yb, ia - ib, qa - qb);
+    let delta = 0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q;
+    (delta / MAX_YIQ_DELTA).sqrt().min(1.0)
+}
+
+fn blend_white(pixel: [u8; 4]) -> [f32; 3] {
+    let alpha = (pixel[3] as f32) / 255.0;
+    [0, 1, 2].map(|c| 255.0 + ((pixel[c] as f32) - 255.0) * alpha)
+}
+
+fn yiq([r, g, b]: [f32; 3]) -> [f32; 3] {
+    [
+        r * 0.298_895 + g * 0.586_622 + b * 0.114_482,
+        r * 0.595_978 - g * 0.274_176 - b * 0.321_802,
+        r * 0.211_470 - g * 0.522_617 + b * 0.311_147,
+    ]
+}
@@ -1,4 +1,6 @@
-use crate::{ GpuContext, GeepuError, Result };
+use crate::{ GpuContext, GeepuError, ResourceKind, Result };
+use crate::resources::{ texture_size_bytes, TrackedResource };
+use crate::profiling::profile_scope;
 use wgpu::util::DeviceExt;
 
 /// A wrapper around wgpu::Texture with convenient methods
@@ -6,6 +8,7 @@ pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    _tracked: Option<TrackedResource>,
 }
 
 impl Texture {
@@ -18,25 +21,28 @@ impl Texture {
         format: wgpu::TextureFormat,
         label: Option<&str>
     ) -> Result<Self> {
-        let texture = context.device.create_texture_with_data(
-            &context.queue,
-            &(wgpu::TextureDescriptor {
-                label,
-                size: wgpu::Extent3d {
-                    width,
-                    height,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            }),
-            wgpu::util::TextureDataOrder::LayerMajor,
-            bytes
-        );
+        profile_scope!("geepu::texture_upload", label.unwrap_or_default());
+        let texture = context.capture_errors("create_texture", label, || {
+            context.device.create_texture_with_data(
+                &context.queue,
+                &(wgpu::TextureDescriptor {
+                    label: context.prefixed_label(label).as_deref(),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                }),
+                wgpu::util::TextureDataOrder::LayerMajor,
+                bytes
+            )
+        })?;
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = context.device.create_sampler(
@@ -51,11 +57,88 @@ impl Texture {
             })
         );
 
-        Ok(Self {
-            texture,
-            view,
-            sampler,
-        })
+        Ok(Self::tracked(context, texture, view, sampler, label))
+    }
+
+    /// Create an sRGB RGBA8 texture from tightly packed pixel data
+    pub fn from_rgba8(
+        context: &GpuContext,
+        bytes: &[u8],
+        width: u32,
+        height: u32,
+        label: Option<&str>
+    ) -> Result<Self> {
+        if bytes.len() != (width as usize) * (height as usize) * 4 {
+            return Err(
+                GeepuError::TextureError(
+                    format!(
+                        "Expected {} bytes for {}x{} RGBA8 data, got {}",
+                        (width as usize) * (height as usize) * 4,
+                        width,
+                        height,
+                        bytes.len()
+                    )
+                )
+            );
+        }
+        Self::from_bytes(context, bytes, width, height, wgpu::TextureFormat::Rgba8UnormSrgb, label)
+    }
+
+    /// Create an HDR `Rgba32Float` texture from tightly packed RGBA floats
+    pub fn from_rgba32f(
+        context: &GpuContext,
+        data: &[f32],
+        width: u32,
+        height: u32,
+        label: Option<&str>
+    ) -> Result<Self> {
+        if data.len() != (width as usize) * (height as usize) * 4 {
+            return Err(
+                GeepuError::TextureError(
+                    format!(
+                        "Expected {} floats for {}x{} RGBA data, got {}",
+                        (width as usize) * (height as usize) * 4,
+                        width,
+                        height,
+                        data.len()
+                    )
+                )
+            );
+        }
+        Self::from_bytes(
+            context,
+            bytemuck::cast_slice(data),
+            width,
+            height,
+            wgpu::TextureFormat::Rgba32Float,
+            label
+        )
+    }
+
+    /// Create a texture from a decoded image (HDR images become `Rgba32Float`)
+    #[cfg(feature = "image")]
+    pub fn from_image(
+        context: &GpuContext,
+        image: &image::DynamicImage,
+        label: Option<&str>
+    ) -> Result<Self> {
+        use image::DynamicImage;
+
+        if matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)) {
+            let rgba = image.to_rgba32f();
+            let (width, height) = rgba.dimensions();
+            return Self::from_rgba32f(context, &rgba, width, height, label);
+        }
+        let rgba = image.to_rgba8();
+        let (width, height) = rgba.dimensions();
+        Self::from_rgba8(context, &rgba, width, height, label)
+    }
+
+    /// Decode an encoded image (PNG, JPEG, Radiance HDR) and create a texture from it
+    #[cfg(feature = "image")]
+    pub fn from_image_bytes(context: &GpuContext, bytes: &[u8], label: Option<&str>) -> Result<Self> {
+        let image = image::load_from_memory(bytes)?;
+        Self::from_image(context, &image, label)
     }
 
     /// Create an empty texture for rendering
@@ -67,22 +150,24 @@ impl Texture {
         usage: wgpu::TextureUsages,
         label: Option<&str>
     ) -> Result<Self> {
-        let texture = context.device.create_texture(
-            &(wgpu::TextureDescriptor {
-                label,
-                size: wgpu::Extent3d {
-                    width,
-                    height,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format,
-                usage,
-                view_formats: &[],
-            })
-        );
+        let texture = context.capture_errors("create_texture", label, || {
+            context.device.create_texture(
+                &(wgpu::TextureDescriptor {
+                    label: context.prefixed_label(label).as_deref(),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+            )
+        })?;
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = context.device.create_sampler(
@@ -97,11 +182,179 @@ impl Texture {
             })
         );
 
-        Ok(Self {
-            texture,
-            view,
-            sampler,
-        })
+        Ok(Self::tracked(context, texture, view, sampler, label))
+    }
+
+    /// Create an empty cube texture with six square faces and a cube view
+    pub fn create_cubemap(
+        context: &GpuContext,
+        size: u32,
+        format: wgpu::TextureFormat,
+        mip_level_count: u32,
+        usage: wgpu::TextureUsages,
+        label: Option<&str>
+    ) -> Result<Self> {
+        let texture = context.capture_errors("create_texture", label, || {
+            context.device.create_texture(
+                &(wgpu::TextureDescriptor {
+                    label: context.prefixed_label(label).as_deref(),
+                    size: wgpu::Extent3d {
+                        width: size,
+                        height: size,
+                        depth_or_array_layers: 6,
+                    },
+                    mip_level_count,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+            )
+        })?;
+
+        Ok(Self::cube(context, texture, label))
+    }
+
+    /// Create a cube texture from six square faces of tightly packed `format`
+    /// data, in +X, -X, +Y, -Y, +Z, -Z order
+    pub fn cubemap_from_bytes(
+        context: &GpuContext,
+        faces: [&[u8]; 6],
+        size: u32,
+        format: wgpu::TextureFormat,
+        label: Option<&str>
+    ) -> Result<Self> {
+        let (block_width, block_height) = format.block_dimensions();
+        let block_size = format.block_copy_size(None).ok_or_else(|| {
+            GeepuError::TextureError(format!("Cannot upload {:?} data to a cubemap", format))
+        })?;
+        let face_size = (size.div_ceil(block_width) as usize) *
+            (size.div_ceil(block_height) as usize) *
+            (block_size as usize);
+        if let Some(face) = faces.iter().position(|face| face.len() != face_size) {
+            return Err(
+                GeepuError::TextureError(
+                    format!(
+                        "Expected {} bytes for cubemap face {} ({}x{} {:?}), got {}",
+                        face_size,
+                        face,
+                        size,
+                        size,
+                        format,
+                        faces[face].len()
+                    )
+                )
+            );
+        }
+
+        profile_scope!("geepu::texture_upload", label.unwrap_or_default());
+        let texture = context.capture_errors("create_texture", label, || {
+            context.device.create_texture_with_data(
+                &context.queue,
+                &(wgpu::TextureDescriptor {
+                    label: context.prefixed_label(label).as_deref(),
+                    size: wgpu::Extent3d {
+                        width: size,
+                        height: size,
+                        depth_or_array_layers: 6,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                }),
+                wgpu::util::TextureDataOrder::LayerMajor,
+                &faces.concat()
+            )
+        })?;
+
+        Ok(Self::cube(context, texture, label))
+    }
+
+    /// Create a cube texture from six decoded images in +X, -X, +Y, -Y, +Z, -Z
+    /// order, all square and the same size. LDR faces become `Rgba8UnormSrgb`;
+    /// if any face is HDR they all become [`CUBEMAP_FORMAT`](crate::CUBEMAP_FORMAT),
+    /// which stays filterable for [`Skybox`](crate::Skybox) and IBL.
+    #[cfg(feature = "image")]
+    pub fn cubemap_from_images(
+        context: &GpuContext,
+        faces: [&image::DynamicImage; 6],
+        label: Option<&str>
+    ) -> Result<Self> {
+        use image::DynamicImage;
+
+        let size = faces[0].width();
+        if let Some(face) = faces.iter().position(|f| f.width() != size || f.height() != size) {
+            return Err(
+                GeepuError::TextureError(
+                    format!(
+                        "Cubemap faces must be square and the same size; face 0 is {}x{}, \
+                         face {} is {}x{}",
+                        size,
+                        faces[0].height(),
+                        face,
+                        faces[face].width(),
+                        faces[face].height()
+                    )
+                )
+            );
+        }
+
+        let is_hdr = |face: &&DynamicImage| {
+            matches!(face, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_))
+        };
+        let hdr = faces.iter().any(is_hdr);
+        let data: Vec<Vec<u8>> = if hdr {
+            faces
+                .iter()
+                .map(|face| {
+                    let halves: Vec<u16> = face
+                        .to_rgba32f()
+                        .iter()
+                        .map(|&value| crate::f32_to_f16(value))
+                        .collect();
+                    bytemuck::cast_slice(&halves).to_vec()
+                })
+                .collect()
+        } else {
+            faces
+                .iter()
+                .map(|face| face.to_rgba8().into_raw())
+                .collect()
+        };
+        let format = if hdr {
+            crate::CUBEMAP_FORMAT
+        } else {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        };
+        let faces = std::array::from_fn(|i| data[i].as_slice());
+        Self::cubemap_from_bytes(context, faces, size, format, label)
+    }
+
+    /// Wrap a six-layer texture with a cube view and a trilinear sampler
+    fn cube(context: &GpuContext, texture: wgpu::Texture, label: Option<&str>) -> Self {
+        let view = texture.create_view(
+            &(wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                ..Default::default()
+            })
+        );
+        let sampler = context.device.create_sampler(
+            &(wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            })
+        );
+
+        Self::tracked(context, texture, view, sampler, label)
     }
 
     /// Create a depth texture
@@ -112,23 +365,25 @@ impl Texture {
         label: Option<&str>
     ) -> Result<Self> {
         let format = wgpu::TextureFormat::Depth32Float;
-        let texture = context.device.create_texture(
-            &(wgpu::TextureDescriptor {
-                label,
-                size: wgpu::Extent3d {
-                    width,
-                    height,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format,
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT |
-                wgpu::TextureUsages::TEXTURE_BINDING,
-                view_formats: &[],
-            })
-        );
+        let texture = context.capture_errors("create_texture", label, || {
+            context.device.create_texture(
+                &(wgpu::TextureDescriptor {
+                    label: context.prefixed_label(label).as_deref(),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT |
+                    wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+            )
+        })?;
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = context.device.create_sampler(
@@ -146,11 +401,7 @@ impl Texture {
             })
         );
 
-        Ok(Self {
-            texture,
-            view,
-            sampler,
-        })
+        Ok(Self::tracked(context, texture, view, sampler, label))
     }
 
     /// Create a render target texture
@@ -171,6 +422,21 @@ impl Texture {
         )
     }
 
+    pub(crate) fn tracked(
+        context: &GpuContext,
+        texture: wgpu::Texture,
+        view: wgpu::TextureView,
+        sampler: wgpu::Sampler,
+        label: Option<&str>
+    ) -> Self {
+        let _tracked = context.track_resource(
+            ResourceKind::Texture,
+            label.unwrap_or("Texture"),
+            texture_size_bytes(&texture)
+        );
+        Self { texture, view, sampler, _tracked }
+    }
+
     /// Get size of the texture
     pub fn size(&self) -> (u32, u32) {
         let size = self.texture.size();
@@ -181,6 +447,34 @@ impl Texture {
     pub fn format(&self) -> wgpu::TextureFormat {
         self.texture.format()
     }
+
+    /// Number of array layers, or the depth of a 3D texture
+    pub fn depth_or_array_layers(&self) -> u32 {
+        self.texture.depth_or_array_layers()
+    }
+
+    /// Number of mip levels
+    pub fn mip_level_count(&self) -> u32 {
+        self.texture.mip_level_count()
+    }
+
+    /// Size of mip `level`, never below 1x1
+    pub fn mip_size(&self, level: u32) -> (u32, u32) {
+        let (width, height) = self.size();
+        ((width >> level).max(1), (height >> level).max(1))
+    }
+
+    /// A view of the single mip `level`, e.g. to bind it as a compute shader's
+    /// storage texture output while other levels are read
+    pub fn mip_view(&self, level: u32) -> wgpu::TextureView {
+        self.texture.create_view(
+            &(wgpu::TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        )
+    }
 }
 
 /// Builder for creating textures with custom settings
@@ -192,6 +486,8 @@ pub struct TextureBuilder {
     label: Option<String>,
     mip_level_count: u32,
     sample_count: u32,
+    depth_or_array_layers: u32,
+    dimension: wgpu::TextureViewDimension,
     sampler_descriptor: wgpu::SamplerDescriptor<'static>,
 }
 
@@ -205,6 +501,8 @@ impl TextureBuilder {
             label: None,
             mip_level_count: 1,
             sample_count: 1,
+            depth_or_array_layers: 1,
+            dimension: wgpu::TextureViewDimension::D2,
             sampler_descriptor: wgpu::SamplerDescriptor {
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
                 address_mode_v: wgpu::AddressMode::ClampToEdge,
@@ -242,49 +540,107 @@ impl TextureBuilder {
         self
     }
 
+    /// Number of array layers, or the depth of a 3D texture
+    pub fn depth_or_array_layers(mut self, count: u32) -> Self {
+        self.depth_or_array_layers = count;
+        self
+    }
+
+    /// Shape of the texture's view, e.g. `D2Array` for a sprite array or `D3`
+    /// for a volume. The texture itself is 3D for `D3`, 1D for `D1` and 2D
+    /// otherwise.
+    pub fn dimension(mut self, dimension: wgpu::TextureViewDimension) -> Self {
+        self.dimension = dimension;
+        self
+    }
+
     pub fn sampler(mut self, sampler_descriptor: wgpu::SamplerDescriptor<'static>) -> Self {
         self.sampler_descriptor = sampler_descriptor;
         self
     }
 
     pub fn build(self, context: &GpuContext) -> Result<Texture> {
-        let texture = context.device.create_texture(
-            &(wgpu::TextureDescriptor {
-                label: self.label.as_deref(),
-                size: wgpu::Extent3d {
-                    width: self.width,
-                    height: self.height,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: self.mip_level_count,
-                sample_count: self.sample_count,
-                dimension: wgpu::TextureDimension::D2,
-                format: self.format,
-                usage: self.usage,
-                view_formats: &[],
+        let layers = self.depth_or_array_layers;
+        let valid_layers = match self.dimension {
+            wgpu::TextureViewDimension::D1 | wgpu::TextureViewDimension::D2 => layers == 1,
+            wgpu::TextureViewDimension::Cube => layers == 6,
+            wgpu::TextureViewDimension::CubeArray => layers > 0 && layers.is_multiple_of(6),
+            wgpu::TextureViewDimension::D2Array | wgpu::TextureViewDimension::D3 => layers > 0,
+        };
+        if !valid_layers {
+            return Err(
+                GeepuError::TextureError(
+                    format!("{:?} texture cannot have {} layers", self.dimension, layers)
+                )
+            );
+        }
+        let dimension = match self.dimension {
+            wgpu::TextureViewDimension::D1 => wgpu::TextureDimension::D1,
+            wgpu::TextureViewDimension::D3 => wgpu::TextureDimension::D3,
+            _ => wgpu::TextureDimension::D2,
+        };
+
+        let texture = context.capture_errors("create_texture", self.label.as_deref(), || {
+            context.device.create_texture(
+                &(wgpu::TextureDescriptor {
+                    label: context.prefixed_label(self.label.as_deref()).as_deref(),
+                    size: wgpu::Extent3d {
+                        width: self.width,
+                        height: self.height,
+                        depth_or_array_layers: layers,
+                    },
+                    mip_level_count: self.mip_level_count,
+                    sample_count: self.sample_count,
+                    dimension,
+                    format: self.format,
+                    usage: self.usage,
+                    view_formats: &[],
+                })
+            )
+        })?;
+
+        let view = texture.create_view(
+            &(wgpu::TextureViewDescriptor {
+                dimension: Some(self.dimension),
+                ..Default::default()
+            })
+        );
+        let sampler_label = context.prefixed_label(self.sampler_descriptor.label);
+        let sampler = context.device.create_sampler(
+            &(wgpu::SamplerDescriptor {
+                label: sampler_label.as_deref(),
+                ..self.sampler_descriptor.clone()
             })
         );
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = context.device.create_sampler(&self.sampler_descriptor);
-
-        Ok(Texture {
-            texture,
-            view,
-            sampler,
-        })
+        Ok(Texture::tracked(context, texture, view, sampler, self.label.as_deref()))
     }
 }
 
 /// Convenience functions for common texture operations
 impl Texture {
-    /// Write data to texture
+    /// Write data to texture, starting at the first layer. `data` may hold
+    /// several consecutive `width` x `height` layers (array layers or slices
+    /// of a 3D texture).
     pub fn write_data(
         &self,
         context: &GpuContext,
         data: &[u8],
         width: u32,
         height: u32
+    ) -> Result<()> {
+        self.write_layers(context, 0, data, width, height)
+    }
+
+    /// Write one or more consecutive `width` x `height` layers starting at
+    /// array layer (or 3D slice) `first_layer`
+    pub fn write_layers(
+        &self,
+        context: &GpuContext,
+        first_layer: u32,
+        data: &[u8],
+        width: u32,
+        height: u32
     ) -> Result<()> {
         let bytes_per_pixel = match self.format() {
             | wgpu::TextureFormat::Rgba8Unorm
@@ -303,11 +659,39 @@ impl Texture {
             }
         };
 
-        context.queue.write_texture(
+        let layer_size = (bytes_per_pixel as usize) * (width as usize) * (height as usize);
+        let available = self.depth_or_array_layers().saturating_sub(first_layer);
+        if layer_size == 0 || !data.len().is_multiple_of(layer_size) {
+            return Err(
+                GeepuError::TextureError(
+                    format!(
+                        "{} bytes is not a whole number of {}x{} layers",
+                        data.len(),
+                        width,
+                        height
+                    )
+                )
+            );
+        }
+        let layers = (data.len() / layer_size) as u32;
+        if layers > available {
+            return Err(
+                GeepuError::TextureError(
+                    format!(
+                        "Cannot write {} layers from layer {} of a texture with {}",
+                        layers,
+                        first_layer,
+                        self.depth_or_array_layers()
+                    )
+                )
+            );
+        }
+
+        context.write_texture(
             wgpu::ImageCopyTexture {
                 texture: &self.texture,
                 mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
+                origin: wgpu::Origin3d { x: 0, y: 0, z: first_layer },
                 aspect: wgpu::TextureAspect::All,
             },
             data,
@@ -319,7 +703,7 @@ impl Texture {
             wgpu::Extent3d {
                 width,
                 height,
-                depth_or_array_layers: 1,
+                depth_or_array_layers: layers,
             }
         );
 
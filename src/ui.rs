@@ -0,0 +1,460 @@
+use crate::{
+    BindGroupBuilder,
+    BindGroupLayoutBuilder,
+    Camera,
+    CameraUniform,
+    DynamicMesh,
+    GpuContext,
+    RenderPass,
+    RenderPipeline,
+    Result,
+    Texture,
+    TypedBuffer,
+};
+
+const KIND_TEXTURED: u32 = 0;
+const KIND_SHAPE: u32 = 1;
+
+/// Vertex of a UI quad
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UiVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+    /// Offset from the shape's center
+    pub local: [f32; 2],
+    pub half_size: [f32; 2],
+    /// Corner radius and border width
+    pub shape: [f32; 2],
+    pub border_color: [f32; 4],
+    /// 0 for textured quads, 1 for rounded-rect shapes
+    pub kind: u32,
+}
+
+/// Fill, corner radius and border of a rectangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RectStyle {
+    pub color: [f32; 4],
+    pub radius: f32,
+    pub border_width: f32,
+    pub border_color: [f32; 4],
+}
+
+impl RectStyle {
+    /// A solid rectangle with square corners and no border
+    pub fn new(color: [f32; 4]) -> Self {
+        Self {
+            color,
+            radius: 0.0,
+            border_width: 0.0,
+            border_color: color,
+        }
+    }
+
+    pub fn radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Draw a border of `width` inside the rectangle's edge
+    pub fn border(mut self, width: f32, color: [f32; 4]) -> Self {
+        self.border_width = width;
+        self.border_color = color;
+        self
+    }
+}
+
+/// A texture region stretched as a nine-slice panel: corners keep their size,
+/// edges stretch along one axis and the center along both
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NineSlice {
+    /// Source region in texels as `[x, y, width, height]`
+    pub region: [f32; 4],
+    /// Border sizes in texels as `[left, top, right, bottom]`
+    pub insets: [f32; 4],
+}
+
+impl NineSlice {
+    pub fn new(region: [f32; 4], insets: [f32; 4]) -> Self {
+        Self { region, insets }
+    }
+
+    /// Split a destination rectangle into up to nine `(min, size, uv_rect)` cells for a
+    /// texture of `texture_size`. Borders keep their texel size unless the
+    /// rectangle is too small to fit them; empty cells are skipped.
+    pub fn cells(
+        &self,
+        min: [f32; 2],
+        size: [f32; 2],
+        texture_size: [f32; 2]
+    ) -> Vec<([f32; 2], [f32; 2], [f32; 4])> {
+        let [x, y, width, height] = self.region;
+        let [left, top, right, bottom] = self.insets;
+        // Shrink the borders proportionally when they do not fit
+        let scale_x = (size[0] / (left + right).max(f32::EPSILON)).min(1.0);
+        let scale_y = (size[1] / (top + bottom).max(f32::EPSILON)).min(1.0);
+
+        let columns = [0.0, left * scale_x, size[0] - right * scale_x, size[0]];
+        let rows = [0.0, top * scale_y, size[1] - bottom * scale_y, size[1]];
+        let u = [x, x + left, x + width - right, x + width].map(|u| u / texture_size[0]);
+        let v = [y, y + top, y + height - bottom, y + height].map(|v| v / texture_size[1]);
+
+        let mut cells = Vec::with_capacity(9);
+        for row in 0..3 {
+            for column in 0..3 {
+                let cell_size = [columns[column + 1] - columns[column], rows[row + 1] - rows[row]];
+                if cell_size[0] <= 0.0 || cell_size[1] <= 0.0 {
+                    continue;
+                }
+                cells.push((
+                    [min[0] + columns[column], min[1] + rows[row]],
+                    cell_size,
+                    [u[column], v[row], u[column + 1], v[row + 1]],
+                ));
+            }
+        }
+        cells
+    }
+}
+
+/// Batches UI quads (solid and rounded rectangles with borders, nine-slice
+/// panels, wide lines) into one vertex buffer drawn in submission order.
+///
+/// Coordinates are those of the camera passed to [`prepare`](Self::prepare),
+/// typically [`Camera::pixels`] so positions are window pixels with Y down.
+/// Rounded corners are anti-aliased with a signed distance in the fragment shader.
+pub struct UiRenderer {
+    vertices: DynamicMesh<UiVertex>,
+    texture_size: [f32; 2],
+    camera: TypedBuffer<CameraUniform>,
+    bind_group: wgpu::BindGroup,
+    pipeline: RenderPipeline,
+    _texture: Texture,
+}
+
+impl UiRenderer {
+    /// Create a UI renderer; `texture` is the atlas nine-slice panels are cut from
+    pub fn new(
+        context: &GpuContext,
+        texture: Option<Texture>,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>
+    ) -> Result<Self> {
+        Self::new_multisampled(context, texture, color_format, depth_format, 1)
+    }
+
+    /// [`new`](Self::new) for targets with `samples` samples per pixel, e.g.
+    /// [`GpuContext::sample_count`] for passes into the surface
+    pub fn new_multisampled(
+        context: &GpuContext,
+        texture: Option<Texture>,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        samples: u32
+    ) -> Result<Self> {
+        let texture = match texture {
+            Some(texture) => texture,
+            None => Texture::from_rgba8(context, &[255; 4], 1, 1, Some("UI White"))?,
+        };
+        let (width, height) = texture.size();
+        let camera = TypedBuffer::uniform(context, &[bytemuck::Zeroable::zeroed()])?;
+        let layout = BindGroupLayoutBuilder::new()
+            .uniform_buffer(0, wgpu::ShaderStages::VERTEX)
+            .texture(
+                1,
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::TextureViewDimension::D2,
+                false
+            )
+            .sampler(2, wgpu::ShaderStages::FRAGMENT, wgpu::SamplerBindingType::Filtering)
+            .build(context, Some("UI Bind Group Layout"));
+        let bind_group = BindGroupBuilder::new(&layout)
+            .buffer(0, camera.buffer())
+            .texture_view(1, &texture.view)
+            .sampler(2, &texture.sampler)
+            .build(context, Some("UI Bind Group"));
+
+        let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&context.label("UI Shader")),
+            source: wgpu::ShaderSource::Wgsl(format!("{}{}", Camera::WGSL, SHADER).into()),
+        });
+        let pipeline_layout = context.device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some(&context.label("UI Pipeline Layout")),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            })
+        );
+        let pipeline = context.device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some(&context.label("UI Pipeline")),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<UiVertex>() as u64,
+                            step_mode: wgpu::VertexStepMode::Vertex,
+                            attributes: &wgpu::vertex_attr_array![
+                                0 => Float32x2,
+                                1 => Float32x2,
+                                2 => Float32x4,
+                                3 => Float32x2,
+                                4 => Float32x2,
+                                5 => Float32x2,
+                                6 => Float32x4,
+                                7 => Uint32
+                            ],
+                        },
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: color_format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // UI draws on top of whatever is in the pass
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: samples,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            })
+        );
+
+        Ok(Self {
+            vertices: DynamicMesh::new(context, 1024)?,
+            texture_size: [width as f32, height as f32],
+            camera,
+            bind_group,
+            pipeline: RenderPipeline {
+                pipeline,
+                bind_group_layouts: vec![layout],
+                vertex_buffer_count: 1,
+                bindings: Vec::new(),
+            },
+            _texture: texture,
+        })
+    }
+
+    /// Draw a solid rectangle with its top-left corner at `min`
+    pub fn rect(&mut self, min: [f32; 2], size: [f32; 2], color: [f32; 4]) {
+        self.styled_rect(min, size, RectStyle::new(color));
+    }
+
+    /// Draw a rectangle with rounded corners and an optional border
+    pub fn styled_rect(&mut self, min: [f32; 2], size: [f32; 2], style: RectStyle) {
+        let half_size = [size[0] * 0.5, size[1] * 0.5];
+        let radius = style.radius.clamp(0.0, half_size[0].min(half_size[1]));
+        self.push_quad(min, size, [0.0; 4], |vertex, corner| {
+            vertex.color = style.color;
+            vertex.local = [
+                (corner[0] - 0.5) * size[0],
+                (corner[1] - 0.5) * size[1],
+            ];
+            vertex.half_size = half_size;
+            vertex.shape = [radius, style.border_width];
+            vertex.border_color = style.border_color;
+            vertex.kind = KIND_SHAPE;
+        });
+    }
+
+    /// Draw a nine-slice panel from the atlas, tinted by `color`
+    pub fn nine_slice(
+        &mut self,
+        min: [f32; 2],
+        size: [f32; 2],
+        slice: &NineSlice,
+        color: [f32; 4]
+    ) {
+        for (cell_min, cell_size, uv_rect) in slice.cells(min, size, self.texture_size) {
+            self.push_quad(cell_min, cell_size, uv_rect, |vertex, _| {
+                vertex.color = color;
+                vertex.kind = KIND_TEXTURED;
+            });
+        }
+    }
+
+    /// Draw a region of the atlas (`[x, y, width, height]` in texels) stretched over a rectangle
+    pub fn image(&mut self, min: [f32; 2], size: [f32; 2], region: [f32; 4], color: [f32; 4]) {
+        let [x, y, width, height] = region;
+        let [tw, th] = self.texture_size;
+        let uv_rect = [x / tw, y / th, (x + width) / tw, (y + height) / th];
+        self.push_quad(min, size, uv_rect, |vertex, _| {
+            vertex.color = color;
+            vertex.kind = KIND_TEXTURED;
+        });
+    }
+
+    /// Draw an anti-aliased line from `start` to `end`, `width` pixels wide, with round caps
+    pub fn line(&mut self, start: [f32; 2], end: [f32; 2], width: f32, color: [f32; 4]) {
+        let delta = [end[0] - start[0], end[1] - start[1]];
+        let length = (delta[0] * delta[0] + delta[1] * delta[1]).sqrt();
+        let direction = if length > f32::EPSILON {
+            [delta[0] / length, delta[1] / length]
+        } else {
+            [1.0, 0.0]
+        };
+        let normal = [-direction[1], direction[0]];
+        let radius = width.max(0.0) * 0.5;
+        // A capsule is a rounded rectangle along the segment; one extra pixel
+        // around it leaves room for the anti-aliased edge
+        let half_size = [length * 0.5 + radius, radius];
+        let extent = [half_size[0] + 1.0, half_size[1] + 1.0];
+        let center = [(start[0] + end[0]) * 0.5, (start[1] + end[1]) * 0.5];
+        for corner in [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [0.0, 0.0], [1.0, 1.0], [1.0, 0.0]] {
+            let local = [(corner[0] * 2.0 - 1.0) * extent[0], (corner[1] * 2.0 - 1.0) * extent[1]];
+            self.vertices.push(UiVertex {
+                position: [
+                    center[0] + direction[0] * local[0] + normal[0] * local[1],
+                    center[1] + direction[1] * local[0] + normal[1] * local[1],
+                ],
+                color,
+                local,
+                half_size,
+                shape: [radius, 0.0],
+                border_color: color,
+                kind: KIND_SHAPE,
+                ..bytemuck::Zeroable::zeroed()
+            });
+        }
+    }
+
+    /// Draw connected lines through `points`; the round caps make round joins
+    pub fn polyline(&mut self, points: &[[f32; 2]], width: f32, color: [f32; 4]) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], width, color);
+        }
+    }
+
+    /// Number of quads queued for the current frame
+    pub fn quad_count(&self) -> usize {
+        self.vertices.pending().len() / 6
+    }
+
+    /// Upload this frame's quads and camera, then clear the list for the next frame
+    pub fn prepare(&mut self, context: &GpuContext, camera: &Camera) -> Result<()> {
+        camera.update_buffer(context, &self.camera)?;
+        self.vertices.upload(context)
+    }
+
+    /// Draw the quads uploaded by the last [`prepare`](Self::prepare)
+    pub fn render<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if self.vertices.vertex_count() == 0 {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        self.vertices.draw(pass);
+    }
+
+    /// Append two triangles covering a rectangle, letting `style` fill in the
+    /// per-vertex fields from the corner's position in `0..=1`
+    fn push_quad(
+        &mut self,
+        min: [f32; 2],
+        size: [f32; 2],
+        uv_rect: [f32; 4],
+        style: impl Fn(&mut UiVertex, [f32; 2])
+    ) {
+        for corner in [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [0.0, 0.0], [1.0, 1.0], [1.0, 0.0]] {
+            let mut vertex = UiVertex {
+                position: [min[0] + corner[0] * size[0], min[1] + corner[1] * size[1]],
+                uv: [
+                    uv_rect[0] + corner[0] * (uv_rect[2] - uv_rect[0]),
+                    uv_rect[1] + corner[1] * (uv_rect[3] - uv_rect[1]),
+                ],
+                ..bytemuck::Zeroable::zeroed()
+            };
+            style(&mut vertex, corner);
+            self.vertices.push(vertex);
+        }
+    }
+}
+
+const SHADER: &str =
+    r#"
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var ui_texture: texture_2d<f32>;
+@group(0) @binding(2) var ui_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) local: vec2<f32>,
+    @location(4) half_size: vec2<f32>,
+    @location(5) shape: vec2<f32>,
+    @location(6) border_color: vec4<f32>,
+    @location(7) kind: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) local: vec2<f32>,
+    @location(3) half_size: vec2<f32>,
+    @location(4) shape: vec2<f32>,
+    @location(5) border_color: vec4<f32>,
+    @location(6) @interpolate(flat) kind: u32,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_projection * vec4<f32>(in.position, 0.0, 1.0);
+    out.uv = in.uv;
+    out.color = in.color;
+    out.local = in.local;
+    out.half_size = in.half_size;
+    out.shape = in.shape;
+    out.border_color = in.border_color;
+    out.kind = in.kind;
+    return out;
+}
+
+fn rounded_box(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + vec2<f32>(radius);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = textureSample(ui_texture, ui_sampler, in.uv);
+    let distance = rounded_box(in.local, in.half_size, in.shape.x);
+    let pixel = max(fwidth(distance), 1e-4);
+    if (in.kind == 0u) {
+        return in.color * texel;
+    }
+
+    // Fill inside the border, border color in the band along the edge
+    let fill = clamp(0.5 - (distance + in.shape.y) / pixel, 0.0, 1.0);
+    var color = in.color;
+    if (in.shape.y > 0.0) {
+        color = mix(in.border_color, in.color, fill);
+    }
+    color.a = color.a * clamp(0.5 - distance / pixel, 0.0, 1.0);
+    return color;
+}
+"#;
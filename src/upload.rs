@@ -0,0 +1,172 @@
+use std::num::NonZeroU64;
+use std::sync::Mutex;
+use wgpu::util::StagingBelt;
+
+/// Size of each staging chunk; larger writes get a chunk of their own
+const CHUNK_SIZE: u64 = 1 << 20;
+
+/// Buffer and texture writes collected through a frame and encoded as copies
+/// out of shared staging memory, submitted ahead of the next command buffer.
+pub(crate) struct UploadBelt {
+    state: Mutex<Uploads>,
+}
+
+struct Uploads {
+    belt: StagingBelt,
+    encoder: Option<wgpu::CommandEncoder>,
+    copies: usize,
+    bytes: u64,
+}
+
+/// Copies [`UploadBelt::finish`] encoded since the last flush
+pub(crate) struct FinishedUploads {
+    pub commands: wgpu::CommandBuffer,
+    pub copies: usize,
+    pub bytes: u64,
+}
+
+impl UploadBelt {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(Uploads {
+                belt: StagingBelt::new(CHUNK_SIZE),
+                encoder: None,
+                copies: 0,
+                bytes: 0,
+            }),
+        }
+    }
+
+    /// Stage `data` to be copied into `buffer` at `offset`
+    pub(crate) fn write_buffer(
+        &self,
+        device: &wgpu::Device,
+        buffer: &wgpu::Buffer,
+        offset: u64,
+        data: &[u8]
+    ) {
+        let Some(size) = NonZeroU64::new(data.len() as u64) else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        let Uploads { belt, encoder, copies, bytes } = &mut *state;
+        let encoder = encoder.get_or_insert_with(|| Self::create_encoder(device));
+        belt.write_buffer(encoder, buffer, offset, size, device).copy_from_slice(data);
+        *copies += 1;
+        *bytes += size.get();
+    }
+
+    /// Stage a texture write, re-laying out the rows to the pitch texture
+    /// copies require. Returns `false` for formats without a single-aspect
+    /// block size, which have to go through the queue instead.
+    pub(crate) fn write_texture(
+        &self,
+        device: &wgpu::Device,
+        texture: wgpu::ImageCopyTexture,
+        data: &[u8],
+        layout: wgpu::ImageDataLayout,
+        size: wgpu::Extent3d
+    ) -> bool {
+        let format = texture.texture.format();
+        let Some(block_size) = format.block_copy_size(Some(texture.aspect)) else {
+            return false;
+        };
+        let (block_width, block_height) = format.block_dimensions();
+        let row_bytes = size.width.div_ceil(block_width) * block_size;
+        let rows = size.height.div_ceil(block_height);
+        let source_pitch = layout.bytes_per_row.unwrap_or(row_bytes) as usize;
+        let source_image_pitch = layout.rows_per_image.unwrap_or(rows) as usize * source_pitch;
+        let pitch = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let staged_size = pitch as u64 * rows as u64 * size.depth_or_array_layers as u64;
+        if staged_size == 0 {
+            return true;
+        }
+
+        let staging = device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some("Texture Upload"),
+                size: staged_size,
+                usage: wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            })
+        );
+        {
+            let mut mapped = staging.slice(..).get_mapped_range_mut();
+            let staged_rows = mapped.chunks_exact_mut(pitch as usize);
+            let source_rows = (0..size.depth_or_array_layers as usize).flat_map(|layer| {
+                (0..rows as usize).map(move |row| {
+                    (layout.offset as usize) + layer * source_image_pitch + row * source_pitch
+                })
+            });
+            for (staged, start) in staged_rows.zip(source_rows) {
+                staged[..row_bytes as usize].copy_from_slice(
+                    &data[start..start + row_bytes as usize]
+                );
+            }
+        }
+        staging.unmap();
+
+        let mut state = self.state.lock().unwrap();
+        let Uploads { encoder, copies, bytes, .. } = &mut *state;
+        let encoder = encoder.get_or_insert_with(|| Self::create_encoder(device));
+        encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: &staging,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(pitch),
+                    rows_per_image: Some(rows),
+                },
+            },
+            texture,
+            size
+        );
+        *copies += 1;
+        *bytes += staged_size;
+        true
+    }
+
+    /// Copy `size` bytes from the start of `source` into `destination` along
+    /// with the staged writes
+    pub(crate) fn copy_buffer(
+        &self,
+        device: &wgpu::Device,
+        source: &wgpu::Buffer,
+        destination: &wgpu::Buffer,
+        size: u64
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let Uploads { encoder, copies, bytes, .. } = &mut *state;
+        let encoder = encoder.get_or_insert_with(|| Self::create_encoder(device));
+        encoder.copy_buffer_to_buffer(source, 0, destination, 0, size);
+        *copies += 1;
+        *bytes += size;
+    }
+
+    /// Close the staging memory and finish the copies recorded so far, if any.
+    /// Submit the commands before calling [`recall`](Self::recall).
+    pub(crate) fn finish(&self) -> Option<FinishedUploads> {
+        let mut state = self.state.lock().unwrap();
+        let encoder = state.encoder.take()?;
+        state.belt.finish();
+        let finished = FinishedUploads {
+            commands: encoder.finish(),
+            copies: std::mem::take(&mut state.copies),
+            bytes: std::mem::take(&mut state.bytes),
+        };
+        Some(finished)
+    }
+
+    /// Make the staging memory of submitted copies available again
+    pub(crate) fn recall(&self) {
+        self.state.lock().unwrap().belt.recall();
+    }
+
+    fn create_encoder(device: &wgpu::Device) -> wgpu::CommandEncoder {
+        device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("Uploads"),
+            })
+        )
+    }
+}
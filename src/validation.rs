@@ -0,0 +1,139 @@
+use crate::{ GeepuError, Result };
+
+/// Inputs a pipeline expects to be bound before it can draw or dispatch
+#[derive(Debug, Clone, Copy)]
+struct PipelineInputs {
+    bind_groups: usize,
+    vertex_buffers: usize,
+}
+
+/// What a render or compute pass has bound so far.
+///
+/// Passes keep one when [`GpuConfig::validate_commands`](crate::GpuConfig::validate_commands)
+/// is on and check it in `try_draw`, `try_draw_indexed` and
+/// `try_dispatch_workgroups`, so a missing binding is reported with the pass
+/// label instead of as a backend validation failure at submit.
+#[derive(Debug, Clone, Default)]
+pub struct PassState {
+    label: Option<String>,
+    pipeline: Option<PipelineInputs>,
+    /// Bit `n` is set once bind group `n` is bound
+    bind_groups: u32,
+    /// Bit `n` is set once vertex buffer slot `n` is bound
+    vertex_buffers: u32,
+    /// Length of the bound index buffer, in indices
+    index_count: Option<u64>,
+}
+
+impl PassState {
+    pub fn new(label: Option<&str>) -> Self {
+        Self { label: label.map(str::to_string), ..Self::default() }
+    }
+
+    /// Record a pipeline with `bind_groups` layouts and `vertex_buffers` vertex buffer layouts
+    pub fn set_pipeline(&mut self, bind_groups: usize, vertex_buffers: usize) {
+        self.pipeline = Some(PipelineInputs { bind_groups, vertex_buffers });
+    }
+
+    pub fn set_bind_group(&mut self, index: u32) {
+        self.bind_groups |= 1u32.checked_shl(index).unwrap_or(0);
+    }
+
+    pub fn set_vertex_buffer(&mut self, slot: u32) {
+        self.vertex_buffers |= 1u32.checked_shl(slot).unwrap_or(0);
+    }
+
+    /// Record an index buffer holding `count` indices
+    pub fn set_index_buffer(&mut self, count: u64) {
+        self.index_count = Some(count);
+    }
+
+    /// Check that a non-indexed draw has everything it needs
+    pub fn check_draw(&self) -> Result<()> {
+        let pipeline = self.require_pipeline("draw")?;
+        self.check_bind_groups(pipeline, "draw")?;
+        self.check_vertex_buffers(pipeline, "draw")
+    }
+
+    /// Check an indexed draw, including that `indices` is ordered and lies within
+    /// the index buffer
+    pub fn check_draw_indexed(&self, indices: &std::ops::Range<u32>) -> Result<()> {
+        let pipeline = self.require_pipeline("draw_indexed")?;
+        self.check_bind_groups(pipeline, "draw_indexed")?;
+        self.check_vertex_buffers(pipeline, "draw_indexed")?;
+        if indices.start > indices.end {
+            let message = format!("index range {}..{} is inverted", indices.start, indices.end);
+            return Err(self.error("draw_indexed", message));
+        }
+        match self.index_count {
+            None => Err(self.error("draw_indexed", "no index buffer is bound".to_string())),
+            Some(count) if (indices.end as u64) > count => {
+                Err(
+                    self.error(
+                        "draw_indexed",
+                        format!(
+                            "indices {}..{} are out of range for an index buffer of {} indices",
+                            indices.start,
+                            indices.end,
+                            count
+                        )
+                    )
+                )
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Check that a dispatch has a pipeline and all of its bind groups
+    pub fn check_dispatch(&self) -> Result<()> {
+        let pipeline = self.require_pipeline("dispatch_workgroups")?;
+        self.check_bind_groups(pipeline, "dispatch_workgroups")
+    }
+
+    fn require_pipeline(&self, source_op: &'static str) -> Result<PipelineInputs> {
+        self.pipeline.ok_or_else(|| {
+            let message = format!("no pipeline is set; call set_pipeline before {}", source_op);
+            self.error(source_op, message)
+        })
+    }
+
+    fn check_bind_groups(&self, pipeline: PipelineInputs, source_op: &'static str) -> Result<()> {
+        match (0..pipeline.bind_groups).find(|&index| !bit(self.bind_groups, index)) {
+            Some(index) => {
+                Err(
+                    self.error(
+                        source_op,
+                        format!("bind group {} required by the pipeline is not set", index)
+                    )
+                )
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn check_vertex_buffers(
+        &self,
+        pipeline: PipelineInputs,
+        source_op: &'static str
+    ) -> Result<()> {
+        match (0..pipeline.vertex_buffers).find(|&slot| !bit(self.vertex_buffers, slot)) {
+            Some(slot) => {
+                Err(
+                    self.error(
+                        source_op,
+                        format!("vertex buffer slot {} required by the pipeline is not bound", slot)
+                    )
+                )
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn error(&self, source_op: &'static str, message: String) -> GeepuError {
+        GeepuError::Validation { message, label: self.label.clone(), source_op }
+    }
+}
+
+fn bit(mask: u32, index: usize) -> bool {
+    index < 32 && mask & (1 << index) != 0
+}
@@ -0,0 +1,193 @@
+use crate::{ GeepuError, GpuContext, Result };
+use std::sync::Arc;
+use winit::dpi::{ LogicalPosition, LogicalSize, PhysicalSize, Position, Size };
+use winit::event_loop::ActiveEventLoop;
+use winit::monitor::MonitorHandle;
+use winit::window::{ Fullscreen, Icon, Window };
+
+/// How a window occupies the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowMode {
+    /// A regular window
+    #[default]
+    Windowed,
+    /// A borderless window covering the current monitor
+    BorderlessFullscreen,
+    /// Exclusive fullscreen using the monitor's highest resolution video mode
+    ExclusiveFullscreen,
+}
+
+impl WindowMode {
+    /// Resolve the mode into winit's fullscreen setting on `monitor`
+    fn fullscreen(self, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+        match self {
+            WindowMode::Windowed => None,
+            WindowMode::BorderlessFullscreen => Some(Fullscreen::Borderless(monitor)),
+            WindowMode::ExclusiveFullscreen =>
+                monitor
+                    .and_then(|monitor| {
+                        monitor.video_modes().max_by_key(|mode| {
+                            let size = mode.size();
+                            (size.width * size.height, mode.refresh_rate_millihertz())
+                        })
+                    })
+                    .map(Fullscreen::Exclusive)
+                    .or(Some(Fullscreen::Borderless(None))),
+        }
+    }
+}
+
+/// Window creation options.
+///
+/// Sizes and positions are logical (DPI-independent) units unless set through
+/// the `physical_*` variants, so a window keeps the same apparent size on HiDPI
+/// displays.
+#[derive(Debug, Clone)]
+pub struct WindowConfig {
+    pub title: String,
+    pub size: Size,
+    pub resizable: bool,
+    pub mode: WindowMode,
+    pub decorations: bool,
+    pub min_size: Option<Size>,
+    pub max_size: Option<Size>,
+    pub position: Option<Position>,
+    icon: Option<(Vec<u8>, u32, u32)>,
+}
+
+impl WindowConfig {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            size: LogicalSize::new(800, 600).into(),
+            resizable: true,
+            mode: WindowMode::Windowed,
+            decorations: true,
+            min_size: None,
+            max_size: None,
+            position: None,
+            icon: None,
+        }
+    }
+
+    /// Set the inner size in logical units
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.size = LogicalSize::new(width, height).into();
+        self
+    }
+
+    /// Set the inner size in physical pixels
+    pub fn physical_size(mut self, width: u32, height: u32) -> Self {
+        self.size = PhysicalSize::new(width, height).into();
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    /// Start windowed, borderless fullscreen or exclusive fullscreen
+    pub fn mode(mut self, mode: WindowMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Show or hide the title bar and borders
+    pub fn decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    /// Set the minimum inner size in logical units
+    pub fn min_size(mut self, width: u32, height: u32) -> Self {
+        self.min_size = Some(LogicalSize::new(width, height).into());
+        self
+    }
+
+    /// Set the maximum inner size in logical units
+    pub fn max_size(mut self, width: u32, height: u32) -> Self {
+        self.max_size = Some(LogicalSize::new(width, height).into());
+        self
+    }
+
+    /// Place the window's top-left corner at a desktop position in logical units
+    pub fn position(mut self, x: i32, y: i32) -> Self {
+        self.position = Some(LogicalPosition::new(x, y).into());
+        self
+    }
+
+    /// Set the window icon from RGBA8 pixels
+    pub fn icon(mut self, rgba: Vec<u8>, width: u32, height: u32) -> Self {
+        self.icon = Some((rgba, width, height));
+        self
+    }
+
+    /// Create the window on a running event loop
+    pub fn create_window(&self, event_loop: &ActiveEventLoop) -> Result<Arc<Window>> {
+        let mut attributes = Window::default_attributes()
+            .with_title(self.title.clone())
+            .with_inner_size(self.size)
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_fullscreen(self.mode.fullscreen(event_loop.primary_monitor()));
+
+        if let Some(size) = self.min_size {
+            attributes = attributes.with_min_inner_size(size);
+        }
+        if let Some(size) = self.max_size {
+            attributes = attributes.with_max_inner_size(size);
+        }
+        if let Some(position) = self.position {
+            attributes = attributes.with_position(position);
+        }
+        if let Some((rgba, width, height)) = &self.icon {
+            let icon = Icon::from_rgba(rgba.clone(), *width, *height).map_err(|e|
+                GeepuError::Other(format!("Invalid window icon: {}", e))
+            )?;
+            attributes = attributes.with_window_icon(Some(icon));
+        }
+
+        event_loop
+            .create_window(attributes)
+            .map(Arc::new)
+            .map_err(|e| GeepuError::Other(format!("Failed to create window: {}", e)))
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self::new("Geepu")
+    }
+}
+
+impl GpuContext {
+    /// Ratio of physical pixels to logical units for the context's window (1.0 without one)
+    pub fn scale_factor(&self) -> f64 {
+        self.window.as_ref().map_or(1.0, |window| window.scale_factor())
+    }
+
+    /// Reconfigure the surface after `WindowEvent::ScaleFactorChanged`.
+    ///
+    /// The window's physical size changes with the scale factor, so the surface
+    /// is resized to the window's new inner size.
+    pub fn scale_factor_changed(&mut self) -> Result<()> {
+        if let Some(window) = self.window.clone() {
+            self.resize(window.inner_size())?;
+        }
+        Ok(())
+    }
+
+    /// Switch the context's window between windowed and fullscreen modes
+    pub fn set_fullscreen(&self, mode: WindowMode) -> Result<()> {
+        let window = self.window
+            .as_ref()
+            .ok_or_else(||
+                GeepuError::Other(
+                    "No window available - context was created without window".to_string()
+                )
+            )?;
+        window.set_fullscreen(mode.fullscreen(window.current_monitor()));
+        Ok(())
+    }
+}